@@ -0,0 +1,352 @@
+//! An [EthereumApi] spread over several Ethereum endpoints.
+
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use anyhow::Context;
+use pathfinder_common::EthereumChain;
+use primitive_types::H160;
+
+use crate::{EthereumApi, EthereumClient, EthereumStateUpdate, L1ToL2MessageLog, StateUpdateLog};
+
+/// Spreads the [EthereumApi] over several endpoints, so that a single failing, lagging or
+/// compromised one can neither stall nor mislead the node.
+///
+/// Requests go to one endpoint at a time, moving on to the next one whenever a request fails.
+/// The Starknet state and the logs of the core contract are instead requested from all
+/// endpoints, and only returned once `quorum` of them agree on them. Likewise, the finalized
+/// block is the latest one which at least `quorum` endpoints consider finalized.
+#[derive(Clone, Debug)]
+pub struct FailoverClient<T = EthereumClient> {
+    clients: Arc<[T]>,
+    /// The index of the endpoint which last served a request.
+    current: Arc<AtomicUsize>,
+    quorum: usize,
+}
+
+impl<T> FailoverClient<T> {
+    /// Panics if there are fewer `clients` than the `quorum`.
+    pub fn new(clients: Vec<T>, quorum: NonZeroUsize) -> Self {
+        assert!(
+            clients.len() >= quorum.get(),
+            "The quorum of {quorum} exceeds the {} Ethereum endpoints",
+            clients.len()
+        );
+
+        Self {
+            clients: clients.into(),
+            current: Default::default(),
+            quorum: quorum.get(),
+        }
+    }
+}
+
+impl<T: EthereumApi> FailoverClient<T> {
+    /// Calls `request` on each endpoint in turn, starting from the current one, until it succeeds.
+    async fn with_failover<'a, R, F, Fut>(&'a self, method: &str, request: F) -> anyhow::Result<R>
+    where
+        F: Fn(&'a T) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        let start = self.current.load(Ordering::Relaxed);
+        let mut last_error = None;
+
+        for offset in 0..self.clients.len() {
+            let endpoint = (start + offset) % self.clients.len();
+            match request(&self.clients[endpoint]).await {
+                Ok(result) => {
+                    self.current.store(endpoint, Ordering::Relaxed);
+                    return Ok(result);
+                }
+                Err(error) => {
+                    tracing::debug!(%endpoint, %method, ?error, "Ethereum request failed");
+                    last_error = Some(error);
+                }
+            }
+        }
+
+        Err(last_error.expect("There is at least one endpoint"))
+            .with_context(|| format!("Calling {method} failed on all Ethereum endpoints"))
+    }
+
+    /// Calls `request` on all endpoints, and returns the result which at least `quorum` of them
+    /// agree on. Without a quorum this is the same as [with_failover](Self::with_failover).
+    async fn with_quorum<'a, R, F, Fut>(&'a self, method: &str, request: F) -> anyhow::Result<R>
+    where
+        R: PartialEq + std::fmt::Debug,
+        F: Fn(&'a T) -> Fut,
+        Fut: Future<Output = anyhow::Result<R>>,
+    {
+        if self.quorum == 1 {
+            return self.with_failover(method, request).await;
+        }
+
+        let results = futures::future::join_all(self.clients.iter().map(request)).await;
+
+        // The distinct results returned, and by how many endpoints.
+        let mut votes: Vec<(R, usize)> = Vec::new();
+        for (endpoint, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(result) => match votes.iter_mut().find(|(other, _)| *other == result) {
+                    Some((_, count)) => *count += 1,
+                    None => votes.push((result, 1)),
+                },
+                Err(error) => {
+                    tracing::debug!(%endpoint, %method, ?error, "Ethereum request failed");
+                }
+            }
+        }
+
+        if votes.len() > 1 {
+            tracing::warn!(%method, results=?votes, "Ethereum endpoints disagree");
+        }
+
+        votes
+            .into_iter()
+            .find(|(_, count)| *count >= self.quorum)
+            .map(|(result, _)| result)
+            .with_context(|| {
+                format!(
+                    "Fewer than {} Ethereum endpoints agree on the result of {method}",
+                    self.quorum
+                )
+            })
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: EthereumApi + Send + Sync> EthereumApi for FailoverClient<T> {
+    async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate> {
+        self.with_quorum("get_starknet_state", |client| {
+            client.get_starknet_state(address)
+        })
+        .await
+    }
+
+    async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+        self.with_failover("get_chain", |client| client.get_chain())
+            .await
+    }
+
+    async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+        const METHOD: &str = "get_finalized_block_number";
+
+        if self.quorum == 1 {
+            return self
+                .with_failover(METHOD, |client| client.get_finalized_block_number())
+                .await;
+        }
+
+        let results = futures::future::join_all(
+            self.clients
+                .iter()
+                .map(|client| client.get_finalized_block_number()),
+        )
+        .await;
+
+        let mut blocks = Vec::new();
+        for (endpoint, result) in results.into_iter().enumerate() {
+            match result {
+                Ok(block) => blocks.push(block),
+                Err(error) => {
+                    tracing::debug!(%endpoint, method=%METHOD, ?error, "Ethereum request failed");
+                }
+            }
+        }
+
+        // Endpoints lag behind each other, so rather than agreeing on the exact block they only
+        // need to agree that it is finalized.
+        blocks.sort_unstable_by(|a, b| b.cmp(a));
+        blocks.get(self.quorum - 1).copied().with_context(|| {
+            format!(
+                "Fewer than {} Ethereum endpoints answered {METHOD}",
+                self.quorum
+            )
+        })
+    }
+
+    async fn get_l1_to_l2_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+        self.with_quorum("get_l1_to_l2_message_logs", |client| {
+            client.get_l1_to_l2_message_logs(address, from_block, to_block)
+        })
+        .await
+    }
+
+    async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>> {
+        self.with_quorum("get_state_update_logs", |client| {
+            client.get_state_update_logs(address, from_block, to_block)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockHash, BlockNumber};
+
+    use super::*;
+    use crate::MockEthereumApi;
+
+    /// Reports the given Starknet state, and logs it in every block range, or fails if there is
+    /// none.
+    fn endpoint(state: Option<EthereumStateUpdate>) -> MockEthereumApi {
+        let mut ethereum = MockEthereumApi::new();
+        let logged = state.clone();
+        ethereum
+            .expect_get_starknet_state()
+            .returning(move |_| state.clone().context("Endpoint down"));
+        ethereum
+            .expect_get_state_update_logs()
+            .returning(move |_, _, _| {
+                let state = logged.clone().context("Endpoint down")?;
+                Ok(vec![StateUpdateLog {
+                    state_root: state.state_root,
+                    block_number: state.block_number,
+                    block_hash: Some(state.block_hash),
+                }])
+            });
+        ethereum
+    }
+
+    fn state(block_hash: BlockHash) -> EthereumStateUpdate {
+        EthereumStateUpdate {
+            state_root: state_commitment!("0x1"),
+            block_number: BlockNumber::new_or_panic(10),
+            block_hash,
+        }
+    }
+
+    #[tokio::test]
+    async fn failover() {
+        let expected = state(block_hash!("0x2"));
+
+        let mut down = MockEthereumApi::new();
+        down.expect_get_starknet_state()
+            .times(1)
+            .returning(|_| Err(anyhow::anyhow!("Endpoint down")));
+        // The second request goes straight to the working endpoint.
+        let mut up = MockEthereumApi::new();
+        let state = expected.clone();
+        up.expect_get_starknet_state()
+            .times(2)
+            .returning(move |_| Ok(state.clone()));
+
+        let client = FailoverClient::new(vec![down, up], NonZeroUsize::new(1).unwrap());
+
+        for _ in 0..2 {
+            let state = client.get_starknet_state(&H160::zero()).await.unwrap();
+            assert_eq!(state, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn quorum() {
+        let honest = state(block_hash!("0x2"));
+        let lying = state(block_hash!("0x3"));
+
+        let client = FailoverClient::new(
+            vec![
+                endpoint(Some(lying.clone())),
+                endpoint(None),
+                endpoint(Some(honest.clone())),
+                endpoint(Some(honest.clone())),
+            ],
+            NonZeroUsize::new(2).unwrap(),
+        );
+        let state = client.get_starknet_state(&H160::zero()).await.unwrap();
+        assert_eq!(state, honest);
+
+        let client = FailoverClient::new(
+            vec![
+                endpoint(Some(lying)),
+                endpoint(None),
+                endpoint(Some(honest)),
+            ],
+            NonZeroUsize::new(2).unwrap(),
+        );
+        client.get_starknet_state(&H160::zero()).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn finalized_block_quorum() {
+        fn endpoint(finalized: Option<u64>) -> MockEthereumApi {
+            let mut ethereum = MockEthereumApi::new();
+            ethereum
+                .expect_get_finalized_block_number()
+                .returning(move || finalized.context("Endpoint down"));
+            ethereum
+        }
+
+        // A single endpoint reporting an inflated block is outvoted.
+        let client = FailoverClient::new(
+            vec![
+                endpoint(Some(10)),
+                endpoint(Some(1_000)),
+                endpoint(Some(12)),
+            ],
+            NonZeroUsize::new(2).unwrap(),
+        );
+        let finalized = client.get_finalized_block_number().await.unwrap();
+        assert_eq!(finalized, 12);
+
+        let client = FailoverClient::new(
+            vec![endpoint(Some(1_000)), endpoint(None), endpoint(Some(10))],
+            NonZeroUsize::new(2).unwrap(),
+        );
+        let finalized = client.get_finalized_block_number().await.unwrap();
+        assert_eq!(finalized, 10);
+
+        let client = FailoverClient::new(
+            vec![endpoint(Some(1_000)), endpoint(None), endpoint(None)],
+            NonZeroUsize::new(2).unwrap(),
+        );
+        client.get_finalized_block_number().await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn logs_quorum() {
+        let honest = state(block_hash!("0x2"));
+        let lying = state(block_hash!("0x3"));
+
+        let client = FailoverClient::new(
+            vec![
+                endpoint(Some(lying.clone())),
+                endpoint(Some(honest.clone())),
+                endpoint(Some(honest.clone())),
+            ],
+            NonZeroUsize::new(2).unwrap(),
+        );
+        let logs = client
+            .get_state_update_logs(&H160::zero(), 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].block_hash, Some(honest.block_hash));
+
+        let client = FailoverClient::new(
+            vec![
+                endpoint(Some(lying)),
+                endpoint(None),
+                endpoint(Some(honest)),
+            ],
+            NonZeroUsize::new(2).unwrap(),
+        );
+        client
+            .get_state_update_logs(&H160::zero(), 0, 10)
+            .await
+            .unwrap_err();
+    }
+}
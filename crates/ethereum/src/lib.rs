@@ -1,7 +1,19 @@
+use std::time::Duration;
+
 use pathfinder_common::{BlockHash, BlockNumber, EthereumChain, StateCommitment};
 use primitive_types::{H160, H256, U256};
 use stark_hash::Felt;
 
+mod failover;
+mod messages;
+mod state_update;
+mod subscription;
+
+pub use failover::FailoverClient;
+pub use messages::{L1ToL2Message, L1ToL2MessageEvent, L1ToL2MessageLog};
+pub use state_update::StateUpdateLog;
+pub use subscription::SubscriptionClient;
+
 pub mod core_addr {
     use const_decoder::Decoder;
 
@@ -19,10 +31,33 @@ pub struct EthereumStateUpdate {
     pub block_hash: BlockHash,
 }
 
+#[cfg_attr(any(test, feature = "test-utils"), mockall::automock)]
 #[async_trait::async_trait]
 pub trait EthereumApi {
     async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate>;
     async fn get_chain(&self) -> anyhow::Result<EthereumChain>;
+    async fn get_finalized_block_number(&self) -> anyhow::Result<u64>;
+    /// Returns the logs emitted by the core contract for L1 to L2 messages, from the given
+    /// Ethereum block range (inclusive).
+    async fn get_l1_to_l2_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L1ToL2MessageLog>>;
+    /// Returns the state updates logged by the core contract, from the given Ethereum block
+    /// range (inclusive).
+    async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>>;
+    /// Waits until the Starknet state may have changed. Implementations without a way of being
+    /// notified wait for `poll_interval`.
+    async fn wait_for_state_update(&self, poll_interval: Duration) {
+        tokio::time::sleep(poll_interval).await
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -84,6 +119,37 @@ impl EthereumClient {
         .await
     }
 
+    /// Returns the logs emitted by the contract in the Ethereum block range (inclusive), whose
+    /// first topic is one of `topics`.
+    async fn get_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+        topics: Vec<String>,
+    ) -> anyhow::Result<Vec<serde_json::Value>> {
+        let logs = self
+            .call_ethereum(serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_getLogs",
+                "params": [
+                    {
+                        "address": format!("0x{}", hex::encode(address.as_bytes())),
+                        "fromBlock": format!("0x{from_block:x}"),
+                        "toBlock": format!("0x{to_block:x}"),
+                        "topics": [topics]
+                    }
+                ],
+                "id": 0
+            }))
+            .await?;
+
+        match logs {
+            serde_json::Value::Array(logs) => Ok(logs),
+            _ => anyhow::bail!("Failed to fetch logs"),
+        }
+    }
+
     async fn call_ethereum(&self, value: serde_json::Value) -> anyhow::Result<serde_json::Value> {
         let res = self.http.post(self.url.clone()).json(&value).send().await?;
 
@@ -142,6 +208,54 @@ impl EthereumApi for EthereumClient {
             x => EthereumChain::Other(x),
         })
     }
+
+    async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+        self.call_ethereum(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_getBlockByNumber",
+            "params": [
+                "finalized",
+                false
+            ],
+            "id": 0
+        }))
+        .await
+        .and_then(|value| get_u256(&value["number"]))
+        .map(|number| number.as_u64())
+    }
+
+    async fn get_l1_to_l2_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+        self.get_logs(address, from_block, to_block, messages::topics())
+            .await?
+            .iter()
+            .map(messages::parse_log)
+            .collect()
+    }
+
+    async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>> {
+        let logs = self
+            .get_logs(address, from_block, to_block, state_update::topics())
+            .await?;
+
+        let mut updates = Vec::with_capacity(logs.len());
+        for log in &logs {
+            if let Some(update) = state_update::parse_log(log)? {
+                updates.push(update);
+            }
+        }
+
+        Ok(updates)
+    }
 }
 
 fn encode_ethereum_call_data(signature: &[u8]) -> String {
@@ -150,6 +264,13 @@ fn encode_ethereum_call_data(signature: &[u8]) -> String {
     format!("0x{}", hex::encode(&output[0..4]))
 }
 
+/// Returns the topic identifying logs of the event with the given signature.
+fn log_topic(signature: &str) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    keccak_hash::keccak_256(signature.as_bytes(), &mut output);
+    output
+}
+
 fn get_h256(value: &serde_json::Value) -> anyhow::Result<H256> {
     use std::str::FromStr;
     value
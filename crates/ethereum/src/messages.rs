@@ -0,0 +1,204 @@
+//! L1 to L2 messages sent through the Starknet core contract, and the logs it emits for them.
+
+use anyhow::Context;
+use pathfinder_common::{
+    ContractAddress, EntryPoint, EthereumAddress, L1ToL2MessageNonce, L1ToL2MessagePayloadElem,
+};
+use primitive_types::{H160, H256, U256};
+
+use crate::{get_felt, get_h256, get_u256, log_topic};
+
+/// Emitted when a message is sent. The event gained a trailing `fee` with Starknet v0.11.
+const LOG_MESSAGE_TO_L2: &str = "LogMessageToL2(address,uint256,uint256,uint256[],uint256,uint256)";
+const LOG_MESSAGE_TO_L2_V0: &str = "LogMessageToL2(address,uint256,uint256,uint256[],uint256)";
+const CANCELLATION_STARTED: &str =
+    "MessageToL2CancellationStarted(address,uint256,uint256,uint256[],uint256)";
+const CANCELED: &str = "MessageToL2Canceled(address,uint256,uint256,uint256[],uint256)";
+
+/// An L1 to L2 message, which is consumed on L2 by an L1 handler transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L1ToL2Message {
+    pub from_address: EthereumAddress,
+    pub to_address: ContractAddress,
+    pub selector: EntryPoint,
+    pub payload: Vec<L1ToL2MessagePayloadElem>,
+    pub nonce: L1ToL2MessageNonce,
+}
+
+impl L1ToL2Message {
+    /// The hash of the message, as computed by the core contract. This identifies the message on
+    /// both L1 and L2.
+    pub fn hash(&self) -> H256 {
+        let mut data = Vec::with_capacity(32 * (5 + self.payload.len()));
+        data.extend_from_slice(H256::from(self.from_address.0).as_bytes());
+        data.extend_from_slice(self.to_address.get().as_be_bytes());
+        data.extend_from_slice(self.nonce.0.as_be_bytes());
+        data.extend_from_slice(self.selector.0.as_be_bytes());
+        data.extend_from_slice(H256::from_low_u64_be(self.payload.len() as u64).as_bytes());
+        for elem in &self.payload {
+            data.extend_from_slice(elem.0.as_be_bytes());
+        }
+
+        let mut hash = H256::zero();
+        keccak_hash::keccak_256(&data, hash.as_bytes_mut());
+        hash
+    }
+}
+
+/// What a [L1ToL2MessageLog] records about its message.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum L1ToL2MessageEvent {
+    Sent,
+    CancellationStarted,
+    Canceled,
+}
+
+/// A log emitted by the core contract for an L1 to L2 message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L1ToL2MessageLog {
+    pub message: L1ToL2Message,
+    pub event: L1ToL2MessageEvent,
+    pub l1_block_number: u64,
+    pub l1_transaction_hash: H256,
+}
+
+/// The topics of all logs parsed by [parse_log], as expected by `eth_getLogs`.
+pub(crate) fn topics() -> Vec<String> {
+    [
+        LOG_MESSAGE_TO_L2,
+        LOG_MESSAGE_TO_L2_V0,
+        CANCELLATION_STARTED,
+        CANCELED,
+    ]
+    .into_iter()
+    .map(|signature| format!("0x{}", hex::encode(log_topic(signature))))
+    .collect()
+}
+
+/// Parses a log returned by `eth_getLogs` for the [topics].
+pub(crate) fn parse_log(log: &serde_json::Value) -> anyhow::Result<L1ToL2MessageLog> {
+    let topics = log["topics"]
+        .as_array()
+        .context("Log topics missing")?
+        .iter()
+        .map(get_h256)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    anyhow::ensure!(
+        topics.len() == 4,
+        "Expected 4 log topics, got {}",
+        topics.len()
+    );
+
+    let event = match topics[0].to_fixed_bytes() {
+        x if x == log_topic(LOG_MESSAGE_TO_L2) || x == log_topic(LOG_MESSAGE_TO_L2_V0) => {
+            L1ToL2MessageEvent::Sent
+        }
+        x if x == log_topic(CANCELLATION_STARTED) => L1ToL2MessageEvent::CancellationStarted,
+        x if x == log_topic(CANCELED) => L1ToL2MessageEvent::Canceled,
+        _ => anyhow::bail!("Unexpected log topic {:?}", topics[0]),
+    };
+
+    // The payload is ABI encoded as an offset to its length, followed by its elements. The nonce
+    // is the word following the offset.
+    let data = log["data"].as_str().context("Log data missing")?;
+    let data = hex::decode(data.strip_prefix("0x").unwrap_or(data)).context("Decoding log data")?;
+    let words = data
+        .chunks_exact(32)
+        .map(H256::from_slice)
+        .collect::<Vec<_>>();
+    let word = |index: usize| {
+        words
+            .get(index)
+            .copied()
+            .with_context(|| format!("Log data is missing word {index}"))
+    };
+
+    let offset = U256::from_big_endian(word(0)?.as_bytes()) / 32;
+    anyhow::ensure!(offset < U256::from(words.len()), "Invalid payload offset");
+    let offset = offset.as_usize();
+    let length = U256::from_big_endian(word(offset)?.as_bytes());
+    anyhow::ensure!(length < U256::from(words.len()), "Invalid payload length");
+    let payload = (0..length.as_usize())
+        .map(|i| {
+            word(offset + 1 + i)
+                .and_then(get_felt)
+                .map(L1ToL2MessagePayloadElem)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let message = L1ToL2Message {
+        from_address: EthereumAddress(H160::from(topics[1])),
+        to_address: ContractAddress::new(get_felt(topics[2])?)
+            .context("Invalid message recipient")?,
+        selector: EntryPoint(get_felt(topics[3])?),
+        payload,
+        nonce: L1ToL2MessageNonce(get_felt(word(1)?)?),
+    };
+
+    Ok(L1ToL2MessageLog {
+        message,
+        event,
+        l1_block_number: get_u256(&log["blockNumber"])?.as_u64(),
+        l1_transaction_hash: get_h256(&log["transactionHash"])?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn parse_and_hash() {
+        let log = serde_json::json!({
+            "address": "0xc662c410c0ecf747543f5ba90660f6abebd9c8c4",
+            "topics": [
+                format!("0x{}", hex::encode(log_topic(LOG_MESSAGE_TO_L2))),
+                "0x000000000000000000000000ae0ee0a63a2ce6baeeffe56e7714fb4efe48d419",
+                "0x073314940630fd6dcda0d772d4c972c4e0a9946bef9dabf4ef84eda8ef542b82",
+                "0x02d757788a8d8d6f21d1cd40bce38a8222d70654214e96ff95d8086e684fbee5"
+            ],
+            "data": concat!(
+                "0x",
+                "0000000000000000000000000000000000000000000000000000000000000060",
+                "000000000000000000000000000000000000000000000000000000000000000a",
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "0000000000000000000000000000000000000000000000000000000000000002",
+                "0000000000000000000000000000000000000000000000000000000000000123",
+                "0000000000000000000000000000000000000000000000000000000000000456"
+            ),
+            "blockNumber": "0x10",
+            "transactionHash": "0x9921984fd976f261e0d70618b51e3db3724b9f4d28d0534c3483dd2162f13fff"
+        });
+
+        let log = parse_log(&log).unwrap();
+
+        assert_eq!(log.event, L1ToL2MessageEvent::Sent);
+        assert_eq!(log.l1_block_number, 16);
+        assert_eq!(
+            log.message,
+            L1ToL2Message {
+                from_address: EthereumAddress(H160::from_slice(
+                    &hex::decode("ae0ee0a63a2ce6baeeffe56e7714fb4efe48d419").unwrap()
+                )),
+                to_address: contract_address!(
+                    "0x073314940630fd6dcda0d772d4c972c4e0a9946bef9dabf4ef84eda8ef542b82"
+                ),
+                selector: EntryPoint(felt!(
+                    "0x02d757788a8d8d6f21d1cd40bce38a8222d70654214e96ff95d8086e684fbee5"
+                )),
+                payload: vec![
+                    L1ToL2MessagePayloadElem(felt!("0x123")),
+                    L1ToL2MessagePayloadElem(felt!("0x456")),
+                ],
+                nonce: L1ToL2MessageNonce(felt!("0xa")),
+            }
+        );
+
+        // The hash covers every field of the message.
+        let mut other = log.message.clone();
+        other.nonce = L1ToL2MessageNonce(felt!("0xb"));
+        assert_ne!(log.message.hash(), other.hash());
+    }
+}
@@ -0,0 +1,110 @@
+//! The `LogStateUpdate` logs emitted by the Starknet core contract for each state update.
+
+use anyhow::Context;
+use pathfinder_common::{BlockHash, BlockNumber, StateCommitment};
+use primitive_types::{H256, U256};
+
+use crate::{get_felt, get_h256, get_number, log_topic};
+
+/// The block hash was added to the event with Starknet v0.11.
+const LOG_STATE_UPDATE: &str = "LogStateUpdate(uint256,int256,uint256)";
+const LOG_STATE_UPDATE_V0: &str = "LogStateUpdate(uint256,int256)";
+
+/// A state update logged by the core contract.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateUpdateLog {
+    pub state_root: StateCommitment,
+    pub block_number: BlockNumber,
+    /// Missing from logs emitted before Starknet v0.11.
+    pub block_hash: Option<BlockHash>,
+}
+
+/// The topics of all logs parsed by [parse_log], as expected by `eth_getLogs`.
+pub(crate) fn topics() -> Vec<String> {
+    [LOG_STATE_UPDATE, LOG_STATE_UPDATE_V0]
+        .into_iter()
+        .map(|signature| format!("0x{}", hex::encode(log_topic(signature))))
+        .collect()
+}
+
+/// Parses a log returned by `eth_getLogs` for the [topics]. Returns `None` for a negative block
+/// number, which the core contract uses for the state preceding genesis.
+pub(crate) fn parse_log(log: &serde_json::Value) -> anyhow::Result<Option<StateUpdateLog>> {
+    let topic = get_h256(&log["topics"][0]).context("Log topic missing")?;
+    let has_hash = match topic.to_fixed_bytes() {
+        x if x == log_topic(LOG_STATE_UPDATE) => true,
+        x if x == log_topic(LOG_STATE_UPDATE_V0) => false,
+        _ => anyhow::bail!("Unexpected log topic {topic:?}"),
+    };
+
+    let data = log["data"].as_str().context("Log data missing")?;
+    let data = hex::decode(data.strip_prefix("0x").unwrap_or(data)).context("Decoding log data")?;
+    let words = data
+        .chunks_exact(32)
+        .map(H256::from_slice)
+        .collect::<Vec<_>>();
+    let expected = if has_hash { 3 } else { 2 };
+    anyhow::ensure!(
+        words.len() == expected,
+        "Expected {expected} words of log data, got {}",
+        words.len()
+    );
+
+    // The block number is signed.
+    if words[1].as_bytes()[0] & 0x80 != 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(StateUpdateLog {
+        state_root: StateCommitment(get_felt(words[0])?),
+        block_number: get_number(U256::from_big_endian(words[1].as_bytes()))?,
+        block_hash: match has_hash {
+            true => Some(BlockHash(get_felt(words[2])?)),
+            false => None,
+        },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    fn log(signature: &str, data: &[&str]) -> serde_json::Value {
+        serde_json::json!({
+            "topics": [format!("0x{}", hex::encode(log_topic(signature)))],
+            "data": format!("0x{}", data.concat()),
+        })
+    }
+
+    #[test]
+    fn parse() {
+        let root = "02a4651c1ba5151c48ebeb4477216b04d7a65058a5b99e5fbc602507ae933d2f";
+        let number = "0000000000000000000000000000000000000000000000000000000000007eeb";
+        let hash = "0000000000000000000000000000000000000000000000000000000000000abc";
+
+        let update = parse_log(&log(LOG_STATE_UPDATE, &[root, number, hash]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            update,
+            StateUpdateLog {
+                state_root: state_commitment!(
+                    "0x02a4651c1ba5151c48ebeb4477216b04d7a65058a5b99e5fbc602507ae933d2f"
+                ),
+                block_number: BlockNumber::new_or_panic(0x7eeb),
+                block_hash: Some(block_hash!("0xabc")),
+            }
+        );
+
+        let update = parse_log(&log(LOG_STATE_UPDATE_V0, &[root, number]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(update.block_hash, None);
+
+        let negative = "f".repeat(64);
+        let update = parse_log(&log(LOG_STATE_UPDATE_V0, &[root, &negative])).unwrap();
+        assert_eq!(update, None);
+    }
+}
@@ -0,0 +1,387 @@
+//! Learning of Starknet state updates from Ethereum websocket subscriptions instead of polling.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use futures::{SinkExt, StreamExt};
+use pathfinder_common::EthereumChain;
+use primitive_types::H160;
+use tokio::sync::{watch, Mutex};
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    get_u256, state_update, EthereumApi, EthereumStateUpdate, FailoverClient, L1ToL2MessageLog,
+    StateUpdateLog,
+};
+
+/// The delay before reconnecting a dropped websocket.
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+/// A new head is expected every 12 seconds, so a silent websocket is considered dropped.
+const SILENCE_TIMEOUT: Duration = Duration::from_secs(60);
+/// The Starknet state is still polled this often while subscribed, in case a log was missed.
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(600);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Subscription {
+    /// The websocket is down, so state updates are found by polling.
+    Disconnected,
+    /// Counts the logged state updates whose Ethereum block was finalized so far.
+    Connected { finalized_updates: u64 },
+}
+
+/// Wraps an [EthereumApi] to wait for Starknet state updates over websocket subscriptions to
+/// new heads and to the core contract's `LogStateUpdate` logs, instead of polling.
+///
+/// A logged state update is reported once its Ethereum block is finalized, which is checked on
+/// each new head. While the websocket is down, [wait_for_state_update] falls back to waiting for
+/// the poll interval.
+///
+/// [wait_for_state_update]: EthereumApi::wait_for_state_update
+#[derive(Clone, Debug)]
+pub struct SubscriptionClient<T = FailoverClient> {
+    inner: T,
+    subscription: Option<Arc<Mutex<watch::Receiver<Subscription>>>>,
+}
+
+impl<T> SubscriptionClient<T>
+where
+    T: EthereumApi + Clone + Send + Sync + 'static,
+{
+    /// Subscribes at the websocket `url` to the logs of the core contract at `core_address`,
+    /// reconnecting in the background whenever the websocket drops.
+    pub fn new(inner: T, url: reqwest::Url, core_address: H160) -> Self {
+        let (sender, receiver) = watch::channel(Subscription::Disconnected);
+        tokio::spawn(subscribe(url, core_address, inner.clone(), sender));
+
+        Self {
+            inner,
+            subscription: Some(Arc::new(Mutex::new(receiver))),
+        }
+    }
+
+    /// Polls for state updates without subscribing.
+    pub fn polling(inner: T) -> Self {
+        Self {
+            inner,
+            subscription: None,
+        }
+    }
+}
+
+/// Keeps the subscriptions up until the [SubscriptionClient] is dropped.
+async fn subscribe<T: EthereumApi>(
+    url: reqwest::Url,
+    core_address: H160,
+    ethereum: T,
+    sender: watch::Sender<Subscription>,
+) {
+    let mut finalized_updates = 0;
+
+    loop {
+        match run_subscriptions(
+            &url,
+            &core_address,
+            &ethereum,
+            &sender,
+            &mut finalized_updates,
+        )
+        .await
+        {
+            Ok(()) => tracing::info!("Ethereum websocket closed, falling back to polling"),
+            Err(error) => {
+                tracing::warn!(?error, "Ethereum websocket failed, falling back to polling")
+            }
+        }
+
+        if sender.send(Subscription::Disconnected).is_err() {
+            return;
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn run_subscriptions<T: EthereumApi>(
+    url: &reqwest::Url,
+    core_address: &H160,
+    ethereum: &T,
+    sender: &watch::Sender<Subscription>,
+    finalized_updates: &mut u64,
+) -> anyhow::Result<()> {
+    const HEADS_REQUEST_ID: u64 = 1;
+    const LOGS_REQUEST_ID: u64 = 2;
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .context("Connecting to Ethereum websocket")?;
+
+    for request in [
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_subscribe",
+            "params": ["newHeads"],
+            "id": HEADS_REQUEST_ID
+        }),
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_subscribe",
+            "params": [
+                "logs",
+                {
+                    "address": format!("0x{}", hex::encode(core_address.as_bytes())),
+                    "topics": [state_update::topics()]
+                }
+            ],
+            "id": LOGS_REQUEST_ID
+        }),
+    ] {
+        socket
+            .send(Message::Text(request.to_string()))
+            .await
+            .context("Subscribing")?;
+    }
+
+    let mut heads_subscription = None;
+    let mut logs_subscription = None;
+    // The Ethereum blocks of the logged state updates which are not finalized yet.
+    let mut pending_blocks: Vec<u64> = Vec::new();
+
+    loop {
+        let message = tokio::time::timeout(SILENCE_TIMEOUT, socket.next())
+            .await
+            .context("Ethereum websocket went silent")?;
+        let text = match message {
+            None | Some(Ok(Message::Close(_))) => return Ok(()),
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(_)) => continue,
+            Some(Err(error)) => return Err(error).context("Reading from Ethereum websocket"),
+        };
+        let message: serde_json::Value =
+            serde_json::from_str(&text).context("Parsing websocket message")?;
+
+        if let Some(id) = message["id"].as_u64() {
+            anyhow::ensure!(
+                message["error"].is_null(),
+                "Subscribing failed: {}",
+                message["error"]
+            );
+            let subscription = message["result"].as_str().map(ToOwned::to_owned);
+            match id {
+                HEADS_REQUEST_ID => heads_subscription = subscription,
+                LOGS_REQUEST_ID => logs_subscription = subscription,
+                _ => {}
+            }
+
+            if heads_subscription.is_some() && logs_subscription.is_some() {
+                tracing::debug!("Subscribed to Ethereum heads and state update logs");
+                // State updates may have been missed while disconnected.
+                *finalized_updates += 1;
+                sender
+                    .send(Subscription::Connected {
+                        finalized_updates: *finalized_updates,
+                    })
+                    .context("Subscription client dropped")?;
+            }
+            continue;
+        }
+
+        let subscription = message["params"]["subscription"].as_str();
+        let result = &message["params"]["result"];
+
+        if subscription.is_some() && subscription == logs_subscription.as_deref() {
+            // A reorged log is followed by its replacement, if any.
+            if result["removed"].as_bool() == Some(true) {
+                continue;
+            }
+            let block = get_u256(&result["blockNumber"])
+                .context("Log block number missing")?
+                .as_u64();
+            pending_blocks.push(block);
+            tracing::trace!(ethereum_block=%block, "State update logged");
+        } else if subscription.is_some()
+            && subscription == heads_subscription.as_deref()
+            && !pending_blocks.is_empty()
+        {
+            let finalized = ethereum
+                .get_finalized_block_number()
+                .await
+                .context("Fetching finalized block number")?;
+
+            let pending = pending_blocks.len();
+            pending_blocks.retain(|block| *block > finalized);
+            if pending_blocks.len() < pending {
+                *finalized_updates += 1;
+                sender
+                    .send(Subscription::Connected {
+                        finalized_updates: *finalized_updates,
+                    })
+                    .context("Subscription client dropped")?;
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: EthereumApi + Send + Sync> EthereumApi for SubscriptionClient<T> {
+    async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate> {
+        self.inner.get_starknet_state(address).await
+    }
+
+    async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+        self.inner.get_chain().await
+    }
+
+    async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+        self.inner.get_finalized_block_number().await
+    }
+
+    async fn get_l1_to_l2_message_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+        self.inner
+            .get_l1_to_l2_message_logs(address, from_block, to_block)
+            .await
+    }
+
+    async fn get_state_update_logs(
+        &self,
+        address: &H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> anyhow::Result<Vec<StateUpdateLog>> {
+        self.inner
+            .get_state_update_logs(address, from_block, to_block)
+            .await
+    }
+
+    async fn wait_for_state_update(&self, poll_interval: Duration) {
+        let Some(subscription) = &self.subscription else {
+            return tokio::time::sleep(poll_interval).await;
+        };
+
+        let mut subscription = subscription.lock().await;
+        if *subscription.borrow() == Subscription::Disconnected {
+            // Still wake up on reconnection, to catch up on the updates missed meanwhile.
+            _ = tokio::time::timeout(poll_interval, subscription.changed()).await;
+            return;
+        }
+
+        _ = tokio::time::timeout(MAX_POLL_INTERVAL, subscription.changed()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::MockEthereumApi;
+
+    /// Shares a mock, as the [SubscriptionClient] requires its client to be `Clone`.
+    #[derive(Clone)]
+    struct SharedMock(Arc<MockEthereumApi>);
+
+    #[async_trait::async_trait]
+    impl EthereumApi for SharedMock {
+        async fn get_starknet_state(&self, address: &H160) -> anyhow::Result<EthereumStateUpdate> {
+            self.0.get_starknet_state(address).await
+        }
+
+        async fn get_chain(&self) -> anyhow::Result<EthereumChain> {
+            self.0.get_chain().await
+        }
+
+        async fn get_finalized_block_number(&self) -> anyhow::Result<u64> {
+            self.0.get_finalized_block_number().await
+        }
+
+        async fn get_l1_to_l2_message_logs(
+            &self,
+            address: &H160,
+            from_block: u64,
+            to_block: u64,
+        ) -> anyhow::Result<Vec<L1ToL2MessageLog>> {
+            self.0
+                .get_l1_to_l2_message_logs(address, from_block, to_block)
+                .await
+        }
+
+        async fn get_state_update_logs(
+            &self,
+            address: &H160,
+            from_block: u64,
+            to_block: u64,
+        ) -> anyhow::Result<Vec<StateUpdateLog>> {
+            self.0
+                .get_state_update_logs(address, from_block, to_block)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn wakes_up_on_finalized_update() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = reqwest::Url::parse(&format!("ws://{}", listener.local_addr().unwrap())).unwrap();
+
+        // Logs a state update in block 10 and then announces a head, once the test is waiting.
+        let (waiting_sender, waiting) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            for id in [1, 2] {
+                socket.next().await.unwrap().unwrap();
+                let reply =
+                    serde_json::json!({"jsonrpc": "2.0", "id": id, "result": format!("0x{id}")});
+                socket.send(Message::Text(reply.to_string())).await.unwrap();
+            }
+
+            waiting.await.unwrap();
+            for (subscription, result) in [
+                (
+                    "0x2",
+                    serde_json::json!({"blockNumber": "0xa", "removed": false}),
+                ),
+                ("0x1", serde_json::json!({"number": "0xb"})),
+            ] {
+                let notification = serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "eth_subscription",
+                    "params": {"subscription": subscription, "result": result}
+                });
+                socket
+                    .send(Message::Text(notification.to_string()))
+                    .await
+                    .unwrap();
+            }
+
+            // Keep the websocket open.
+            socket.next().await;
+        });
+
+        let mut ethereum = MockEthereumApi::new();
+        ethereum
+            .expect_get_finalized_block_number()
+            .returning(|| Ok(10));
+        let ethereum = SharedMock(Arc::new(ethereum));
+        let client = SubscriptionClient::new(ethereum, url, H160::zero());
+        let poll_interval = Duration::from_secs(60);
+
+        // Returns on subscribing, as updates may have been missed before.
+        let start = Instant::now();
+        client.wait_for_state_update(poll_interval).await;
+        assert!(start.elapsed() < poll_interval);
+
+        waiting_sender.send(()).unwrap();
+        let start = Instant::now();
+        client.wait_for_state_update(poll_interval).await;
+        assert!(start.elapsed() < poll_interval);
+
+        server.abort();
+    }
+}
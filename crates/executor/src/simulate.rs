@@ -2,6 +2,7 @@ use blockifier::{
     transaction::transaction_execution::Transaction,
     transaction::{errors::TransactionExecutionError, transactions::ExecutableTransaction},
 };
+use pathfinder_common::TransactionHash;
 use primitive_types::U256;
 
 use crate::types::{
@@ -73,6 +74,48 @@ pub fn simulate(
     Ok(simulations)
 }
 
+/// Re-executes `transactions` in order on top of `execution_state` and returns the
+/// trace of each one.
+///
+/// Unlike [simulate] this charges fees and runs validation exactly like the sequencer
+/// did, and reverted transactions do not abort execution since their traces are
+/// still meaningful.
+pub fn trace(
+    mut execution_state: ExecutionState,
+    transactions: Vec<Transaction>,
+) -> Result<Vec<(TransactionHash, TransactionTrace)>, CallError> {
+    let block_number = execution_state.block_number;
+
+    let (mut state, block_context) = execution_state.starknet_state()?;
+
+    let mut traces = Vec::with_capacity(transactions.len());
+    for (transaction_idx, transaction) in transactions.into_iter().enumerate() {
+        let transaction_hash = super::transaction::transaction_hash(&transaction);
+        let _span =
+            tracing::debug_span!("trace", %transaction_hash, %block_number, %transaction_idx)
+                .entered();
+
+        let transaction_type = transaction_type(&transaction);
+
+        match transaction.execute(&mut state, &block_context, true, true) {
+            Ok(tx_info) => {
+                if let Some(revert_error) = &tx_info.revert_error {
+                    tracing::trace!(%revert_error, "Transaction reverted");
+                }
+
+                tracing::trace!(actual_fee=%tx_info.actual_fee.0, actual_resources=?tx_info.actual_resources, "Transaction re-execution finished");
+
+                traces.push((transaction_hash, to_trace(transaction_type, tx_info)?));
+            }
+            Err(error) => {
+                tracing::debug!(%error, %transaction_idx, "Transaction re-execution failed");
+                return Err(error.into());
+            }
+        }
+    }
+    Ok(traces)
+}
+
 enum TransactionType {
     Declare,
     DeployAccount,
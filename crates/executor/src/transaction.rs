@@ -1,8 +1,16 @@
+use anyhow::Context;
 use blockifier::transaction::transaction_execution::Transaction;
+use starknet_api::core::PatriciaKey;
 
 use pathfinder_common::TransactionHash;
 
-use super::felt::IntoFelt;
+use super::felt::{IntoFelt, IntoStarkFelt};
+
+/// The fee paid on L1 for an L1 handler transaction is not part of the transaction, so this
+/// is used instead when re-executing stored L1 handler transactions. It only has to be
+/// large enough for blockifier's L1 fee check to pass.
+const L1_HANDLER_PAID_FEE: starknet_api::transaction::Fee =
+    starknet_api::transaction::Fee(1_000_000_000_000);
 
 pub(super) fn transaction_hash(transaction: &Transaction) -> TransactionHash {
     TransactionHash(
@@ -24,3 +32,296 @@ pub(super) fn transaction_hash(transaction: &Transaction) -> TransactionHash {
         .into_felt(),
     )
 }
+
+/// Maps a transaction stored in the database to the executor's transaction type.
+///
+/// Class definitions of declare transactions are read from `db_transaction`. Deploy
+/// transactions are not supported by blockifier, and result in an error.
+pub fn map_gateway_transaction(
+    transaction: starknet_gateway_types::reply::transaction::Transaction,
+    db_transaction: &pathfinder_storage::Transaction<'_>,
+) -> anyhow::Result<Transaction> {
+    use starknet_api::hash::StarkFelt;
+
+    let tx_hash = starknet_api::transaction::TransactionHash(transaction.hash().0.into_starkfelt());
+
+    tracing::trace!(%tx_hash, "Converting transaction");
+
+    match transaction {
+        starknet_gateway_types::reply::transaction::Transaction::Declare(tx) => match tx {
+            starknet_gateway_types::reply::transaction::DeclareTransaction::V0(tx) => {
+                let class_definition = db_transaction
+                    .class_definition(tx.class_hash)?
+                    .context("Fetching class definition")?;
+
+                let contract_class =
+                    crate::class::parse_deprecated_class_definition(class_definition)?;
+
+                let tx = starknet_api::transaction::DeclareTransactionV0V1 {
+                    max_fee: starknet_api::transaction::Fee(u128::from_be_bytes(
+                        tx.max_fee.0.to_be_bytes()[16..].try_into().unwrap(),
+                    )),
+                    signature: starknet_api::transaction::TransactionSignature(
+                        tx.signature
+                            .into_iter()
+                            .map(|s| s.0.into_starkfelt())
+                            .collect(),
+                    ),
+                    nonce: starknet_api::core::Nonce(tx.nonce.0.into_starkfelt()),
+                    class_hash: starknet_api::core::ClassHash(tx.class_hash.0.into_starkfelt()),
+                    sender_address: starknet_api::core::ContractAddress(
+                        PatriciaKey::try_from(tx.sender_address.get().into_starkfelt())
+                            .expect("No sender address overflow expected"),
+                    ),
+                };
+
+                let tx = Transaction::from_api(
+                    starknet_api::transaction::Transaction::Declare(
+                        starknet_api::transaction::DeclareTransaction::V0(tx),
+                    ),
+                    tx_hash,
+                    Some(contract_class),
+                    None,
+                    None,
+                )?;
+
+                Ok(tx)
+            }
+            starknet_gateway_types::reply::transaction::DeclareTransaction::V1(tx) => {
+                let class_definition = db_transaction
+                    .class_definition(tx.class_hash)?
+                    .context("Fetching class definition")?;
+
+                let contract_class =
+                    crate::class::parse_deprecated_class_definition(class_definition)?;
+
+                let tx = starknet_api::transaction::DeclareTransactionV0V1 {
+                    max_fee: starknet_api::transaction::Fee(u128::from_be_bytes(
+                        tx.max_fee.0.to_be_bytes()[16..].try_into().unwrap(),
+                    )),
+                    signature: starknet_api::transaction::TransactionSignature(
+                        tx.signature
+                            .into_iter()
+                            .map(|s| s.0.into_starkfelt())
+                            .collect(),
+                    ),
+                    nonce: starknet_api::core::Nonce(tx.nonce.0.into_starkfelt()),
+                    class_hash: starknet_api::core::ClassHash(tx.class_hash.0.into_starkfelt()),
+                    sender_address: starknet_api::core::ContractAddress(
+                        PatriciaKey::try_from(tx.sender_address.get().into_starkfelt())
+                            .expect("No sender address overflow expected"),
+                    ),
+                };
+
+                let tx = Transaction::from_api(
+                    starknet_api::transaction::Transaction::Declare(
+                        starknet_api::transaction::DeclareTransaction::V1(tx),
+                    ),
+                    tx_hash,
+                    Some(contract_class),
+                    None,
+                    None,
+                )?;
+
+                Ok(tx)
+            }
+            starknet_gateway_types::reply::transaction::DeclareTransaction::V2(tx) => {
+                let casm_definition = db_transaction
+                    .casm_definition(tx.class_hash)?
+                    .context("Fetching class definition")?;
+
+                let contract_class = crate::class::parse_casm_definition(casm_definition)?;
+
+                let tx = starknet_api::transaction::DeclareTransactionV2 {
+                    max_fee: starknet_api::transaction::Fee(u128::from_be_bytes(
+                        tx.max_fee.0.to_be_bytes()[16..].try_into().unwrap(),
+                    )),
+                    signature: starknet_api::transaction::TransactionSignature(
+                        tx.signature
+                            .into_iter()
+                            .map(|s| s.0.into_starkfelt())
+                            .collect(),
+                    ),
+                    nonce: starknet_api::core::Nonce(tx.nonce.0.into_starkfelt()),
+                    class_hash: starknet_api::core::ClassHash(tx.class_hash.0.into_starkfelt()),
+                    sender_address: starknet_api::core::ContractAddress(
+                        PatriciaKey::try_from(tx.sender_address.get().into_starkfelt())
+                            .expect("No sender address overflow expected"),
+                    ),
+                    compiled_class_hash: starknet_api::core::CompiledClassHash(
+                        tx.compiled_class_hash.0.into_starkfelt(),
+                    ),
+                };
+
+                let tx = Transaction::from_api(
+                    starknet_api::transaction::Transaction::Declare(
+                        starknet_api::transaction::DeclareTransaction::V2(tx),
+                    ),
+                    tx_hash,
+                    Some(contract_class),
+                    None,
+                    None,
+                )?;
+
+                Ok(tx)
+            }
+        },
+        starknet_gateway_types::reply::transaction::Transaction::Deploy(_) => {
+            anyhow::bail!("Deploy transactions are not supported by blockifier")
+        }
+        starknet_gateway_types::reply::transaction::Transaction::DeployAccount(tx) => {
+            let contract_address = starknet_api::core::ContractAddress(
+                PatriciaKey::try_from(tx.contract_address.get().into_starkfelt())
+                    .expect("No contract address overflow expected"),
+            );
+
+            let tx = starknet_api::transaction::DeployAccountTransaction {
+                max_fee: starknet_api::transaction::Fee(u128::from_be_bytes(
+                    tx.max_fee.0.to_be_bytes()[16..].try_into().unwrap(),
+                )),
+                version: starknet_api::transaction::TransactionVersion(
+                    StarkFelt::new(tx.version.0.as_fixed_bytes().to_owned())
+                        .expect("No transaction version overflow expected"),
+                ),
+                signature: starknet_api::transaction::TransactionSignature(
+                    tx.signature
+                        .into_iter()
+                        .map(|s| s.0.into_starkfelt())
+                        .collect(),
+                ),
+                nonce: starknet_api::core::Nonce(tx.nonce.0.into_starkfelt()),
+                class_hash: starknet_api::core::ClassHash(tx.class_hash.0.into_starkfelt()),
+
+                contract_address_salt: starknet_api::transaction::ContractAddressSalt(
+                    tx.contract_address_salt.0.into_starkfelt(),
+                ),
+                constructor_calldata: starknet_api::transaction::Calldata(std::sync::Arc::new(
+                    tx.constructor_calldata
+                        .into_iter()
+                        .map(|c| c.0.into_starkfelt())
+                        .collect(),
+                )),
+            };
+
+            let tx = Transaction::from_api(
+                starknet_api::transaction::Transaction::DeployAccount(tx),
+                tx_hash,
+                None,
+                None,
+                Some(contract_address),
+            )?;
+
+            Ok(tx)
+        }
+        starknet_gateway_types::reply::transaction::Transaction::Invoke(tx) => match tx {
+            starknet_gateway_types::reply::transaction::InvokeTransaction::V0(tx) => {
+                let tx = starknet_api::transaction::InvokeTransactionV0 {
+                    // TODO: maybe we should store tx.max_fee as u128 internally?
+                    max_fee: starknet_api::transaction::Fee(u128::from_be_bytes(
+                        tx.max_fee.0.to_be_bytes()[16..].try_into().unwrap(),
+                    )),
+                    signature: starknet_api::transaction::TransactionSignature(
+                        tx.signature
+                            .into_iter()
+                            .map(|s| s.0.into_starkfelt())
+                            .collect(),
+                    ),
+                    contract_address: starknet_api::core::ContractAddress(
+                        PatriciaKey::try_from(tx.sender_address.get().into_starkfelt())
+                            .expect("No sender address overflow expected"),
+                    ),
+                    entry_point_selector: starknet_api::core::EntryPointSelector(
+                        tx.entry_point_selector.0.into_starkfelt(),
+                    ),
+                    calldata: starknet_api::transaction::Calldata(std::sync::Arc::new(
+                        tx.calldata
+                            .into_iter()
+                            .map(|c| c.0.into_starkfelt())
+                            .collect(),
+                    )),
+                };
+
+                let tx = Transaction::from_api(
+                    starknet_api::transaction::Transaction::Invoke(
+                        starknet_api::transaction::InvokeTransaction::V0(tx),
+                    ),
+                    tx_hash,
+                    None,
+                    None,
+                    None,
+                )?;
+
+                Ok(tx)
+            }
+            starknet_gateway_types::reply::transaction::InvokeTransaction::V1(tx) => {
+                let tx = starknet_api::transaction::InvokeTransactionV1 {
+                    // TODO: maybe we should store tx.max_fee as u128 internally?
+                    max_fee: starknet_api::transaction::Fee(u128::from_be_bytes(
+                        tx.max_fee.0.to_be_bytes()[16..].try_into().unwrap(),
+                    )),
+                    signature: starknet_api::transaction::TransactionSignature(
+                        tx.signature
+                            .into_iter()
+                            .map(|s| s.0.into_starkfelt())
+                            .collect(),
+                    ),
+                    nonce: starknet_api::core::Nonce(tx.nonce.0.into_starkfelt()),
+                    sender_address: starknet_api::core::ContractAddress(
+                        PatriciaKey::try_from(tx.sender_address.get().into_starkfelt())
+                            .expect("No sender address overflow expected"),
+                    ),
+                    calldata: starknet_api::transaction::Calldata(std::sync::Arc::new(
+                        tx.calldata
+                            .into_iter()
+                            .map(|c| c.0.into_starkfelt())
+                            .collect(),
+                    )),
+                };
+
+                let tx = Transaction::from_api(
+                    starknet_api::transaction::Transaction::Invoke(
+                        starknet_api::transaction::InvokeTransaction::V1(tx),
+                    ),
+                    tx_hash,
+                    None,
+                    None,
+                    None,
+                )?;
+
+                Ok(tx)
+            }
+        },
+        starknet_gateway_types::reply::transaction::Transaction::L1Handler(tx) => {
+            let tx = starknet_api::transaction::L1HandlerTransaction {
+                version: starknet_api::transaction::TransactionVersion(
+                    StarkFelt::new(tx.version.0.as_fixed_bytes().to_owned())
+                        .expect("No transaction version overflow expected"),
+                ),
+                nonce: starknet_api::core::Nonce(tx.nonce.0.into_starkfelt()),
+                contract_address: starknet_api::core::ContractAddress(
+                    PatriciaKey::try_from(tx.contract_address.get().into_starkfelt())
+                        .expect("No contract address overflow expected"),
+                ),
+                entry_point_selector: starknet_api::core::EntryPointSelector(
+                    tx.entry_point_selector.0.into_starkfelt(),
+                ),
+                calldata: starknet_api::transaction::Calldata(std::sync::Arc::new(
+                    tx.calldata
+                        .into_iter()
+                        .map(|c| c.0.into_starkfelt())
+                        .collect(),
+                )),
+            };
+
+            let tx = Transaction::from_api(
+                starknet_api::transaction::Transaction::L1Handler(tx),
+                tx_hash,
+                None,
+                Some(L1_HANDLER_PAID_FEE),
+                None,
+            )?;
+
+            Ok(tx)
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::io::Read;
+
+use anyhow::Context;
+use pathfinder_common::hash::PedersenHash;
+use pathfinder_common::trie::TrieNode;
+use pathfinder_common::{
+    ClassCommitment, ClassHash, ContractNonce, ContractRoot, StateCommitment, StorageCommitment,
+};
+use pathfinder_merkle_tree::contract_state::calculate_contract_state_hash;
+use pathfinder_merkle_tree::proof::{verify_proof, Membership};
+use stark_hash::Felt;
+
+/// The output of `pathfinder_getProof`.
+#[derive(serde::Deserialize)]
+struct GetProofOutput {
+    class_commitment: Option<Felt>,
+    contract_proof: Vec<ProofNode>,
+    contract_data: Option<ContractData>,
+}
+
+#[derive(serde::Deserialize)]
+struct ContractData {
+    class_hash: Felt,
+    nonce: Felt,
+    root: Felt,
+    storage_proofs: Vec<Vec<ProofNode>>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ProofNode {
+    Binary { left: Felt, right: Felt },
+    Edge { path: Path, child: Felt },
+}
+
+#[derive(serde::Deserialize)]
+struct Path {
+    value: Felt,
+    len: usize,
+}
+
+impl TryFrom<ProofNode> for TrieNode {
+    type Error = anyhow::Error;
+
+    fn try_from(node: ProofNode) -> Result<Self, Self::Error> {
+        Ok(match node {
+            ProofNode::Binary { left, right } => TrieNode::Binary { left, right },
+            ProofNode::Edge { path, child } => {
+                let bits = path.value.view_bits();
+                let start = bits
+                    .len()
+                    .checked_sub(path.len)
+                    .context("Edge path is longer than 251 bits")?;
+                TrieNode::Edge {
+                    child,
+                    path: bits[start..].to_bitvec(),
+                }
+            }
+        })
+    }
+}
+
+fn parse_proof(nodes: Vec<ProofNode>) -> anyhow::Result<Vec<TrieNode>> {
+    nodes.into_iter().map(TrieNode::try_from).collect()
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args();
+    let myself = args.next().unwrap_or_else(|| String::from("verify_proof"));
+    let args = args.collect::<Vec<_>>();
+
+    if args.len() < 2 {
+        println!("USAGE: {myself} state_commitment contract_address [storage_key=value ...]");
+        println!("verifies the output of pathfinder_getProof, read from stdin, against a trusted state commitment.");
+        println!("storage keys must be given in the same order as they were requested.");
+        std::process::exit(1);
+    }
+
+    let state_commitment =
+        Felt::from_hex_str(&args[0]).context("Parsing state commitment parameter")?;
+    let contract_address =
+        Felt::from_hex_str(&args[1]).context("Parsing contract address parameter")?;
+    let storage = args[2..]
+        .iter()
+        .map(|arg| {
+            let (key, value) = arg
+                .split_once('=')
+                .with_context(|| format!("Storage parameter {arg} is not of the form key=value"))?;
+            let key = Felt::from_hex_str(key).context("Parsing storage key")?;
+            let value = Felt::from_hex_str(value).context("Parsing storage value")?;
+            anyhow::Ok((key, value))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .context("Reading stdin")?;
+    let mut input: serde_json::Value = serde_json::from_str(&input).context("Parsing input")?;
+    // Accept both the full JSON-RPC response and only its result.
+    if let Some(result) = input.get_mut("result") {
+        input = result.take();
+    }
+    let output: GetProofOutput =
+        serde_json::from_value(input).context("Parsing pathfinder_getProof output")?;
+
+    let contract_proof = parse_proof(output.contract_proof)?;
+    let storage_commitment = contract_proof
+        .first()
+        .map(|node| node.hash::<PedersenHash>())
+        .unwrap_or_default();
+    let class_commitment = output.class_commitment.unwrap_or_default();
+
+    let calculated = StateCommitment::calculate(
+        StorageCommitment(storage_commitment),
+        ClassCommitment(class_commitment),
+    );
+    anyhow::ensure!(
+        calculated.0 == state_commitment,
+        "State commitment mismatch: proof leads to {}",
+        calculated.0
+    );
+    println!("state commitment: {state_commitment:x}");
+
+    let contract_state_hash = output.contract_data.as_ref().map(|data| {
+        calculate_contract_state_hash(
+            ClassHash(data.class_hash),
+            ContractRoot(data.root),
+            ContractNonce(data.nonce),
+        )
+        .0
+    });
+    let membership = verify_proof::<PedersenHash>(
+        storage_commitment,
+        contract_address.view_bits(),
+        contract_state_hash,
+        &contract_proof,
+    )
+    .context("Verifying contract proof")?;
+
+    let data = match (membership, output.contract_data) {
+        (Membership::Member(_), Some(data)) => data,
+        (Membership::NonMember, None) => {
+            println!("contract {contract_address:x}: does not exist");
+            anyhow::ensure!(storage.is_empty(), "Contract has no storage to verify");
+            return Ok(());
+        }
+        (Membership::Member(_), None) => {
+            anyhow::bail!("Contract exists but its data is missing")
+        }
+        (Membership::NonMember, Some(_)) => {
+            anyhow::bail!("Contract data is given but the contract does not exist")
+        }
+    };
+    println!(
+        "contract {contract_address:x}: class hash {:x}, nonce {:x}",
+        data.class_hash, data.nonce
+    );
+
+    anyhow::ensure!(
+        storage.len() == data.storage_proofs.len(),
+        "Expected {} storage proofs but got {}",
+        storage.len(),
+        data.storage_proofs.len()
+    );
+
+    for ((key, value), proof) in storage.into_iter().zip(data.storage_proofs) {
+        let proof = parse_proof(proof)?;
+        // Storage slots with a zero value are not part of the tree.
+        let expected = (value != Felt::ZERO).then_some(value);
+        let membership = verify_proof::<PedersenHash>(data.root, key.view_bits(), expected, &proof)
+            .with_context(|| format!("Verifying storage proof for key {key:x}"))?;
+
+        match membership {
+            Membership::Member(value) => println!("storage {key:x}: {value:x}"),
+            Membership::NonMember => {
+                anyhow::ensure!(
+                    expected.is_none(),
+                    "Storage key {key:x} is not set but expected {value:x}"
+                );
+                println!("storage {key:x}: 0x0 (not set)");
+            }
+        }
+    }
+
+    Ok(())
+}
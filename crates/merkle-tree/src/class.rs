@@ -40,6 +40,16 @@ impl<'tx> ClassCommitmentTree<'tx> {
         self.tree.set(&self.storage, class.view_bits(), value.0)
     }
 
+    pub fn get(&self, class: SierraHash) -> anyhow::Result<Option<ClassCommitmentLeafHash>> {
+        let value = self.tree.get(&self.storage, class.view_bits())?;
+        Ok(value.map(ClassCommitmentLeafHash))
+    }
+
+    /// Generates a proof for the given `class`. See [`MerkleTree::get_proof`].
+    pub fn get_proof(&self, class: &SierraHash) -> anyhow::Result<Vec<TrieNode>> {
+        self.tree.get_proof(&self.storage, class.view_bits())
+    }
+
     /// Commits the changes and calculates the new node hashes. Returns the new commitment and
     /// any potentially newly created nodes.
     pub fn commit(self) -> anyhow::Result<(ClassCommitment, HashMap<Felt, TrieNode>)> {
@@ -3,13 +3,15 @@ use std::collections::HashMap;
 use crate::{ContractsStorageTree, StorageCommitmentTree};
 use anyhow::Context;
 use pathfinder_common::{
-    ClassHash, ContractAddress, ContractNonce, ContractRoot, ContractStateHash, StorageAddress,
-    StorageValue,
+    BlockNumber, ClassHash, ContractAddress, ContractNonce, ContractRoot, ContractStateHash,
+    StorageAddress, StorageValue,
 };
-use pathfinder_storage::Transaction;
+use pathfinder_storage::{Transaction, TrieRootChange};
 use stark_hash::{stark_hash, Felt};
 
 /// Updates a contract's state with and returns the resulting [ContractStateHash].
+///
+/// A change of the contract's storage root is recorded as part of `block`.
 pub fn update_contract_state(
     contract_address: ContractAddress,
     updates: &HashMap<StorageAddress, StorageValue>,
@@ -17,6 +19,7 @@ pub fn update_contract_state(
     new_class_hash: Option<ClassHash>,
     storage_commitment_tree: &StorageCommitmentTree<'_>,
     transaction: &Transaction<'_>,
+    block: BlockNumber,
     verify_hashes: bool,
 ) -> anyhow::Result<ContractStateHash> {
     // Update the contract state tree.
@@ -56,6 +59,15 @@ pub fn update_contract_state(
             .insert_contract_trie(contract_root, &nodes)
             .context("Persisting contract trie")?;
         tracing::trace!(contract=%contract_address, new_nodes=%count, "Persisted contract trie");
+        transaction
+            .insert_trie_root_change(
+                block,
+                TrieRootChange::Contract {
+                    old: old_root,
+                    new: contract_root,
+                },
+            )
+            .context("Recording contract trie root change")?;
 
         contract_root
     } else {
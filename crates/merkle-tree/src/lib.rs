@@ -1,5 +1,6 @@
 pub mod contract_state;
 pub mod merkle_node;
+pub mod proof;
 pub mod tree;
 
 mod class;
@@ -0,0 +1,259 @@
+//! Verification of the proofs generated by [MerkleTree::get_proof](crate::tree::MerkleTree::get_proof).
+//!
+//! This does not require access to any storage and can therefore be used to check a proof
+//! returned by an untrusted node, such as the output of `pathfinder_getProof`.
+
+use bitvec::prelude::Msb0;
+use bitvec::slice::BitSlice;
+use pathfinder_common::hash::FeltHash;
+use pathfinder_common::trie::TrieNode;
+use stark_hash::Felt;
+
+use crate::merkle_node::Direction;
+
+/// The height of all of Starknet's tries.
+const HEIGHT: usize = 251;
+
+/// The outcome of a successfully verified proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Membership {
+    /// The key is part of the tree and maps to the given value.
+    Member(Felt),
+    /// The key is not part of the tree.
+    NonMember,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ProofError {
+    #[error("Key has length {0} but must have length {HEIGHT}")]
+    InvalidKeyLength(usize),
+    #[error("Hash of node {index} is {actual} but expected {expected}")]
+    HashMismatch {
+        index: usize,
+        expected: Felt,
+        actual: Felt,
+    },
+    #[error("Node {index} continues past the end of the key")]
+    PathTooLong { index: usize },
+    #[error("Proof ends before reaching a leaf or diverging from the key")]
+    Incomplete,
+    #[error("Proof contains {extra} nodes past the end of the key's path")]
+    TrailingNodes { extra: usize },
+    #[error("Key maps to {actual} but expected {expected}")]
+    ValueMismatch { expected: Felt, actual: Felt },
+}
+
+/// Verifies that `proof` is a valid proof for `key` in the tree with the given `root`, using the
+/// hash function `H` of that tree, i.e. [PedersenHash](pathfinder_common::hash::PedersenHash)
+/// for storage tries and [PoseidonHash](pathfinder_common::hash::PoseidonHash) for the class trie.
+///
+/// If `value` is given then a proof of membership must also match it. Note that this means
+/// a proof of non-membership is still returned as [Membership::NonMember] and it is up to the caller
+/// to decide whether that is acceptable, e.g. for storage where absent keys have a zero value.
+///
+/// The algorithm follows this logic:
+/// 1. init expected_hash <- root hash
+/// 2. loop over nodes: current <- nodes[i]
+///    1. verify the current node's hash matches expected_hash (if not then we have a bad proof)
+///    2. move towards the target - if current is:
+///       1. binary node then choose the child that moves towards the target, else if
+///       2. edge node then check the path against the target bits
+///          1. If it matches then proceed with the child, else
+///          2. if it does not match then we now have a proof that the target does not exist
+///    3. nibble off target bits according to which child you got in (2). If all bits are gone then you
+///       have reached the target and the child hash is the value you wanted and the proof is complete.
+///    4. set expected_hash <- to the child hash
+/// 3. check that we have reached the leaf and that expected_hash is `value`
+pub fn verify_proof<H: FeltHash>(
+    root: Felt,
+    key: &BitSlice<u8, Msb0>,
+    value: Option<Felt>,
+    proof: &[TrieNode],
+) -> Result<Membership, ProofError> {
+    if key.len() != HEIGHT {
+        return Err(ProofError::InvalidKeyLength(key.len()));
+    }
+
+    // An empty tree contains nothing.
+    if proof.is_empty() && root == Felt::ZERO {
+        return Ok(Membership::NonMember);
+    }
+
+    let mut expected_hash = root;
+    let mut remaining_path: &BitSlice<u8, Msb0> = key;
+
+    for (index, node) in proof.iter().enumerate() {
+        if remaining_path.is_empty() {
+            return Err(ProofError::TrailingNodes {
+                extra: proof.len() - index,
+            });
+        }
+
+        let actual = node.hash::<H>();
+        if actual != expected_hash {
+            return Err(ProofError::HashMismatch {
+                index,
+                expected: expected_hash,
+                actual,
+            });
+        }
+
+        match node {
+            TrieNode::Binary { left, right } => {
+                expected_hash = match Direction::from(remaining_path[0]) {
+                    Direction::Left => *left,
+                    Direction::Right => *right,
+                };
+
+                remaining_path = &remaining_path[1..];
+            }
+            TrieNode::Edge { child, path } => {
+                if path.len() > remaining_path.len() {
+                    return Err(ProofError::PathTooLong { index });
+                }
+
+                if path != &remaining_path[..path.len()] {
+                    // The proof moved towards the key for as long as possible and hashes up to
+                    // the root, so the key cannot be in this tree.
+                    if index + 1 != proof.len() {
+                        return Err(ProofError::TrailingNodes {
+                            extra: proof.len() - index - 1,
+                        });
+                    }
+                    return Ok(Membership::NonMember);
+                }
+
+                expected_hash = *child;
+                remaining_path = &remaining_path[path.len()..];
+            }
+        }
+    }
+
+    if !remaining_path.is_empty() {
+        return Err(ProofError::Incomplete);
+    }
+
+    match value {
+        Some(value) if value != expected_hash => Err(ProofError::ValueMismatch {
+            expected: value,
+            actual: expected_hash,
+        }),
+        _ => Ok(Membership::Member(expected_hash)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitvec::bitvec;
+    use pathfinder_common::felt;
+    use pathfinder_common::hash::PedersenHash;
+
+    /// A tree containing only `key` -> `value`, i.e. a single edge node from the root to the leaf.
+    fn single_leaf(key: Felt, value: Felt) -> (Felt, Vec<TrieNode>) {
+        let edge = TrieNode::Edge {
+            child: value,
+            path: key.view_bits().to_bitvec(),
+        };
+        (edge.hash::<PedersenHash>(), vec![edge])
+    }
+
+    #[test]
+    fn empty_tree() {
+        let key = felt!("0x1");
+        let result = verify_proof::<PedersenHash>(Felt::ZERO, key.view_bits(), None, &[]);
+        assert_eq!(result, Ok(Membership::NonMember));
+    }
+
+    #[test]
+    fn single_leaf_membership() {
+        let key = felt!("0x1");
+        let value = felt!("0xabc");
+        let (root, proof) = single_leaf(key, value);
+
+        let result = verify_proof::<PedersenHash>(root, key.view_bits(), Some(value), &proof);
+        assert_eq!(result, Ok(Membership::Member(value)));
+
+        let result = verify_proof::<PedersenHash>(root, key.view_bits(), None, &proof);
+        assert_eq!(result, Ok(Membership::Member(value)));
+
+        let result =
+            verify_proof::<PedersenHash>(root, key.view_bits(), Some(felt!("0xdef")), &proof);
+        assert_eq!(
+            result,
+            Err(ProofError::ValueMismatch {
+                expected: felt!("0xdef"),
+                actual: value
+            })
+        );
+    }
+
+    #[test]
+    fn single_leaf_non_membership() {
+        let (root, proof) = single_leaf(felt!("0x1"), felt!("0xabc"));
+
+        let key = felt!("0x2");
+        let result = verify_proof::<PedersenHash>(root, key.view_bits(), None, &proof);
+        assert_eq!(result, Ok(Membership::NonMember));
+    }
+
+    #[test]
+    fn missing_nodes() {
+        let key = felt!("0x1");
+        let (_, proof) = single_leaf(key, felt!("0xabc"));
+
+        // Only the first half of the edge is present.
+        let TrieNode::Edge { child, path } = &proof[0] else {
+            unreachable!()
+        };
+        let half = TrieNode::Edge {
+            child: *child,
+            path: path[..100].to_bitvec(),
+        };
+        let root = half.hash::<PedersenHash>();
+
+        let result = verify_proof::<PedersenHash>(root, key.view_bits(), None, &[half]);
+        assert_eq!(result, Err(ProofError::Incomplete));
+    }
+
+    #[test]
+    fn trailing_nodes() {
+        let key = felt!("0x1");
+        let (root, mut proof) = single_leaf(key, felt!("0xabc"));
+        proof.push(TrieNode::Binary {
+            left: Felt::ZERO,
+            right: Felt::ZERO,
+        });
+
+        let result = verify_proof::<PedersenHash>(root, key.view_bits(), None, &proof);
+        assert_eq!(result, Err(ProofError::TrailingNodes { extra: 1 }));
+    }
+
+    #[test]
+    fn path_too_long() {
+        let key = felt!("0x1");
+        let binary = TrieNode::Binary {
+            left: felt!("0x2"),
+            right: felt!("0x3"),
+        };
+        let edge = TrieNode::Edge {
+            child: felt!("0x4"),
+            path: bitvec![u8, Msb0; 0; 251],
+        };
+        let binary = TrieNode::Binary {
+            left: edge.hash::<PedersenHash>(),
+            right: binary.hash::<PedersenHash>(),
+        };
+        let root = binary.hash::<PedersenHash>();
+
+        let result = verify_proof::<PedersenHash>(root, key.view_bits(), None, &[binary, edge]);
+        assert_eq!(result, Err(ProofError::PathTooLong { index: 1 }));
+    }
+
+    #[test]
+    fn invalid_key_length() {
+        let key = bitvec![u8, Msb0; 0; 250];
+        let result = verify_proof::<PedersenHash>(Felt::ZERO, &key, None, &[]);
+        assert_eq!(result, Err(ProofError::InvalidKeyLength(250)));
+    }
+}
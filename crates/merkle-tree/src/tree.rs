@@ -1490,7 +1490,7 @@ mod tests {
         use pathfinder_common::hash::{FeltHash, PedersenHash};
         use pathfinder_common::trie::TrieNode;
 
-        use super::{Direction, MerkleTree, TestStorage, TestTree};
+        use super::{MerkleTree, TestStorage, TestTree};
         use bitvec::prelude::Msb0;
         use bitvec::slice::BitSlice;
         use pathfinder_common::felt;
@@ -1502,82 +1502,17 @@ mod tests {
             NonMember,
         }
 
-        /// Verifies that the key `key` with value `value` is indeed part of the MPT that has root
-        /// `root`, given `proofs`.
-        /// Supports proofs of non-membership as well as proof of membership: this function returns
-        /// an enum corresponding to the membership of `value`, or returns `None` in case of a hash mismatch.
-        /// The algorithm follows this logic:
-        /// 1. init expected_hash <- root hash
-        /// 2. loop over nodes: current <- nodes[i]
-        ///    1. verify the current node's hash matches expected_hash (if not then we have a bad proof)
-        ///    2. move towards the target - if current is:
-        ///       1. binary node then choose the child that moves towards the target, else if
-        ///       2. edge node then check the path against the target bits
-        ///          1. If it matches then proceed with the child, else
-        ///          2. if it does not match then we now have a proof that the target does not exist
-        ///    3. nibble off target bits according to which child you got in (2). If all bits are gone then you
-        ///       have reached the target and the child hash is the value you wanted and the proof is complete.
-        ///    4. set expected_hash <- to the child hash
-        /// 3. check that the expected_hash is `value` (we should've reached the leaf)
+        /// Verifies the proof using [crate::proof::verify_proof], returning `None` if the proof is invalid
+        /// or does not match `value`.
         fn verify_proof(
             root: Felt,
             key: &BitSlice<u8, Msb0>,
             value: Felt,
             proofs: &[TrieNode],
         ) -> Option<Membership> {
-            // Protect from ill-formed keys
-            if key.len() != 251 {
-                return None;
-            }
-
-            let mut expected_hash = root;
-            let mut remaining_path: &BitSlice<u8, Msb0> = key;
-
-            for proof_node in proofs.iter() {
-                // Hash mismatch? Return None.
-                if proof_node.hash::<PedersenHash>() != expected_hash {
-                    return None;
-                }
-                match proof_node {
-                    TrieNode::Binary { left, right } => {
-                        // Direction will always correspond to the 0th index
-                        // because we're removing bits on every iteration.
-                        let direction = Direction::from(remaining_path[0]);
-
-                        // Set the next hash to be the left or right hash,
-                        // depending on the direction
-                        expected_hash = match direction {
-                            Direction::Left => *left,
-                            Direction::Right => *right,
-                        };
-
-                        // Advance by a single bit
-                        remaining_path = &remaining_path[1..];
-                    }
-                    TrieNode::Edge { child, path } => {
-                        if path != &remaining_path[..path.len()] {
-                            // If paths don't match, we've found a proof of non membership because we:
-                            // 1. Correctly moved towards the target insofar as is possible, and
-                            // 2. hashing all the nodes along the path does result in the root hash, which means
-                            // 3. the target definitely does not exist in this tree
-                            return Some(Membership::NonMember);
-                        }
-
-                        // Set the next hash to the child's hash
-                        expected_hash = *child;
-
-                        // Advance by the whole edge path
-                        remaining_path = &remaining_path[path.len()..];
-                    }
-                }
-            }
-
-            // At this point, we should reach `value` !
-            if expected_hash == value {
-                Some(Membership::Member)
-            } else {
-                // Hash mismatch. Return `None`.
-                None
+            match crate::proof::verify_proof::<PedersenHash>(root, key, Some(value), proofs).ok()? {
+                crate::proof::Membership::Member(_) => Some(Membership::Member),
+                crate::proof::Membership::NonMember => Some(Membership::NonMember),
             }
         }
 
@@ -1,12 +1,13 @@
 use clap::{CommandFactory, Parser};
 #[cfg(feature = "p2p")]
 use p2p::libp2p::Multiaddr;
-use pathfinder_common::AllowedOrigins;
-use pathfinder_storage::JournalMode;
+use pathfinder_common::{AllowedOrigins, ContractAddress};
+use pathfinder_rpc::middleware::rate_limit::RateLimitConfig;
+use pathfinder_storage::{EventIndexPolicy, JournalMode, TriePruneMode};
 use reqwest::Url;
 use std::collections::HashSet;
 use std::net::SocketAddr;
-use std::num::NonZeroUsize;
+use std::num::{NonZeroU64, NonZeroUsize};
 use std::path::PathBuf;
 
 use pathfinder_common::consts::VERGEN_GIT_DESCRIBE;
@@ -41,14 +42,44 @@ struct Cli {
         long = "ethereum.url",
         long_help = r"This should point to the HTTP RPC endpoint of your Ethereum entry-point, typically a local Ethereum client or a hosted gateway service such as Infura or Cloudflare.
 
+A comma separated list of endpoints may be given, in which case requests move on to the next endpoint whenever one fails.
+
 Examples:
     infura: https://goerli.infura.io/v3/<PROJECT_ID>
-    geth:   https://localhost:8545",
-        value_name = "HTTP(s) URL",
+    geth:   https://localhost:8545
+    a list: https://localhost:8545,https://goerli.infura.io/v3/<PROJECT_ID>",
+        value_name = "HTTP(s) URL LIST",
         value_hint = clap::ValueHint::Url,
+        value_delimiter = ',',
+        required = true,
         env = "PATHFINDER_ETHEREUM_API_URL", 
     )]
-    ethereum_url: Url,
+    ethereum_url: Vec<Url>,
+
+    #[arg(
+        long = "ethereum.quorum",
+        long_help = r"The number of Ethereum endpoints which must agree on the Starknet state, the finalized block and the logs of the core contract before they are accepted.
+
+This cannot exceed the number of endpoints given by --ethereum.url.",
+        default_value = "1",
+        env = "PATHFINDER_ETHEREUM_QUORUM"
+    )]
+    ethereum_quorum: NonZeroUsize,
+
+    #[arg(
+        long = "ethereum.ws-url",
+        long_help = r"The optional websocket RPC endpoint of your Ethereum entry-point, used to subscribe to new heads and to the state updates of the Starknet core contract.
+
+Blocks are then reported as accepted on L1 as soon as their state update is finalized, instead of polling for it. Polling resumes while the websocket is down.
+
+Examples:
+    infura: wss://goerli.infura.io/ws/v3/<PROJECT_ID>
+    geth:   ws://localhost:8546",
+        value_name = "WS(s) URL",
+        value_hint = clap::ValueHint::Url,
+        env = "PATHFINDER_ETHEREUM_WS_URL"
+    )]
+    ethereum_ws_url: Option<Url>,
 
     #[arg(
         long = "http-rpc",
@@ -106,6 +137,63 @@ Examples:
     )]
     execution_concurrency: Option<std::num::NonZeroU32>,
 
+    #[arg(
+        long = "rpc.rate-limit.capacity",
+        long_help = r"Enables per-client rate limiting of JSON-RPC requests, with each client allowed a burst of this many request units.
+
+Most methods cost a single unit, while expensive ones such as starknet_getEvents, starknet_call, starknet_estimateFee and starknet_simulateTransactions cost more.
+
+Clients are identified by their address, or by a known API key. Requests from trusted proxies are identified by their X-Forwarded-For or X-Real-IP header instead.",
+        value_name = "UNITS",
+        env = "PATHFINDER_RPC_RATE_LIMIT_CAPACITY"
+    )]
+    rpc_rate_limit_capacity: Option<std::num::NonZeroU32>,
+
+    #[arg(
+        long = "rpc.rate-limit.refill-rate",
+        long_help = "The number of request units each client regains per second when rate limiting is enabled",
+        value_name = "UNITS",
+        default_value = "100",
+        env = "PATHFINDER_RPC_RATE_LIMIT_REFILL_RATE"
+    )]
+    rpc_rate_limit_refill_rate: std::num::NonZeroU32,
+
+    #[arg(
+        long = "rpc.rate-limit.api-key-header",
+        long_help = "The HTTP header identifying clients by API key when rate limiting is enabled",
+        value_name = "HEADER",
+        default_value = "x-api-key",
+        env = "PATHFINDER_RPC_RATE_LIMIT_API_KEY_HEADER"
+    )]
+    rpc_rate_limit_api_key_header: http::HeaderName,
+
+    #[arg(
+        long = "rpc.rate-limit.api-keys",
+        long_help = "A comma separated list of API keys which identify clients when rate limiting is enabled. Requests with any other key are identified by address.",
+        value_name = "KEYS",
+        value_delimiter = ',',
+        env = "PATHFINDER_RPC_RATE_LIMIT_API_KEYS"
+    )]
+    rpc_rate_limit_api_keys: Vec<String>,
+
+    #[arg(
+        long = "rpc.rate-limit.trusted-proxies",
+        long_help = "A comma separated list of reverse proxy IP addresses whose X-Forwarded-For and X-Real-IP headers are trusted when rate limiting is enabled",
+        value_name = "IPS",
+        value_delimiter = ',',
+        env = "PATHFINDER_RPC_RATE_LIMIT_TRUSTED_PROXIES"
+    )]
+    rpc_rate_limit_trusted_proxies: Vec<std::net::IpAddr>,
+
+    #[arg(
+        long = "rpc.db-stats",
+        long_help = "Enables the pathfinder_dbStats JSON-RPC method. It reads most of the database, which can take a long time for large databases.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_RPC_DB_STATS"
+    )]
+    rpc_db_stats: bool,
+
     #[arg(
         long = "monitor-address",
         long_help = "The address at which pathfinder will serve monitoring related information",
@@ -199,6 +287,148 @@ This should only be enabled for debugging purposes as it adds substantial proces
         value_name = "BOOL"
     )]
     verify_tree_node_data: bool,
+
+    #[arg(
+        long = "sync.l1-mismatch",
+        long_help = r"What to do when the block hash attested on L1 differs from the hash of the synced block: `warn` only logs it, `halt` stops syncing new blocks while still serving the synced ones until L1 matches them again, and `rollback` purges the blocks following the latest one matching L1 and syncs them again, halting instead if the same block keeps mismatching.
+
+The mismatch is also reported by `pathfinder_getL1Status` and the `l1_l2_mismatch` metric.",
+        default_value = "warn",
+        env = "PATHFINDER_SYNC_L1_MISMATCH",
+        value_name = "POLICY"
+    )]
+    l1_mismatch: L1Mismatch,
+
+    #[arg(
+        long = "sync.l1-messages-start-block",
+        long_help = "The Ethereum block from which to index the L1 to L2 messages of the core contract when the database has none yet. Defaults to a block preceding the deployment of the network's core contract, so that the status of every message can be queried.",
+        env = "PATHFINDER_SYNC_L1_MESSAGES_START_BLOCK",
+        value_name = "BLOCK"
+    )]
+    l1_messages_start_block: Option<u64>,
+
+    #[arg(
+        long = "storage.state-tries",
+        long_help = r"How much history of the state tries to keep: either `archive` to keep the state tries of all blocks, or the number of blocks preceding the latest block for which to keep them.
+
+Keeping fewer blocks reduces the database size, but proofs, storage queries and execution are no longer possible for blocks older than that. Pruning can only be enabled for a new database.",
+        default_value = "archive",
+        env = "PATHFINDER_STORAGE_STATE_TRIES",
+        value_name = "archive|N",
+        value_parser = parse_state_tries
+    )]
+    state_tries: TriePruneMode,
+
+    #[arg(
+        long = "events.index",
+        long_help = r"Which events to index: `all`, `none`, or a comma separated list of the addresses of the contracts whose events are indexed.
+
+Events which are not indexed cannot be queried using `starknet_getEvents`. Changing this for an existing database re-indexes the events of all blocks in the background. Events which were indexed under both the previous and the new setting remain queryable while re-indexing.",
+        default_value = "all",
+        env = "PATHFINDER_EVENTS_INDEX",
+        value_name = "all|none|ADDRESSES",
+        value_parser = parse_event_index_policy
+    )]
+    event_index: EventIndexPolicy,
+
+    #[arg(
+        long = "read-only",
+        long_help = r"Serve RPC from an existing database without syncing, migrating or otherwise modifying it.
+
+The database must be kept up to date by another pathfinder process, which is polled for new blocks every `--sync.poll-interval` seconds. This allows running several RPC frontends off a single syncing node on the same host.",
+        action = clap::ArgAction::Set,
+        default_value = "false",
+        env = "PATHFINDER_READ_ONLY"
+    )]
+    read_only: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Maintenance commands which are run instead of the node. These use the same configuration
+/// as the node to locate its database and network.
+#[derive(clap::Subcommand)]
+pub enum Command {
+    /// Export or import database snapshots.
+    #[command(subcommand)]
+    Snapshot(SnapshotCommand),
+    /// Inspect the database.
+    #[command(subcommand)]
+    Db(DbCommand),
+}
+
+#[derive(clap::Subcommand)]
+pub enum SnapshotCommand {
+    /// Writes a consistent snapshot of the database to a new compressed archive. This is safe to
+    /// use while the node is running, and opens the database read-only, so it must already have
+    /// been migrated to the latest schema.
+    Export {
+        #[arg(value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+        archive: PathBuf,
+    },
+    /// Creates the database from a snapshot archive, after verifying the archive's checksum and
+    /// its latest block against the Starknet network. The database must not exist yet.
+    Import {
+        #[arg(value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+        archive: PathBuf,
+    },
+}
+
+#[derive(clap::Subcommand)]
+pub enum DbCommand {
+    /// Checks the stored blocks, transactions, events and state tries by recomputing their
+    /// hashes and commitments. Stops at the first inconsistency found, and exits with an error
+    /// if there is one.
+    Verify {
+        /// The first block to check.
+        #[arg(long, value_name = "BLOCK", default_value = "0")]
+        from: u64,
+        /// The last block to check, defaults to the latest block.
+        #[arg(long, value_name = "BLOCK")]
+        to: Option<u64>,
+        /// Writes the JSON report to this file instead of stdout.
+        #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+        report: Option<PathBuf>,
+    },
+    /// Reports the row count and size of each table, the node count of each state trie and the
+    /// data added per range of blocks, as JSON. This only reads the database, and is safe to use
+    /// while the node is running.
+    Stats {
+        /// The number of blocks per range.
+        #[arg(long, value_name = "BLOCKS", default_value = "10000")]
+        block_range_size: NonZeroU64,
+        /// Writes the JSON report to this file instead of stdout.
+        #[arg(long, value_name = "FILE", value_hint = clap::ValueHint::FilePath)]
+        report: Option<PathBuf>,
+    },
+}
+
+fn parse_state_tries(s: &str) -> Result<TriePruneMode, String> {
+    match s {
+        "archive" => Ok(TriePruneMode::Archive),
+        history => history
+            .parse()
+            .map(|history| TriePruneMode::Prune { history })
+            .map_err(|_| "Expected `archive` or a number of blocks".to_string()),
+    }
+}
+
+fn parse_event_index_policy(s: &str) -> Result<EventIndexPolicy, String> {
+    match s {
+        "all" => Ok(EventIndexPolicy::All),
+        "none" => Ok(EventIndexPolicy::None),
+        addresses => addresses
+            .split(',')
+            .map(|address| {
+                stark_hash::Felt::from_hex_str(address.trim())
+                    .ok()
+                    .and_then(ContractAddress::new)
+                    .ok_or_else(|| format!("Invalid contract address: {address}"))
+            })
+            .collect::<Result<_, _>>()
+            .map(EventIndexPolicy::Contracts),
+    }
 }
 
 #[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
@@ -227,6 +457,13 @@ pub enum RpcVersion {
     V04,
 }
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum L1Mismatch {
+    Warn,
+    Halt,
+    Rollback,
+}
+
 #[derive(clap::Args)]
 struct NetworkCli {
     #[arg(
@@ -422,6 +659,8 @@ pub struct Config {
     pub rpc_address: SocketAddr,
     pub rpc_cors_domains: Option<AllowedOrigins>,
     pub rpc_root_version: RpcVersion,
+    pub rpc_rate_limit: Option<RateLimitConfig>,
+    pub rpc_db_stats: bool,
     pub ws: Option<WebSocket>,
     pub monitor_address: Option<SocketAddr>,
     pub network: Option<NetworkConfig>,
@@ -434,6 +673,12 @@ pub struct Config {
     pub p2p: P2PConfig,
     pub debug: DebugConfig,
     pub verify_tree_hashes: bool,
+    pub l1_mismatch: L1Mismatch,
+    pub l1_messages_start_block: Option<u64>,
+    pub trie_prune_mode: TriePruneMode,
+    pub event_index_policy: EventIndexPolicy,
+    pub read_only: bool,
+    pub command: Option<Command>,
 }
 
 pub struct WebSocket {
@@ -441,8 +686,10 @@ pub struct WebSocket {
 }
 
 pub struct Ethereum {
-    pub url: Url,
+    pub urls: Vec<Url>,
     pub password: Option<String>,
+    pub quorum: NonZeroUsize,
+    pub ws_url: Option<Url>,
 }
 
 pub enum NetworkConfig {
@@ -579,6 +826,36 @@ impl DebugConfig {
     }
 }
 
+impl Ethereum {
+    fn parse_or_exit(
+        urls: Vec<Url>,
+        password: Option<String>,
+        quorum: NonZeroUsize,
+        ws_url: Option<Url>,
+    ) -> Self {
+        use clap::error::ErrorKind;
+
+        if quorum.get() > urls.len() {
+            Cli::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!(
+                        "The Ethereum quorum of {quorum} exceeds the {} configured endpoints",
+                        urls.len()
+                    ),
+                )
+                .exit()
+        }
+
+        Self {
+            urls,
+            password,
+            quorum,
+            ws_url,
+        }
+    }
+}
+
 impl Config {
     pub fn parse() -> Self {
         let cli = Cli::parse();
@@ -587,13 +864,23 @@ impl Config {
 
         Config {
             data_directory: cli.data_directory,
-            ethereum: Ethereum {
-                password: cli.ethereum_password,
-                url: cli.ethereum_url,
-            },
+            ethereum: Ethereum::parse_or_exit(
+                cli.ethereum_url,
+                cli.ethereum_password,
+                cli.ethereum_quorum,
+                cli.ethereum_ws_url,
+            ),
             rpc_address: cli.rpc_address,
             rpc_cors_domains: parse_cors_or_exit(cli.rpc_cors_domains),
             rpc_root_version: cli.rpc_root_version,
+            rpc_rate_limit: cli.rpc_rate_limit_capacity.map(|capacity| RateLimitConfig {
+                capacity,
+                refill_rate: cli.rpc_rate_limit_refill_rate,
+                api_key_header: cli.rpc_rate_limit_api_key_header,
+                api_keys: cli.rpc_rate_limit_api_keys.into_iter().collect(),
+                trusted_proxies: cli.rpc_rate_limit_trusted_proxies.into_iter().collect(),
+            }),
+            rpc_db_stats: cli.rpc_db_stats,
             ws: cli.ws.then_some(WebSocket {
                 capacity: cli.ws_capacity,
             }),
@@ -614,6 +901,12 @@ impl Config {
             p2p: P2PConfig::parse_or_exit(cli.p2p),
             debug: DebugConfig::parse(cli.debug),
             verify_tree_hashes: cli.verify_tree_node_data,
+            l1_mismatch: cli.l1_mismatch,
+            l1_messages_start_block: cli.l1_messages_start_block,
+            trie_prune_mode: cli.state_tries,
+            event_index_policy: cli.event_index,
+            read_only: cli.read_only,
+            command: cli.command,
         }
     }
 }
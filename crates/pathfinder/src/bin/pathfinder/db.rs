@@ -0,0 +1,116 @@
+//! Database maintenance commands.
+
+use std::num::{NonZeroU32, NonZeroU64};
+use std::path::PathBuf;
+
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+use pathfinder_lib::state::integrity;
+use pathfinder_storage::Storage;
+use tracing::info;
+
+use crate::config::DbCommand;
+use crate::PathfinderContext;
+
+pub async fn run(command: DbCommand, context: &PathfinderContext) -> anyhow::Result<()> {
+    match command {
+        DbCommand::Verify { from, to, report } => verify(context, from, to, report).await,
+        DbCommand::Stats {
+            block_range_size,
+            report,
+        } => stats(context, block_range_size, report).await,
+    }
+}
+
+async fn verify(
+    context: &PathfinderContext,
+    from: u64,
+    to: Option<u64>,
+    report_file: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        context.database.exists(),
+        "Database {} does not exist",
+        context.database.display()
+    );
+
+    let start = BlockNumber::new(from).context("Invalid start block")?;
+    let end = to
+        .map(|to| BlockNumber::new(to).context("Invalid end block"))
+        .transpose()?;
+
+    let storage = Storage::open_read_only(context.database.clone())
+        .context("Opening database")?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .context("Creating database connection pool")?;
+    let chain = context.network;
+    let chain_id = context.network_id;
+
+    info!(%start, "Verifying database integrity, this may take a while");
+    let report = tokio::task::spawn_blocking(move || {
+        integrity::check_integrity(&storage, chain, chain_id, start, end)
+    })
+    .await
+    .context("Joining verification task")?
+    .context("Verifying database")?;
+
+    let json = serde_json::to_string_pretty(&report).context("Serializing report")?;
+    match report_file {
+        Some(path) => std::fs::write(&path, json)
+            .with_context(|| format!("Writing report to {}", path.display()))?,
+        None => println!("{json}"),
+    }
+
+    match report.inconsistency {
+        Some(inconsistency) => {
+            anyhow::bail!("Database is inconsistent: {inconsistency:?}")
+        }
+        None => {
+            info!(
+                start=%report.start,
+                end=%report.end,
+                blocks=%report.checked_blocks,
+                "Database is consistent"
+            );
+            Ok(())
+        }
+    }
+}
+
+async fn stats(
+    context: &PathfinderContext,
+    block_range_size: NonZeroU64,
+    report_file: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        context.database.exists(),
+        "Database {} does not exist",
+        context.database.display()
+    );
+
+    let storage = Storage::open_read_only(context.database.clone())
+        .context("Opening database")?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .context("Creating database connection pool")?;
+
+    info!("Collecting database statistics, this may take a while");
+    let stats = tokio::task::spawn_blocking(move || {
+        let mut db = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+        tx.database_stats(block_range_size)
+    })
+    .await
+    .context("Joining statistics task")?
+    .context("Collecting database statistics")?;
+
+    let json = serde_json::to_string_pretty(&stats).context("Serializing report")?;
+    match report_file {
+        Some(path) => std::fs::write(&path, json)
+            .with_context(|| format!("Writing report to {}", path.display()))?,
+        None => println!("{json}"),
+    }
+
+    Ok(())
+}
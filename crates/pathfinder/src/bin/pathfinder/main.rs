@@ -3,11 +3,11 @@
 use anyhow::Context;
 use metrics_exporter_prometheus::PrometheusBuilder;
 use pathfinder_common::{consts::VERGEN_GIT_DESCRIBE, BlockNumber, Chain, ChainId, EthereumChain};
-use pathfinder_ethereum::{EthereumApi, EthereumClient};
+use pathfinder_ethereum::{EthereumApi, EthereumClient, FailoverClient, SubscriptionClient};
 use pathfinder_lib::state::SyncContext;
 use pathfinder_lib::{monitoring, state};
 use pathfinder_rpc::{metrics::logger::RpcMetricsLogger, SyncState};
-use pathfinder_storage::Storage;
+use pathfinder_storage::{EventIndexPolicy, Storage, TriePruneMode};
 use primitive_types::H160;
 use starknet_gateway_client::GatewayApi;
 use starknet_gateway_types::pending::PendingData;
@@ -20,6 +20,8 @@ use tracing::info;
 use crate::config::NetworkConfig;
 
 mod config;
+mod db;
+mod snapshot;
 mod update;
 
 #[tokio::main]
@@ -44,7 +46,7 @@ async fn main() -> anyhow::Result<()> {
     // A readiness flag which is used to indicate that pathfinder is ready via monitoring.
     let readiness = Arc::new(AtomicBool::new(false));
 
-    let ethereum = EthereumContext::setup(config.ethereum.url, config.ethereum.password)
+    let ethereum = EthereumContext::setup(config.ethereum)
         .await
         .context("Creating Ethereum context")?;
 
@@ -77,9 +79,22 @@ async fn main() -> anyhow::Result<()> {
 
     verify_networks(pathfinder_context.network, ethereum.chain)?;
 
+    match config.command {
+        Some(config::Command::Snapshot(command)) => {
+            return snapshot::run(command, &pathfinder_context).await;
+        }
+        Some(config::Command::Db(command)) => {
+            return db::run(command, &pathfinder_context).await;
+        }
+        None => {}
+    }
+
     // Setup and verify database
-    let storage_manager =
-        Storage::migrate(pathfinder_context.database.clone(), config.sqlite_wal).unwrap();
+    let storage_manager = match config.read_only {
+        true => Storage::open_read_only(pathfinder_context.database.clone())
+            .context("Opening database in read-only mode")?,
+        false => Storage::migrate(pathfinder_context.database.clone(), config.sqlite_wal).unwrap(),
+    };
     let sync_storage = storage_manager
         .create_pool(NonZeroU32::new(5).unwrap())
         .context(
@@ -118,7 +133,11 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
       Try increasing the file limit to using `ulimit` or similar tooling.",
         )?;
 
-    info!(location=?pathfinder_context.database, "Database migrated.");
+    if config.read_only {
+        info!(location=?pathfinder_context.database, "Database opened in read-only mode.");
+    } else {
+        info!(location=?pathfinder_context.database, "Database migrated.");
+    }
     verify_database(
         &sync_storage,
         pathfinder_context.network,
@@ -127,6 +146,23 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
     .await
     .context("Verifying database")?;
 
+    if !config.read_only {
+        configure_trie_pruning(&sync_storage, config.trie_prune_mode)
+            .context("Configuring state trie pruning")?;
+
+        let reindex = configure_event_index(&sync_storage, &config.event_index_policy)
+            .context("Configuring event indexing")?;
+        if let Some(until) = reindex {
+            info!(%until, "Re-indexing the events of existing blocks in the background");
+            let storage = sync_storage.clone();
+            tokio::spawn(async move {
+                if let Err(error) = state::event_index::reindex_events(storage).await {
+                    tracing::error!(?error, "Re-indexing events failed");
+                }
+            });
+        }
+    }
+
     let sync_state = Arc::new(SyncState::default());
     let pending_state = PendingData::default();
 
@@ -138,10 +174,15 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         pathfinder_context.gateway.clone(),
     );
 
-    let context = match config.poll_pending {
+    // Pending data is only polled by sync, which does not run in read-only mode.
+    let context = match config.poll_pending && !config.read_only {
         true => context.with_pending_data(pending_state.clone()),
         false => context,
     };
+    let context = match config.rpc_db_stats {
+        true => context.with_db_stats(),
+        false => context,
+    };
 
     let default_version = match config.rpc_root_version {
         config::RpcVersion::V03 => pathfinder_rpc::DefaultVersion::V03,
@@ -154,41 +195,88 @@ Hint: This is usually caused by exceeding the file descriptor limit of your syst
         None => rpc_server,
     };
 
+    let rpc_server = match config.rpc_rate_limit {
+        Some(rate_limit) => rpc_server.with_rate_limit(rate_limit),
+        None => rpc_server,
+    };
+
     let rpc_server = match config.ws {
         Some(ws) => rpc_server.with_ws(ws.capacity),
         None => rpc_server,
     };
 
-    let (p2p_handle, sequencer) = start_p2p(
-        pathfinder_context.network_id,
-        p2p_storage,
-        sync_state.clone(),
-        pathfinder_context.gateway,
-        config.p2p,
-    )
-    .await?;
-
-    let sync_context = SyncContext {
-        storage: sync_storage,
-        ethereum: ethereum.client,
-        chain: pathfinder_context.network,
-        chain_id: pathfinder_context.network_id,
-        core_address: pathfinder_context.l1_core_address,
-        sequencer,
-        state: sync_state.clone(),
-        head_poll_interval: config.poll_interval,
-        pending_data: pending_state,
-        pending_poll_interval: config
-            .poll_pending
-            .then_some(std::time::Duration::from_secs(2)),
-        block_validation_mode: state::l2::BlockValidationMode::Strict,
-        websocket_txs: rpc_server.get_ws_senders(),
-        block_cache_size: 1_000,
-        restart_delay: config.debug.restart_delay,
-        verify_tree_hashes: config.verify_tree_hashes,
-    };
+    let (sync_handle, p2p_handle) = if config.read_only {
+        info!("Running in read-only mode, the database must be synced by another process");
+        let poll_handle = tokio::spawn(state::replica::poll_heads(
+            sync_storage,
+            rpc_server.get_ws_senders(),
+            config.poll_interval,
+        ));
+        let p2p_handle = tokio::spawn(futures::future::pending());
+        (poll_handle, p2p_handle)
+    } else {
+        let (p2p_handle, sequencer) = start_p2p(
+            pathfinder_context.network_id,
+            p2p_storage,
+            sync_state.clone(),
+            pathfinder_context.gateway,
+            config.p2p,
+        )
+        .await?;
+
+        tokio::spawn(state::l1_messages::sync(
+            sync_storage.clone(),
+            ethereum.client.clone(),
+            pathfinder_context.l1_core_address,
+            config.l1_messages_start_block.unwrap_or_else(|| {
+                state::l1_messages::default_start_block(pathfinder_context.network)
+            }),
+            config.poll_interval,
+        ));
+
+        tokio::spawn(state::l1_history::sync(
+            sync_storage.clone(),
+            ethereum.client.clone(),
+            pathfinder_context.l1_core_address,
+            config.poll_interval,
+        ));
+
+        // L1 sync waits for state updates over the websocket, if there is one.
+        let l1_ethereum = match ethereum.ws_url {
+            Some(url) => {
+                SubscriptionClient::new(ethereum.client, url, pathfinder_context.l1_core_address)
+            }
+            None => SubscriptionClient::polling(ethereum.client),
+        };
+
+        let sync_context = SyncContext {
+            storage: sync_storage,
+            ethereum: l1_ethereum,
+            chain: pathfinder_context.network,
+            chain_id: pathfinder_context.network_id,
+            core_address: pathfinder_context.l1_core_address,
+            sequencer,
+            state: sync_state.clone(),
+            head_poll_interval: config.poll_interval,
+            pending_data: pending_state,
+            pending_poll_interval: config
+                .poll_pending
+                .then_some(std::time::Duration::from_secs(2)),
+            block_validation_mode: state::l2::BlockValidationMode::Strict,
+            websocket_txs: rpc_server.get_ws_senders(),
+            block_cache_size: 1_000,
+            restart_delay: config.debug.restart_delay,
+            verify_tree_hashes: config.verify_tree_hashes,
+            l1_mismatch_policy: match config.l1_mismatch {
+                config::L1Mismatch::Warn => state::L1MismatchPolicy::Warn,
+                config::L1Mismatch::Halt => state::L1MismatchPolicy::Halt,
+                config::L1Mismatch::Rollback => state::L1MismatchPolicy::Rollback,
+            },
+        };
 
-    let sync_handle = tokio::spawn(state::sync(sync_context, state::l1::sync, state::l2::sync));
+        let sync_handle = tokio::spawn(state::sync(sync_context, state::l1::sync, state::l2::sync));
+        (sync_handle, p2p_handle)
+    };
 
     let (rpc_handle, local_addr) = rpc_server
         .with_logger(RpcMetricsLogger)
@@ -383,26 +471,66 @@ async fn spawn_monitoring(
 
 /// Convenience bundle for an Ethereum transport and chain.
 struct EthereumContext {
-    client: EthereumClient,
+    client: FailoverClient,
     chain: EthereumChain,
+    ws_url: Option<reqwest::Url>,
 }
 
 impl EthereumContext {
-    /// Configure an [EthereumContext]'s transport and read the chain ID using it.
-    async fn setup(url: reqwest::Url, password: Option<String>) -> anyhow::Result<Self> {
-        let client = if let Some(password) = password.as_ref() {
-            EthereumClient::with_password(url, password).context("Creating Ethereum client")?
-        } else {
-            EthereumClient::new(url).context("Creating Ethereum client")?
-        };
+    /// Configure an [EthereumContext]'s transport and read the chain ID using it. All endpoints
+    /// must be on the same chain.
+    ///
+    /// Only `quorum` endpoints need to respond. The others are still used, in case they recover.
+    async fn setup(config: config::Ethereum) -> anyhow::Result<Self> {
+        let mut clients = Vec::with_capacity(config.urls.len());
+        let mut chain = None;
+        let mut responded = 0;
+
+        for url in config.urls {
+            let host = url.host_str().unwrap_or_default().to_owned();
+            let client = if let Some(password) = config.password.as_ref() {
+                EthereumClient::with_password(url, password).context("Creating Ethereum client")?
+            } else {
+                EthereumClient::new(url).context("Creating Ethereum client")?
+            };
 
-        let chain = client.get_chain().await.context(
-            r"Determining Ethereum chain.
-                            
-Hint: Make sure the provided ethereum.url and ethereum.password are good.",
-        )?;
+            match client.get_chain().await {
+                Ok(endpoint_chain) => {
+                    if let Some(chain) = chain {
+                        anyhow::ensure!(
+                            endpoint_chain == chain,
+                            "The Ethereum endpoints are on different chains: {chain:?} and {endpoint_chain:?}"
+                        );
+                    }
+
+                    chain = Some(endpoint_chain);
+                    responded += 1;
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        endpoint=%host, ?error,
+                        "Ethereum endpoint failed to return its chain"
+                    );
+                }
+            }
+
+            clients.push(client);
+        }
 
-        Ok(Self { client, chain })
+        anyhow::ensure!(
+            responded >= config.quorum.get(),
+            r"Only {} Ethereum endpoints returned their chain, fewer than the quorum of {}.
+
+Hint: Make sure the provided ethereum.url and ethereum.password are good.",
+            responded,
+            config.quorum,
+        );
+
+        Ok(Self {
+            client: FailoverClient::new(clients, config.quorum),
+            chain: chain.context("No Ethereum endpoint configured")?,
+            ws_url: config.ws_url,
+        })
     }
 
     /// Maps the Ethereum network to its default Starknet network:
@@ -559,6 +687,47 @@ fn verify_networks(starknet: Chain, ethereum: EthereumChain) -> anyhow::Result<(
     Ok(())
 }
 
+/// Applies the configured [TriePruneMode] to the database, which fails if the database
+/// is incompatible with it.
+fn configure_trie_pruning(storage: &Storage, mode: TriePruneMode) -> anyhow::Result<()> {
+    let mut conn = storage.connection().context("Create database connection")?;
+    let tx = conn.transaction().context("Create database transaction")?;
+    tx.set_trie_prune_mode(mode)?;
+    tx.commit().context("Committing database transaction")?;
+
+    match mode {
+        TriePruneMode::Archive => info!("Keeping all state trie history"),
+        TriePruneMode::Prune { history } => {
+            info!(%history, "Pruning state tries older than the given number of blocks")
+        }
+    }
+
+    Ok(())
+}
+
+/// Sets the event index policy, and returns the last block whose events still have to be
+/// re-indexed, if any.
+fn configure_event_index(
+    storage: &Storage,
+    policy: &EventIndexPolicy,
+) -> anyhow::Result<Option<BlockNumber>> {
+    let mut conn = storage.connection().context("Create database connection")?;
+    let tx = conn.transaction().context("Create database transaction")?;
+    tx.set_event_index_policy(policy)?;
+    let reindex = tx.event_reindex_until()?;
+    tx.commit().context("Committing database transaction")?;
+
+    match policy {
+        EventIndexPolicy::All => info!("Indexing all events"),
+        EventIndexPolicy::None => info!("Not indexing events"),
+        EventIndexPolicy::Contracts(contracts) => {
+            info!(contracts=%contracts.len(), "Indexing the events of selected contracts")
+        }
+    }
+
+    Ok(reindex)
+}
+
 async fn verify_database(
     storage: &Storage,
     network: Chain,
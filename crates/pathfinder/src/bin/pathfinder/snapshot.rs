@@ -0,0 +1,93 @@
+//! Export and import of database snapshots, see [pathfinder_storage::snapshot].
+
+use std::num::NonZeroU32;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use pathfinder_storage::{snapshot, Storage};
+use starknet_gateway_client::GatewayApi;
+use tracing::info;
+
+use crate::config::SnapshotCommand;
+use crate::PathfinderContext;
+
+pub async fn run(command: SnapshotCommand, context: &PathfinderContext) -> anyhow::Result<()> {
+    match command {
+        SnapshotCommand::Export { archive } => export(context, archive).await,
+        SnapshotCommand::Import { archive } => import(context, archive).await,
+    }
+}
+
+async fn export(context: &PathfinderContext, archive: PathBuf) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        context.database.exists(),
+        "Database {} does not exist",
+        context.database.display()
+    );
+
+    let storage = Storage::open_read_only(context.database.clone())
+        .context("Opening database")?
+        .create_pool(NonZeroU32::new(1).unwrap())
+        .context("Creating database connection pool")?;
+    let chain_id = context.network_id;
+
+    info!(archive=%archive.display(), "Exporting snapshot, this may take a while");
+    let metadata =
+        tokio::task::spawn_blocking(move || snapshot::export(&storage, chain_id, &archive))
+            .await
+            .context("Joining export task")?
+            .context("Exporting snapshot")?;
+
+    info!(
+        block=%metadata.head_number,
+        hash=%metadata.head_hash,
+        schema_version=%metadata.schema_version,
+        checksum=%metadata.checksum,
+        "Snapshot exported"
+    );
+
+    Ok(())
+}
+
+async fn import(context: &PathfinderContext, archive: PathBuf) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        !context.database.exists(),
+        "Database {} already exists, remove it to import a snapshot",
+        context.database.display()
+    );
+
+    let metadata = snapshot::read_metadata(&archive).context("Reading snapshot metadata")?;
+    anyhow::ensure!(
+        metadata.chain_id == context.network_id,
+        "Snapshot is for chain {} but the node is configured for chain {}",
+        metadata.chain_id.to_hex_str(),
+        context.network_id.to_hex_str()
+    );
+
+    // Verify the snapshot against the network before spending time on decompressing it.
+    let block = context
+        .gateway
+        .block(metadata.head_number.into())
+        .await
+        .context("Downloading snapshot head block from gateway")?
+        .as_block()
+        .context("Snapshot head block should not be pending")?;
+    anyhow::ensure!(
+        block.block_hash == metadata.head_hash,
+        "Snapshot head block {} hash {} does not match the gateway's {}",
+        metadata.head_number,
+        metadata.head_hash,
+        block.block_hash
+    );
+
+    info!(archive=%archive.display(), block=%metadata.head_number, "Importing snapshot, this may take a while");
+    let database = context.database.clone();
+    tokio::task::spawn_blocking(move || snapshot::unpack(&archive, &database))
+        .await
+        .context("Joining import task")?
+        .context("Importing snapshot")?;
+
+    info!(location=?context.database, "Snapshot imported");
+
+    Ok(())
+}
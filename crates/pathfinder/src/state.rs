@@ -1,4 +1,9 @@
 pub mod block_hash;
+pub mod event_index;
+pub mod integrity;
+pub mod l1_history;
+pub mod l1_messages;
+pub mod replica;
 mod sync;
 
-pub use sync::{l1, l2, sync, SyncContext};
+pub use sync::{l1, l2, sync, L1MismatchPolicy, SyncContext};
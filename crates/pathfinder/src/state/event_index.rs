@@ -0,0 +1,75 @@
+//! Re-indexing of events after the event index policy changed.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use pathfinder_storage::{Storage, TransactionBehavior};
+
+/// The number of blocks re-indexed per database transaction. This keeps the transactions short,
+/// so that sync is not held up.
+const BATCH_SIZE: u64 = 100;
+
+/// Re-indexes the events of all blocks scheduled by
+/// [set_event_index_policy](pathfinder_storage::Transaction::set_event_index_policy), from the
+/// latest block down to genesis. Returns once all blocks are done.
+///
+/// Progress is committed after every batch of blocks, so re-indexing resumes where it left off
+/// after a restart.
+pub async fn reindex_events(storage: Storage) -> anyhow::Result<()> {
+    let mut timer = Instant::now();
+
+    loop {
+        let storage = storage.clone();
+        let remaining = tokio::task::spawn_blocking(move || {
+            let mut db = storage
+                .connection()
+                .context("Creating database connection")?;
+            let tx = db
+                .transaction_with_behavior(TransactionBehavior::Immediate)
+                .context("Creating database transaction")?;
+            let remaining = tx.reindex_events(BATCH_SIZE)?;
+            tx.commit().context("Committing database transaction")?;
+            anyhow::Ok(remaining)
+        })
+        .await
+        .context("Joining database task")?
+        .context("Re-indexing events")?;
+
+        match remaining {
+            Some(block) if timer.elapsed() > Duration::from_secs(10) => {
+                tracing::info!(remaining=%block, "Re-indexing events");
+                timer = Instant::now();
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    tracing::info!("Finished re-indexing events");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_storage::{test_utils, EventIndexPolicy};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn reindex_events() {
+        let (storage, test_data) = test_utils::setup_test_storage();
+        let address = test_data.events[0].from_address;
+
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.set_event_index_policy(&EventIndexPolicy::Contracts([address].into()))
+            .unwrap();
+        tx.commit().unwrap();
+
+        super::reindex_events(storage.clone()).await.unwrap();
+
+        let tx = db.transaction().unwrap();
+        assert_eq!(tx.event_reindex_until().unwrap(), None);
+    }
+}
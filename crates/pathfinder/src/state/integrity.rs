@@ -0,0 +1,675 @@
+//! Verifies the integrity of the data stored in the database, by recomputing the hashes and
+//! commitments of a range of blocks and comparing them to the stored values.
+
+use std::collections::HashSet;
+
+use anyhow::Context;
+use pathfinder_common::hash::{FeltHash, PedersenHash, PoseidonHash};
+use pathfinder_common::trie::TrieNode;
+use pathfinder_common::{
+    BlockHash, BlockNumber, Chain, ChainId, EventCommitment, StateCommitment,
+    TransactionCommitment, TransactionHash,
+};
+use pathfinder_storage::{Storage, Transaction};
+use stark_hash::Felt;
+use starknet_gateway_types::reply::{Block, Status};
+use starknet_gateway_types::transaction_hash;
+
+use crate::state::block_hash::{
+    calculate_event_commitment, calculate_transaction_commitment, verify_block_hash,
+    TransactionCommitmentFinalHashType, VerifyResult,
+};
+
+/// The outcome of [check_integrity].
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Report {
+    pub start: BlockNumber,
+    pub end: BlockNumber,
+    /// The number of blocks which were found to be consistent.
+    pub checked_blocks: u64,
+    /// The number of blocks whose hash cannot be recomputed, see [VerifyResult::NotVerifiable].
+    pub unverifiable_block_hashes: u64,
+    /// The number of transactions whose hash cannot be recomputed.
+    pub unverifiable_transaction_hashes: u64,
+    /// The first inconsistency found, in which case the remaining blocks were not checked.
+    pub inconsistency: Option<Inconsistency>,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Inconsistency {
+    MissingBlock {
+        block: BlockNumber,
+    },
+    ParentHash {
+        block: BlockNumber,
+        stored: BlockHash,
+        expected: BlockHash,
+    },
+    TransactionCount {
+        block: BlockNumber,
+        stored: usize,
+        actual: usize,
+    },
+    EventCount {
+        block: BlockNumber,
+        stored: usize,
+        actual: usize,
+    },
+    TransactionHash {
+        block: BlockNumber,
+        index: usize,
+        stored: TransactionHash,
+        calculated: TransactionHash,
+    },
+    BlockHash {
+        block: BlockNumber,
+        stored: BlockHash,
+    },
+    TransactionCommitment {
+        block: BlockNumber,
+        stored: TransactionCommitment,
+        calculated: TransactionCommitment,
+    },
+    EventCommitment {
+        block: BlockNumber,
+        stored: EventCommitment,
+        calculated: EventCommitment,
+    },
+    MissingTrieNode {
+        block: BlockNumber,
+        trie: Trie,
+        hash: Felt,
+    },
+    TrieNodeHash {
+        block: BlockNumber,
+        trie: Trie,
+        stored: Felt,
+        calculated: Felt,
+    },
+    StateCommitment {
+        block: BlockNumber,
+        stored: StateCommitment,
+        calculated: StateCommitment,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trie {
+    Storage,
+    Class,
+}
+
+/// The height of the state tries. Their leaves are not stored as nodes.
+const TRIE_HEIGHT: usize = 251;
+
+/// The maximum number of trie nodes remembered as verified. Consecutive blocks share most of
+/// their trie nodes, which are then only walked once, but the set must not grow without bound.
+const MAX_VERIFIED_NODES: usize = 5_000_000;
+
+/// Checks the blocks from `start` up to and including `end`, or the latest block if `end`
+/// is not given, and stops at the first inconsistency found.
+///
+/// For each block this recomputes
+/// - the transaction hashes,
+/// - the transaction and event commitments, and the block hash,
+/// - the hash of every node of the stored state tries, and the state commitment from their
+///   roots, unless the tries have been pruned.
+///
+/// Block hashes and the commitments derived from them are only checked for blocks whose hash
+/// can be verified, since the commitments of other blocks are not stored.
+pub fn check_integrity(
+    storage: &Storage,
+    chain: Chain,
+    chain_id: ChainId,
+    start: BlockNumber,
+    end: Option<BlockNumber>,
+) -> anyhow::Result<Report> {
+    let mut db = storage
+        .connection()
+        .context("Opening database connection")?;
+    // A single transaction ensures a consistent view while the node may be syncing.
+    let tx = db.transaction().context("Creating database transaction")?;
+
+    let latest = tx
+        .block_id(pathfinder_storage::BlockId::Latest)
+        .context("Fetching latest block number")?
+        .context("Database contains no blocks")?
+        .0;
+    let end = end.unwrap_or(latest);
+    anyhow::ensure!(start <= end, "Start block {start} is after end block {end}");
+
+    // The parent of the first block is only checked if it is present.
+    let mut parent_hash = match start.get().checked_sub(1) {
+        None => Some(BlockHash::ZERO),
+        Some(parent) => tx
+            .block_id(BlockNumber::new_or_panic(parent).into())
+            .context("Fetching parent block hash")?
+            .map(|(_, hash)| hash),
+    };
+
+    let mut report = Report {
+        start,
+        end,
+        checked_blocks: 0,
+        unverifiable_block_hashes: 0,
+        unverifiable_transaction_hashes: 0,
+        inconsistency: None,
+    };
+
+    let mut verified_nodes = HashSet::new();
+
+    let mut number = start;
+    loop {
+        if let Some(inconsistency) = check_block(
+            &tx,
+            chain,
+            chain_id,
+            number,
+            &mut parent_hash,
+            &mut verified_nodes,
+            &mut report,
+        )
+        .with_context(|| format!("Checking block {number}"))?
+        {
+            report.inconsistency = Some(inconsistency);
+            break;
+        }
+        report.checked_blocks += 1;
+
+        if number.get() % 1000 == 0 {
+            tracing::info!(block=%number, %end, "Checking database integrity");
+        }
+
+        if number >= end {
+            break;
+        }
+        number += 1;
+    }
+
+    Ok(report)
+}
+
+fn check_block(
+    tx: &Transaction<'_>,
+    chain: Chain,
+    chain_id: ChainId,
+    number: BlockNumber,
+    parent_hash: &mut Option<BlockHash>,
+    verified_nodes: &mut HashSet<(Trie, Felt)>,
+    report: &mut Report,
+) -> anyhow::Result<Option<Inconsistency>> {
+    let Some(header) = tx
+        .block_header(number.into())
+        .context("Fetching block header")?
+    else {
+        return Ok(Some(Inconsistency::MissingBlock { block: number }));
+    };
+
+    if let Some(expected) = parent_hash.replace(header.hash) {
+        if header.parent_hash != expected {
+            return Ok(Some(Inconsistency::ParentHash {
+                block: number,
+                stored: header.parent_hash,
+                expected,
+            }));
+        }
+    }
+
+    let transaction_data = tx
+        .transaction_data_for_block(number.into())
+        .context("Fetching transaction data")?
+        .unwrap_or_default();
+    let (transactions, receipts): (Vec<_>, Vec<_>) = transaction_data.into_iter().unzip();
+
+    if transactions.len() != header.transaction_count {
+        return Ok(Some(Inconsistency::TransactionCount {
+            block: number,
+            stored: header.transaction_count,
+            actual: transactions.len(),
+        }));
+    }
+    let event_count = receipts.iter().map(|r| r.events.len()).sum();
+    if event_count != header.event_count {
+        return Ok(Some(Inconsistency::EventCount {
+            block: number,
+            stored: header.event_count,
+            actual: event_count,
+        }));
+    }
+
+    for (index, transaction) in transactions.iter().enumerate() {
+        match transaction_hash::verify(transaction, chain_id, number) {
+            transaction_hash::VerifyResult::Match => {}
+            transaction_hash::VerifyResult::Mismatch(calculated) => {
+                return Ok(Some(Inconsistency::TransactionHash {
+                    block: number,
+                    index,
+                    stored: transaction.hash(),
+                    calculated,
+                }))
+            }
+            transaction_hash::VerifyResult::NotVerifiable => {
+                report.unverifiable_transaction_hashes += 1
+            }
+        }
+    }
+
+    let block = Block {
+        block_hash: header.hash,
+        block_number: header.number,
+        gas_price: Some(header.gas_price),
+        parent_block_hash: header.parent_hash,
+        sequencer_address: Some(header.sequencer_address),
+        state_commitment: header.state_commitment,
+        status: Status::AcceptedOnL2,
+        timestamp: header.timestamp,
+        transaction_receipts: receipts,
+        transactions,
+        starknet_version: header.starknet_version.clone(),
+    };
+
+    match verify_block_hash(&block, chain, chain_id, header.hash).context("Verifying block hash")? {
+        VerifyResult::Match((transaction_commitment, event_commitment)) => {
+            if transaction_commitment != header.transaction_commitment {
+                return Ok(Some(Inconsistency::TransactionCommitment {
+                    block: number,
+                    stored: header.transaction_commitment,
+                    calculated: transaction_commitment,
+                }));
+            }
+            if event_commitment != header.event_commitment {
+                return Ok(Some(Inconsistency::EventCommitment {
+                    block: number,
+                    stored: header.event_commitment,
+                    calculated: event_commitment,
+                }));
+            }
+        }
+        VerifyResult::Mismatch => {
+            // Distinguish between a corrupt header and corrupt block contents where possible.
+            let final_hash_type =
+                TransactionCommitmentFinalHashType::for_version(&header.starknet_version)?;
+            let transaction_commitment =
+                calculate_transaction_commitment(&block.transactions, final_hash_type)?;
+            if header.transaction_commitment != TransactionCommitment::ZERO
+                && transaction_commitment != header.transaction_commitment
+            {
+                return Ok(Some(Inconsistency::TransactionCommitment {
+                    block: number,
+                    stored: header.transaction_commitment,
+                    calculated: transaction_commitment,
+                }));
+            }
+            let event_commitment = calculate_event_commitment(&block.transaction_receipts)?;
+            if header.event_commitment != EventCommitment::ZERO
+                && event_commitment != header.event_commitment
+            {
+                return Ok(Some(Inconsistency::EventCommitment {
+                    block: number,
+                    stored: header.event_commitment,
+                    calculated: event_commitment,
+                }));
+            }
+
+            return Ok(Some(Inconsistency::BlockHash {
+                block: number,
+                stored: header.hash,
+            }));
+        }
+        VerifyResult::NotVerifiable => report.unverifiable_block_hashes += 1,
+    }
+
+    if tx
+        .block_pruned(number)
+        .context("Checking if block is pruned")?
+    {
+        return Ok(None);
+    }
+
+    if let Some(inconsistency) = check_trie::<PedersenHash>(
+        tx,
+        number,
+        Trie::Storage,
+        header.storage_commitment.0,
+        verified_nodes,
+    )
+    .context("Checking storage trie")?
+    {
+        return Ok(Some(inconsistency));
+    }
+    if let Some(inconsistency) = check_trie::<PoseidonHash>(
+        tx,
+        number,
+        Trie::Class,
+        header.class_commitment.0,
+        verified_nodes,
+    )
+    .context("Checking class trie")?
+    {
+        return Ok(Some(inconsistency));
+    }
+
+    // Both roots are now known to be the hashes of their tries.
+    let state_commitment =
+        StateCommitment::calculate(header.storage_commitment, header.class_commitment);
+    if state_commitment != header.state_commitment {
+        return Ok(Some(Inconsistency::StateCommitment {
+            block: number,
+            stored: header.state_commitment,
+            calculated: state_commitment,
+        }));
+    }
+
+    Ok(None)
+}
+
+/// Walks the trie with the given root, and checks that each node is stored and hashes to the
+/// value its parent refers to it by.
+///
+/// Nodes in `verified_nodes` have been checked along with their descendants, and are skipped.
+/// The nodes of a consistent trie are added to it. The empty trie has no root node and a root
+/// of zero.
+fn check_trie<H: FeltHash>(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+    trie: Trie,
+    root: Felt,
+    verified_nodes: &mut HashSet<(Trie, Felt)>,
+) -> anyhow::Result<Option<Inconsistency>> {
+    if root == Felt::ZERO {
+        return Ok(None);
+    }
+
+    let mut to_check = vec![(root, TRIE_HEIGHT)];
+    let mut checked = Vec::new();
+
+    while let Some((hash, height)) = to_check.pop() {
+        // Leaves are values rather than nodes.
+        if height == 0 || verified_nodes.contains(&(trie, hash)) {
+            continue;
+        }
+
+        let node = match trie {
+            Trie::Storage => tx.storage_trie_reader().get(&hash),
+            Trie::Class => tx.class_trie_reader().get(&hash),
+        }
+        .context("Fetching trie node")?;
+        let Some(node) = node else {
+            return Ok(Some(Inconsistency::MissingTrieNode { block, trie, hash }));
+        };
+
+        let calculated = node.hash::<H>();
+        if calculated != hash {
+            return Ok(Some(Inconsistency::TrieNodeHash {
+                block,
+                trie,
+                stored: hash,
+                calculated,
+            }));
+        }
+
+        match node {
+            TrieNode::Binary { left, right } => {
+                to_check.push((left, height - 1));
+                to_check.push((right, height - 1));
+            }
+            TrieNode::Edge { child, path } => {
+                to_check.push((child, height.saturating_sub(path.len())));
+            }
+        }
+        checked.push(hash);
+    }
+
+    if verified_nodes.len() + checked.len() > MAX_VERIFIED_NODES {
+        verified_nodes.clear();
+    }
+    verified_nodes.extend(checked.into_iter().map(|hash| (trie, hash)));
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockHeader, ClassCommitment, ContractStateHash, StorageCommitment};
+    use pathfinder_merkle_tree::StorageCommitmentTree;
+    use std::collections::HashMap;
+
+    /// Stores a single real block, and returns its header.
+    fn setup(tamper: impl FnOnce(&mut BlockHeader)) -> (Storage, BlockHeader) {
+        let json = starknet_gateway_test_fixtures::v0_9_0::block::NUMBER_231579;
+        let block: Block = serde_json::from_str(json).unwrap();
+
+        let VerifyResult::Match((transaction_commitment, event_commitment)) =
+            verify_block_hash(&block, Chain::Testnet, ChainId::TESTNET, block.block_hash).unwrap()
+        else {
+            panic!("Fixture block hash should match");
+        };
+
+        // The block predates class commitments, so its state commitment is its storage commitment.
+        let mut header = BlockHeader {
+            hash: block.block_hash,
+            parent_hash: block.parent_block_hash,
+            number: block.block_number,
+            timestamp: block.timestamp,
+            gas_price: block.gas_price.unwrap_or_default(),
+            sequencer_address: block.sequencer_address.unwrap_or_default(),
+            starknet_version: block.starknet_version.clone(),
+            class_commitment: ClassCommitment::ZERO,
+            event_commitment,
+            state_commitment: block.state_commitment,
+            storage_commitment: StorageCommitment(block.state_commitment.0),
+            transaction_commitment,
+            transaction_count: block.transactions.len(),
+            event_count: block
+                .transaction_receipts
+                .iter()
+                .map(|r| r.events.len())
+                .sum(),
+        };
+        tamper(&mut header);
+
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.insert_block_header(&header).unwrap();
+        let transaction_data = block
+            .transactions
+            .into_iter()
+            .zip(block.transaction_receipts)
+            .collect::<Vec<_>>();
+        tx.insert_transaction_data(header.hash, header.number, &transaction_data)
+            .unwrap();
+        tx.commit().unwrap();
+
+        (storage, header)
+    }
+
+    #[test]
+    fn missing_trie() {
+        let (storage, header) = setup(|_| {});
+
+        let report = check_integrity(
+            &storage,
+            Chain::Testnet,
+            ChainId::TESTNET,
+            header.number,
+            None,
+        )
+        .unwrap();
+
+        // Everything but the tries, which are not part of the fixture, is consistent.
+        assert_eq!(report.checked_blocks, 0);
+        assert_eq!(report.unverifiable_block_hashes, 0);
+        assert_eq!(
+            report.inconsistency,
+            Some(Inconsistency::MissingTrieNode {
+                block: header.number,
+                trie: Trie::Storage,
+                hash: header.storage_commitment.0,
+            })
+        );
+    }
+
+    #[test]
+    fn event_commitment_mismatch() {
+        let (storage, header) = setup(|header| header.event_commitment = EventCommitment::ZERO);
+
+        let report = check_integrity(
+            &storage,
+            Chain::Testnet,
+            ChainId::TESTNET,
+            header.number,
+            Some(header.number),
+        )
+        .unwrap();
+
+        assert_eq!(report.checked_blocks, 0);
+        assert_matches::assert_matches!(
+            report.inconsistency,
+            Some(Inconsistency::EventCommitment { stored, .. }) if stored == EventCommitment::ZERO
+        );
+    }
+
+    #[test]
+    fn block_hash_mismatch() {
+        let (storage, header) = setup(|header| header.timestamp = Default::default());
+
+        let report = check_integrity(
+            &storage,
+            Chain::Testnet,
+            ChainId::TESTNET,
+            header.number,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.inconsistency,
+            Some(Inconsistency::BlockHash {
+                block: header.number,
+                stored: header.hash,
+            })
+        );
+    }
+
+    /// Builds a storage trie containing two contracts, without storing it.
+    fn storage_trie(
+        storage: &Storage,
+        state_hash: ContractStateHash,
+    ) -> (StorageCommitment, HashMap<Felt, TrieNode>) {
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        let mut tree = StorageCommitmentTree::load(&tx, StorageCommitment::ZERO);
+        tree.set(contract_address!("0x1"), contract_state_hash!("0x100"))
+            .unwrap();
+        tree.set(contract_address!("0x3"), state_hash).unwrap();
+        tree.commit().unwrap()
+    }
+
+    /// Stores a block which is too old for its hash to be verified, with the given storage trie.
+    fn setup_with_trie(
+        storage_commitment: StorageCommitment,
+        nodes: &HashMap<Felt, TrieNode>,
+    ) -> (Storage, BlockHeader) {
+        let header = BlockHeader::builder()
+            .with_number(BlockNumber::new_or_panic(120_000))
+            .with_storage_commitment(storage_commitment)
+            .with_state_commitment(StateCommitment::calculate(
+                storage_commitment,
+                ClassCommitment::ZERO,
+            ))
+            .finalize_with_hash(block_hash_bytes!(b"block"));
+
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.insert_block_header(&header).unwrap();
+        tx.insert_storage_trie(storage_commitment, nodes).unwrap();
+        tx.commit().unwrap();
+
+        (storage, header)
+    }
+
+    #[test]
+    fn consistent() {
+        let (storage_commitment, nodes) = storage_trie(
+            &Storage::in_memory().unwrap(),
+            contract_state_hash!("0x300"),
+        );
+        let (storage, header) = setup_with_trie(storage_commitment, &nodes);
+
+        let report = check_integrity(
+            &storage,
+            Chain::Testnet,
+            ChainId::TESTNET,
+            header.number,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            report,
+            Report {
+                start: header.number,
+                end: header.number,
+                checked_blocks: 1,
+                unverifiable_block_hashes: 1,
+                unverifiable_transaction_hashes: 0,
+                inconsistency: None,
+            }
+        );
+    }
+
+    #[test]
+    fn trie_node_hash_mismatch() {
+        let (storage_commitment, mut nodes) = storage_trie(
+            &Storage::in_memory().unwrap(),
+            contract_state_hash!("0x300"),
+        );
+        let (other_commitment, other_nodes) = storage_trie(
+            &Storage::in_memory().unwrap(),
+            contract_state_hash!("0x301"),
+        );
+
+        // Both contracts share a path below the root, so the root is an edge to the node which
+        // differs between the two tries. Store the other trie's node under this trie's hash.
+        let TrieNode::Edge {
+            child: tampered, ..
+        } = nodes[&storage_commitment.0]
+        else {
+            panic!("Root should be an edge node");
+        };
+        let TrieNode::Edge { child: other, .. } = other_nodes[&other_commitment.0] else {
+            panic!("Root should be an edge node");
+        };
+        let replacement = other_nodes[&other].clone();
+        nodes.extend(other_nodes);
+        nodes.insert(tampered, replacement);
+
+        let (storage, header) = setup_with_trie(storage_commitment, &nodes);
+
+        let report = check_integrity(
+            &storage,
+            Chain::Testnet,
+            ChainId::TESTNET,
+            header.number,
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(report.checked_blocks, 0);
+        assert_eq!(
+            report.inconsistency,
+            Some(Inconsistency::TrieNodeHash {
+                block: header.number,
+                trie: Trie::Storage,
+                stored: tampered,
+                calculated: other,
+            })
+        );
+    }
+}
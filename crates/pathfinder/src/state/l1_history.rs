@@ -0,0 +1,256 @@
+//! Backfilling of the L1 state from the state update logs of the Starknet core contract.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+use pathfinder_ethereum::{EthereumApi, EthereumStateUpdate, StateUpdateLog};
+use pathfinder_storage::{Storage, TransactionBehavior};
+use primitive_types::H160;
+
+/// The maximum number of Ethereum blocks whose logs are requested at once, as providers limit
+/// the block range of `eth_getLogs`.
+const MAX_BLOCK_RANGE: u64 = 10_000;
+
+/// Stores the state update logged by the core contract for every Starknet block in the L1 state,
+/// so that blocks which became L1 accepted between the polls of [l1::sync](super::l1::sync) are
+/// known too. Never returns.
+///
+/// Newly finalized Ethereum blocks are scanned every `poll_interval`. Older blocks are scanned
+/// backwards until the state update of the Starknet genesis block is found.
+pub async fn sync<T: EthereumApi>(
+    storage: Storage,
+    ethereum: T,
+    core_address: H160,
+    poll_interval: Duration,
+) {
+    loop {
+        if let Err(error) = scan(&storage, &ethereum, &core_address).await {
+            tracing::warn!(?error, "Scanning L1 state updates failed");
+        }
+
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+async fn scan<T: EthereumApi>(
+    storage: &Storage,
+    ethereum: &T,
+    core_address: &H160,
+) -> anyhow::Result<()> {
+    let finalized = ethereum
+        .get_finalized_block_number()
+        .await
+        .context("Fetching finalized block number")?;
+
+    let storage_clone = storage.clone();
+    let scanned = tokio::task::spawn_blocking(move || {
+        let mut db = storage_clone
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+        tx.l1_state_scanned()
+    })
+    .await
+    .context("Joining database task")??;
+
+    // Nothing is scanned yet, which is the empty range following the finalized block.
+    let (mut from, mut until) = scanned.unwrap_or((finalized + 1, finalized));
+
+    while until < finalized {
+        let to = finalized.min(until + MAX_BLOCK_RANGE);
+        let logs = ethereum
+            .get_state_update_logs(core_address, until + 1, to)
+            .await
+            .with_context(|| {
+                format!("Fetching state update logs of blocks {} to {to}", until + 1)
+            })?;
+
+        if !store(storage.clone(), logs, from, to).await? {
+            return Ok(());
+        }
+        until = to;
+    }
+
+    let mut timer = Instant::now();
+    while from > 0 {
+        let start = from.saturating_sub(MAX_BLOCK_RANGE);
+        let logs = ethereum
+            .get_state_update_logs(core_address, start, from - 1)
+            .await
+            .with_context(|| {
+                format!(
+                    "Fetching state update logs of blocks {start} to {}",
+                    from - 1
+                )
+            })?;
+
+        // Nothing precedes the genesis state update.
+        let start = match logs
+            .iter()
+            .any(|log| log.block_number == BlockNumber::GENESIS)
+        {
+            true => 0,
+            false => start,
+        };
+
+        if !store(storage.clone(), logs, start, until).await? {
+            tracing::debug!(
+                ethereum_block=%from,
+                "Waiting for L2 sync to reach the blocks of older L1 state updates"
+            );
+            return Ok(());
+        }
+        from = start;
+
+        if timer.elapsed() > Duration::from_secs(10) {
+            tracing::info!(ethereum_block=%from, "Scanning older L1 state updates");
+            timer = Instant::now();
+        }
+    }
+
+    Ok(())
+}
+
+/// Stores the logged state updates and the range of scanned Ethereum blocks. Returns `false`
+/// without storing anything if a log lacks the block hash and its block is not synced yet.
+async fn store(
+    storage: Storage,
+    logs: Vec<StateUpdateLog>,
+    from: u64,
+    until: u64,
+) -> anyhow::Result<bool> {
+    tokio::task::spawn_blocking(move || {
+        let mut db = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .context("Creating database transaction")?;
+
+        for log in logs {
+            // Logs emitted before Starknet v0.11 lack the block hash, which is then taken from
+            // the synced block if its state commitment matches.
+            let block_hash = match log.block_hash {
+                Some(block_hash) => block_hash,
+                None => match tx
+                    .block_header(log.block_number.into())
+                    .context("Fetching block header")?
+                {
+                    Some(header) if header.state_commitment == log.state_root => header.hash,
+                    Some(header) => {
+                        tracing::warn!(block_number=%log.block_number, L1=?log.state_root, L2=?header.state_commitment, "L1/L2 state root mismatch");
+                        continue;
+                    }
+                    None => return Ok(false),
+                },
+            };
+
+            let update = EthereumStateUpdate {
+                state_root: log.state_root,
+                block_number: log.block_number,
+                block_hash,
+            };
+            tx.upsert_l1_state(&update).context("Inserting L1 state")?;
+        }
+
+        tx.set_l1_state_scanned(from, until)
+            .context("Updating scanned range")?;
+
+        // The new states may attest blocks which are already synced.
+        let pointer = tx.l1_l2_pointer().context("Querying L1-L2 pointer")?;
+        let latest_match = tx
+            .latest_l1_l2_match()
+            .context("Querying latest L1/L2 match")?;
+        if latest_match > pointer {
+            tx.update_l1_l2_pointer(latest_match)
+                .context("Updating L1-L2 pointer")?;
+        }
+
+        tx.commit().context("Committing database transaction")?;
+
+        Ok(true)
+    })
+    .await
+    .context("Joining database task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::BlockHeader;
+    use pathfinder_ethereum::MockEthereumApi;
+
+    use super::*;
+
+    /// Serves the state update logs emitted in each Ethereum block.
+    fn ethereum(finalized: u64, logs: BTreeMap<u64, StateUpdateLog>) -> MockEthereumApi {
+        let mut ethereum = MockEthereumApi::new();
+        ethereum
+            .expect_get_finalized_block_number()
+            .returning(move || Ok(finalized));
+        ethereum
+            .expect_get_state_update_logs()
+            .returning(move |_, from_block, to_block| {
+                Ok(logs
+                    .range(from_block..=to_block)
+                    .map(|(_, log)| log.clone())
+                    .collect())
+            });
+        ethereum
+    }
+
+    #[tokio::test]
+    async fn scan_to_genesis() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+
+        let headers = [
+            (block_hash!("0xa0"), state_commitment!("0xb0")),
+            (block_hash!("0xa1"), state_commitment!("0xb1")),
+            (block_hash!("0xa2"), state_commitment!("0xb2")),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(number, (hash, state_commitment))| {
+            BlockHeader::builder()
+                .with_number(BlockNumber::new_or_panic(number as u64))
+                .with_state_commitment(state_commitment)
+                .finalize_with_hash(hash)
+        })
+        .collect::<Vec<_>>();
+
+        let tx = db.transaction().unwrap();
+        for header in &headers {
+            tx.insert_block_header(header).unwrap();
+        }
+        tx.commit().unwrap();
+
+        // The last state update is logged without its block hash, as before Starknet v0.11.
+        let logs = [(100, true), (15_000, true), (24_000, false)]
+            .into_iter()
+            .zip(&headers)
+            .map(|((ethereum_block, has_hash), header)| {
+                let log = StateUpdateLog {
+                    state_root: header.state_commitment,
+                    block_number: header.number,
+                    block_hash: has_hash.then_some(header.hash),
+                };
+                (ethereum_block, log)
+            })
+            .collect();
+        let ethereum = ethereum(25_000, logs);
+
+        scan(&storage, &ethereum, &H160::zero()).await.unwrap();
+
+        let tx = db.transaction().unwrap();
+        assert_eq!(tx.l1_state_scanned().unwrap(), Some((0, 25_000)));
+        for header in &headers {
+            let update = tx.l1_state_at_number(header.number).unwrap().unwrap();
+            assert_eq!(update.block_hash, header.hash);
+        }
+        assert_eq!(tx.l1_l2_pointer().unwrap(), Some(headers[2].number));
+    }
+}
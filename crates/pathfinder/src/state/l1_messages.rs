@@ -0,0 +1,201 @@
+//! Indexing of the L1 to L2 messages sent through the Starknet core contract.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_common::Chain;
+use pathfinder_ethereum::{EthereumApi, L1ToL2MessageLog};
+use pathfinder_storage::Storage;
+use primitive_types::H160;
+
+/// The maximum number of Ethereum blocks whose logs are requested at once, as providers limit
+/// the block range of `eth_getLogs`.
+const MAX_BLOCK_RANGE: u64 = 1_000;
+
+/// Returns an Ethereum block preceding the deployment of the chain's core contract, from which
+/// its message logs can be synced without missing any.
+///
+/// The core contract of a custom chain is unknown, so its logs are synced from genesis.
+pub fn default_start_block(chain: Chain) -> u64 {
+    match chain {
+        Chain::Mainnet => 13_000_000,
+        // All of these are deployed on Goerli.
+        Chain::Testnet | Chain::Testnet2 | Chain::Integration => 4_500_000,
+        Chain::Custom => 0,
+    }
+}
+
+/// Stores the L1 to L2 message logs of the core contract up to the finalized Ethereum block,
+/// polling for new ones every `poll_interval`. Never returns.
+///
+/// A database without any messages starts at Ethereum block `start_block`, so messages sent
+/// before it are not indexed.
+pub async fn sync<T: EthereumApi>(
+    storage: Storage,
+    ethereum: T,
+    core_address: H160,
+    start_block: u64,
+    poll_interval: Duration,
+) {
+    loop {
+        if let Err(error) = sync_to_finalized(&storage, &ethereum, &core_address, start_block).await
+        {
+            tracing::warn!(?error, "Syncing L1 to L2 messages failed");
+        }
+
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+async fn sync_to_finalized<T: EthereumApi>(
+    storage: &Storage,
+    ethereum: &T,
+    core_address: &H160,
+    start_block: u64,
+) -> anyhow::Result<()> {
+    let finalized = ethereum
+        .get_finalized_block_number()
+        .await
+        .context("Fetching finalized block number")?;
+
+    let storage_clone = storage.clone();
+    let synced_until = tokio::task::spawn_blocking(move || {
+        let mut db = storage_clone
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+        tx.l1_messages_synced_until()
+    })
+    .await
+    .context("Joining database task")??;
+
+    let mut from = match synced_until {
+        Some(until) => until + 1,
+        None => start_block,
+    };
+
+    while from <= finalized {
+        let to = finalized.min(from + MAX_BLOCK_RANGE - 1);
+        let logs = ethereum
+            .get_l1_to_l2_message_logs(core_address, from, to)
+            .await
+            .with_context(|| format!("Fetching message logs of blocks {from} to {to}"))?;
+
+        insert_logs(storage.clone(), logs, to).await?;
+        tracing::trace!(%from, %to, "Synced L1 to L2 messages");
+
+        from = to + 1;
+    }
+
+    Ok(())
+}
+
+async fn insert_logs(
+    storage: Storage,
+    logs: Vec<L1ToL2MessageLog>,
+    synced_until: u64,
+) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let mut db = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+        tx.insert_l1_to_l2_message_logs(&logs, synced_until)
+            .context("Inserting message logs")?;
+        tx.commit().context("Committing database transaction")
+    })
+    .await
+    .context("Joining database task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::predicate::{always, eq};
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{EntryPoint, EthereumAddress, L1ToL2MessageNonce};
+    use pathfinder_ethereum::{L1ToL2Message, L1ToL2MessageEvent, MockEthereumApi};
+    use primitive_types::H256;
+
+    use super::*;
+
+    /// Expects the logs of exactly the given block ranges to be requested, in order, and returns
+    /// a message sent in each block.
+    fn ethereum(finalized: u64, ranges: &[(u64, u64)]) -> MockEthereumApi {
+        let mut ethereum = MockEthereumApi::new();
+        ethereum
+            .expect_get_finalized_block_number()
+            .returning(move || Ok(finalized));
+
+        let mut seq = mockall::Sequence::new();
+        for &(from, to) in ranges {
+            ethereum
+                .expect_get_l1_to_l2_message_logs()
+                .with(always(), eq(from), eq(to))
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(|_, from, to| {
+                    Ok((from..=to)
+                        .map(|block| L1ToL2MessageLog {
+                            message: L1ToL2Message {
+                                from_address: EthereumAddress(H160::from_low_u64_be(1)),
+                                to_address: contract_address!("0x2"),
+                                selector: EntryPoint(felt!("0x3")),
+                                payload: vec![],
+                                nonce: L1ToL2MessageNonce(block.into()),
+                            },
+                            event: L1ToL2MessageEvent::Sent,
+                            l1_block_number: block,
+                            l1_transaction_hash: H256::from_low_u64_be(block),
+                        })
+                        .collect())
+                });
+        }
+
+        ethereum
+    }
+
+    #[tokio::test]
+    async fn sync_to_finalized() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.insert_l1_to_l2_message_logs(&[], 10).unwrap();
+        tx.commit().unwrap();
+
+        let finalized = 10 + MAX_BLOCK_RANGE + 5;
+        let ethereum = ethereum(
+            finalized,
+            &[
+                (11, 10 + MAX_BLOCK_RANGE),
+                (11 + MAX_BLOCK_RANGE, 15 + MAX_BLOCK_RANGE),
+            ],
+        );
+        let core_address = H160::zero();
+
+        super::sync_to_finalized(&storage, &ethereum, &core_address, 0)
+            .await
+            .unwrap();
+
+        let tx = db.transaction().unwrap();
+        assert_eq!(tx.l1_messages_synced_until().unwrap(), Some(finalized));
+        let messages = tx.l1_to_l2_messages(H256::from_low_u64_be(11)).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].consumed_by, None);
+    }
+
+    #[tokio::test]
+    async fn new_database_starts_at_start_block() {
+        let storage = Storage::in_memory().unwrap();
+
+        let ethereum = ethereum(100, &[(42, 100)]);
+        let core_address = H160::zero();
+
+        super::sync_to_finalized(&storage, &ethereum, &core_address, 42)
+            .await
+            .unwrap();
+
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        assert_eq!(tx.l1_messages_synced_until().unwrap(), Some(100));
+    }
+}
@@ -0,0 +1,165 @@
+//! Support for read-only replicas, which serve RPC from a database synced by another process.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use pathfinder_common::{BlockHash, BlockNumber};
+use pathfinder_rpc::websocket::types::{BlockHeader, WebsocketSenders};
+use pathfinder_storage::{BlockId, Storage, Transaction};
+use starknet_gateway_types::reply::Status;
+
+/// Polls the database for blocks added by the syncing process, and broadcasts them to the
+/// websocket new heads subscribers.
+///
+/// Blocks which already exist when polling starts are not broadcast.
+pub async fn poll_heads(
+    storage: Storage,
+    websocket_txs: WebsocketSenders,
+    poll_interval: Duration,
+) -> anyhow::Result<()> {
+    let mut head = latest(&storage).await.context("Fetching latest block")?;
+
+    loop {
+        tokio::time::sleep(poll_interval).await;
+
+        let storage = storage.clone();
+        let (new_head, headers) = tokio::task::spawn_blocking(move || {
+            let mut db = storage
+                .connection()
+                .context("Creating database connection")?;
+            let tx = db.transaction().context("Creating database transaction")?;
+            new_heads(&tx, head)
+        })
+        .await
+        .context("Joining database task")??;
+
+        for header in headers {
+            tracing::debug!(block_number=%header.block_number, "New head");
+            websocket_txs.new_head.send_if_receiving(header);
+        }
+        head = new_head;
+    }
+}
+
+async fn latest(storage: &Storage) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
+    let storage = storage.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut db = storage
+            .connection()
+            .context("Creating database connection")?;
+        let tx = db.transaction().context("Creating database transaction")?;
+        tx.block_id(BlockId::Latest)
+    })
+    .await
+    .context("Joining database task")?
+}
+
+/// Returns the latest block and the headers of the blocks added since `head`.
+///
+/// If the latest block no longer extends `head` due to a reorg, the blocks from the reorg
+/// onwards are returned. At most the 100 latest blocks are returned, e.g. if the syncing process
+/// caught up on many blocks in the meantime.
+fn new_heads(
+    tx: &Transaction<'_>,
+    head: Option<(BlockNumber, BlockHash)>,
+) -> anyhow::Result<(Option<(BlockNumber, BlockHash)>, Vec<BlockHeader>)> {
+    const MAX_NEW_HEADS: u64 = 100;
+
+    let Some(latest) = tx
+        .block_id(BlockId::Latest)
+        .context("Fetching latest block")?
+    else {
+        return Ok((None, Vec::new()));
+    };
+
+    let first = match head {
+        Some(head) if head == latest => return Ok((Some(head), Vec::new())),
+        Some((number, _)) if number < latest.0 => number + 1,
+        // The head was reorged away, so the latest block replaces it.
+        Some(_) => latest.0,
+        None => BlockNumber::GENESIS,
+    };
+    let first = std::cmp::max(
+        first.get(),
+        latest.0.get().saturating_sub(MAX_NEW_HEADS - 1),
+    );
+
+    let headers = (first..=latest.0.get())
+        .map(|number| {
+            let number = BlockNumber::new_or_panic(number);
+            let header = tx
+                .block_header(number.into())
+                .context("Fetching block header")?
+                .context("Block header is missing")?;
+            let status = match tx
+                .block_is_l1_accepted(number.into())
+                .context("Querying block status")?
+            {
+                true => Status::AcceptedOnL1,
+                false => Status::AcceptedOnL2,
+            };
+
+            Ok(BlockHeader {
+                block_hash: header.hash,
+                block_number: header.number,
+                gas_price: Some(header.gas_price),
+                parent_block_hash: header.parent_hash,
+                sequencer_address: Some(header.sequencer_address),
+                state_commitment: header.state_commitment,
+                status,
+                timestamp: header.timestamp,
+                starknet_version: header.starknet_version,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok((Some(latest), headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    #[test]
+    fn new_heads() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let (head, headers) = super::new_heads(&tx, None).unwrap();
+        assert_eq!(head, None);
+        assert!(headers.is_empty());
+
+        let genesis = pathfinder_common::BlockHeader::builder()
+            .with_number(BlockNumber::GENESIS)
+            .finalize_with_hash(block_hash!("0x0"));
+        let block_1 = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash!("0x1"));
+        tx.insert_block_header(&genesis).unwrap();
+        tx.insert_block_header(&block_1).unwrap();
+
+        let (head, headers) = super::new_heads(&tx, Some((genesis.number, genesis.hash))).unwrap();
+        assert_eq!(head, Some((block_1.number, block_1.hash)));
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].block_hash, block_1.hash);
+        assert_eq!(headers[0].status, Status::AcceptedOnL2);
+
+        let (_, headers) = super::new_heads(&tx, head).unwrap();
+        assert!(headers.is_empty());
+
+        // Block 1 is replaced by a reorg.
+        tx.purge_block(block_1.number).unwrap();
+        let block_1_reorg = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash!("0x1b"));
+        tx.insert_block_header(&block_1_reorg).unwrap();
+
+        let (head, headers) = super::new_heads(&tx, head).unwrap();
+        assert_eq!(head, Some((block_1_reorg.number, block_1_reorg.hash)));
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].block_hash, block_1_reorg.hash);
+    }
+}
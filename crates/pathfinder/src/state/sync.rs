@@ -7,17 +7,19 @@ use anyhow::Context;
 use pathfinder_common::{
     BlockHash, BlockHeader, BlockNumber, CasmHash, Chain, ChainId, ClassCommitment, ClassHash,
     EventCommitment, GasPrice, SequencerAddress, SierraHash, StarknetVersion, StateCommitment,
-    StateUpdate, StorageCommitment, TransactionCommitment,
+    StateUpdate, StorageCommitment, TransactionCommitment, TransactionHash,
 };
 use pathfinder_ethereum::{EthereumApi, EthereumStateUpdate};
 use pathfinder_merkle_tree::contract_state::update_contract_state;
 use pathfinder_merkle_tree::{ClassCommitmentTree, StorageCommitmentTree};
 use pathfinder_rpc::{
     v02::types::syncing::{self, NumberedBlock, Syncing},
-    websocket::types::WebsocketSenders,
-    SyncState,
+    websocket::types::{EmittedEvent, TransactionStatusUpdate, WebsocketSenders},
+    L1Mismatch, SyncState,
+};
+use pathfinder_storage::{
+    Connection, Storage, Transaction, TransactionBehavior, TriePruneMode, TrieRootChange,
 };
-use pathfinder_storage::{Connection, Storage, Transaction, TransactionBehavior};
 use primitive_types::H160;
 use stark_hash::Felt;
 use starknet_gateway_client::{GatewayApi, GossipApi};
@@ -28,6 +30,7 @@ use std::future::Future;
 use std::time::Instant;
 use std::{sync::Arc, time::Duration};
 use tokio::sync::mpsc::{self, Receiver};
+use tokio::sync::oneshot;
 
 use crate::state::l1::L1SyncContext;
 use crate::state::l2::{BlockChain, L2SyncContext};
@@ -61,6 +64,42 @@ pub enum SyncEvent {
     Pending(Arc<PendingBlock>, Arc<StateUpdate>),
 }
 
+/// What to do when the block hash attested on L1 differs from the one synced from L2.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum L1MismatchPolicy {
+    /// Log the mismatch and keep syncing.
+    #[default]
+    Warn,
+    /// Stop syncing L2 blocks, while still serving the ones already synced, until L1 matches
+    /// the synced blocks again.
+    Halt,
+    /// Purge the L2 blocks following the latest one matching L1, and sync them again. Halts
+    /// like [L1MismatchPolicy::Halt] if the same block keeps mismatching.
+    Rollback,
+}
+
+/// The number of times the blocks following the same mismatching block are rolled back, before
+/// [L1MismatchPolicy::Rollback] halts L2 sync instead.
+const MAX_ROLLBACKS: usize = 3;
+
+/// How an L1 state update compares to the synced L2 blocks.
+enum L1Comparison {
+    /// The block attested on L1 has been synced with the same hash.
+    Match,
+    /// The block attested on L1 has been synced with a different hash.
+    Mismatch(L1Mismatch),
+    /// The block attested on L1 has not been synced.
+    NotSynced,
+}
+
+/// Requests from the consumer to the main sync loop to control the L2 producer.
+enum L2Command {
+    /// Stops the L2 producer, replying once it can no longer emit events.
+    Stop(oneshot::Sender<()>),
+    /// Starts the L2 producer from the stored L2 head.
+    Start,
+}
+
 #[derive(Clone)]
 pub struct SyncContext<G, E> {
     pub storage: Storage,
@@ -78,6 +117,7 @@ pub struct SyncContext<G, E> {
     pub block_cache_size: usize,
     pub restart_delay: Duration,
     pub verify_tree_hashes: bool,
+    pub l1_mismatch_policy: L1MismatchPolicy,
 }
 
 impl<G, E> From<SyncContext<G, E>> for L1SyncContext<E> {
@@ -114,7 +154,7 @@ pub async fn sync<Ethereum, SequencerClient, F1, F2, L1Sync, L2Sync>(
     l2_sync: L2Sync,
 ) -> anyhow::Result<()>
 where
-    Ethereum: EthereumApi + Clone + Send + 'static,
+    Ethereum: EthereumApi + Clone + Send + Sync + 'static,
     SequencerClient: GatewayApi + GossipApi + Clone + Send + Sync + 'static,
     F1: Future<Output = anyhow::Result<()>> + Send + 'static,
     F2: Future<Output = anyhow::Result<()>> + Send + 'static,
@@ -146,6 +186,7 @@ where
         block_cache_size,
         restart_delay,
         verify_tree_hashes: _,
+        l1_mismatch_policy: _,
     } = context.clone();
 
     let mut db_conn = storage
@@ -154,6 +195,7 @@ where
 
     // TODO: consider increasing the capacity.
     let (event_sender, event_receiver) = mpsc::channel(2);
+    let (l2_command_sender, mut l2_commands) = mpsc::channel(1);
 
     let l2_head = tokio::task::block_in_place(|| -> anyhow::Result<_> {
         let tx = db_conn.transaction()?;
@@ -211,6 +253,9 @@ where
         state: context.state,
         pending_data: context.pending_data,
         verify_tree_hashes: context.verify_tree_hashes,
+        websocket_txs: context.websocket_txs,
+        l1_mismatch_policy: context.l1_mismatch_policy,
+        l2_commands: l2_command_sender,
     };
     let mut consumer_handle = tokio::spawn(consumer(event_receiver, consumer_context));
 
@@ -251,12 +296,7 @@ where
                     }
                 }
 
-                let l2_head = tokio::task::block_in_place(|| {
-                    let tx = db_conn.transaction()?;
-                    tx.block_header(pathfinder_storage::BlockId::Latest)
-                })
-                .context("Query L2 head from database")?
-                .map(|block| (block.number, block.hash, block.state_commitment, block.starknet_version));
+                let l2_head = stored_l2_head(&mut db_conn)?;
 
                 let latest_blocks = latest_n_blocks(&mut db_conn, block_cache_size).await.context("Fetching latest blocks from storage")?;
                 let block_chain = BlockChain::with_capacity(1_000, latest_blocks);
@@ -268,6 +308,25 @@ where
                 });
                 tracing::info!("L2 sync process restarted.");
             },
+            Some(command) = l2_commands.recv() => {
+                match command {
+                    L2Command::Stop(stopped) => {
+                        l2_handle.abort();
+                        // Cancellation is the expected outcome.
+                        _ = (&mut l2_handle).await;
+                        l2_handle = tokio::spawn(std::future::pending());
+                        _ = stopped.send(());
+                        tracing::info!("L2 sync process stopped.");
+                    }
+                    L2Command::Start => {
+                        let l2_head = stored_l2_head(&mut db_conn)?;
+                        let latest_blocks = latest_n_blocks(&mut db_conn, block_cache_size).await.context("Fetching latest blocks from storage")?;
+                        let block_chain = BlockChain::with_capacity(1_000, latest_blocks);
+                        l2_handle = tokio::spawn(l2_sync(event_sender.clone(), l2_context.clone(), l2_head, block_chain));
+                        tracing::info!("L2 sync process started.");
+                    }
+                }
+            },
             consumer_result = &mut consumer_handle => {
                 match consumer_result {
                     Ok(Ok(())) => {
@@ -330,6 +389,69 @@ struct ConsumerContext {
     pub state: Arc<SyncState>,
     pub pending_data: PendingData,
     pub verify_tree_hashes: bool,
+    pub websocket_txs: WebsocketSenders,
+    pub l1_mismatch_policy: L1MismatchPolicy,
+    pub l2_commands: mpsc::Sender<L2Command>,
+}
+
+/// Returns the latest stored block, from which L2 sync continues.
+#[allow(clippy::type_complexity)]
+fn stored_l2_head(
+    connection: &mut Connection,
+) -> anyhow::Result<Option<(BlockNumber, BlockHash, StateCommitment, StarknetVersion)>> {
+    let head = tokio::task::block_in_place(|| {
+        let tx = connection.transaction()?;
+        tx.block_header(pathfinder_storage::BlockId::Latest)
+    })
+    .context("Query L2 head from database")?
+    .map(|block| {
+        (
+            block.number,
+            block.hash,
+            block.state_commitment,
+            block.starknet_version,
+        )
+    });
+
+    Ok(head)
+}
+
+/// Tracks which receipts of the current pending block have already been broadcast
+/// to websocket subscribers, so that each pending update only pushes what is new.
+#[derive(Default)]
+struct PendingCursor {
+    parent_hash: BlockHash,
+    receipts: usize,
+    /// The transaction of the last receipt seen.
+    last_transaction: Option<TransactionHash>,
+}
+
+impl PendingCursor {
+    /// Returns the index of the first receipt of `block` which has not been seen yet and
+    /// advances the cursor past the last one.
+    ///
+    /// A different parent hash means a new pending block, and a different transaction at the
+    /// position of the last one seen means the pending block was replaced. In both cases all
+    /// receipts are considered new.
+    fn advance(&mut self, block: &PendingBlock) -> usize {
+        let last_transaction = self
+            .receipts
+            .checked_sub(1)
+            .and_then(|last| block.transaction_receipts.get(last))
+            .map(|receipt| receipt.transaction_hash);
+        if block.parent_hash != self.parent_hash || last_transaction != self.last_transaction {
+            self.parent_hash = block.parent_hash;
+            self.receipts = 0;
+        }
+
+        let first_new = self.receipts;
+        self.receipts = block.transaction_receipts.len();
+        self.last_transaction = block
+            .transaction_receipts
+            .last()
+            .map(|receipt| receipt.transaction_hash);
+        first_new
+    }
 }
 
 async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) -> anyhow::Result<()> {
@@ -338,8 +460,18 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
         state,
         pending_data,
         verify_tree_hashes,
+        websocket_txs,
+        l1_mismatch_policy,
+        l2_commands,
     } = context;
 
+    // Set while L2 sync is halted due to a mismatch with L1.
+    let mut halted = false;
+    // The mismatching block which was last rolled back, and how many times.
+    let mut rollbacks: Option<(BlockNumber, usize)> = None;
+
+    let mut pending_cursor = PendingCursor::default();
+
     let mut last_block_start = std::time::Instant::now();
     let mut block_time_avg = std::time::Duration::ZERO;
     const BLOCK_TIME_WEIGHT: f32 = 0.05;
@@ -366,8 +498,71 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
         use SyncEvent::*;
         match event {
             L1Update(update) => {
-                l1_update(&mut db_conn, &update).await?;
+                let comparison = l1_update(&mut db_conn, &update).await?;
+                websocket_txs
+                    .transaction_status
+                    .send_if_receiving(TransactionStatusUpdate::AcceptedOnL1(update.block_number));
                 tracing::info!("L1 sync updated to block {}", update.block_number);
+
+                let mismatch = match &comparison {
+                    L1Comparison::Mismatch(mismatch) => Some(*mismatch),
+                    L1Comparison::Match | L1Comparison::NotSynced => None,
+                };
+                metrics::gauge!("l1_l2_mismatch", u8::from(mismatch.is_some()) as f64);
+                *state.l1_mismatch.write().await = mismatch;
+
+                match (comparison, l1_mismatch_policy) {
+                    (_, L1MismatchPolicy::Warn) | (L1Comparison::NotSynced, _) => {}
+                    (L1Comparison::Match, _) => {
+                        rollbacks = None;
+                        if halted {
+                            tracing::info!(block_number=%update.block_number, "Resuming L2 sync as L1 matches the synced blocks again");
+                            halted = false;
+                            l2_commands
+                                .send(L2Command::Start)
+                                .await
+                                .context("Starting L2 sync")?;
+                        }
+                    }
+                    (L1Comparison::Mismatch(_), _) if halted => {}
+                    (L1Comparison::Mismatch(mismatch), policy) => {
+                        stop_l2(&mut events, &l2_commands, &mut db_conn).await?;
+                        pending_data.clear().await;
+
+                        let attempts = match rollbacks {
+                            Some((block, attempts)) if block == mismatch.block_number => {
+                                attempts + 1
+                            }
+                            _ => 1,
+                        };
+
+                        if policy == L1MismatchPolicy::Rollback && attempts <= MAX_ROLLBACKS {
+                            rollbacks = Some((mismatch.block_number, attempts));
+                            match l1_rollback(&mut db_conn, mismatch.block_number).await {
+                                Ok(head) => {
+                                    tracing::warn!(head=?head, "Rolled back L2 state to the latest block matching L1");
+                                    l2_commands
+                                        .send(L2Command::Start)
+                                        .await
+                                        .context("Starting L2 sync")?;
+                                }
+                                Err(error) => {
+                                    tracing::error!(
+                                        ?error,
+                                        "Rolling back L2 state failed, L2 sync remains stopped until L1 matches"
+                                    );
+                                    halted = true;
+                                }
+                            }
+                        } else {
+                            if policy == L1MismatchPolicy::Rollback {
+                                tracing::error!(block_number=%mismatch.block_number, rollbacks=%MAX_ROLLBACKS, "Rolling back did not resolve the L1/L2 block hash mismatch");
+                            }
+                            tracing::error!(block_number=%mismatch.block_number, "Halting L2 sync due to L1/L2 block hash mismatch");
+                            halted = true;
+                        }
+                    }
+                }
             }
             Block((block, (tx_comm, ev_comm)), state_update, timings) => {
                 let block_number = block.block_number;
@@ -378,6 +573,11 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                     .iter()
                     .map(|x| x.1.storage.len())
                     .sum();
+                // Only collect events if someone is listening as this requires a copy of each event.
+                let events = (websocket_txs.events.0.receiver_count() > 0)
+                    .then(|| Arc::new(EmittedEvent::from_block(&block)));
+                let status_update = (websocket_txs.transaction_status.0.receiver_count() > 0)
+                    .then(|| TransactionStatusUpdate::accepted_on_l2(&block));
                 let update_t = std::time::Instant::now();
                 l2_update(
                     &mut db_conn,
@@ -393,6 +593,14 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                 // there is no easy way of having a transaction over both memory and database. sqlite does support
                 // multi-database transactions, but it does not work for WAL mode.
                 pending_data.clear().await;
+                if let Some(events) = events {
+                    websocket_txs.events.send_if_receiving(events);
+                }
+                if let Some(status_update) = status_update {
+                    websocket_txs
+                        .transaction_status
+                        .send_if_receiving(status_update);
+                }
                 let block_time = last_block_start.elapsed();
                 let update_t = update_t.elapsed();
                 last_block_start = std::time::Instant::now();
@@ -514,6 +722,34 @@ async fn consumer(mut events: Receiver<SyncEvent>, context: ConsumerContext) ->
                 tracing::debug!(sierra=%sierra_hash, casm=%casm_hash, "Inserted new Sierra class");
             }
             Pending(block, state_update) => {
+                let first_new = pending_cursor.advance(&block);
+                let new_receipts = &block.transaction_receipts[first_new..];
+                if !new_receipts.is_empty() && websocket_txs.events.0.receiver_count() > 0 {
+                    let events = EmittedEvent::from_pending_receipts(new_receipts);
+                    websocket_txs.events.send_if_receiving(Arc::new(events));
+                }
+                if !new_receipts.is_empty()
+                    && websocket_txs.transaction_status.0.receiver_count() > 0
+                {
+                    websocket_txs
+                        .transaction_status
+                        .send_if_receiving(TransactionStatusUpdate::pending(new_receipts));
+                }
+                if !new_receipts.is_empty()
+                    && websocket_txs.pending_transactions.0.receiver_count() > 0
+                {
+                    let transactions = block
+                        .transactions
+                        .iter()
+                        .zip(&block.transaction_receipts)
+                        .skip(first_new)
+                        .map(|(transaction, receipt)| (transaction.clone(), receipt.clone()))
+                        .collect();
+                    websocket_txs
+                        .pending_transactions
+                        .send_if_receiving(Arc::new(transactions));
+                }
+
                 pending_data.set(block, state_update).await;
                 tracing::debug!("Updated pending data");
             }
@@ -638,10 +874,11 @@ async fn propagate_head(
     *last_propagated = Instant::now();
 }
 
+/// Stores the L1 state update, and compares it to the synced block.
 async fn l1_update(
     connection: &mut Connection,
     update: &EthereumStateUpdate,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<L1Comparison> {
     tokio::task::block_in_place(move || {
         let transaction = connection
             .transaction_with_behavior(TransactionBehavior::Immediate)
@@ -656,24 +893,87 @@ async fn l1_update(
             .context("Fetching block hash")?
             .map(|(_, hash)| hash);
 
+        let mut comparison = L1Comparison::NotSynced;
         if let Some(l2_hash) = l2_hash {
             if l2_hash == update.block_hash {
                 transaction
                     .update_l1_l2_pointer(Some(update.block_number))
                     .context("Updating L1-L2 pointer")?;
                 tracing::info!(block=?update.block_number, "Updated L1/L2 match");
+                comparison = L1Comparison::Match;
             } else {
                 tracing::warn!(block_number=?update.block_number, L1=?update.block_hash, L2=?l2_hash, "L1/L2 block hash mismatch");
                 if let Some(matching_block_number) = transaction.l1_l2_pointer()? {
                     tracing::warn!(block_number=?matching_block_number, "Most recent L1/L2 block hash match")
                 }
+                comparison = L1Comparison::Mismatch(L1Mismatch {
+                    block_number: update.block_number,
+                    l1_block_hash: update.block_hash,
+                    l2_block_hash: l2_hash,
+                });
             }
         }
 
-        transaction.commit().context("Commit database transaction")
+        transaction
+            .commit()
+            .context("Commit database transaction")?;
+
+        Ok(comparison)
     })
 }
 
+/// Stops the L2 producer and discards the events it already emitted, except for L1 updates which
+/// are still stored.
+async fn stop_l2(
+    events: &mut Receiver<SyncEvent>,
+    l2_commands: &mpsc::Sender<L2Command>,
+    connection: &mut Connection,
+) -> anyhow::Result<()> {
+    let (stopped_sender, stopped) = oneshot::channel();
+    l2_commands
+        .send(L2Command::Stop(stopped_sender))
+        .await
+        .context("Stopping L2 sync")?;
+    stopped.await.context("Waiting for L2 sync to stop")?;
+
+    while let Ok(event) = events.try_recv() {
+        if let SyncEvent::L1Update(update) = event {
+            l1_update(connection, &update).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Purges the blocks following the latest one matching L1, which precedes the mismatching
+/// `block_number`. Returns the new L2 head.
+async fn l1_rollback(
+    connection: &mut Connection,
+    block_number: BlockNumber,
+) -> anyhow::Result<Option<BlockNumber>> {
+    let pointer = tokio::task::block_in_place(|| {
+        let transaction = connection
+            .transaction()
+            .context("Create database transaction")?;
+        transaction.l1_l2_pointer().context("Query L1-L2 head")
+    })?;
+
+    let reorg_tail = match pointer {
+        Some(pointer) => (pointer + 1).min(block_number),
+        None => BlockNumber::GENESIS,
+    };
+    l2_reorg(connection, reorg_tail)
+        .await
+        .with_context(|| format!("Reorg L2 state to {reorg_tail:?}"))?;
+
+    let head = match reorg_tail {
+        BlockNumber::GENESIS => None,
+        other => Some(other - 1),
+    };
+
+    Ok(head)
+}
+
 /// Returns the new [StateCommitment] after the update.
 async fn l2_update(
     connection: &mut Connection,
@@ -687,9 +987,13 @@ async fn l2_update(
         let transaction = connection
             .transaction_with_behavior(TransactionBehavior::Immediate)
             .context("Create database transaction")?;
-        let (storage_commitment, class_commitment) =
-            update_starknet_state(&transaction, &state_update, verify_tree_hashes)
-                .context("Updating Starknet state")?;
+        let (storage_commitment, class_commitment) = update_starknet_state(
+            &transaction,
+            &state_update,
+            block.block_number,
+            verify_tree_hashes,
+        )
+        .context("Updating Starknet state")?;
         let state_commitment = StateCommitment::calculate(storage_commitment, class_commitment);
 
         // Ensure that roots match.. what should we do if it doesn't? For now the whole sync process ends..
@@ -754,6 +1058,10 @@ async fn l2_update(
             .insert_state_update(block.block_number, &state_update)
             .context("Insert state update into database")?;
 
+        transaction
+            .prune_tries(header.number)
+            .context("Pruning state tries")?;
+
         // Track combined L1 and L2 state.
         let l1_l2_head = transaction.l1_l2_pointer().context("Query L1-L2 head")?;
         let expected_next = l1_l2_head
@@ -791,6 +1099,19 @@ async fn l2_reorg(connection: &mut Connection, reorg_tail: BlockNumber) -> anyho
             .context("Latest block number is none during reorg")?
             .0;
 
+        // The state of the new head must still be present.
+        if let TriePruneMode::Prune { history } = transaction
+            .trie_prune_mode()
+            .context("Querying trie prune mode")?
+        {
+            let oldest = head.get().saturating_sub(history);
+            anyhow::ensure!(
+                reorg_tail == BlockNumber::GENESIS || reorg_tail.get() > oldest,
+                "Reorg to block {} exceeds the {history} blocks of state trie history kept by this database",
+                reorg_tail - 1
+            );
+        }
+
         // Purge each block one at a time.
         //
         // This is done 1-by-1 to allow sending the reorg'd block data
@@ -835,6 +1156,7 @@ async fn l2_reorg(connection: &mut Connection, reorg_tail: BlockNumber) -> anyho
 fn update_starknet_state(
     transaction: &Transaction<'_>,
     state_update: &StateUpdate,
+    block: BlockNumber,
     verify_hashes: bool,
 ) -> anyhow::Result<(StorageCommitment, ClassCommitment)> {
     let (storage_commitment, class_commitment) = transaction
@@ -854,6 +1176,7 @@ fn update_starknet_state(
             update.class.as_ref().map(|x| x.class_hash()),
             &storage_commitment_tree,
             transaction,
+            block,
             verify_hashes,
         )
         .context("Update contract state")?;
@@ -871,6 +1194,7 @@ fn update_starknet_state(
             None,
             &storage_commitment_tree,
             transaction,
+            block,
             verify_hashes,
         )
         .context("Update system contract state")?;
@@ -888,6 +1212,15 @@ fn update_starknet_state(
         .insert_storage_trie(new_storage_commitment, &nodes)
         .context("Persisting storage trie")?;
     tracing::trace!(new_nodes=%count, "Storage trie persisted");
+    transaction
+        .insert_trie_root_change(
+            block,
+            TrieRootChange::Storage {
+                old: storage_commitment,
+                new: new_storage_commitment,
+            },
+        )
+        .context("Recording storage trie root change")?;
 
     // Add new Sierra classes to class commitment tree.
     let mut class_commitment_tree =
@@ -906,27 +1239,40 @@ fn update_starknet_state(
     }
 
     // Apply all class commitment tree changes.
-    let (class_commitment, nodes) = class_commitment_tree
+    let (new_class_commitment, nodes) = class_commitment_tree
         .commit()
         .context("Apply class commitment tree updates")?;
     let count = transaction
-        .insert_class_trie(class_commitment, &nodes)
+        .insert_class_trie(new_class_commitment, &nodes)
         .context("Persisting class trie")?;
     tracing::trace!(new_nodes=%count, "Class trie persisted");
+    transaction
+        .insert_trie_root_change(
+            block,
+            TrieRootChange::Class {
+                old: class_commitment,
+                new: new_class_commitment,
+            },
+        )
+        .context("Recording class trie root change")?;
 
-    Ok((new_storage_commitment, class_commitment))
+    Ok((new_storage_commitment, new_class_commitment))
 }
 
 #[cfg(test)]
 mod tests {
     use super::l2;
-    use crate::state::sync::{consumer, ConsumerContext, SyncEvent};
+    use crate::state::sync::{
+        consumer, ConsumerContext, L1MismatchPolicy, L2Command, PendingCursor, SyncEvent,
+    };
+    use fake::{Fake, Faker};
     use pathfinder_common::macro_prelude::*;
     use pathfinder_common::{
         felt_bytes, BlockHash, BlockHeader, BlockNumber, ClassHash, EventCommitment, SierraHash,
         StateCommitment, StateUpdate, TransactionCommitment,
     };
-    use pathfinder_rpc::SyncState;
+    use pathfinder_ethereum::EthereumStateUpdate;
+    use pathfinder_rpc::{websocket::types::WebsocketSenders, L1Mismatch, SyncState};
     use pathfinder_storage::Storage;
     use stark_hash::Felt;
     use starknet_gateway_types::reply::Block;
@@ -1018,6 +1364,9 @@ mod tests {
             state: Arc::new(SyncState::default()),
             pending_data: PendingData::default(),
             verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Warn,
+            l2_commands: tokio::sync::mpsc::channel(1).0,
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1061,6 +1410,9 @@ mod tests {
             state: Arc::new(SyncState::default()),
             pending_data: PendingData::default(),
             verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Warn,
+            l2_commands: tokio::sync::mpsc::channel(1).0,
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1103,6 +1455,9 @@ mod tests {
             state: Arc::new(SyncState::default()),
             pending_data: PendingData::default(),
             verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Warn,
+            l2_commands: tokio::sync::mpsc::channel(1).0,
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1112,6 +1467,283 @@ mod tests {
         assert!(!genesis_exists);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn l1_mismatch_rollback() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let (command_tx, mut command_rx) = tokio::sync::mpsc::channel(1);
+
+        let block_data = generate_block_data();
+        let update_of = |i: usize| EthereumStateUpdate {
+            state_root: block_data[i].0 .0.state_commitment,
+            block_number: block_data[i].0 .0.block_number,
+            block_hash: block_data[i].0 .0.block_hash,
+        };
+        let matching = update_of(1);
+        let mismatching = EthereumStateUpdate {
+            block_hash: block_hash!("0x1234"),
+            ..update_of(2)
+        };
+        let l2_hash = block_data[2].0 .0.block_hash;
+
+        for (a, b, c) in block_data {
+            event_tx.send(SyncEvent::Block(a, b, c)).await.unwrap();
+        }
+        event_tx.send(SyncEvent::L1Update(matching)).await.unwrap();
+        event_tx
+            .send(SyncEvent::L1Update(mismatching.clone()))
+            .await
+            .unwrap();
+        // Close the event channel which allows the consumer task to exit.
+        drop(event_tx);
+
+        // Stands in for the main sync loop controlling the L2 producer.
+        let commands = tokio::spawn(async move {
+            let mut started = false;
+            while let Some(command) = command_rx.recv().await {
+                match command {
+                    L2Command::Stop(stopped) => stopped.send(()).unwrap(),
+                    L2Command::Start => started = true,
+                }
+            }
+            started
+        });
+
+        let state = Arc::new(SyncState::default());
+        let context = ConsumerContext {
+            storage,
+            state: state.clone(),
+            pending_data: PendingData::default(),
+            verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Rollback,
+            l2_commands: command_tx,
+        };
+
+        consumer(event_rx, context).await.unwrap();
+        assert!(commands.await.unwrap());
+
+        assert_eq!(
+            *state.l1_mismatch.read().await,
+            Some(L1Mismatch {
+                block_number: mismatching.block_number,
+                l1_block_hash: mismatching.block_hash,
+                l2_block_hash: l2_hash,
+            })
+        );
+
+        let tx = connection.transaction().unwrap();
+        let block_1_exists = tx
+            .block_exists(BlockNumber::new_or_panic(1).into())
+            .unwrap();
+        assert!(block_1_exists);
+
+        let block_2_exists = tx
+            .block_exists(BlockNumber::new_or_panic(2).into())
+            .unwrap();
+        assert!(!block_2_exists);
+    }
+
+    fn pending_block(
+        parent_hash: BlockHash,
+        transactions: &[(reply::transaction::Transaction, reply::transaction::Receipt)],
+    ) -> reply::PendingBlock {
+        reply::PendingBlock {
+            gas_price: Default::default(),
+            parent_hash,
+            sequencer_address: Default::default(),
+            status: reply::Status::Pending,
+            timestamp: Default::default(),
+            transaction_receipts: transactions
+                .iter()
+                .map(|(_, receipt)| receipt.clone())
+                .collect(),
+            transactions: transactions
+                .iter()
+                .map(|(transaction, _)| transaction.clone())
+                .collect(),
+            starknet_version: Default::default(),
+        }
+    }
+
+    /// Sets some pending data, which the consumer clears once it has stopped L2 sync and
+    /// drained its events.
+    async fn set_pending(pending_data: &PendingData) {
+        let block = pending_block(BlockHash::ZERO, &[]);
+        pending_data
+            .set(Arc::new(block), Arc::new(StateUpdate::default()))
+            .await;
+    }
+
+    async fn wait_for_cleared(pending_data: &PendingData) {
+        while pending_data.block().await.is_some() {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn l1_mismatch_halt_resumes_on_match() {
+        let storage = Storage::in_memory().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let (command_tx, mut command_rx) = tokio::sync::mpsc::channel(1);
+
+        let block_data = generate_block_data();
+        let matching = EthereumStateUpdate {
+            state_root: block_data[2].0 .0.state_commitment,
+            block_number: block_data[2].0 .0.block_number,
+            block_hash: block_data[2].0 .0.block_hash,
+        };
+        let mismatching = EthereumStateUpdate {
+            block_hash: block_hash!("0x1234"),
+            ..matching.clone()
+        };
+
+        for (a, b, c) in block_data {
+            event_tx.send(SyncEvent::Block(a, b, c)).await.unwrap();
+        }
+        event_tx
+            .send(SyncEvent::L1Update(mismatching))
+            .await
+            .unwrap();
+
+        let pending_data = PendingData::default();
+        set_pending(&pending_data).await;
+
+        // Stands in for the main sync loop controlling the L2 producer.
+        let commands = tokio::spawn({
+            let pending_data = pending_data.clone();
+            async move {
+                let mut event_tx = Some(event_tx);
+                let mut started = false;
+                while let Some(command) = command_rx.recv().await {
+                    match command {
+                        L2Command::Stop(stopped) => {
+                            stopped.send(()).unwrap();
+                            wait_for_cleared(&pending_data).await;
+                            // L1 attests the synced block after all.
+                            event_tx
+                                .as_ref()
+                                .unwrap()
+                                .send(SyncEvent::L1Update(matching.clone()))
+                                .await
+                                .unwrap();
+                        }
+                        L2Command::Start => {
+                            started = true;
+                            // Close the event channel which allows the consumer task to exit.
+                            event_tx = None;
+                        }
+                    }
+                }
+                started
+            }
+        });
+
+        let state = Arc::new(SyncState::default());
+        let context = ConsumerContext {
+            storage,
+            state: state.clone(),
+            pending_data,
+            verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Halt,
+            l2_commands: command_tx,
+        };
+
+        consumer(event_rx, context).await.unwrap();
+        assert!(commands.await.unwrap());
+        assert_eq!(*state.l1_mismatch.read().await, None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn l1_mismatch_rollback_halts_when_repeated() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let (command_tx, mut command_rx) = tokio::sync::mpsc::channel(1);
+
+        let block_data = generate_block_data();
+        let update_of = |i: usize| EthereumStateUpdate {
+            state_root: block_data[i].0 .0.state_commitment,
+            block_number: block_data[i].0 .0.block_number,
+            block_hash: block_data[i].0 .0.block_hash,
+        };
+        let matching = update_of(1);
+        let mismatching = EthereumStateUpdate {
+            block_hash: block_hash!("0x1234"),
+            ..update_of(2)
+        };
+
+        for (a, b, c) in block_data {
+            event_tx.send(SyncEvent::Block(a, b, c)).await.unwrap();
+        }
+        event_tx.send(SyncEvent::L1Update(matching)).await.unwrap();
+        event_tx
+            .send(SyncEvent::L1Update(mismatching.clone()))
+            .await
+            .unwrap();
+
+        let pending_data = PendingData::default();
+        set_pending(&pending_data).await;
+
+        // Stands in for the main sync loop, with L2 syncing the same block after each rollback.
+        let commands = tokio::spawn({
+            let pending_data = pending_data.clone();
+            async move {
+                let mut event_tx = Some(event_tx);
+                let mut starts = 0;
+                while let Some(command) = command_rx.recv().await {
+                    match command {
+                        L2Command::Stop(stopped) => {
+                            stopped.send(()).unwrap();
+                            wait_for_cleared(&pending_data).await;
+                            if starts == super::MAX_ROLLBACKS {
+                                // Close the event channel which allows the consumer task to exit.
+                                event_tx = None;
+                            }
+                        }
+                        L2Command::Start => {
+                            starts += 1;
+                            set_pending(&pending_data).await;
+                            let (a, b, c) = generate_block_data().remove(2);
+                            let event_tx = event_tx.as_ref().unwrap();
+                            event_tx.send(SyncEvent::Block(a, b, c)).await.unwrap();
+                            event_tx
+                                .send(SyncEvent::L1Update(mismatching.clone()))
+                                .await
+                                .unwrap();
+                        }
+                    }
+                }
+                starts
+            }
+        });
+
+        let context = ConsumerContext {
+            storage,
+            state: Arc::new(SyncState::default()),
+            pending_data,
+            verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Rollback,
+            l2_commands: command_tx,
+        };
+
+        consumer(event_rx, context).await.unwrap();
+        assert_eq!(commands.await.unwrap(), super::MAX_ROLLBACKS);
+
+        // The last mismatch halted L2 sync instead of rolling back again.
+        let tx = connection.transaction().unwrap();
+        let block_2_exists = tx
+            .block_exists(BlockNumber::new_or_panic(2).into())
+            .unwrap();
+        assert!(block_2_exists);
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn new_cairo_contract() {
         let storage = Storage::in_memory().unwrap();
@@ -1137,6 +1769,9 @@ mod tests {
             state: Arc::new(SyncState::default()),
             pending_data: PendingData::default(),
             verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Warn,
+            l2_commands: tokio::sync::mpsc::channel(1).0,
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1174,6 +1809,9 @@ mod tests {
             state: Arc::new(SyncState::default()),
             pending_data: PendingData::default(),
             verify_tree_hashes: false,
+            websocket_txs: WebsocketSenders::for_test(),
+            l1_mismatch_policy: L1MismatchPolicy::Warn,
+            l2_commands: tokio::sync::mpsc::channel(1).0,
         };
 
         consumer(event_rx, context).await.unwrap();
@@ -1183,4 +1821,111 @@ mod tests {
 
         assert_eq!(definition, expected_definition);
     }
+
+    fn fake_transactions(
+        count: usize,
+    ) -> Vec<(reply::transaction::Transaction, reply::transaction::Receipt)> {
+        (0..count)
+            .map(|_| {
+                let transaction: reply::transaction::Transaction = Faker.fake();
+                let receipt = reply::transaction::Receipt {
+                    transaction_hash: transaction.hash(),
+                    ..Faker.fake()
+                };
+                (transaction, receipt)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pending_cursor() {
+        let transactions = fake_transactions(3);
+        let mut cursor = PendingCursor::default();
+
+        let block = pending_block(block_hash!("0x1"), &transactions[..2]);
+        assert_eq!(cursor.advance(&block), 0);
+        // Repeated polls of the same pending block have nothing new.
+        assert_eq!(cursor.advance(&block), 2);
+        assert_eq!(cursor.advance(&block), 2);
+
+        let block = pending_block(block_hash!("0x1"), &transactions);
+        assert_eq!(cursor.advance(&block), 2);
+        assert_eq!(cursor.advance(&block), 3);
+
+        // A replaced pending block with fewer transactions starts over.
+        let block = pending_block(block_hash!("0x1"), &transactions[..1]);
+        assert_eq!(cursor.advance(&block), 0);
+        assert_eq!(cursor.advance(&block), 1);
+
+        // As does one with as many transactions or more.
+        let replacement = fake_transactions(2);
+        let block = pending_block(block_hash!("0x1"), &replacement);
+        assert_eq!(cursor.advance(&block), 0);
+        assert_eq!(cursor.advance(&block), 2);
+
+        // So does a pending block on top of a new block.
+        let block = pending_block(block_hash!("0x2"), &transactions);
+        assert_eq!(cursor.advance(&block), 0);
+        assert_eq!(cursor.advance(&block), 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pending_transactions_are_broadcast_once() {
+        let (event_tx, event_rx) = tokio::sync::mpsc::channel(100);
+        let websocket_txs = WebsocketSenders::for_test();
+        let mut pending_transactions = websocket_txs.pending_transactions.0.subscribe();
+
+        let transactions = fake_transactions(4);
+        for (parent_hash, transactions) in [
+            (block_hash!("0x1"), &transactions[..2]),
+            (block_hash!("0x1"), &transactions[..2]),
+            (block_hash!("0x1"), &transactions[..3]),
+            (block_hash!("0x2"), &transactions[3..]),
+        ] {
+            let block = pending_block(parent_hash, transactions);
+            event_tx
+                .send(SyncEvent::Pending(
+                    Arc::new(block),
+                    Arc::new(StateUpdate::default()),
+                ))
+                .await
+                .unwrap();
+        }
+        // Close the event channel which allows the consumer task to exit.
+        drop(event_tx);
+
+        let context = ConsumerContext {
+            storage: Storage::in_memory().unwrap(),
+            state: Arc::new(SyncState::default()),
+            pending_data: PendingData::default(),
+            verify_tree_hashes: false,
+            websocket_txs,
+            l1_mismatch_policy: L1MismatchPolicy::Warn,
+            l2_commands: tokio::sync::mpsc::channel(1).0,
+        };
+
+        consumer(event_rx, context).await.unwrap();
+
+        let mut broadcasts = Vec::new();
+        while let Ok(broadcast) = pending_transactions.try_recv() {
+            broadcasts.push(
+                broadcast
+                    .iter()
+                    .map(|(transaction, _)| transaction.hash())
+                    .collect::<Vec<_>>(),
+            );
+        }
+        let hashes = transactions
+            .iter()
+            .map(|(transaction, _)| transaction.hash())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            broadcasts,
+            vec![
+                hashes[..2].to_vec(),
+                hashes[2..3].to_vec(),
+                hashes[3..].to_vec()
+            ]
+        );
+    }
 }
@@ -24,7 +24,7 @@ pub async fn sync<T>(
     context: L1SyncContext<T>,
 ) -> anyhow::Result<()>
 where
-    T: EthereumApi + Clone,
+    T: EthereumApi + Clone + Sync,
 {
     let L1SyncContext {
         ethereum,
@@ -50,6 +50,6 @@ where
             tx_event.send(SyncEvent::L1Update(state_update)).await?;
         }
 
-        tokio::time::sleep(poll_interval).await;
+        ethereum.wait_for_state_update(poll_interval).await;
     }
 }
@@ -39,6 +39,8 @@ pub struct RpcContext {
     pub eth_gas_price: gas_price::Cached,
     pub sequencer: SequencerClient,
     pub version: RpcVersion,
+    /// Whether `pathfinder_dbStats` is served, which reads most of the database.
+    pub db_stats: bool,
 }
 
 impl RpcContext {
@@ -58,6 +60,7 @@ impl RpcContext {
             eth_gas_price: gas_price::Cached::new(sequencer.clone()),
             sequencer,
             version: RpcVersion::default(),
+            db_stats: false,
         }
     }
 
@@ -108,6 +111,13 @@ impl RpcContext {
         }
     }
 
+    pub fn with_db_stats(self) -> Self {
+        Self {
+            db_stats: true,
+            ..self
+        }
+    }
+
     pub async fn for_tests_with_pending() -> Self {
         // This is a bit silly with the arc in and out, but since its for tests the ergonomics of
         // having Arc also constructed is nice.
@@ -61,6 +61,16 @@ pub enum RpcError {
     UnexpectedError { data: String },
     #[error("Too many storage keys requested")]
     ProofLimitExceeded { limit: u32, requested: u32 },
+    #[error("The state of the requested block has been pruned")]
+    BlockPruned,
+    #[error("The requested events are not indexed by this node")]
+    EventsNotIndexed,
+    #[error("Deploy transactions cannot be re-executed")]
+    DeployNotSupported,
+    #[error("Database statistics are disabled on this node")]
+    DbStatsDisabled,
+    #[error("The first block of the range follows the last one")]
+    InvalidBlockRange,
     #[error(transparent)]
     GatewayError(starknet_gateway_types::error::StarknetError),
     #[error(transparent)]
@@ -97,6 +107,11 @@ impl RpcError {
             RpcError::UnsupportedContractClassVersion => 62,
             RpcError::UnexpectedError { .. } => 63,
             RpcError::ProofLimitExceeded { .. } => 10000,
+            RpcError::BlockPruned => 10001,
+            RpcError::EventsNotIndexed => 10002,
+            RpcError::DeployNotSupported => 10003,
+            RpcError::DbStatsDisabled => 10004,
+            RpcError::InvalidBlockRange => 10005,
             RpcError::GatewayError(_) | RpcError::Internal(_) => {
                 jsonrpsee::types::error::ErrorCode::InternalError.code()
             }
@@ -6,8 +6,8 @@ use stark_hash::Felt;
 use starknet_api::core::PatriciaKey;
 
 use super::v02::types::request::BroadcastedTransaction;
-use pathfinder_common::ChainId;
 use pathfinder_common::{BlockId, BlockTimestamp, StateUpdate};
+use pathfinder_common::{BlockNumber, ChainId, TransactionHash};
 use pathfinder_executor::IntoStarkFelt;
 use starknet_gateway_types::pending::PendingData;
 
@@ -15,6 +15,7 @@ use crate::context::RpcContext;
 
 pub enum ExecutionStateError {
     BlockNotFound,
+    BlockPruned,
     Internal(anyhow::Error),
 }
 
@@ -46,6 +47,13 @@ pub(crate) async fn execution_state(
             .context("Reading block")?
             .ok_or_else(|| ExecutionStateError::BlockNotFound)?;
 
+        if tx
+            .block_pruned(block.number)
+            .context("Checking if block is pruned")?
+        {
+            return Err(ExecutionStateError::BlockPruned);
+        }
+
         Ok::<_, ExecutionStateError>(block)
     })
     .await
@@ -74,6 +82,109 @@ pub(crate) async fn execution_state(
     Ok(execution_state)
 }
 
+pub enum ReExecutionError {
+    BlockNotFound,
+    /// The state of the block's parent, which the block is executed on, has been pruned.
+    BlockPruned,
+    /// The transactions to re-execute include a Deploy transaction, which blockifier
+    /// does not support.
+    DeployNotSupported,
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ReExecutionError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Internal(error)
+    }
+}
+
+/// Prepares the re-execution of a block stored in the database.
+///
+/// Returns an execution state for `block_id` on top of its parent block's state, together
+/// with the block's transactions. If `last_transaction` is set, transactions following it are
+/// omitted.
+pub(crate) fn re_execution_state(
+    storage: &pathfinder_storage::Storage,
+    chain_id: ChainId,
+    block_id: pathfinder_storage::BlockId,
+    last_transaction: Option<TransactionHash>,
+) -> Result<
+    (
+        pathfinder_executor::ExecutionState,
+        Vec<pathfinder_executor::Transaction>,
+    ),
+    ReExecutionError,
+> {
+    let mut db = storage
+        .connection()
+        .context("Opening database connection")?;
+    let db_tx = db.transaction().context("Creating database transaction")?;
+
+    let header = db_tx
+        .block_header(block_id)
+        .context("Reading block header")?
+        .ok_or(ReExecutionError::BlockNotFound)?;
+
+    let state_at_block = header
+        .number
+        .get()
+        .checked_sub(1)
+        .map(BlockNumber::new_or_panic);
+    if let Some(parent) = state_at_block {
+        if db_tx
+            .block_pruned(parent)
+            .context("Checking if block is pruned")?
+        {
+            return Err(ReExecutionError::BlockPruned);
+        }
+    }
+
+    let mut transactions = db_tx
+        .transaction_data_for_block(header.hash.into())
+        .context("Reading block transactions")?
+        .context("Block transactions missing")?
+        .into_iter()
+        .map(|(transaction, _)| transaction)
+        .collect::<Vec<_>>();
+
+    if let Some(last) = last_transaction {
+        let position = transactions
+            .iter()
+            .position(|tx| tx.hash() == last)
+            .context("Transaction missing from its block")?;
+        transactions.truncate(position + 1);
+    }
+
+    // Skipping the Deploy transactions would silently produce wrong traces for any
+    // transactions depending on the deployed contracts.
+    if transactions.iter().any(|tx| {
+        matches!(
+            tx,
+            starknet_gateway_types::reply::transaction::Transaction::Deploy(_)
+        )
+    }) {
+        return Err(ReExecutionError::DeployNotSupported);
+    }
+
+    let transactions = transactions
+        .into_iter()
+        .map(|tx| pathfinder_executor::map_gateway_transaction(tx, &db_tx))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let execution_state = pathfinder_executor::ExecutionState {
+        connection: storage.connection()?,
+        chain_id,
+        block_number: header.number,
+        block_timestamp: header.timestamp,
+        sequencer_address: header.sequencer_address,
+        state_at_block,
+        gas_price: header.gas_price.0.into(),
+        pending_update: None,
+    };
+
+    Ok((execution_state, transactions))
+}
+
 async fn prepare_block(
     context: &RpcContext,
     block_id: BlockId,
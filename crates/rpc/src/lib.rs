@@ -25,7 +25,7 @@ use context::RpcContext;
 use http::Request;
 use hyper::Body;
 use jsonrpsee::server::{ServerBuilder, ServerHandle};
-use pathfinder_common::AllowedOrigins;
+use pathfinder_common::{AllowedOrigins, BlockHash, BlockNumber};
 use std::num::NonZeroUsize;
 use std::{net::SocketAddr, result::Result};
 use tokio::sync::RwLock;
@@ -39,6 +39,7 @@ pub struct RpcServer {
     logger: MaybeRpcMetricsLogger,
     max_connections: u32,
     cors: Option<CorsLayer>,
+    rate_limit: Option<middleware::rate_limit::RateLimitLayer>,
     ws_senders: Option<WebsocketSenders>,
     default_version: DefaultVersion,
 }
@@ -51,6 +52,7 @@ impl RpcServer {
             logger: MaybeRpcMetricsLogger::NoOp,
             max_connections: DEFAULT_MAX_CONNECTIONS,
             cors: None,
+            rate_limit: None,
             ws_senders: None,
             default_version,
         }
@@ -82,6 +84,13 @@ impl RpcServer {
         }
     }
 
+    pub fn with_rate_limit(self, config: middleware::rate_limit::RateLimitConfig) -> Self {
+        Self {
+            rate_limit: Some(middleware::rate_limit::RateLimitLayer::new(config)),
+            ..self
+        }
+    }
+
     /// Starts the HTTP-RPC server.
     pub async fn run(self) -> Result<(ServerHandle, SocketAddr), anyhow::Error> {
         const TEN_MB: u32 = 10 * 1024 * 1024;
@@ -92,7 +101,8 @@ impl RpcServer {
 			}
             .max_connections(self.max_connections)
             .max_request_body_size(TEN_MB)
-            .set_logger(self.logger)
+            // The rate limiter relies on the logger for the peer address.
+            .set_logger((self.logger, middleware::rate_limit::PeerAddrLogger))
             .set_middleware(tower::ServiceBuilder::new()
                 .option_layer(self.cors)
                 .map_result(middleware::versioning::try_map_errors_to_responses)
@@ -107,6 +117,7 @@ impl RpcServer {
 
                     middleware::versioning::prefix_rpc_method_names_with_version(request, TEN_MB, default_version).await
                 }})
+                .option_layer(self.rate_limit)
             )
             .build(self.addr)
             .await
@@ -158,16 +169,27 @@ Hint: If you are looking to run two instances of pathfinder, you must configure
 
 pub struct SyncState {
     pub status: RwLock<Syncing>,
+    /// Set while the latest state update on L1 contradicts the synced L2 block.
+    pub l1_mismatch: RwLock<Option<L1Mismatch>>,
 }
 
 impl Default for SyncState {
     fn default() -> Self {
         Self {
             status: RwLock::new(Syncing::False(false)),
+            l1_mismatch: RwLock::new(None),
         }
     }
 }
 
+/// A block whose hash attested on L1 differs from the one synced from L2.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct L1Mismatch {
+    pub block_number: BlockNumber,
+    pub l1_block_hash: BlockHash,
+    pub l2_block_hash: BlockHash,
+}
+
 pub mod test_utils {
     use pathfinder_common::event::Event;
     use pathfinder_common::macro_prelude::*;
@@ -280,6 +302,7 @@ pub mod test_utils {
             Some(class0_hash),
             &storage_commitment_tree,
             &db_txn,
+            BlockNumber::GENESIS,
             false,
         )
         .unwrap();
@@ -299,6 +322,7 @@ pub mod test_utils {
             Some(class1_hash),
             &storage_commitment_tree,
             &db_txn,
+            BlockNumber::new_or_panic(1),
             false,
         )
         .unwrap();
@@ -312,6 +336,7 @@ pub mod test_utils {
             None,
             &storage_commitment_tree,
             &db_txn,
+            BlockNumber::new_or_panic(1),
             false,
         )
         .unwrap();
@@ -331,6 +356,7 @@ pub mod test_utils {
             None,
             &storage_commitment_tree,
             &db_txn,
+            BlockNumber::new_or_panic(2),
             false,
         )
         .unwrap();
@@ -344,6 +370,7 @@ pub mod test_utils {
             Some(class2_hash),
             &storage_commitment_tree,
             &db_txn,
+            BlockNumber::new_or_panic(2),
             false,
         )
         .unwrap();
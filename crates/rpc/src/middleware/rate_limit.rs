@@ -0,0 +1,615 @@
+//! Per-client rate limiting of JSON-RPC requests.
+//!
+//! Each client gets a token bucket which refills at a constant rate. Every JSON-RPC call
+//! takes tokens from its client's bucket according to the [cost](method_cost) of the
+//! method, and requests for which there are not enough tokens are rejected with a
+//! [LIMIT_EXCEEDED] JSON-RPC error.
+//!
+//! Clients are identified by their peer address. Requests relayed by one of the configured
+//! trusted proxies are instead identified by the address in their `X-Forwarded-For` or
+//! `X-Real-IP` header, and requests carrying one of the configured API keys by that key.
+//!
+//! jsonrpsee does not expose the peer address to middleware, so it is picked up by
+//! [PeerAddrLogger] which must be installed as (part of) the server's logger. Websocket
+//! connections are not rate limited.
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::num::NonZeroU32;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::header::HeaderName;
+use http::HeaderMap;
+use hyper::{Body, Request, Response};
+use jsonrpsee::server::logger::{HttpRequest, Logger, MethodKind, TransportProtocol};
+use jsonrpsee::types::{Id, Params};
+
+/// JSON-RPC error code returned to throttled clients.
+pub const LIMIT_EXCEEDED: i32 = -32005;
+
+/// How often buckets of idle clients are evicted.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// The maximum number of tokens a client can accumulate, i.e. its burst size.
+    pub capacity: NonZeroU32,
+    /// Tokens added to each client's bucket per second.
+    pub refill_rate: NonZeroU32,
+    /// Header used to identify clients by API key.
+    pub api_key_header: HeaderName,
+    /// API keys which identify clients. Requests with any other key are identified by address.
+    pub api_keys: HashSet<String>,
+    /// Reverse proxies whose forwarding headers are trusted.
+    pub trusted_proxies: HashSet<IpAddr>,
+}
+
+/// The number of tokens a call to `method` costs.
+///
+/// `method` may or may not include the version prefix.
+pub fn method_cost(method: &str) -> u32 {
+    let method = crate::module::split_version_prefix(method)
+        .filter(|(version, _)| version.starts_with("v0."))
+        .map(|(_, method)| Cow::Owned(method))
+        .unwrap_or(Cow::Borrowed(method));
+
+    match method.as_ref() {
+        "starknet_traceBlockTransactions" | "pathfinder_dbStats" => 50,
+        "starknet_simulateTransaction"
+        | "starknet_simulateTransactions"
+        | "starknet_traceTransaction" => 20,
+        "starknet_getEvents"
+        | "starknet_estimateFee"
+        | "starknet_estimateMessageFee"
+        | "pathfinder_getProofs" => 10,
+        "starknet_call"
+        | "starknet_getBlockWithReceipts"
+        | "pathfinder_getFeeHistory"
+        | "pathfinder_getProof"
+        | "pathfinder_getStorageChanges"
+        | "pathfinder_getStorageHistory" => 5,
+        _ => 1,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientId {
+    ApiKey(String),
+    Ip(IpAddr),
+}
+
+/// How clients are told apart.
+#[derive(Debug)]
+struct Identification {
+    api_key_header: HeaderName,
+    api_keys: HashSet<String>,
+    trusted_proxies: HashSet<IpAddr>,
+}
+
+impl Identification {
+    fn identify(&self, peer: IpAddr, headers: &HeaderMap) -> ClientId {
+        match header(headers, self.api_key_header.as_str()) {
+            Some(key) if self.api_keys.contains(key) => ClientId::ApiKey(key.to_owned()),
+            _ => ClientId::Ip(self.client_ip(peer, headers)),
+        }
+    }
+
+    fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.trusted_proxies.contains(&peer) {
+            return peer;
+        }
+
+        let forwarded = match header(headers, "x-forwarded-for") {
+            Some(forwarded) => forwarded,
+            None => {
+                return header(headers, "x-real-ip")
+                    .and_then(|ip| ip.parse().ok())
+                    .unwrap_or(peer)
+            }
+        };
+
+        // Every proxy appends the address it received the request from, so the client is the
+        // right-most address which is not one of our proxies. Anything left of it may be forged.
+        let mut client = peer;
+        for hop in forwarded.rsplit(',') {
+            match hop.trim().parse() {
+                Ok(ip) => {
+                    client = ip;
+                    if !self.trusted_proxies.contains(&ip) {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        client
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+/// Slot in which [PeerAddrLogger] records the peer address of a request.
+#[derive(Debug, Clone, Default)]
+struct PeerAddr(Arc<Mutex<Option<SocketAddr>>>);
+
+/// A jsonrpsee [Logger] which passes the peer address of each request on to [RateLimit].
+///
+/// jsonrpsee reports the peer address to its logger when the request is handed to its
+/// service, but before the request is actually handled.
+#[derive(Debug, Clone)]
+pub struct PeerAddrLogger;
+
+impl Logger for PeerAddrLogger {
+    type Instant = ();
+
+    fn on_connect(&self, remote_addr: SocketAddr, request: &HttpRequest, _: TransportProtocol) {
+        if let Some(slot) = request.extensions().get::<PeerAddr>() {
+            *slot.0.lock().unwrap() = Some(remote_addr);
+        }
+    }
+
+    fn on_request(&self, _: TransportProtocol) -> Self::Instant {}
+
+    fn on_call(&self, _: &str, _: Params<'_>, _: MethodKind, _: TransportProtocol) {}
+
+    fn on_result(&self, _: &str, _: bool, _: Self::Instant, _: TransportProtocol) {}
+
+    fn on_response(&self, _: &str, _: Self::Instant, _: TransportProtocol) {}
+
+    fn on_disconnect(&self, _: SocketAddr, _: TransportProtocol) {}
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<ClientId, Bucket>,
+    last_eviction: Instant,
+}
+
+/// Token buckets of all clients.
+#[derive(Debug, Clone)]
+struct RateLimiter {
+    capacity: f64,
+    refill_rate: f64,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    fn new(capacity: NonZeroU32, refill_rate: NonZeroU32, now: Instant) -> Self {
+        Self {
+            capacity: capacity.get() as f64,
+            refill_rate: refill_rate.get() as f64,
+            buckets: Arc::new(Mutex::new(Buckets {
+                buckets: Default::default(),
+                last_eviction: now,
+            })),
+        }
+    }
+
+    /// Takes `cost` tokens from the client's bucket, or returns how long
+    /// the client has to wait until there are enough of them.
+    fn try_acquire(&self, client: ClientId, cost: u32, now: Instant) -> Result<(), Duration> {
+        // A cost above capacity could never be satisfied.
+        let cost = (cost as f64).min(self.capacity);
+
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if now.saturating_duration_since(buckets.last_eviction) >= EVICTION_INTERVAL {
+            // Full buckets are indistinguishable from new ones.
+            buckets
+                .buckets
+                .retain(|_, bucket| self.refill(*bucket, now).tokens < self.capacity);
+            buckets.last_eviction = now;
+        }
+
+        let bucket = buckets.buckets.entry(client).or_insert(Bucket {
+            tokens: self.capacity,
+            updated: now,
+        });
+        *bucket = self.refill(*bucket, now);
+
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (cost - bucket.tokens) / self.refill_rate,
+            ))
+        }
+    }
+
+    fn refill(&self, bucket: Bucket, now: Instant) -> Bucket {
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        Bucket {
+            tokens: (bucket.tokens + elapsed * self.refill_rate).min(self.capacity),
+            updated: now,
+        }
+    }
+}
+
+/// A [tower::Layer] which rate limits JSON-RPC requests per client.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limiter: RateLimiter,
+    identification: Arc<Identification>,
+}
+
+impl RateLimitLayer {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            limiter: RateLimiter::new(config.capacity, config.refill_rate, Instant::now()),
+            identification: Arc::new(Identification {
+                api_key_header: config.api_key_header,
+                api_keys: config.api_keys,
+                trusted_proxies: config.trusted_proxies,
+            }),
+        }
+    }
+}
+
+impl<S> tower::Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+            identification: self.identification.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: RateLimiter,
+    identification: Arc<Identification>,
+}
+
+impl<S> tower::Service<Request<Body>> for RateLimit<S>
+where
+    S: tower::Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // The service which was driven to readiness is the one we must call.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        // Skip websocket handshakes.
+        if request.headers().get("sec-websocket-key").is_some() {
+            return Box::pin(inner.call(request));
+        }
+
+        let limiter = self.limiter.clone();
+        let identification = self.identification.clone();
+
+        Box::pin(async move {
+            // The body size has already been limited by the versioning middleware.
+            let (mut parts, body) = request.into_parts();
+            let body = match hyper::body::to_bytes(body).await {
+                Ok(body) => body,
+                Err(_) => return Ok(response::internal()),
+            };
+
+            let (id, cost) = request_cost(&body);
+
+            let headers = parts.headers.clone();
+            let slot = PeerAddr::default();
+            parts.extensions.insert(slot.clone());
+
+            // The peer address is recorded while the response future is created, and nothing
+            // is executed until that future is polled.
+            let response = inner.call(Request::from_parts(parts, body.clone().into()));
+
+            let peer = *slot.0.lock().unwrap();
+            let peer = match peer {
+                Some(peer) => peer,
+                // The request was rejected by jsonrpsee before reaching the logger.
+                None => return response.await,
+            };
+
+            let client = identification.identify(peer.ip(), &headers);
+            if let Err(retry_after) = limiter.try_acquire(client, cost, Instant::now()) {
+                metrics::increment_counter!("rpc_rate_limited_total");
+                return Ok(response::throttled(id, retry_after));
+            }
+
+            response.await
+        })
+    }
+}
+
+/// Returns the total cost of the request, along with its id if it is a single request.
+///
+/// Malformed requests cost a single token and are left to jsonrpsee to reject.
+fn request_cost(body: &[u8]) -> (Id<'_>, u32) {
+    #[derive(serde::Deserialize)]
+    struct Call<'a> {
+        #[serde(borrow)]
+        id: Option<Id<'a>>,
+        #[serde(borrow)]
+        method: Cow<'a, str>,
+    }
+
+    if let Ok(call) = serde_json::from_slice::<Call<'_>>(body) {
+        return (call.id.unwrap_or(Id::Null), method_cost(&call.method));
+    }
+
+    if let Ok(batch) = serde_json::from_slice::<Vec<Call<'_>>>(body) {
+        let cost = batch
+            .iter()
+            .map(|call| method_cost(&call.method))
+            .fold(0u32, u32::saturating_add);
+        return (Id::Null, cost.max(1));
+    }
+
+    (Id::Null, 1)
+}
+
+mod response {
+    use super::*;
+    use http::StatusCode;
+    use jsonrpsee::types::error::{ErrorCode, ErrorObject, ErrorResponse};
+
+    const JSON: &str = "application/json; charset=utf-8";
+
+    pub(super) fn throttled(id: Id<'_>, retry_after: Duration) -> Response<Body> {
+        #[derive(serde::Serialize)]
+        struct Data {
+            retry_after_ms: u128,
+        }
+
+        let error = ErrorObject::owned(
+            LIMIT_EXCEEDED,
+            "Rate limit exceeded",
+            Some(Data {
+                retry_after_ms: retry_after.as_millis(),
+            }),
+        );
+
+        // Retry-After only supports whole seconds.
+        let retry_after = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+        with_rpc_error(StatusCode::TOO_MANY_REQUESTS, error, id)
+            .map(|mut response| {
+                response
+                    .headers_mut()
+                    .insert(http::header::RETRY_AFTER, retry_after.into());
+                response
+            })
+            .expect("response is properly formed")
+    }
+
+    pub(super) fn internal() -> Response<Body> {
+        with_rpc_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::InternalError.into(),
+            Id::Null,
+        )
+        .expect("response is properly formed")
+    }
+
+    fn with_rpc_error(
+        code: StatusCode,
+        error: ErrorObject<'_>,
+        id: Id<'_>,
+    ) -> http::Result<Response<Body>> {
+        let body = ErrorResponse::borrowed(error, id);
+        let body = serde_json::to_string(&body).expect("error response is serializable");
+
+        http::response::Builder::new()
+            .status(code)
+            .header(http::header::CONTENT_TYPE, JSON)
+            .body(body.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(capacity: u32, refill_rate: u32, now: Instant) -> RateLimiter {
+        RateLimiter::new(
+            NonZeroU32::new(capacity).unwrap(),
+            NonZeroU32::new(refill_rate).unwrap(),
+            now,
+        )
+    }
+
+    #[test]
+    fn method_costs() {
+        assert_eq!(method_cost("starknet_getEvents"), 10);
+        assert_eq!(method_cost("v0.4_starknet_getEvents"), 10);
+        assert_eq!(method_cost("v0.4_starknet_simulateTransactions"), 20);
+        assert_eq!(method_cost("starknet_chainId"), 1);
+        assert_eq!(method_cost("not a method"), 1);
+    }
+
+    #[test]
+    fn bucket_empties_and_refills() {
+        let now = Instant::now();
+        let limiter = limiter(10, 2, now);
+        let client = ClientId::Ip([1, 2, 3, 4].into());
+
+        limiter.try_acquire(client.clone(), 10, now).unwrap();
+        let retry_after = limiter.try_acquire(client.clone(), 1, now).unwrap_err();
+        assert_eq!(retry_after, Duration::from_millis(500));
+
+        limiter
+            .try_acquire(client.clone(), 1, now + Duration::from_millis(500))
+            .unwrap();
+        // Refills are capped to capacity.
+        limiter
+            .try_acquire(client.clone(), 10, now + Duration::from_secs(100))
+            .unwrap();
+        limiter
+            .try_acquire(client, 1, now + Duration::from_secs(100))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn clients_are_independent() {
+        let now = Instant::now();
+        let limiter = limiter(10, 1, now);
+
+        limiter
+            .try_acquire(ClientId::ApiKey("a".to_owned()), 10, now)
+            .unwrap();
+        limiter
+            .try_acquire(ClientId::ApiKey("b".to_owned()), 10, now)
+            .unwrap();
+        limiter
+            .try_acquire(ClientId::ApiKey("a".to_owned()), 1, now)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn cost_is_capped_to_capacity() {
+        let now = Instant::now();
+        let limiter = limiter(5, 1, now);
+
+        limiter
+            .try_acquire(ClientId::Ip([1, 2, 3, 4].into()), 50, now)
+            .unwrap();
+    }
+
+    #[test]
+    fn client_identification() {
+        let identification = Identification {
+            api_key_header: HeaderName::from_static("x-api-key"),
+            api_keys: HashSet::from(["secret".to_owned()]),
+            trusted_proxies: HashSet::from([[10, 0, 0, 1].into(), [10, 0, 0, 2].into()]),
+        };
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        HeaderName::from_static(name),
+                        http::HeaderValue::from_static(value),
+                    )
+                })
+                .collect::<HeaderMap>()
+        };
+        let ip = |ip: [u8; 4]| ClientId::Ip(ip.into());
+        let peer = IpAddr::from([9, 9, 9, 9]);
+        let proxy = IpAddr::from([10, 0, 0, 1]);
+
+        let known_key = headers(&[("x-api-key", "secret"), ("x-forwarded-for", "1.1.1.1")]);
+        assert_eq!(
+            identification.identify(peer, &known_key),
+            ClientId::ApiKey("secret".to_owned())
+        );
+
+        let unknown_key = headers(&[("x-api-key", "guess")]);
+        assert_eq!(
+            identification.identify(peer, &unknown_key),
+            ip([9, 9, 9, 9])
+        );
+
+        // Forwarding headers are ignored unless the peer is a trusted proxy.
+        let forwarded = headers(&[
+            ("x-forwarded-for", "1.1.1.1, 2.2.2.2"),
+            ("x-real-ip", "3.3.3.3"),
+        ]);
+        assert_eq!(identification.identify(peer, &forwarded), ip([9, 9, 9, 9]));
+        assert_eq!(identification.identify(proxy, &forwarded), ip([2, 2, 2, 2]));
+
+        // Hops through other trusted proxies are skipped, forged hops left of the client are not used.
+        let chained = headers(&[("x-forwarded-for", "6.6.6.6, 1.1.1.1, 10.0.0.2")]);
+        assert_eq!(identification.identify(proxy, &chained), ip([1, 1, 1, 1]));
+
+        let malformed = headers(&[("x-forwarded-for", "1.1.1.1, garbage")]);
+        assert_eq!(
+            identification.identify(proxy, &malformed),
+            ip([10, 0, 0, 1])
+        );
+
+        let real_ip = headers(&[("x-real-ip", "3.3.3.3")]);
+        assert_eq!(identification.identify(proxy, &real_ip), ip([3, 3, 3, 3]));
+
+        assert_eq!(
+            identification.identify(proxy, &HeaderMap::new()),
+            ip([10, 0, 0, 1])
+        );
+    }
+
+    #[tokio::test]
+    async fn throttles_by_peer_address() {
+        use tower::{Layer, Service, ServiceExt};
+
+        let layer = RateLimitLayer::new(RateLimitConfig {
+            capacity: NonZeroU32::new(1).unwrap(),
+            refill_rate: NonZeroU32::new(1).unwrap(),
+            api_key_header: HeaderName::from_static("x-api-key"),
+            api_keys: HashSet::new(),
+            trusted_proxies: HashSet::new(),
+        });
+
+        // Stands in for jsonrpsee, which reports the peer address to its logger.
+        let connection = |peer: [u8; 4]| {
+            layer.layer(tower::service_fn(move |request: Request<Body>| {
+                PeerAddrLogger.on_connect((peer, 1234).into(), &request, TransportProtocol::Http);
+                async { Ok::<_, std::convert::Infallible>(Response::new(Body::empty())) }
+            }))
+        };
+        let request = || {
+            Request::new(Body::from(
+                r#"{"jsonrpc":"2.0","id":0,"method":"starknet_chainId"}"#,
+            ))
+        };
+
+        let mut first = connection([1, 1, 1, 1]);
+        let response = first.ready().await.unwrap().call(request()).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+        let response = first.ready().await.unwrap().call(request()).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+
+        // Another connection from the same address shares the bucket.
+        let mut again = connection([1, 1, 1, 1]);
+        let response = again.ready().await.unwrap().call(request()).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::TOO_MANY_REQUESTS);
+
+        let mut second = connection([2, 2, 2, 2]);
+        let response = second.ready().await.unwrap().call(request()).await.unwrap();
+        assert_eq!(response.status(), http::StatusCode::OK);
+    }
+
+    #[test]
+    fn batch_cost() {
+        let body = br#"[
+            {"jsonrpc":"2.0","id":0,"method":"starknet_getEvents","params":[]},
+            {"jsonrpc":"2.0","id":1,"method":"starknet_chainId"}
+        ]"#;
+        assert_eq!(request_cost(body), (Id::Null, 11));
+
+        let body = br#"{"jsonrpc":"2.0","id":7,"method":"v0.4_starknet_call","params":[]}"#;
+        assert_eq!(request_cost(body), (Id::Number(7), 5));
+
+        assert_eq!(request_cost(b"garbage"), (Id::Null, 1));
+    }
+}
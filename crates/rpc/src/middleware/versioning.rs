@@ -289,10 +289,24 @@ pub mod test_utils {
             "starknet_pendingTransactions",
             "starknet_syncing",
         ];
-        pub const COMMON_FOR_ALL: [&str; 2] =
-            ["pathfinder_getProof", "pathfinder_getTransactionStatus"];
+        pub const COMMON_FOR_ALL: [&str; 9] = [
+            "pathfinder_dbStats",
+            "pathfinder_getFeeHistory",
+            "pathfinder_getL1MessageStatus",
+            "pathfinder_getL1Status",
+            "pathfinder_getProof",
+            "pathfinder_getProofs",
+            "pathfinder_getStorageChanges",
+            "pathfinder_getStorageHistory",
+            "pathfinder_getTransactionStatus",
+        ];
         pub const V03_ONLY: [&str; 1] = ["starknet_simulateTransaction"];
-        pub const V04_ONLY: [&str; 1] = ["starknet_simulateTransactions"];
+        pub const V04_ONLY: [&str; 4] = [
+            "starknet_getBlockWithReceipts",
+            "starknet_simulateTransactions",
+            "starknet_traceBlockTransactions",
+            "starknet_traceTransaction",
+        ];
         pub const PATHFINDER_ONLY: [&str; 1] = ["pathfinder_version"];
     }
 
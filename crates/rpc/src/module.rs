@@ -166,6 +166,66 @@ impl Module {
 
         Ok(self)
     }
+
+    /// Registers RPC Websocket subscription endpoints which take input parameters.
+    ///
+    /// Subscriptions with malformed input are rejected before `subscription` is called.
+    pub fn register_subscription_with_input<
+        Input,
+        Subscription,
+        WSAnySubscriptionEvent: 'static + Send + Clone,
+    >(
+        mut self,
+        subscription_name: &'static str,
+        subscription_answer_name: &'static str,
+        unsubscription_name: &'static str,
+        subscription: Subscription,
+        ws_broadcast_tx: SubscriptionBroadcaster<WSAnySubscriptionEvent>,
+    ) -> anyhow::Result<Self>
+    where
+        Input: ::serde::de::DeserializeOwned + Send + Sync,
+        Subscription: (Fn(
+                RpcContext,
+                Input,
+                SubscriptionSink,
+                &SubscriptionBroadcaster<WSAnySubscriptionEvent>,
+            ) -> SubscriptionResult)
+            + Copy
+            + Send
+            + Sync
+            + 'static,
+    {
+        use anyhow::Context;
+        use jsonrpsee::types::Params;
+
+        metrics::register_counter!("rpc_subscription_calls_total", "subscription" => subscription_name);
+
+        let subscription_callback =
+            move |params: Params<'_>, mut sink: SubscriptionSink, context: Arc<RpcContext>| {
+                metrics::increment_counter!("rpc_subscription_calls_total", "subscription" => subscription_name);
+
+                let input = match params.parse::<Input>() {
+                    Ok(input) => input,
+                    Err(error) => {
+                        let _ = sink.reject(error);
+                        return Ok(());
+                    }
+                };
+
+                subscription((*context).clone(), input, sink, &ws_broadcast_tx.clone())
+            };
+
+        self.0
+            .register_subscription(
+                subscription_name,
+                subscription_answer_name,
+                unsubscription_name,
+                subscription_callback,
+            )
+            .with_context(|| format!("Registering subscription {subscription_name}"))?;
+
+        Ok(self)
+    }
 }
 
 #[cfg(test)]
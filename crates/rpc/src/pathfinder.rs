@@ -9,7 +9,23 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
         .register_method_with_no_input("v0.1_pathfinder_version", |_| async {
             Result::<_, RpcError>::Ok(pathfinder_common::consts::VERGEN_GIT_DESCRIBE)
         })?
+        .register_method_with_no_input("v0.1_pathfinder_dbStats", methods::db_stats)?
+        .register_method("v0.1_pathfinder_getFeeHistory", methods::get_fee_history)?
+        .register_method(
+            "v0.1_pathfinder_getL1MessageStatus",
+            methods::get_l1_message_status,
+        )?
+        .register_method_with_no_input("v0.1_pathfinder_getL1Status", methods::get_l1_status)?
         .register_method("v0.1_pathfinder_getProof", methods::get_proof)?
+        .register_method("v0.1_pathfinder_getProofs", methods::get_proofs)?
+        .register_method(
+            "v0.1_pathfinder_getStorageChanges",
+            methods::get_storage_changes,
+        )?
+        .register_method(
+            "v0.1_pathfinder_getStorageHistory",
+            methods::get_storage_history,
+        )?
         .register_method(
             "v0.1_pathfinder_getTransactionStatus",
             methods::get_transaction_status,
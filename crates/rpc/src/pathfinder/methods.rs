@@ -1,5 +1,19 @@
+mod db_stats;
+mod get_fee_history;
+mod get_l1_message_status;
+mod get_l1_status;
 mod get_proof;
+mod get_proofs;
+mod get_storage_changes;
+mod get_storage_history;
 mod get_transaction_status;
 
+pub(crate) use db_stats::db_stats;
+pub(crate) use get_fee_history::get_fee_history;
+pub(crate) use get_l1_message_status::get_l1_message_status;
+pub(crate) use get_l1_status::get_l1_status;
 pub(crate) use get_proof::get_proof;
-pub(crate) use get_transaction_status::get_transaction_status;
+pub(crate) use get_proofs::get_proofs;
+pub(crate) use get_storage_changes::get_storage_changes;
+pub(crate) use get_storage_history::get_storage_history;
+pub(crate) use get_transaction_status::{get_transaction_status, TransactionStatus};
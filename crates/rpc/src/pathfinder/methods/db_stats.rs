@@ -0,0 +1,70 @@
+use std::num::NonZeroU64;
+
+use anyhow::Context;
+use pathfinder_storage::DatabaseStats;
+
+use crate::context::RpcContext;
+
+/// The number of blocks per range of the reported growth.
+const BLOCK_RANGE_SIZE: u64 = 10_000;
+
+crate::error::generate_rpc_error_subset!(DbStatsError: DbStatsDisabled);
+
+/// Returns the size of each table, the node counts of the state tries and the data added per
+/// range of blocks. This reads most of the database, and is only served if enabled.
+pub async fn db_stats(context: RpcContext) -> Result<DatabaseStats, DbStatsError> {
+    if !context.db_stats {
+        return Err(DbStatsError::DbStatsDisabled);
+    }
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || -> Result<_, DbStatsError> {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let stats = tx
+            .database_stats(NonZeroU64::new(BLOCK_RANGE_SIZE).unwrap())
+            .context("Collecting database statistics")?;
+
+        Ok(stats)
+    })
+    .await
+    .context("Database read panic or shutting down")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn stats() {
+        let context = RpcContext::for_tests().with_db_stats();
+
+        let stats = db_stats(context).await.unwrap();
+
+        let headers = stats
+            .tables
+            .iter()
+            .find(|table| table.name == "block_headers")
+            .unwrap();
+        assert_eq!(headers.rows, 3);
+        assert_eq!(stats.tries.len(), 3);
+        assert_eq!(stats.block_ranges.len(), 1);
+        assert_eq!(stats.block_ranges[0].last_block.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn disabled_by_default() {
+        let context = RpcContext::for_tests();
+
+        let error = db_stats(context).await.unwrap_err();
+
+        assert_matches::assert_matches!(error, DbStatsError::DbStatsDisabled);
+    }
+}
@@ -0,0 +1,309 @@
+use anyhow::Context;
+use pathfinder_common::{BlockId, BlockNumber, BlockTimestamp, GasPrice};
+use pathfinder_serde::GasPriceAsHexStr;
+use pathfinder_storage::BlockFeeData;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, skip_serializing_none};
+
+use crate::context::RpcContext;
+
+/// The maximum number of blocks which may be requested in a single call.
+const MAX_BLOCK_COUNT: u64 = 1024;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetFeeHistoryInput {
+    /// The number of blocks to include, ending with (and including) `newest_block`.
+    block_count: u64,
+    newest_block: BlockId,
+}
+
+crate::error::generate_rpc_error_subset!(GetFeeHistoryError: BlockNotFound, PageSizeTooBig);
+
+/// The fee related data of a single block.
+#[serde_as]
+#[skip_serializing_none]
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct BlockFees {
+    /// Absent for the pending block.
+    block_number: Option<BlockNumber>,
+    timestamp: BlockTimestamp,
+    #[serde_as(as = "GasPriceAsHexStr")]
+    gas_price: GasPrice,
+    transaction_count: u64,
+    event_count: u64,
+}
+
+impl From<BlockFeeData> for BlockFees {
+    fn from(data: BlockFeeData) -> Self {
+        Self {
+            block_number: Some(data.number),
+            timestamp: data.timestamp,
+            gas_price: data.gas_price,
+            transaction_count: data.transaction_count as u64,
+            event_count: data.event_count as u64,
+        }
+    }
+}
+
+/// Statistics over the gas price of a range of blocks. The mean is rounded down.
+#[serde_as]
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct GasPriceStatistics {
+    #[serde_as(as = "GasPriceAsHexStr")]
+    min: GasPrice,
+    #[serde_as(as = "GasPriceAsHexStr")]
+    max: GasPrice,
+    #[serde_as(as = "GasPriceAsHexStr")]
+    mean: GasPrice,
+    #[serde_as(as = "GasPriceAsHexStr")]
+    median: GasPrice,
+}
+
+/// Statistics over a per-block count of a range of blocks. The mean is rounded down.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct CountStatistics {
+    min: u64,
+    max: u64,
+    mean: u64,
+    median: u64,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct FeeHistory {
+    /// The blocks in ascending order.
+    blocks: Vec<BlockFees>,
+    /// Absent if there are no blocks.
+    gas_price: Option<GasPriceStatistics>,
+    transaction_count: Option<CountStatistics>,
+    event_count: Option<CountStatistics>,
+}
+
+/// Returns the gas price, transaction count and event count of up to `block_count` blocks ending with
+/// `newest_block`, along with statistics over them.
+///
+/// If `newest_block` is `pending` then the pending block is included as the newest block, if it is available.
+pub async fn get_fee_history(
+    context: RpcContext,
+    input: GetFeeHistoryInput,
+) -> Result<FeeHistory, GetFeeHistoryError> {
+    if input.block_count > MAX_BLOCK_COUNT {
+        return Err(GetFeeHistoryError::PageSizeTooBig);
+    }
+
+    let (block_id, pending) = match input.newest_block {
+        BlockId::Pending => {
+            let pending = match &context.pending_data {
+                Some(pending_data) => pending_data.block().await,
+                None => None,
+            };
+
+            let pending = pending.map(|block| BlockFees {
+                block_number: None,
+                timestamp: block.timestamp,
+                gas_price: block.gas_price,
+                transaction_count: block.transactions.len() as u64,
+                event_count: block
+                    .transaction_receipts
+                    .iter()
+                    .map(|receipt| receipt.events.len() as u64)
+                    .sum(),
+            });
+
+            (pathfinder_storage::BlockId::Latest, pending)
+        }
+        other => (
+            other.try_into().expect("Only pending cast should fail"),
+            None,
+        ),
+    };
+
+    // The pending block takes up one of the requested blocks.
+    let has_pending = pending.is_some();
+    let stored_count = input.block_count - u64::from(has_pending && input.block_count > 0);
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    let mut blocks = tokio::task::spawn_blocking(move || -> Result<_, GetFeeHistoryError> {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let newest = match tx.block_id(block_id).context("Fetching block number")? {
+            Some((number, _)) => number,
+            // An empty database is fine as long as we have a pending block to report.
+            None if has_pending => return Ok(Vec::new()),
+            None => return Err(GetFeeHistoryError::BlockNotFound),
+        };
+
+        if stored_count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let oldest = BlockNumber::new_or_panic(newest.get().saturating_sub(stored_count - 1));
+
+        let blocks = tx
+            .block_fee_history(oldest, newest)
+            .context("Fetching block fee history")?
+            .into_iter()
+            .map(BlockFees::from)
+            .collect::<Vec<_>>();
+
+        Ok(blocks)
+    })
+    .await
+    .context("Database read panic or shutting down")??;
+
+    if input.block_count > 0 {
+        blocks.extend(pending);
+    }
+
+    Ok(FeeHistory {
+        gas_price: statistics(blocks.iter().map(|b| b.gas_price.0)).map(
+            |(min, max, mean, median)| GasPriceStatistics {
+                min: GasPrice(min),
+                max: GasPrice(max),
+                mean: GasPrice(mean),
+                median: GasPrice(median),
+            },
+        ),
+        transaction_count: count_statistics(blocks.iter().map(|b| b.transaction_count)),
+        event_count: count_statistics(blocks.iter().map(|b| b.event_count)),
+        blocks,
+    })
+}
+
+fn count_statistics(values: impl Iterator<Item = u64>) -> Option<CountStatistics> {
+    statistics(values.map(u128::from)).map(|(min, max, mean, median)| CountStatistics {
+        min: min as u64,
+        max: max as u64,
+        mean: mean as u64,
+        median: median as u64,
+    })
+}
+
+/// Returns the `(min, max, mean, median)` of `values`, or `None` if there are no values.
+///
+/// The median of an even number of values is the mean of the two middle values, rounded down.
+fn statistics(values: impl Iterator<Item = u128>) -> Option<(u128, u128, u128, u128)> {
+    let mut values = values.collect::<Vec<_>>();
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+
+    let len = values.len();
+    let min = values[0];
+    let max = values[len - 1];
+    let mean = values.iter().map(|v| v / len as u128).sum::<u128>()
+        + values.iter().map(|v| v % len as u128).sum::<u128>() / len as u128;
+    let median = if len % 2 == 0 {
+        let (a, b) = (values[len / 2 - 1], values[len / 2]);
+        a / 2 + b / 2 + (a % 2 + b % 2) / 2
+    } else {
+        values[len / 2]
+    };
+
+    Some((min, max, mean, median))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn statistics_of_values() {
+        assert_eq!(statistics(std::iter::empty()), None);
+        assert_eq!(statistics([5].into_iter()), Some((5, 5, 5, 5)));
+        assert_eq!(statistics([3, 1, 2].into_iter()), Some((1, 3, 2, 2)));
+        assert_eq!(statistics([4, 1, 2, 8].into_iter()), Some((1, 8, 3, 3)));
+        // Must not overflow.
+        assert_eq!(
+            statistics([u128::MAX, u128::MAX].into_iter()),
+            Some((u128::MAX, u128::MAX, u128::MAX, u128::MAX))
+        );
+    }
+
+    #[tokio::test]
+    async fn latest() {
+        let context = RpcContext::for_tests();
+        let input = GetFeeHistoryInput {
+            block_count: 2,
+            newest_block: BlockId::Latest,
+        };
+
+        let history = get_fee_history(context, input).await.unwrap();
+        let numbers = history
+            .blocks
+            .iter()
+            .map(|b| b.block_number)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            numbers,
+            vec![
+                Some(BlockNumber::new_or_panic(1)),
+                Some(BlockNumber::new_or_panic(2))
+            ]
+        );
+        assert!(history.gas_price.is_some());
+    }
+
+    #[tokio::test]
+    async fn more_than_available() {
+        let context = RpcContext::for_tests();
+        let input = GetFeeHistoryInput {
+            block_count: 100,
+            newest_block: BlockId::Number(BlockNumber::new_or_panic(1)),
+        };
+
+        let history = get_fee_history(context, input).await.unwrap();
+        assert_eq!(history.blocks.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn pending() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let input = GetFeeHistoryInput {
+            block_count: 2,
+            newest_block: BlockId::Pending,
+        };
+
+        let history = get_fee_history(context, input).await.unwrap();
+        assert_eq!(history.blocks.len(), 2);
+        assert_eq!(
+            history.blocks[0].block_number,
+            Some(BlockNumber::new_or_panic(2))
+        );
+        assert_eq!(history.blocks[1].block_number, None);
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetFeeHistoryInput {
+            block_count: 1,
+            newest_block: BlockId::Number(BlockNumber::new_or_panic(9999)),
+        };
+
+        let error = get_fee_history(context, input).await.unwrap_err();
+        assert_matches!(error, GetFeeHistoryError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn too_many_blocks() {
+        let context = RpcContext::for_tests();
+        let input = GetFeeHistoryInput {
+            block_count: MAX_BLOCK_COUNT + 1,
+            newest_block: BlockId::Latest,
+        };
+
+        let error = get_fee_history(context, input).await.unwrap_err();
+        assert_matches!(error, GetFeeHistoryError::PageSizeTooBig);
+    }
+}
@@ -0,0 +1,187 @@
+use anyhow::Context;
+use pathfinder_common::{
+    BlockNumber, ContractAddress, EntryPoint, EthereumAddress, L1ToL2MessageNonce,
+    L1ToL2MessagePayloadElem, TransactionHash,
+};
+use pathfinder_storage::L1ToL2MessageRecord;
+use primitive_types::H256;
+use serde::{Deserialize, Serialize};
+
+use crate::context::RpcContext;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetL1MessageStatusInput {
+    l1_transaction_hash: H256,
+}
+
+crate::error::generate_rpc_error_subset!(GetL1MessageStatusError:);
+
+#[derive(Copy, Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum MessageStatus {
+    /// Sent on L1, but not yet consumed on L2.
+    Sent,
+    /// Consumed on L2 by an L1 handler transaction.
+    Consumed,
+    /// The sender started cancelling the message on L1.
+    CancellationStarted,
+    /// Cancelled on L1, so it can no longer be consumed.
+    Cancelled,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct L1HandlerTransaction {
+    transaction_hash: TransactionHash,
+    block_number: BlockNumber,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct L1MessageStatus {
+    message_hash: H256,
+    from_address: EthereumAddress,
+    to_address: ContractAddress,
+    selector: EntryPoint,
+    payload: Vec<L1ToL2MessagePayloadElem>,
+    nonce: L1ToL2MessageNonce,
+    l1_block_number: u64,
+    status: MessageStatus,
+    /// The L1 handler transaction which consumed the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    l1_handler_transaction: Option<L1HandlerTransaction>,
+    /// The L1 transaction which started the cancellation of the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cancellation_started_by: Option<H256>,
+    /// The L1 transaction which completed the cancellation of the message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cancelled_by: Option<H256>,
+}
+
+impl From<L1ToL2MessageRecord> for L1MessageStatus {
+    fn from(record: L1ToL2MessageRecord) -> Self {
+        let status = match (
+            &record.consumed_by,
+            &record.canceled_by,
+            &record.cancellation_started_by,
+        ) {
+            (Some(_), _, _) => MessageStatus::Consumed,
+            (None, Some(_), _) => MessageStatus::Cancelled,
+            (None, None, Some(_)) => MessageStatus::CancellationStarted,
+            (None, None, None) => MessageStatus::Sent,
+        };
+
+        Self {
+            message_hash: record.message_hash,
+            from_address: record.message.from_address,
+            to_address: record.message.to_address,
+            selector: record.message.selector,
+            payload: record.message.payload,
+            nonce: record.message.nonce,
+            l1_block_number: record.l1_block_number,
+            status,
+            l1_handler_transaction: record.consumed_by.map(|(transaction_hash, block_number)| {
+                L1HandlerTransaction {
+                    transaction_hash,
+                    block_number,
+                }
+            }),
+            cancellation_started_by: record.cancellation_started_by,
+            cancelled_by: record.canceled_by,
+        }
+    }
+}
+
+/// Returns the L1 to L2 messages sent by an L1 transaction, and what happened to each of them.
+///
+/// Messages are only known once the node indexed the Ethereum block of the transaction, so an
+/// empty list is returned for transactions which are not finalized yet, or precede the first
+/// indexed block.
+pub async fn get_l1_message_status(
+    context: RpcContext,
+    input: GetL1MessageStatusInput,
+) -> Result<Vec<L1MessageStatus>, GetL1MessageStatusError> {
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || -> Result<_, GetL1MessageStatusError> {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let messages = tx
+            .l1_to_l2_messages(input.l1_transaction_hash)
+            .context("Fetching messages")?
+            .into_iter()
+            .map(L1MessageStatus::from)
+            .collect();
+
+        Ok(messages)
+    })
+    .await
+    .context("Database read panic or shutting down")?
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_ethereum::{L1ToL2Message, L1ToL2MessageEvent, L1ToL2MessageLog};
+    use primitive_types::H160;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn cancelled() {
+        let context = RpcContext::for_tests();
+        let l1_transaction_hash = H256::from_low_u64_be(1);
+        let cancellation = H256::from_low_u64_be(2);
+
+        let message = L1ToL2Message {
+            from_address: EthereumAddress(H160::from_low_u64_be(3)),
+            to_address: contract_address!("0x4"),
+            selector: EntryPoint(felt!("0x5")),
+            payload: vec![L1ToL2MessagePayloadElem(felt!("0x6"))],
+            nonce: L1ToL2MessageNonce(felt!("0x7")),
+        };
+        let logs = [
+            (L1ToL2MessageEvent::Sent, l1_transaction_hash),
+            (L1ToL2MessageEvent::CancellationStarted, cancellation),
+            (L1ToL2MessageEvent::Canceled, cancellation),
+        ]
+        .map(|(event, l1_transaction_hash)| L1ToL2MessageLog {
+            message: message.clone(),
+            event,
+            l1_block_number: 10,
+            l1_transaction_hash,
+        });
+
+        let mut db = context.storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.insert_l1_to_l2_message_logs(&logs, 10).unwrap();
+        tx.commit().unwrap();
+
+        let input = GetL1MessageStatusInput {
+            l1_transaction_hash,
+        };
+        let result = get_l1_message_status(context, input).await.unwrap();
+
+        assert_eq!(
+            result,
+            vec![L1MessageStatus {
+                message_hash: message.hash(),
+                from_address: message.from_address,
+                to_address: message.to_address,
+                selector: message.selector,
+                payload: message.payload.clone(),
+                nonce: message.nonce,
+                l1_block_number: 10,
+                status: MessageStatus::Cancelled,
+                l1_handler_transaction: None,
+                cancellation_started_by: Some(cancellation),
+                cancelled_by: Some(cancellation),
+            }]
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::Context;
+use pathfinder_common::{BlockHash, BlockNumber};
+use pathfinder_storage::BlockId;
+use serde::Serialize;
+
+use crate::context::RpcContext;
+use crate::L1Mismatch;
+
+crate::error::generate_rpc_error_subset!(GetL1StatusError:);
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct L1AcceptedBlock {
+    block_number: BlockNumber,
+    block_hash: BlockHash,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct L1Status {
+    /// The latest synced block whose hash is attested on L1.
+    l1_accepted_block: Option<L1AcceptedBlock>,
+    /// Set while the latest state update on L1 contradicts the synced block of the same number.
+    mismatch: Option<L1Mismatch>,
+}
+
+/// Returns the latest block accepted on L1, and whether L1 contradicts the synced blocks.
+pub async fn get_l1_status(context: RpcContext) -> Result<L1Status, GetL1StatusError> {
+    let mismatch = *context.sync_status.l1_mismatch.read().await;
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    let l1_accepted_block = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let Some(number) = tx.l1_l2_pointer().context("Querying L1-L2 pointer")? else {
+            return Ok(None);
+        };
+        let block = tx
+            .block_id(BlockId::Number(number))
+            .context("Fetching block hash")?
+            .map(|(block_number, block_hash)| L1AcceptedBlock {
+                block_number,
+                block_hash,
+            });
+
+        Ok(block)
+    })
+    .await
+    .context("Database read panic or shutting down")??;
+
+    Ok(L1Status {
+        l1_accepted_block,
+        mismatch,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn mismatch() {
+        let context = RpcContext::for_tests();
+
+        let mut db = context.storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        let (block_number, block_hash) = tx.block_id(BlockId::Latest).unwrap().unwrap();
+        tx.update_l1_l2_pointer(Some(block_number)).unwrap();
+        tx.commit().unwrap();
+
+        let mismatch = L1Mismatch {
+            block_number: block_number + 1,
+            l1_block_hash: block_hash!("0x1"),
+            l2_block_hash: block_hash!("0x2"),
+        };
+        *context.sync_status.l1_mismatch.write().await = Some(mismatch);
+
+        let status = get_l1_status(context).await.unwrap();
+
+        assert_eq!(
+            status,
+            L1Status {
+                l1_accepted_block: Some(L1AcceptedBlock {
+                    block_number,
+                    block_hash,
+                }),
+                mismatch: Some(mismatch),
+            }
+        );
+    }
+}
@@ -6,7 +6,7 @@ use serde_with::skip_serializing_none;
 use crate::context::RpcContext;
 use pathfinder_common::{
     BlockId, ClassCommitment, ClassHash, ContractAddress, ContractNonce, ContractRoot,
-    StateCommitment, StorageAddress,
+    ContractStateHash, StateCommitment, StorageAddress,
 };
 use pathfinder_merkle_tree::{ContractsStorageTree, StorageCommitmentTree};
 use stark_hash::Felt;
@@ -23,6 +23,7 @@ pub struct GetProofInput {
 pub enum GetProofError {
     Internal(anyhow::Error),
     BlockNotFound,
+    BlockPruned,
     ProofLimitExceeded { limit: u32, requested: u32 },
 }
 impl From<anyhow::Error> for GetProofError {
@@ -37,6 +38,7 @@ impl From<GetProofError> for crate::error::RpcError {
                 Self::ProofLimitExceeded { limit, requested }
             }
             GetProofError::BlockNotFound => Self::BlockNotFound,
+            GetProofError::BlockPruned => Self::BlockPruned,
             GetProofError::Internal(internal) => Self::Internal(internal),
         }
     }
@@ -51,7 +53,7 @@ struct PathWrapper {
 
 /// Wrapper around [`Vec<TrieNode>`] as we don't control [TrieNode] in this crate.
 #[derive(Debug)]
-pub struct ProofNodes(Vec<TrieNode>);
+pub struct ProofNodes(pub(super) Vec<TrieNode>);
 
 impl Serialize for ProofNodes {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -141,12 +143,14 @@ pub struct GetProofOutput {
     contract_data: Option<ContractData>,
 }
 
+/// The maximum number of proofs which may be requested in a single call.
+pub(super) const MAX_KEYS: usize = 100;
+
 /// Returns all the necessary data to trustlessly verify storage slots for a particular contract.
 pub async fn get_proof(
     context: RpcContext,
     input: GetProofInput,
 ) -> Result<GetProofOutput, GetProofError> {
-    const MAX_KEYS: usize = 100;
     if input.keys.len() > MAX_KEYS {
         return Err(GetProofError::ProofLimitExceeded {
             limit: MAX_KEYS as u32,
@@ -176,12 +180,25 @@ pub async fn get_proof(
 
         // Use internal error to indicate that the process of querying for a particular block failed,
         // which is not the same as being sure that the block is not in the db.
-        let (storage_commitment, class_commitment) = tx
+        let (block_number, storage_commitment, class_commitment) = tx
             .block_header(block_id)
             .context("Fetching block header")?
-            .map(|header| (header.storage_commitment, header.class_commitment))
+            .map(|header| {
+                (
+                    header.number,
+                    header.storage_commitment,
+                    header.class_commitment,
+                )
+            })
             .ok_or(GetProofError::BlockNotFound)?;
 
+        if tx
+            .block_pruned(block_number)
+            .context("Checking if block is pruned")?
+        {
+            return Err(GetProofError::BlockPruned);
+        }
+
         let (state_commitment, class_commitment) = if class_commitment == ClassCommitment::ZERO {
             (None, None)
         } else {
@@ -214,34 +231,7 @@ pub async fn get_proof(
             }
         };
 
-        let (contract_state_root, class_hash, nonce) = tx
-            .contract_state(contract_state_hash)
-            .context("Get contract state root and nonce")?
-            // Root and nonce should not be None at this stage since we have a valid block and non-zero contract state_hash.
-            .ok_or_else(|| -> GetProofError {
-                anyhow::anyhow!(
-                    "Root or nonce missing for state_hash={}",
-                    contract_state_hash
-                )
-                .into()
-            })?;
-
-        let contract_state_tree = ContractsStorageTree::load(&tx, contract_state_root);
-
-        let storage_proofs = input
-            .keys
-            .iter()
-            .map(|k| contract_state_tree.get_proof(k.view_bits()).map(ProofNodes))
-            .collect::<anyhow::Result<Vec<_>>>()
-            .context("Get proof from contract state treee")?;
-
-        let contract_data = ContractData {
-            class_hash,
-            nonce,
-            root: contract_state_root,
-            contract_state_hash_version: Felt::ZERO, // Currently, this is defined as 0. Might change in the future.
-            storage_proofs,
-        };
+        let contract_data = contract_data(&tx, contract_state_hash, &input.keys)?;
 
         Ok(GetProofOutput {
             state_commitment,
@@ -254,6 +244,41 @@ pub async fn get_proof(
     jh.await.context("Database read panic or shutting down")?
 }
 
+/// Loads the state of an existing contract and generates proofs for its storage `keys`.
+pub(super) fn contract_data(
+    tx: &pathfinder_storage::Transaction<'_>,
+    contract_state_hash: ContractStateHash,
+    keys: &[StorageAddress],
+) -> Result<ContractData, GetProofError> {
+    let (contract_state_root, class_hash, nonce) = tx
+        .contract_state(contract_state_hash)
+        .context("Get contract state root and nonce")?
+        // Root and nonce should not be None at this stage since we have a valid block and non-zero contract state_hash.
+        .ok_or_else(|| -> GetProofError {
+            anyhow::anyhow!(
+                "Root or nonce missing for state_hash={}",
+                contract_state_hash
+            )
+            .into()
+        })?;
+
+    let contract_state_tree = ContractsStorageTree::load(tx, contract_state_root);
+
+    let storage_proofs = keys
+        .iter()
+        .map(|k| contract_state_tree.get_proof(k.view_bits()).map(ProofNodes))
+        .collect::<anyhow::Result<Vec<_>>>()
+        .context("Get proof from contract state treee")?;
+
+    Ok(ContractData {
+        class_hash,
+        nonce,
+        root: contract_state_root,
+        contract_state_hash_version: Felt::ZERO, // Currently, this is defined as 0. Might change in the future.
+        storage_proofs,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use pathfinder_common::macro_prelude::*;
@@ -0,0 +1,371 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Context};
+use pathfinder_common::hash::PedersenHash;
+use serde::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+
+use super::get_proof::{contract_data, ContractData, GetProofError, ProofNodes, MAX_KEYS};
+use crate::context::RpcContext;
+use pathfinder_common::{
+    BlockId, ClassCommitment, ClassCommitmentLeafHash, ClassHash, ContractAddress, SierraHash,
+    StateCommitment, StorageAddress,
+};
+use pathfinder_merkle_tree::{ClassCommitmentTree, StorageCommitmentTree};
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetProofsInput {
+    pub block_id: BlockId,
+    #[serde(default)]
+    pub contracts: Vec<ContractKeys>,
+    #[serde(default)]
+    pub class_hashes: Vec<ClassHash>,
+}
+
+/// A contract and the storage keys to prove for it.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ContractKeys {
+    pub contract_address: ContractAddress,
+    #[serde(default)]
+    pub keys: Vec<StorageAddress>,
+}
+
+/// Holds the membership/non-membership of a contract and its associated contract data if the contract exists.
+#[derive(Debug, Serialize)]
+#[skip_serializing_none]
+pub struct ContractProof {
+    contract_address: ContractAddress,
+
+    /// Additional contract data if it exists.
+    contract_data: Option<ContractData>,
+}
+
+/// Holds the membership/non-membership proof of a class in the class commitment tree.
+#[derive(Debug, Serialize)]
+#[skip_serializing_none]
+pub struct ClassProof {
+    class_hash: ClassHash,
+
+    /// The leaf of the class in the class commitment tree, derived from the class's compiled class
+    /// hash. Absent if the class is not declared, or is a Cairo 0 class as those are not part of
+    /// the class commitment tree.
+    class_leaf: Option<ClassCommitmentLeafHash>,
+
+    proof: ProofNodes,
+}
+
+#[derive(Debug, Serialize)]
+#[skip_serializing_none]
+pub struct GetProofsOutput {
+    /// The global state commitment for Starknet 0.11.0 blocks onwards, if absent the hash
+    /// of the first node in the [contracts_proof](GetProofsOutput#contracts_proof) is the global state commitment.
+    state_commitment: Option<StateCommitment>,
+    /// Root of the class commitment tree which the [class_proofs](GetProofsOutput#class_proofs) lead to.
+    /// Present only for Starknet blocks 0.11.0 onwards.
+    class_commitment: Option<ClassCommitment>,
+
+    /// Membership / Non-membership proofs for all of the queried contracts. Nodes shared by
+    /// several contracts' paths are only included once, so the nodes are not in path order.
+    ///
+    /// To rebuild a contract's path, start from the node whose hash is the storage commitment and
+    /// follow the children of binary and edge nodes by the bits of the contract address, until
+    /// reaching its leaf or an edge which diverges from the address.
+    contracts_proof: ProofNodes,
+
+    /// The queried contracts (in order of request).
+    contracts: Vec<ContractProof>,
+
+    /// Membership / Non-membership proofs for the queried classes (in order of request).
+    class_proofs: Vec<ClassProof>,
+}
+
+/// Returns the data required to trustlessly verify the storage slots of several contracts, and the
+/// declaration of several classes, in a single call.
+///
+/// Every contract, storage key and class hash counts as one proof towards the limit.
+pub async fn get_proofs(
+    context: RpcContext,
+    input: GetProofsInput,
+) -> Result<GetProofsOutput, GetProofError> {
+    let requested = input.class_hashes.len()
+        + input
+            .contracts
+            .iter()
+            .map(|contract| 1 + contract.keys.len())
+            .sum::<usize>();
+    if requested > MAX_KEYS {
+        return Err(GetProofError::ProofLimitExceeded {
+            limit: MAX_KEYS as u32,
+            requested: requested as u32,
+        });
+    }
+
+    let block_id = match input.block_id {
+        BlockId::Pending => {
+            return Err(GetProofError::Internal(anyhow!(
+                "'pending' is not currently supported by this method!"
+            )))
+        }
+        other => other.try_into().expect("Only pending cast should fail"),
+    };
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    let jh = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let (block_number, storage_commitment, class_commitment) = tx
+            .block_header(block_id)
+            .context("Fetching block header")?
+            .map(|header| {
+                (
+                    header.number,
+                    header.storage_commitment,
+                    header.class_commitment,
+                )
+            })
+            .ok_or(GetProofError::BlockNotFound)?;
+
+        if tx
+            .block_pruned(block_number)
+            .context("Checking if block is pruned")?
+        {
+            return Err(GetProofError::BlockPruned);
+        }
+
+        let (state_commitment, class_commitment) = if class_commitment == ClassCommitment::ZERO {
+            (None, None)
+        } else {
+            (
+                Some(StateCommitment::calculate(
+                    storage_commitment,
+                    class_commitment,
+                )),
+                Some(class_commitment),
+            )
+        };
+
+        let mut storage_commitment_tree = StorageCommitmentTree::load(&tx, storage_commitment);
+
+        let mut contracts_proof = Vec::new();
+        let mut seen_nodes = HashSet::new();
+        let mut contracts = Vec::with_capacity(input.contracts.len());
+
+        for contract in &input.contracts {
+            // The paths of all contracts start at the same root, so only keep the nodes
+            // which have not been included by a previous contract's proof.
+            let proof = storage_commitment_tree.get_proof(&contract.contract_address)?;
+            contracts_proof.extend(
+                proof
+                    .into_iter()
+                    .filter(|node| seen_nodes.insert(node.hash::<PedersenHash>())),
+            );
+
+            let contract_data = match storage_commitment_tree.get(contract.contract_address)? {
+                Some(contract_state_hash) => {
+                    Some(contract_data(&tx, contract_state_hash, &contract.keys)?)
+                }
+                None => None,
+            };
+
+            contracts.push(ContractProof {
+                contract_address: contract.contract_address,
+                contract_data,
+            });
+        }
+
+        let class_commitment_tree =
+            ClassCommitmentTree::load(&tx, class_commitment.unwrap_or(ClassCommitment::ZERO));
+
+        let class_proofs = input
+            .class_hashes
+            .iter()
+            .map(|&class_hash| {
+                let sierra_hash = SierraHash(class_hash.0);
+                Ok(ClassProof {
+                    class_hash,
+                    class_leaf: class_commitment_tree.get(sierra_hash)?,
+                    proof: ProofNodes(class_commitment_tree.get_proof(&sierra_hash)?),
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("Get proof from class commitment tree")?;
+
+        Ok(GetProofsOutput {
+            state_commitment,
+            class_commitment,
+            contracts_proof: ProofNodes(contracts_proof),
+            contracts,
+            class_proofs,
+        })
+    });
+
+    jh.await.context("Database read panic or shutting down")?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::trie::TrieNode;
+    use pathfinder_merkle_tree::proof::{verify_proof, Membership};
+    use stark_hash::Felt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn limit_exceeded() {
+        let context = RpcContext::for_tests();
+        let input = GetProofsInput {
+            block_id: BlockId::Latest,
+            contracts: vec![
+                ContractKeys {
+                    contract_address: contract_address!("0xdeadbeef"),
+                    keys: (0..60)
+                        .map(|idx| StorageAddress::new_or_panic(Felt::from_u64(idx)))
+                        .collect(),
+                },
+                ContractKeys {
+                    contract_address: contract_address!("0xabcd"),
+                    keys: vec![],
+                },
+            ],
+            class_hashes: (0..40).map(|idx| ClassHash(Felt::from_u64(idx))).collect(),
+        };
+
+        let err = get_proofs(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(
+            err,
+            GetProofError::ProofLimitExceeded {
+                limit: 100,
+                requested: 102
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn shared_nodes_are_deduplicated() {
+        let context = RpcContext::for_tests();
+        let storage_commitment = context
+            .storage
+            .connection()
+            .unwrap()
+            .transaction()
+            .unwrap()
+            .block_header(pathfinder_storage::BlockId::Latest)
+            .unwrap()
+            .unwrap()
+            .storage_commitment;
+        let contract = |address| ContractKeys {
+            contract_address: address,
+            keys: vec![storage_address_bytes!(b"storage addr 0")],
+        };
+        let input = GetProofsInput {
+            block_id: BlockId::Latest,
+            contracts: vec![
+                contract(contract_address_bytes!(b"contract 0")),
+                contract(contract_address_bytes!(b"contract 1")),
+                contract(contract_address_bytes!(b"contract 0")),
+            ],
+            class_hashes: vec![],
+        };
+
+        let output = get_proofs(context, input).await.unwrap();
+        assert_eq!(output.contracts.len(), 3);
+        if let Some(state_commitment) = output.state_commitment {
+            let class_commitment = output.class_commitment.unwrap();
+            assert_eq!(
+                state_commitment,
+                StateCommitment::calculate(storage_commitment, class_commitment)
+            );
+        }
+
+        let nodes = output
+            .contracts_proof
+            .0
+            .iter()
+            .map(|node| (node.hash::<PedersenHash>(), node))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(nodes.len(), output.contracts_proof.0.len());
+
+        // Each contract's path can be rebuilt from the shared nodes.
+        for contract in &output.contracts {
+            let key = contract.contract_address.view_bits();
+            let mut path = Vec::new();
+            let mut next = storage_commitment.0;
+            let mut remaining = key;
+
+            while let Some(&node) = nodes.get(&next) {
+                path.push(node.clone());
+                match node {
+                    TrieNode::Binary { left, right } => {
+                        next = if remaining[0] { *right } else { *left };
+                        remaining = &remaining[1..];
+                    }
+                    TrieNode::Edge { child, path: edge } => {
+                        if edge.len() > remaining.len() || edge != &remaining[..edge.len()] {
+                            break;
+                        }
+                        next = *child;
+                        remaining = &remaining[edge.len()..];
+                    }
+                }
+
+                if remaining.is_empty() {
+                    break;
+                }
+            }
+
+            let membership =
+                verify_proof::<PedersenHash>(storage_commitment.0, key, None, &path).unwrap();
+            assert_eq!(
+                matches!(membership, Membership::Member(_)),
+                contract.contract_data.is_some()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn class_leaf() {
+        let storage = pathfinder_storage::Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let sierra_hash = sierra_hash!("0x1");
+        let casm_hash = casm_hash!("0x2");
+        let leaf = pathfinder_common::calculate_class_commitment_leaf_hash(casm_hash);
+        tx.insert_class_commitment_leaf(&leaf, &casm_hash).unwrap();
+
+        let mut tree = ClassCommitmentTree::load(&tx, ClassCommitment::ZERO);
+        tree.set(sierra_hash, leaf).unwrap();
+        let (class_commitment, nodes) = tree.commit().unwrap();
+        tx.insert_class_trie(class_commitment, &nodes).unwrap();
+
+        let header = pathfinder_common::BlockHeader::builder()
+            .with_class_commitment(class_commitment)
+            .finalize_with_hash(block_hash!("0x3"));
+        tx.insert_block_header(&header).unwrap();
+        tx.commit().unwrap();
+
+        let context = RpcContext::for_tests().with_storage(storage);
+        let input = GetProofsInput {
+            block_id: BlockId::Latest,
+            contracts: vec![],
+            class_hashes: vec![ClassHash(sierra_hash.0), class_hash!("0x4")],
+        };
+
+        let output = get_proofs(context, input).await.unwrap();
+        assert_eq!(output.class_commitment, Some(class_commitment));
+        assert_eq!(output.class_proofs[0].class_leaf, Some(leaf));
+        assert_eq!(output.class_proofs[1].class_leaf, None);
+    }
+}
@@ -0,0 +1,183 @@
+use anyhow::Context;
+use pathfinder_common::{BlockId, BlockNumber, ContractAddress, StorageAddress, StorageValue};
+use pathfinder_storage::StorageChange;
+use serde::{Deserialize, Serialize};
+
+use super::get_storage_history::storage_block_id;
+use crate::context::RpcContext;
+
+/// The maximum number of changes returned by a single call, unless a single block
+/// contains more changes.
+const MAX_CHANGES: usize = 1024;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetStorageChangesInput {
+    contract_address: ContractAddress,
+    from_block: BlockId,
+    to_block: BlockId,
+}
+
+crate::error::generate_rpc_error_subset!(
+    GetStorageChangesError: BlockNotFound,
+    InvalidBlockRange
+);
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct StorageSlotUpdate {
+    block_number: BlockNumber,
+    key: StorageAddress,
+    value: StorageValue,
+}
+
+impl From<StorageChange> for StorageSlotUpdate {
+    fn from(change: StorageChange) -> Self {
+        Self {
+            block_number: change.block_number,
+            key: change.key,
+            value: change.value,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct StorageChanges {
+    /// The storage values written by the blocks of the range, ordered by block and then key.
+    changes: Vec<StorageSlotUpdate>,
+    /// The last block covered by `changes`. This precedes `to_block` if the range contains more
+    /// than [MAX_CHANGES] changes, in which case the remaining changes start after this block.
+    last_block: BlockNumber,
+}
+
+/// Returns all storage values written to a contract by the blocks of a range.
+///
+/// The changes of a block are never split across calls.
+pub async fn get_storage_changes(
+    context: RpcContext,
+    input: GetStorageChangesInput,
+) -> Result<StorageChanges, GetStorageChangesError> {
+    let from_block = storage_block_id(input.from_block)?;
+    let to_block = storage_block_id(input.to_block)?;
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || -> Result<_, GetStorageChangesError> {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let (from, _) = tx
+            .block_id(from_block)
+            .context("Fetching from block")?
+            .ok_or(GetStorageChangesError::BlockNotFound)?;
+        let (to, _) = tx
+            .block_id(to_block)
+            .context("Fetching to block")?
+            .ok_or(GetStorageChangesError::BlockNotFound)?;
+        if from > to {
+            return Err(GetStorageChangesError::InvalidBlockRange);
+        }
+
+        let mut changes = tx
+            .storage_changes(input.contract_address, from, to, Some(MAX_CHANGES + 1))
+            .context("Fetching storage changes")?;
+
+        let last_block = if changes.len() > MAX_CHANGES {
+            // The last block may be incomplete, so we leave it to the next call.
+            let incomplete = changes[MAX_CHANGES].block_number;
+            changes.retain(|change| change.block_number < incomplete);
+
+            if changes.is_empty() {
+                // A single block exceeds the limit, which we return in full to make progress.
+                changes = tx
+                    .storage_changes(input.contract_address, incomplete, incomplete, None)
+                    .context("Fetching storage changes of block")?;
+                incomplete
+            } else {
+                incomplete - 1
+            }
+        } else {
+            to
+        };
+
+        Ok(StorageChanges {
+            changes: changes.into_iter().map(StorageSlotUpdate::from).collect(),
+            last_block,
+        })
+    })
+    .await
+    .context("Database read panic or shutting down")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use pathfinder_common::macro_prelude::*;
+
+    #[tokio::test]
+    async fn changes() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageChangesInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            from_block: BlockId::Number(BlockNumber::new_or_panic(2)),
+            to_block: BlockId::Latest,
+        };
+
+        let changes = get_storage_changes(context, input).await.unwrap();
+        assert_eq!(
+            changes,
+            StorageChanges {
+                changes: vec![StorageSlotUpdate {
+                    block_number: BlockNumber::new_or_panic(2),
+                    key: storage_address_bytes!(b"storage addr 0"),
+                    value: storage_value_bytes!(b"storage value 2"),
+                }],
+                last_block: BlockNumber::new_or_panic(2),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn unchanged_contract() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageChangesInput {
+            contract_address: contract_address_bytes!(b"contract 0"),
+            from_block: BlockId::Number(BlockNumber::GENESIS),
+            to_block: BlockId::Latest,
+        };
+
+        let changes = get_storage_changes(context, input).await.unwrap();
+        assert!(changes.changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pending_is_not_supported() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageChangesInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            from_block: BlockId::Number(BlockNumber::GENESIS),
+            to_block: BlockId::Pending,
+        };
+
+        let error = get_storage_changes(context, input).await.unwrap_err();
+        assert_matches!(error, GetStorageChangesError::Internal(_));
+    }
+
+    #[tokio::test]
+    async fn invalid_block_range() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageChangesInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            from_block: BlockId::Latest,
+            to_block: BlockId::Number(BlockNumber::GENESIS),
+        };
+
+        let error = get_storage_changes(context, input).await.unwrap_err();
+        assert_matches!(error, GetStorageChangesError::InvalidBlockRange);
+    }
+}
@@ -0,0 +1,185 @@
+use anyhow::{anyhow, Context};
+use pathfinder_common::{BlockId, BlockNumber, ContractAddress, StorageAddress, StorageValue};
+use serde::{Deserialize, Serialize};
+
+use crate::context::RpcContext;
+
+/// The maximum number of values returned by a single call.
+const MAX_VALUES: usize = 1024;
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetStorageHistoryInput {
+    contract_address: ContractAddress,
+    key: StorageAddress,
+    from_block: BlockId,
+    to_block: BlockId,
+}
+
+crate::error::generate_rpc_error_subset!(
+    GetStorageHistoryError: BlockNotFound,
+    InvalidBlockRange
+);
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct StorageValueUpdate {
+    block_number: BlockNumber,
+    value: StorageValue,
+}
+
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct StorageHistory {
+    /// The values written by the blocks of the range, in ascending block order.
+    values: Vec<StorageValueUpdate>,
+    /// The last block covered by `values`. This precedes `to_block` if the range contains more
+    /// than [MAX_VALUES] values, in which case the remaining values start after this block.
+    last_block: BlockNumber,
+}
+
+/// Returns the values written to a storage slot of a contract by each block of a range,
+/// without having to query the storage at every block of the range.
+pub async fn get_storage_history(
+    context: RpcContext,
+    input: GetStorageHistoryInput,
+) -> Result<StorageHistory, GetStorageHistoryError> {
+    let from_block = storage_block_id(input.from_block)?;
+    let to_block = storage_block_id(input.to_block)?;
+
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || -> Result<_, GetStorageHistoryError> {
+        let _g = span.enter();
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let tx = db.transaction().context("Creating database transaction")?;
+
+        let (from, _) = tx
+            .block_id(from_block)
+            .context("Fetching from block")?
+            .ok_or(GetStorageHistoryError::BlockNotFound)?;
+        let (to, _) = tx
+            .block_id(to_block)
+            .context("Fetching to block")?
+            .ok_or(GetStorageHistoryError::BlockNotFound)?;
+        if from > to {
+            return Err(GetStorageHistoryError::InvalidBlockRange);
+        }
+
+        let mut values = tx
+            .storage_history(input.contract_address, input.key, from, to, MAX_VALUES + 1)
+            .context("Fetching storage history")?;
+
+        // A slot is written at most once per block, so the page ends on a whole block.
+        let last_block = if values.len() > MAX_VALUES {
+            values.truncate(MAX_VALUES);
+            values[MAX_VALUES - 1].0
+        } else {
+            to
+        };
+
+        Ok(StorageHistory {
+            values: values
+                .into_iter()
+                .map(|(block_number, value)| StorageValueUpdate {
+                    block_number,
+                    value,
+                })
+                .collect(),
+            last_block,
+        })
+    })
+    .await
+    .context("Database read panic or shutting down")?
+}
+
+/// Converts the bounds of a storage query's block range, which cannot be pending.
+pub(super) fn storage_block_id(block_id: BlockId) -> anyhow::Result<pathfinder_storage::BlockId> {
+    match block_id {
+        BlockId::Pending => Err(anyhow!(
+            "'pending' is not currently supported by this method!"
+        )),
+        other => Ok(other.try_into().expect("Only pending cast should fail")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use pathfinder_common::macro_prelude::*;
+
+    #[tokio::test]
+    async fn history() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageHistoryInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            key: storage_address_bytes!(b"storage addr 0"),
+            from_block: BlockId::Number(BlockNumber::GENESIS),
+            to_block: BlockId::Latest,
+        };
+
+        let history = get_storage_history(context, input).await.unwrap();
+        assert_eq!(
+            history,
+            StorageHistory {
+                values: vec![
+                    StorageValueUpdate {
+                        block_number: BlockNumber::new_or_panic(1),
+                        value: storage_value_bytes!(b"storage value 1"),
+                    },
+                    StorageValueUpdate {
+                        block_number: BlockNumber::new_or_panic(2),
+                        value: storage_value_bytes!(b"storage value 2"),
+                    },
+                ],
+                last_block: BlockNumber::new_or_panic(2),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn partial_range() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageHistoryInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            key: storage_address_bytes!(b"storage addr 0"),
+            from_block: BlockId::Number(BlockNumber::new_or_panic(2)),
+            to_block: BlockId::Number(BlockNumber::new_or_panic(2)),
+        };
+
+        let history = get_storage_history(context, input).await.unwrap();
+        assert_eq!(history.values.len(), 1);
+        assert_eq!(history.values[0].block_number, BlockNumber::new_or_panic(2));
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageHistoryInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            key: storage_address_bytes!(b"storage addr 0"),
+            from_block: BlockId::Number(BlockNumber::GENESIS),
+            to_block: BlockId::Number(BlockNumber::new_or_panic(9999)),
+        };
+
+        let error = get_storage_history(context, input).await.unwrap_err();
+        assert_matches!(error, GetStorageHistoryError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn invalid_block_range() {
+        let context = RpcContext::for_tests();
+        let input = GetStorageHistoryInput {
+            contract_address: contract_address_bytes!(b"contract 1"),
+            key: storage_address_bytes!(b"storage addr 0"),
+            from_block: BlockId::Number(BlockNumber::new_or_panic(2)),
+            to_block: BlockId::Number(BlockNumber::new_or_panic(1)),
+        };
+
+        let error = get_storage_history(context, input).await.unwrap_err();
+        assert_matches!(error, GetStorageHistoryError::InvalidBlockRange);
+    }
+}
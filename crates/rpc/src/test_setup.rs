@@ -102,3 +102,23 @@ pub async fn test_context() -> (RpcContext, BlockHeader, ContractAddress, Contra
         universal_deployer_address,
     )
 }
+
+/// Returns a context on a copy of the mainnet fixture database, which contains mainnet
+/// blocks 0 to 6. The copy is removed once the returned directory is dropped.
+pub fn mainnet_context() -> (tempfile::TempDir, RpcContext) {
+    let mut source_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    source_path.push("fixtures/mainnet.sqlite");
+
+    let db_dir = tempfile::TempDir::new().unwrap();
+    let db_path = db_dir.path().join("mainnet.sqlite");
+    std::fs::copy(&source_path, &db_path).unwrap();
+
+    let storage = Storage::migrate(db_path, pathfinder_storage::JournalMode::WAL)
+        .unwrap()
+        .create_pool(std::num::NonZeroU32::new(2).unwrap())
+        .unwrap();
+
+    let context = RpcContext::for_tests_on(pathfinder_common::Chain::Mainnet).with_storage(storage);
+
+    (db_dir, context)
+}
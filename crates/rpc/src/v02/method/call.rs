@@ -6,7 +6,8 @@ use pathfinder_common::{BlockId, CallParam, CallResultValue, ContractAddress, En
 crate::error::generate_rpc_error_subset!(
     CallError: BlockNotFound,
     ContractNotFound,
-    ContractError
+    ContractError,
+    BlockPruned
 );
 
 impl From<pathfinder_executor::CallError> for CallError {
@@ -28,6 +29,7 @@ impl From<crate::executor::ExecutionStateError> for CallError {
         use crate::executor::ExecutionStateError::*;
         match error {
             BlockNotFound => Self::BlockNotFound,
+            BlockPruned => Self::BlockPruned,
             Internal(e) => Self::Internal(e),
         }
     }
@@ -16,7 +16,11 @@ pub struct GetStorageAtInput {
 #[derive(serde::Serialize)]
 pub struct GetStorageOutput(#[serde_as(as = "RpcFelt")] StorageValue);
 
-crate::error::generate_rpc_error_subset!(GetStorageAtError: ContractNotFound, BlockNotFound);
+crate::error::generate_rpc_error_subset!(
+    GetStorageAtError: ContractNotFound,
+    BlockNotFound,
+    BlockPruned
+);
 
 /// Get the value of the storage at the given address and key.
 pub async fn get_storage_at(
@@ -65,8 +69,16 @@ pub async fn get_storage_at(
         let tx = db.transaction().context("Creating database transaction")?;
 
         // Check for block existence.
-        if !tx.block_exists(block_id)? {
+        let Some((block_number, _)) = tx.block_id(block_id).context("Querying block number")?
+        else {
             return Err(GetStorageAtError::BlockNotFound);
+        };
+
+        if tx
+            .block_pruned(block_number)
+            .context("Checking if block is pruned")?
+        {
+            return Err(GetStorageAtError::BlockPruned);
         }
 
         let value = tx
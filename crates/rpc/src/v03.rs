@@ -76,10 +76,38 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
             "v0.3_starknet_estimateMessageFee",
             method::estimate_message_fee,
         )?
+        .register_method_with_no_input(
+            "v0.3_pathfinder_dbStats",
+            crate::pathfinder::methods::db_stats,
+        )?
+        .register_method(
+            "v0.3_pathfinder_getFeeHistory",
+            crate::pathfinder::methods::get_fee_history,
+        )?
+        .register_method(
+            "v0.3_pathfinder_getL1MessageStatus",
+            crate::pathfinder::methods::get_l1_message_status,
+        )?
+        .register_method_with_no_input(
+            "v0.3_pathfinder_getL1Status",
+            crate::pathfinder::methods::get_l1_status,
+        )?
         .register_method(
             "v0.3_pathfinder_getProof",
             crate::pathfinder::methods::get_proof,
         )?
+        .register_method(
+            "v0.3_pathfinder_getProofs",
+            crate::pathfinder::methods::get_proofs,
+        )?
+        .register_method(
+            "v0.3_pathfinder_getStorageChanges",
+            crate::pathfinder::methods::get_storage_changes,
+        )?
+        .register_method(
+            "v0.3_pathfinder_getStorageHistory",
+            crate::pathfinder::methods::get_storage_history,
+        )?
         .register_method(
             "v0.3_pathfinder_getTransactionStatus",
             crate::pathfinder::methods::get_transaction_status,
@@ -14,7 +14,8 @@ pub struct EstimateFeeInput {
 crate::error::generate_rpc_error_subset!(
     EstimateFeeError: BlockNotFound,
     ContractNotFound,
-    ContractError
+    ContractError,
+    BlockPruned
 );
 
 impl From<pathfinder_executor::CallError> for EstimateFeeError {
@@ -36,6 +37,7 @@ impl From<crate::executor::ExecutionStateError> for EstimateFeeError {
         use crate::executor::ExecutionStateError::*;
         match error {
             BlockNotFound => Self::BlockNotFound,
+            BlockPruned => Self::BlockPruned,
             Internal(e) => Self::Internal(e),
         }
     }
@@ -23,7 +23,8 @@ pub struct EstimateMessageFeeInput {
 crate::error::generate_rpc_error_subset!(
     EstimateMessageFeeError: BlockNotFound,
     ContractNotFound,
-    ContractError
+    ContractError,
+    BlockPruned
 );
 
 impl From<pathfinder_executor::CallError> for EstimateMessageFeeError {
@@ -45,6 +46,7 @@ impl From<crate::executor::ExecutionStateError> for EstimateMessageFeeError {
         use crate::executor::ExecutionStateError::*;
         match error {
             BlockNotFound => Self::BlockNotFound,
+            BlockPruned => Self::BlockPruned,
             Internal(e) => Self::Internal(e),
         }
     }
@@ -1,9 +1,11 @@
 use crate::context::RpcContext;
 use anyhow::Context;
-use pathfinder_common::{BlockId, BlockNumber, ContractAddress, EventKey};
-use pathfinder_storage::{EventFilterError, V03KeyFilter};
+use pathfinder_common::{BlockHash, BlockId, BlockNumber, ContractAddress, EventKey};
+use pathfinder_storage::{EventCursor, EventFilterError, V03KeyFilter};
 use serde::Deserialize;
+use stark_hash::Felt;
 use starknet_gateway_types::reply::PendingBlock;
+use std::collections::HashSet;
 use tokio::task::JoinHandle;
 
 #[derive(Debug)]
@@ -13,6 +15,7 @@ pub enum GetEventsError {
     PageSizeTooBig,
     InvalidContinuationToken,
     TooManyKeysInFilter { limit: usize, requested: usize },
+    EventsNotIndexed,
 }
 
 impl From<anyhow::Error> for GetEventsError {
@@ -31,6 +34,7 @@ impl From<GetEventsError> for crate::error::RpcError {
             GetEventsError::TooManyKeysInFilter { limit, requested } => {
                 Self::TooManyKeysInFilter { limit, requested }
             }
+            GetEventsError::EventsNotIndexed => Self::EventsNotIndexed,
         }
     }
 }
@@ -59,11 +63,67 @@ pub struct EventFilter {
     // These are inlined here because serde flatten and deny_unknown_fields
     // don't work together.
     pub chunk_size: usize,
-    /// Offset, measured in events, which points to the requested chunk
+    /// Opaque token returned by a previous call, which points to the requested chunk
     #[serde(default)]
     pub continuation_token: Option<String>,
 }
 
+/// The decoded form of the opaque `continuation_token`.
+///
+/// Points to the first event of the next chunk, which lets the database resume the query
+/// with an indexed seek instead of skipping over all preceding events.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct ContinuationToken {
+    cursor: EventCursor,
+    /// The latest block at the time the token was created. If this block is no longer part of
+    /// the canonical chain then the previous chunks may have been reorged away, and the token
+    /// is rejected.
+    ///
+    /// Events of the pending block are positioned as if they were part of the block following
+    /// this one.
+    block_hash: BlockHash,
+}
+
+impl ContinuationToken {
+    const ENCODED_LEN: usize = 3 * std::mem::size_of::<u64>() + 32;
+
+    fn encode(&self) -> String {
+        let mut bytes = Vec::with_capacity(Self::ENCODED_LEN);
+        bytes.extend_from_slice(&self.cursor.block_number.get().to_be_bytes());
+        bytes.extend_from_slice(&(self.cursor.transaction_index as u64).to_be_bytes());
+        bytes.extend_from_slice(&(self.cursor.event_index as u64).to_be_bytes());
+        bytes.extend_from_slice(self.block_hash.0.as_be_bytes());
+
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    }
+
+    fn decode(token: &str) -> Option<Self> {
+        let bytes = base64::decode_config(token, base64::URL_SAFE_NO_PAD).ok()?;
+        if bytes.len() != Self::ENCODED_LEN {
+            return None;
+        }
+
+        let (numbers, block_hash) = bytes.split_at(3 * std::mem::size_of::<u64>());
+        let mut numbers = numbers
+            .chunks_exact(std::mem::size_of::<u64>())
+            .map(|chunk| u64::from_be_bytes(chunk.try_into().expect("Chunk has the size of u64")));
+
+        let block_number = BlockNumber::new(numbers.next()?)?;
+        let transaction_index = numbers.next()?.try_into().ok()?;
+        let event_index = numbers.next()?.try_into().ok()?;
+        let block_hash = BlockHash(Felt::from_be_slice(block_hash).ok()?);
+
+        Some(Self {
+            cursor: EventCursor {
+                block_number,
+                transaction_index,
+                event_index,
+            },
+            block_hash,
+        })
+    }
+}
+
 /// Returns events matching the specified filter
 pub async fn get_events(
     context: RpcContext,
@@ -79,22 +139,19 @@ pub async fn get_events(
     //
     // The database query for 3 and 4 is combined into one step.
     //
-    // 4 requires some additional logic to handle some edge cases:
-    //  a) Query database
-    //  b) if full page           -> return page
-    //  c) else if partially full -> append events from start of pending
-    //  d) else (page is empty):
-    //      i) query database for event count
-    //     ii) query pending data using count for paging into pending events
+    // Pending events are positioned as if the pending block follows the latest block, which
+    // lets a continuation token page from the database into the pending events:
+    //  a) Query database, unless the token already points into the pending block
+    //  b) if the database has more events -> return page
+    //  c) else append events from the token's position in pending, or its start
 
     use BlockId::*;
 
     let request = input.filter;
-    let requested_offset = match request.continuation_token {
-        Some(s) => Some(
-            s.parse::<usize>()
-                .map_err(|_| GetEventsError::InvalidContinuationToken)?,
-        ),
+    let continuation_token = match request.continuation_token.as_deref() {
+        Some(token) => {
+            Some(ContinuationToken::decode(token).ok_or(GetEventsError::InvalidContinuationToken)?)
+        }
         None => None,
     };
 
@@ -105,6 +162,14 @@ pub async fn get_events(
         });
     }
 
+    // Handle the trivial (2) case.
+    if request.from_block == Some(Pending) && request.to_block != Some(Pending) {
+        return Ok(types::GetEventsResult {
+            events: Vec::new(),
+            continuation_token: None,
+        });
+    }
+
     // Grab the pending block so that we can check its validity.
     //
     // This is an async operation, so separating it from the sync database portion is sensible.
@@ -113,59 +178,11 @@ pub async fn get_events(
         None => None,
     };
 
-    // Handle the trivial (1) and (2) cases.
-    match (request.from_block, request.to_block) {
-        (Some(Pending), non_pending) if non_pending != Some(Pending) => {
-            return Ok(types::GetEventsResult {
-                events: Vec::new(),
-                continuation_token: None,
-            });
-        }
-        (Some(Pending), Some(Pending)) => {
-            let skip = requested_offset.unwrap_or_default();
-
-            let keys: Vec<std::collections::HashSet<_>> = request
-                .keys
-                .into_iter()
-                .map(|keys| keys.into_iter().collect())
-                .collect();
-
-            let mut events = Vec::new();
-            let is_last_page = match pending_block {
-                None => true,
-                Some(pending_block) => {
-                    append_pending_events(
-                        &pending_block,
-                        &mut events,
-                        skip,
-                        request.chunk_size,
-                        request.address,
-                        keys,
-                    )
-                    .await
-                }
-            };
-
-            check_continuation_token_validity(requested_offset, &events)?;
-
-            let continuation_token =
-                next_continuation_token(skip, request.chunk_size, is_last_page);
-
-            return Ok(types::GetEventsResult {
-                events,
-                continuation_token,
-            });
-        }
-        _ => {}
-    }
-
     let storage = context.storage.clone();
     let keys = V03KeyFilter::new(request.keys.clone());
 
-    // blocking task to perform database event query and optionally, the event count
-    // required for (4d).
     let span = tracing::Span::current();
-    let db_events: JoinHandle<Result<_, GetEventsError>> = tokio::task::spawn_blocking(move || {
+    let events: JoinHandle<Result<_, GetEventsError>> = tokio::task::spawn_blocking(move || {
         let _g = span.enter();
         let mut connection = storage
             .connection()
@@ -175,120 +192,122 @@ pub async fn get_events(
             .transaction()
             .context("Creating database transaction")?;
 
-        let from_block = map_from_block_to_number(&transaction, request.from_block)?;
-        let to_block = map_to_block_to_number(&transaction, request.to_block)?;
-
-        let filter = pathfinder_storage::EventFilter {
-            from_block,
-            to_block,
-            contract_address: request.address,
-            keys: keys.clone(),
-            page_size: request.chunk_size,
-            offset: requested_offset.unwrap_or_default(),
+        let (pending_number, pending_parent) = match transaction
+            .block_id(pathfinder_storage::BlockId::Latest)
+            .context("Querying latest block")?
+        {
+            Some((number, hash)) => (number + 1, hash),
+            None => (BlockNumber::GENESIS, BlockHash::ZERO),
         };
-        // We don't add context here, because [StarknetEventsTable::get_events] adds its
-        // own context to the errors. This way we get meaningful error information
-        // for errors related to query parameters.
-        let page = transaction.events(&filter).map_err(|e| {
-            if let Some(event_filter_error) = e.downcast_ref::<EventFilterError>() {
-                match event_filter_error {
-                    EventFilterError::PageSizeTooBig(_) => GetEventsError::PageSizeTooBig,
-                    EventFilterError::TooManyMatches => GetEventsError::from(e),
-                }
-            } else {
-                GetEventsError::from(e)
+
+        // Check the token's block is still part of the canonical chain, otherwise the events
+        // it points to may no longer exist, or may have been replaced by different ones.
+        if let Some(token) = &continuation_token {
+            if token.block_hash != pending_parent
+                && transaction
+                    .block_id(token.block_hash.into())
+                    .context("Querying continuation token's block")?
+                    .is_none()
+            {
+                return Err(GetEventsError::InvalidContinuationToken);
             }
-        })?;
+        }
 
-        // Additional information is required if we need to append pending events.
-        // More specifically, we need some database event count in order to page through
-        // the pending events properly.
-        let event_count = if request.to_block == Some(Pending) && page.events.is_empty() {
-            let count = transaction.event_count(from_block, to_block, request.address, &keys)?;
+        // Check pending block validity -- this means checking it's parent is indeed the
+        // latest block in storage. Discard the pending data if it is invalid.
+        let pending_block = pending_block.filter(|block| block.parent_hash == pending_parent);
 
-            Some(count)
-        } else {
-            None
-        };
+        let start = continuation_token.map(|token| token.cursor);
+        let mut events = Vec::new();
+        let mut next = None;
 
-        let continuation_token =
-            next_continuation_token(filter.offset, filter.page_size, page.is_last_page);
+        let query_database = request.from_block != Some(Pending)
+            && start.map_or(true, |start| start.block_number < pending_number);
 
-        // Check pending block validity if it is required -- this means checking it's parent is
-        // indeed the latest block in storage. Replace pending data if it is invalid, or not required.
-        let pending_block = match (request.to_block, pending_block) {
-            (Some(Pending), Some(pending_block)) => {
-                let latest = transaction
-                    .block_id(pathfinder_storage::BlockId::Latest)
-                    .context("Querying latest block hash")?
-                    .context("Latest block hash is missing")?
-                    .1;
+        if query_database {
+            let from_block = map_from_block_to_number(&transaction, request.from_block)?;
+            let to_block = map_to_block_to_number(&transaction, request.to_block)?;
 
-                (pending_block.parent_hash == latest).then_some(pending_block)
-            }
-            (_, original) => original,
-        };
+            let filter = pathfinder_storage::EventFilter {
+                from_block,
+                to_block,
+                contract_address: request.address,
+                keys,
+                page_size: request.chunk_size,
+                start,
+            };
+            // We don't add context here, because [StarknetEventsTable::get_events] adds its
+            // own context to the errors. This way we get meaningful error information
+            // for errors related to query parameters.
+            let page = transaction.events(&filter).map_err(|e| {
+                if let Some(event_filter_error) = e.downcast_ref::<EventFilterError>() {
+                    match event_filter_error {
+                        EventFilterError::PageSizeTooBig(_) => GetEventsError::PageSizeTooBig,
+                        EventFilterError::TooManyMatches => GetEventsError::from(e),
+                        EventFilterError::NotIndexed => GetEventsError::EventsNotIndexed,
+                    }
+                } else {
+                    GetEventsError::from(e)
+                }
+            })?;
 
-        Ok((
-            types::GetEventsResult {
-                events: page.events.into_iter().map(|e| e.into()).collect(),
-                continuation_token,
-            },
-            event_count,
-            pending_block,
-        ))
-    });
+            events.extend(page.events.into_iter().map(types::EmittedEvent::from));
+            next = page.continuation;
+        }
 
-    let (mut events, count, pending_block) = db_events
-        .await
-        .context("Database read panic or shutting down")??;
-
-    // Append pending data if required.
-    if matches!(request.to_block, Some(Pending))
-        && events.events.len() < request.chunk_size
-        && pending_block.is_some()
-    {
-        // This is safe as we just check pending_block.is_some() above.
-        let pending_block = pending_block.unwrap();
-
-        let keys: Vec<std::collections::HashSet<_>> = request
-            .keys
-            .into_iter()
-            .map(|keys| keys.into_iter().collect())
-            .collect();
+        // Append pending data if required.
+        if request.to_block == Some(Pending) && next.is_none() {
+            if let Some(pending_block) = pending_block {
+                let keys: Vec<HashSet<_>> = request
+                    .keys
+                    .into_iter()
+                    .map(|keys| keys.into_iter().collect())
+                    .collect();
+
+                let start = match start {
+                    Some(start) if start.block_number >= pending_number => {
+                        (start.transaction_index, start.event_index)
+                    }
+                    _ => (0, 0),
+                };
+
+                let amount = request.chunk_size - events.len();
+
+                next = append_pending_events(
+                    &pending_block,
+                    &mut events,
+                    start,
+                    amount,
+                    request.address,
+                    &keys,
+                )
+                .map(|(transaction_index, event_index)| EventCursor {
+                    block_number: pending_number,
+                    transaction_index,
+                    event_index,
+                });
+            }
+        }
 
-        let amount = request.chunk_size - events.events.len();
+        check_continuation_token_validity(continuation_token.as_ref(), &events)?;
 
-        let skip = match count {
-            Some(count) => {
-                // This will not yield an underflow error, as when continuation_token is None,
-                // then the count is also always 0, since the last page can only be empty if there's
-                // only a single empty page
-                requested_offset.unwrap_or_default() - count
+        let continuation_token = next.map(|cursor| {
+            ContinuationToken {
+                cursor,
+                block_hash: pending_parent,
             }
-            None => 0,
-        };
-
-        let is_last_page = append_pending_events(
-            &pending_block,
-            &mut events.events,
-            skip,
-            amount,
-            request.address,
-            keys,
-        )
-        .await;
-
-        events.continuation_token = next_continuation_token(
-            requested_offset.unwrap_or_default(),
-            request.chunk_size,
-            is_last_page,
-        );
-    }
+            .encode()
+        });
 
-    check_continuation_token_validity(requested_offset, &events.events)?;
+        Ok(types::GetEventsResult {
+            events,
+            continuation_token,
+        })
+    });
 
-    Ok(events)
+    events
+        .await
+        .context("Database read panic or shutting down")?
 }
 
 // Maps `to_block` BlockId to a block number which can be used by the events query.
@@ -349,34 +368,43 @@ fn map_from_block_to_number(
     }
 }
 
-/// Append's pending events to `dst` based on the filter requirements and returns
-/// true if this was the last pending data i.e. `is_last_page`.
-async fn append_pending_events(
+/// Append's up to `amount` pending events to `dst` based on the filter requirements, starting
+/// with the event at the `(transaction index, event index)` position `start`.
+///
+/// Returns the position of the next matching event, or [None] if this was the last pending data.
+fn append_pending_events(
     pending_block: &PendingBlock,
     dst: &mut Vec<types::EmittedEvent>,
-    skip: usize,
+    start: (usize, usize),
     amount: usize,
     address: Option<ContractAddress>,
-    keys: Vec<std::collections::HashSet<EventKey>>,
-) -> bool {
-    let original_len = dst.len();
-
+    keys: &[HashSet<EventKey>],
+) -> Option<(usize, usize)> {
     let key_filter_is_empty = keys.iter().flatten().count() == 0;
 
-    let pending_events = pending_block
+    let mut pending_events = pending_block
         .transaction_receipts
         .iter()
-        .flat_map(|receipt| {
+        .enumerate()
+        .flat_map(|(transaction_index, receipt)| {
             receipt
                 .events
                 .iter()
-                .zip(std::iter::repeat(receipt.transaction_hash))
+                .enumerate()
+                .map(move |(event_index, event)| {
+                    (
+                        (transaction_index, event_index),
+                        event,
+                        receipt.transaction_hash,
+                    )
+                })
         })
-        .filter(|(event, _)| match address {
+        .filter(|(position, _, _)| *position >= start)
+        .filter(|(_, event, _)| match address {
             Some(address) => event.from_address == address,
             None => true,
         })
-        .filter(|(event, _)| {
+        .filter(|(_, event, _)| {
             if key_filter_is_empty {
                 return true;
             }
@@ -389,58 +417,39 @@ async fn append_pending_events(
                 .zip(keys.iter())
                 .take(keys_to_check)
                 .all(|(key, filter)| filter.contains(key))
-        })
-        .skip(skip)
-        // We need to take an extra event to determine is_last_page.
-        .take(amount + 1)
-        .map(|(event, tx_hash)| types::EmittedEvent {
-            data: event.data.clone(),
-            keys: event.keys.clone(),
-            from_address: event.from_address,
-            block_hash: None,
-            block_number: None,
-            transaction_hash: tx_hash,
         });
 
-    dst.extend(pending_events);
-    let is_last_page = dst.len() <= (original_len + amount);
-    if !is_last_page {
-        dst.pop();
-    }
+    dst.extend(
+        pending_events
+            .by_ref()
+            .take(amount)
+            .map(|(_, event, tx_hash)| types::EmittedEvent {
+                data: event.data.clone(),
+                keys: event.keys.clone(),
+                from_address: event.from_address,
+                block_hash: None,
+                block_number: None,
+                transaction_hash: tx_hash,
+            }),
+    );
 
-    is_last_page
+    pending_events.next().map(|(position, _, _)| position)
 }
 
-/// Continuation token is invalid if:
-/// 1. its value is nonzero (which means that it _actually_ points to some _next page_)
-/// 2. it yields an empty page
+/// Continuation token is invalid if it yields an empty page, since a token is only
+/// handed out if there are more events.
 ///
 /// Unfortunately page retrieval has to be completed before the actual check can be done.
 fn check_continuation_token_validity(
-    continuation_token: Option<usize>,
+    continuation_token: Option<&ContinuationToken>,
     events: &[types::EmittedEvent],
 ) -> Result<(), GetEventsError> {
     match continuation_token {
-        Some(token) if token > 0 && events.is_empty() => {
-            Err(GetEventsError::InvalidContinuationToken)
-        }
+        Some(_) if events.is_empty() => Err(GetEventsError::InvalidContinuationToken),
         Some(_) | None => Ok(()),
     }
 }
 
-fn next_continuation_token(
-    current_offset: usize,
-    chunk_size: usize,
-    is_last_page: bool,
-) -> Option<String> {
-    if is_last_page {
-        None
-    } else {
-        // Point to the next page
-        Some((current_offset + chunk_size).to_string())
-    }
-}
-
 mod types {
     use pathfinder_common::{
         BlockHash, BlockNumber, ContractAddress, EventData, EventKey, TransactionHash,
@@ -480,7 +489,7 @@ mod types {
     #[serde(deny_unknown_fields)]
     pub struct GetEventsResult {
         pub events: Vec<EmittedEvent>,
-        /// Offset, measured in events, which points to the chunk that follows currenty requested chunk (`events`)
+        /// Opaque token which points to the chunk that follows currently requested chunk (`events`)
         pub continuation_token: Option<String>,
     }
 }
@@ -597,7 +606,7 @@ mod tests {
             events: vec![expected_event.clone()],
             continuation_token: None,
         };
-        let mut input = GetEventsInput {
+        let input = GetEventsInput {
             filter: EventFilter {
                 from_block: Some(expected_event.block_number.unwrap().into()),
                 to_block: Some(expected_event.block_number.unwrap().into()),
@@ -608,15 +617,64 @@ mod tests {
                 continuation_token: None,
             },
         };
-        let result = get_events(context.clone(), input.clone()).await.unwrap();
-        assert_eq!(result, expected_result);
-
-        // 0 continuation token should yield the same result as no token
-        input.filter.continuation_token = Some(0.to_string());
         let result = get_events(context, input).await.unwrap();
         assert_eq!(result, expected_result);
     }
 
+    #[tokio::test]
+    async fn get_events_with_malformed_continuation_token() {
+        let (context, _) = setup();
+
+        let input = GetEventsInput {
+            filter: EventFilter {
+                from_block: None,
+                to_block: None,
+                address: None,
+                keys: vec![],
+                chunk_size: 10,
+                // Offsets are no longer accepted.
+                continuation_token: Some(10.to_string()),
+            },
+        };
+        let error = get_events(context, input).await.unwrap_err();
+        assert_eq!(error, GetEventsError::InvalidContinuationToken);
+    }
+
+    #[tokio::test]
+    async fn get_events_with_reorged_continuation_token() {
+        let (context, events) = setup();
+
+        let mut input = GetEventsInput {
+            filter: EventFilter {
+                from_block: None,
+                to_block: None,
+                address: None,
+                keys: vec![],
+                chunk_size: 10,
+                continuation_token: None,
+            },
+        };
+        let result = get_events(context.clone(), input.clone()).await.unwrap();
+        assert_eq!(result.events, &events[..10]);
+
+        let token = ContinuationToken::decode(&result.continuation_token.unwrap()).unwrap();
+        assert_eq!(token.cursor.block_number, events[10].block_number.unwrap());
+
+        // The token's block is still canonical.
+        input.filter.continuation_token = Some(token.encode());
+        let result = get_events(context.clone(), input.clone()).await.unwrap();
+        assert_eq!(result.events, &events[10..20]);
+
+        // A block which is not part of the chain, as if the token's block was reorged away.
+        let reorged = ContinuationToken {
+            block_hash: block_hash_bytes!(b"reorged"),
+            ..token
+        };
+        input.filter.continuation_token = Some(reorged.encode());
+        let error = get_events(context, input).await.unwrap_err();
+        assert_eq!(error, GetEventsError::InvalidContinuationToken);
+    }
+
     #[tokio::test]
     async fn get_events_by_block() {
         let (context, events) = setup();
@@ -736,7 +794,7 @@ mod tests {
         let keys_for_expected_events: Vec<Vec<_>> =
             vec![expected_events.iter().map(|e| e.keys[0]).collect()];
 
-        let input = GetEventsInput {
+        let mut input = GetEventsInput {
             filter: EventFilter {
                 from_block: None,
                 to_block: None,
@@ -746,47 +804,21 @@ mod tests {
                 continuation_token: None,
             },
         };
-        let result = get_events(context.clone(), input).await.unwrap();
-        assert_eq!(
-            result,
-            GetEventsResult {
-                events: expected_events[..1].to_vec(),
-                continuation_token: Some(1.to_string()),
-            }
-        );
+        let result = get_events(context.clone(), input.clone()).await.unwrap();
+        assert_eq!(result.events, &expected_events[..1]);
+        assert!(result.continuation_token.is_some());
 
-        let input = GetEventsInput {
-            filter: EventFilter {
-                from_block: None,
-                to_block: None,
-                address: None,
-                keys: keys_for_expected_events.clone(),
-                chunk_size: 2,
-                continuation_token: Some(1.to_string()),
-            },
-        };
-        let result = get_events(context.clone(), input).await.unwrap();
-        assert_eq!(
-            result,
-            GetEventsResult {
-                events: expected_events[1..3].to_vec(),
-                continuation_token: Some(3.to_string()),
-            }
-        );
+        input.filter.chunk_size = 2;
+        input.filter.continuation_token = result.continuation_token;
+        let result = get_events(context.clone(), input.clone()).await.unwrap();
+        assert_eq!(result.events, &expected_events[1..3]);
+        assert!(result.continuation_token.is_some());
 
-        let input = GetEventsInput {
-            filter: EventFilter {
-                from_block: None,
-                to_block: None,
-                address: None,
-                keys: keys_for_expected_events.clone(),
-                chunk_size: 3,
-                continuation_token: Some(3.to_string()),
-            },
-        };
-        let result = get_events(context.clone(), input).await.unwrap();
+        input.filter.chunk_size = 3;
+        input.filter.continuation_token = result.continuation_token.clone();
+        let last = get_events(context.clone(), input.clone()).await.unwrap();
         assert_eq!(
-            result,
+            last,
             GetEventsResult {
                 events: expected_events[3..].to_vec(),
                 continuation_token: None,
@@ -794,17 +826,18 @@ mod tests {
         );
 
         // nonexistent page
-        let input = GetEventsInput {
-            filter: EventFilter {
-                from_block: None,
-                to_block: None,
-                address: None,
-                keys: keys_for_expected_events.clone(),
-                chunk_size: 1,
-                // Offset pointing to after the last event
-                continuation_token: Some(6.to_string()),
+        let token = ContinuationToken::decode(&result.continuation_token.unwrap()).unwrap();
+        let after_last = ContinuationToken {
+            cursor: EventCursor {
+                // Position after the last event
+                block_number: expected_events[5].block_number.unwrap(),
+                transaction_index: test_utils::TRANSACTIONS_PER_BLOCK,
+                event_index: 0,
             },
+            ..token
         };
+        input.filter.chunk_size = 1;
+        input.filter.continuation_token = Some(after_last.encode());
         let error = get_events(context, input).await.unwrap_err();
         assert_eq!(error, GetEventsError::InvalidContinuationToken);
     }
@@ -836,7 +869,7 @@ mod tests {
         async fn all_events() {
             let context = RpcContext::for_tests_with_pending().await;
 
-            let input0 = GetEventsInput {
+            let mut input = GetEventsInput {
                 filter: EventFilter {
                     from_block: None,
                     to_block: Some(BlockId::Latest),
@@ -846,28 +879,24 @@ mod tests {
                     continuation_token: None,
                 },
             };
-            let mut input1 = input0.clone();
-            input1.filter.continuation_token = Some(0.to_string());
 
-            for mut input in [input0, input1] {
-                let events = get_events(context.clone(), input.clone()).await.unwrap();
+            let events = get_events(context.clone(), input.clone()).await.unwrap();
 
-                input.filter.from_block = Some(BlockId::Pending);
-                input.filter.to_block = Some(BlockId::Pending);
-                let pending_events = get_events(context.clone(), input.clone()).await.unwrap();
+            input.filter.from_block = Some(BlockId::Pending);
+            input.filter.to_block = Some(BlockId::Pending);
+            let pending_events = get_events(context.clone(), input.clone()).await.unwrap();
 
-                input.filter.from_block = None;
-                let all_events = get_events(context.clone(), input.clone()).await.unwrap();
+            input.filter.from_block = None;
+            let all_events = get_events(context.clone(), input.clone()).await.unwrap();
 
-                let expected = events
-                    .events
-                    .into_iter()
-                    .chain(pending_events.events.into_iter())
-                    .collect::<Vec<_>>();
+            let expected = events
+                .events
+                .into_iter()
+                .chain(pending_events.events.into_iter())
+                .collect::<Vec<_>>();
 
-                assert_eq!(all_events.events, expected);
-                assert!(all_events.continuation_token.is_none());
-            }
+            assert_eq!(all_events.events, expected);
+            assert!(all_events.continuation_token.is_none());
         }
 
         #[tokio::test]
@@ -891,26 +920,35 @@ mod tests {
                 .events;
 
             input.filter.chunk_size = 2;
-            input.filter.continuation_token = Some(0.to_string()); // Should yield the same result as None above
             let result = get_events(context.clone(), input.clone()).await.unwrap();
             assert_eq!(result.events, &all[0..2]);
-            assert_eq!(result.continuation_token, Some(2.to_string()));
+            assert!(result.continuation_token.is_some());
 
             input.filter.chunk_size = 1;
             input.filter.continuation_token = result.continuation_token;
             let result = get_events(context.clone(), input.clone()).await.unwrap();
             assert_eq!(result.events, &all[2..3]);
-            assert_eq!(result.continuation_token, Some(3.to_string()));
+            assert!(result.continuation_token.is_some());
 
             input.filter.chunk_size = 100; // Only a single event remains though
-            input.filter.continuation_token = result.continuation_token;
-            let result = get_events(context.clone(), input.clone()).await.unwrap();
-            assert_eq!(result.events, &all[3..4]);
-            assert_eq!(result.continuation_token, None);
+            input.filter.continuation_token = result.continuation_token.clone();
+            let last = get_events(context.clone(), input.clone()).await.unwrap();
+            assert_eq!(last.events, &all[3..4]);
+            assert_eq!(last.continuation_token, None);
 
             // nonexistent page
+            let token = ContinuationToken::decode(&result.continuation_token.unwrap()).unwrap();
+            let after_last = ContinuationToken {
+                cursor: EventCursor {
+                    // Points to after the last pending event
+                    transaction_index: 1000,
+                    event_index: 0,
+                    ..token.cursor
+                },
+                ..token
+            };
             input.filter.chunk_size = 123; // Does not matter
-            input.filter.continuation_token = Some(4.to_string()); // Points to after the last event
+            input.filter.continuation_token = Some(after_last.encode());
             let error = get_events(context.clone(), input).await.unwrap_err();
             assert_eq!(error, GetEventsError::InvalidContinuationToken);
         }
@@ -22,7 +22,8 @@ pub struct SimulateTransactionOutput(pub Vec<dto::SimulatedTransaction>);
 crate::error::generate_rpc_error_subset!(
     SimulateTransactionError: BlockNotFound,
     ContractNotFound,
-    ContractError
+    ContractError,
+    BlockPruned
 );
 
 impl From<CallError> for SimulateTransactionError {
@@ -44,6 +45,7 @@ impl From<crate::executor::ExecutionStateError> for SimulateTransactionError {
         use crate::executor::ExecutionStateError::*;
         match error {
             BlockNotFound => Self::BlockNotFound,
+            BlockPruned => Self::BlockPruned,
             Internal(e) => Self::Internal(e),
         }
     }
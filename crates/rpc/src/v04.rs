@@ -3,6 +3,9 @@ use crate::module::Module;
 mod method;
 mod types;
 
+pub(crate) use method::PendingTransactionReceipt;
+pub(crate) use types::TransactionWithHash;
+
 use crate::v02::method as v02_method;
 use crate::v03::method as v03_method;
 use crate::v04::method as v04_method;
@@ -39,6 +42,10 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
             "v0.4_starknet_getBlockWithTxs",
             v04_method::get_block_with_txs,
         )?
+        .register_method(
+            "v0.4_starknet_getBlockWithReceipts",
+            v04_method::get_block_with_receipts,
+        )?
         .register_method(
             "v0.4_starknet_getBlockTransactionCount",
             v02_method::get_block_transaction_count,
@@ -75,10 +82,38 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
             "v0.4_starknet_simulateTransactions",
             v04_method::simulate_transactions,
         )?
+        .register_method_with_no_input(
+            "v0.4_pathfinder_dbStats",
+            crate::pathfinder::methods::db_stats,
+        )?
+        .register_method(
+            "v0.4_pathfinder_getFeeHistory",
+            crate::pathfinder::methods::get_fee_history,
+        )?
+        .register_method(
+            "v0.4_pathfinder_getL1MessageStatus",
+            crate::pathfinder::methods::get_l1_message_status,
+        )?
+        .register_method_with_no_input(
+            "v0.4_pathfinder_getL1Status",
+            crate::pathfinder::methods::get_l1_status,
+        )?
         .register_method(
             "v0.4_pathfinder_getProof",
             crate::pathfinder::methods::get_proof,
         )?
+        .register_method(
+            "v0.4_pathfinder_getProofs",
+            crate::pathfinder::methods::get_proofs,
+        )?
+        .register_method(
+            "v0.4_pathfinder_getStorageChanges",
+            crate::pathfinder::methods::get_storage_changes,
+        )?
+        .register_method(
+            "v0.4_pathfinder_getStorageHistory",
+            crate::pathfinder::methods::get_storage_history,
+        )?
         .register_method(
             "v0.4_pathfinder_getTransactionStatus",
             crate::pathfinder::methods::get_transaction_status,
@@ -87,6 +122,14 @@ pub fn register_methods(module: Module) -> anyhow::Result<Module> {
         .register_method(
             "v0.4_starknet_estimateMessageFee",
             v04_method::estimate_message_fee,
+        )?
+        .register_method(
+            "v0.4_starknet_traceTransaction",
+            v04_method::trace_transaction,
+        )?
+        .register_method(
+            "v0.4_starknet_traceBlockTransactions",
+            v04_method::trace_block_transactions,
         )?;
 
     Ok(module)
@@ -2,6 +2,7 @@ mod add_declare_transaction;
 mod add_deploy_account_transaction;
 mod add_invoke_transaction;
 mod estimate_message_fee;
+mod get_block_with_receipts;
 mod get_block_with_txs;
 mod get_transaction_by_block_and_index;
 mod get_transaction_by_hash;
@@ -9,15 +10,21 @@ mod get_transaction_receipt;
 mod pending_transactions;
 mod simulate_transactions;
 mod syncing;
+mod trace_block_transactions;
+mod trace_transaction;
 
 pub(super) use add_declare_transaction::add_declare_transaction;
 pub(super) use add_deploy_account_transaction::add_deploy_account_transaction;
 pub(super) use add_invoke_transaction::add_invoke_transaction;
 pub(super) use estimate_message_fee::estimate_message_fee;
+pub(super) use get_block_with_receipts::get_block_with_receipts;
 pub(super) use get_block_with_txs::get_block_with_txs;
 pub(super) use get_transaction_by_block_and_index::get_transaction_by_block_id_and_index;
 pub(super) use get_transaction_by_hash::get_transaction_by_hash;
 pub(super) use get_transaction_receipt::get_transaction_receipt;
+pub(super) use get_transaction_receipt::types::PendingTransactionReceipt;
 pub(super) use pending_transactions::pending_transactions;
 pub(super) use simulate_transactions::simulate_transactions;
 pub(super) use syncing::syncing;
+pub(super) use trace_block_transactions::trace_block_transactions;
+pub(super) use trace_transaction::trace_transaction;
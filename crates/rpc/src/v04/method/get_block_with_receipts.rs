@@ -0,0 +1,252 @@
+use crate::context::RpcContext;
+use crate::v02::types::reply::BlockStatus;
+
+use anyhow::{anyhow, Context};
+use pathfinder_common::BlockId;
+use serde::Deserialize;
+
+use super::get_transaction_receipt::types::{
+    FinalityStatus, MaybePendingTransactionReceipt, PendingTransactionReceipt, TransactionReceipt,
+};
+
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct GetBlockWithReceiptsInput {
+    block_id: BlockId,
+}
+
+crate::error::generate_rpc_error_subset!(GetBlockWithReceiptsError: BlockNotFound);
+
+/// Get block information along with all of its transactions and their receipts.
+pub async fn get_block_with_receipts(
+    context: RpcContext,
+    input: GetBlockWithReceiptsInput,
+) -> Result<types::BlockWithReceipts, GetBlockWithReceiptsError> {
+    let block_id = match input.block_id {
+        BlockId::Pending => {
+            let block = context
+                .pending_data
+                .ok_or_else(|| anyhow!("Pending data not supported in this configuration"))?
+                .block()
+                .await
+                .ok_or(GetBlockWithReceiptsError::BlockNotFound)?;
+
+            return Ok(types::BlockWithReceipts::from_pending(
+                block.as_ref().clone(),
+                context.version,
+            ));
+        }
+        other => other.try_into().expect("Only pending cast should fail"),
+    };
+
+    let storage = context.storage.clone();
+    let version = context.version;
+    let span = tracing::Span::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+        let mut connection = storage
+            .connection()
+            .context("Opening database connection")?;
+
+        let transaction = connection
+            .transaction()
+            .context("Creating database transaction")?;
+
+        let header = transaction
+            .block_header(block_id)
+            .context("Reading block from database")?
+            .ok_or(GetBlockWithReceiptsError::BlockNotFound)?;
+
+        let l1_accepted = transaction.block_is_l1_accepted(header.number.into())?;
+        let (block_status, finality_status) = if l1_accepted {
+            (BlockStatus::AcceptedOnL1, FinalityStatus::AcceptedOnL1)
+        } else {
+            (BlockStatus::AcceptedOnL2, FinalityStatus::AcceptedOnL2)
+        };
+
+        let transactions = transaction
+            .transaction_data_for_block(header.number.into())
+            .context("Reading transactions from database")?
+            .context("Transaction data missing for block")?
+            .into_iter()
+            .map(|(tx, rx)| types::TransactionWithReceipt {
+                receipt: MaybePendingTransactionReceipt::Normal(
+                    TransactionReceipt::with_block_data(
+                        rx,
+                        finality_status.clone(),
+                        header.hash,
+                        header.number,
+                        tx.clone(),
+                        version,
+                    ),
+                ),
+                transaction: tx.into(),
+            })
+            .collect();
+
+        Ok(types::BlockWithReceipts::from_parts(
+            header,
+            block_status,
+            transactions,
+        ))
+    })
+    .await
+    .context("Database read panic or shutting down")?
+}
+
+mod types {
+    use super::{MaybePendingTransactionReceipt, PendingTransactionReceipt};
+    use crate::context::RpcVersion;
+    use crate::felt::RpcFelt;
+    use crate::v02::types::reply::BlockStatus;
+    use crate::v04::types::TransactionWithHash;
+    use pathfinder_common::{
+        BlockHash, BlockHeader, BlockNumber, BlockTimestamp, SequencerAddress, StateCommitment,
+    };
+    use serde::Serialize;
+    use serde_with::{serde_as, skip_serializing_none};
+    use starknet_gateway_types::reply::PendingBlock;
+
+    /// A transaction and its receipt.
+    #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+    pub struct TransactionWithReceipt {
+        pub transaction: TransactionWithHash,
+        pub receipt: MaybePendingTransactionReceipt,
+    }
+
+    /// L2 Block with receipts as returned by the RPC API.
+    #[serde_as]
+    #[skip_serializing_none]
+    #[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+    #[serde(deny_unknown_fields)]
+    pub struct BlockWithReceipts {
+        pub status: BlockStatus,
+        #[serde_as(as = "Option<RpcFelt>")]
+        pub block_hash: Option<BlockHash>,
+        #[serde_as(as = "RpcFelt")]
+        pub parent_hash: BlockHash,
+        pub block_number: Option<BlockNumber>,
+        #[serde_as(as = "Option<RpcFelt>")]
+        pub new_root: Option<StateCommitment>,
+        pub timestamp: BlockTimestamp,
+        #[serde_as(as = "RpcFelt")]
+        pub sequencer_address: SequencerAddress,
+        pub transactions: Vec<TransactionWithReceipt>,
+    }
+
+    impl BlockWithReceipts {
+        pub fn from_parts(
+            header: BlockHeader,
+            status: BlockStatus,
+            transactions: Vec<TransactionWithReceipt>,
+        ) -> Self {
+            Self {
+                status,
+                block_hash: Some(header.hash),
+                parent_hash: header.parent_hash,
+                block_number: Some(header.number),
+                new_root: Some(header.state_commitment),
+                timestamp: header.timestamp,
+                sequencer_address: header.sequencer_address,
+                transactions,
+            }
+        }
+
+        pub fn from_pending(pending: PendingBlock, rpc_version: RpcVersion) -> Self {
+            let transactions = pending
+                .transactions
+                .into_iter()
+                .zip(pending.transaction_receipts)
+                .map(|(tx, rx)| TransactionWithReceipt {
+                    receipt: MaybePendingTransactionReceipt::Pending(
+                        PendingTransactionReceipt::from(rx, &tx, rpc_version),
+                    ),
+                    transaction: tx.into(),
+                })
+                .collect();
+
+            Self {
+                status: pending.status.into(),
+                block_hash: None,
+                parent_hash: pending.parent_hash,
+                block_number: None,
+                new_root: None,
+                timestamp: pending.timestamp,
+                sequencer_address: pending.sequencer_address,
+                transactions,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::BlockNumber;
+    use starknet_gateway_types::pending::PendingData;
+
+    #[tokio::test]
+    async fn latest() {
+        let context = RpcContext::for_tests();
+        let input = GetBlockWithReceiptsInput {
+            block_id: BlockId::Latest,
+        };
+
+        let block = get_block_with_receipts(context, input).await.unwrap();
+        assert_eq!(block.block_hash, Some(block_hash_bytes!(b"latest")));
+        assert_eq!(block.transactions.len(), 5);
+        for (tx, rx) in block
+            .transactions
+            .iter()
+            .map(|t| (&t.transaction, &t.receipt))
+        {
+            assert_matches!(rx, MaybePendingTransactionReceipt::Normal(
+                TransactionReceipt::Invoke(receipt)
+            ) => {
+                assert_eq!(receipt.common.transaction_hash, tx.transaction_hash);
+                assert_eq!(receipt.common.block_hash, block_hash_bytes!(b"latest"));
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn pending() {
+        let context = RpcContext::for_tests_with_pending().await;
+        let input = GetBlockWithReceiptsInput {
+            block_id: BlockId::Pending,
+        };
+
+        let block = get_block_with_receipts(context, input).await.unwrap();
+        assert_eq!(block.block_hash, None);
+        assert_eq!(block.parent_hash, block_hash_bytes!(b"latest"));
+        assert!(!block.transactions.is_empty());
+        for tx in &block.transactions {
+            assert_matches!(tx.receipt, MaybePendingTransactionReceipt::Pending(_));
+        }
+    }
+
+    #[tokio::test]
+    async fn pending_empty() {
+        let context = RpcContext::for_tests().with_pending_data(PendingData::default());
+        let input = GetBlockWithReceiptsInput {
+            block_id: BlockId::Pending,
+        };
+
+        let error = get_block_with_receipts(context, input).await.unwrap_err();
+        assert_matches!(error, GetBlockWithReceiptsError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = GetBlockWithReceiptsInput {
+            block_id: BlockId::Number(BlockNumber::new_or_panic(9999)),
+        };
+
+        let error = get_block_with_receipts(context, input).await.unwrap_err();
+        assert_matches!(error, GetBlockWithReceiptsError::BlockNotFound);
+    }
+}
@@ -22,7 +22,8 @@ pub struct SimulateTransactionOutput(pub Vec<dto::SimulatedTransaction>);
 crate::error::generate_rpc_error_subset!(
     SimulateTransactionError: BlockNotFound,
     ContractNotFound,
-    ContractError
+    ContractError,
+    BlockPruned
 );
 
 impl From<CallError> for SimulateTransactionError {
@@ -43,6 +44,7 @@ impl From<ExecutionStateError> for SimulateTransactionError {
     fn from(error: ExecutionStateError) -> Self {
         match error {
             ExecutionStateError::BlockNotFound => Self::BlockNotFound,
+            ExecutionStateError::BlockPruned => Self::BlockPruned,
             ExecutionStateError::Internal(e) => Self::Internal(e),
         }
     }
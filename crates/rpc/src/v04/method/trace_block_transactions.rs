@@ -0,0 +1,193 @@
+use crate::context::RpcContext;
+use crate::executor::ReExecutionError;
+use crate::felt::RpcFelt;
+use anyhow::Context;
+use pathfinder_common::{BlockId, TransactionHash};
+use pathfinder_executor::CallError;
+use serde::{Deserialize, Serialize};
+
+use super::simulate_transactions::dto::TransactionTrace;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TraceBlockTransactionsInput {
+    block_id: BlockId,
+}
+
+#[serde_with::serde_as]
+#[derive(Debug, Serialize, Eq, PartialEq)]
+pub struct Trace {
+    #[serde_as(as = "RpcFelt")]
+    pub transaction_hash: TransactionHash,
+    pub trace_root: TransactionTrace,
+}
+
+#[derive(Debug, Serialize, Eq, PartialEq)]
+pub struct TraceBlockTransactionsOutput(pub Vec<Trace>);
+
+crate::error::generate_rpc_error_subset!(
+    TraceBlockTransactionsError: BlockNotFound,
+    BlockPruned,
+    DeployNotSupported
+);
+
+impl From<ReExecutionError> for TraceBlockTransactionsError {
+    fn from(value: ReExecutionError) -> Self {
+        match value {
+            ReExecutionError::BlockNotFound => Self::BlockNotFound,
+            ReExecutionError::BlockPruned => Self::BlockPruned,
+            ReExecutionError::DeployNotSupported => Self::DeployNotSupported,
+            ReExecutionError::Internal(e) => Self::Internal(e),
+        }
+    }
+}
+
+impl From<CallError> for TraceBlockTransactionsError {
+    fn from(value: CallError) -> Self {
+        match value {
+            CallError::Internal(e) => Self::Internal(e),
+            other => Self::Internal(anyhow::anyhow!("Re-execution failed: {other:?}")),
+        }
+    }
+}
+
+pub async fn trace_block_transactions(
+    context: RpcContext,
+    input: TraceBlockTransactionsInput,
+) -> Result<TraceBlockTransactionsOutput, TraceBlockTransactionsError> {
+    // Pending blocks are not stored and can therefore not be re-executed.
+    let block_id = match input.block_id {
+        BlockId::Pending => return Err(TraceBlockTransactionsError::BlockNotFound),
+        other => other.try_into().expect("Only pending cast should fail"),
+    };
+
+    let storage = context.execution_storage.clone();
+    let chain_id = context.chain_id;
+    let span = tracing::Span::current();
+
+    let traces = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+
+        let (execution_state, transactions) =
+            crate::executor::re_execution_state(&storage, chain_id, block_id, None)?;
+
+        pathfinder_executor::trace(execution_state, transactions)
+            .map_err(TraceBlockTransactionsError::from)
+    })
+    .await
+    .context("Tracing block transactions")??;
+
+    let traces = traces
+        .into_iter()
+        .map(|(transaction_hash, trace)| Trace {
+            transaction_hash,
+            trace_root: trace.into(),
+        })
+        .collect();
+
+    Ok(TraceBlockTransactionsOutput(traces))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn pending_is_not_supported() {
+        let context = RpcContext::for_tests();
+        let input = TraceBlockTransactionsInput {
+            block_id: BlockId::Pending,
+        };
+
+        let error = trace_block_transactions(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceBlockTransactionsError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn block_not_found() {
+        let context = RpcContext::for_tests();
+        let input = TraceBlockTransactionsInput {
+            block_id: BlockId::Number(pathfinder_common::BlockNumber::new_or_panic(9999)),
+        };
+
+        let error = trace_block_transactions(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceBlockTransactionsError::BlockNotFound);
+    }
+
+    #[tokio::test]
+    async fn block_pruned() {
+        use pathfinder_common::macro_prelude::*;
+        use pathfinder_common::{BlockHeader, BlockNumber};
+        use pathfinder_storage::{Storage, TriePruneMode};
+
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.set_trie_prune_mode(TriePruneMode::Prune { history: 0 })
+            .unwrap();
+        let genesis = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"genesis"));
+        let block1 = genesis
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"block 1"));
+        let block2 = block1
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"block 2"));
+        for header in [&genesis, &block1, &block2] {
+            tx.insert_block_header(header).unwrap();
+        }
+        tx.commit().unwrap();
+
+        // Block 1 is executed on the state of block 0, which is pruned.
+        let context = RpcContext::for_tests().with_storage(storage);
+        let input = TraceBlockTransactionsInput {
+            block_id: BlockId::Number(BlockNumber::new_or_panic(1)),
+        };
+
+        let error = trace_block_transactions(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceBlockTransactionsError::BlockPruned);
+    }
+
+    #[tokio::test]
+    async fn mainnet_block() {
+        use super::super::simulate_transactions::dto::InvokeTxnTrace;
+        use pathfinder_common::macro_prelude::*;
+
+        let (_db_dir, context) = crate::test_setup::mainnet_context();
+        // Mainnet block 6 only contains invoke transactions.
+        let input = TraceBlockTransactionsInput {
+            block_id: BlockId::Number(pathfinder_common::BlockNumber::new_or_panic(6)),
+        };
+
+        let traces = trace_block_transactions(context, input).await.unwrap().0;
+        assert_eq!(traces.len(), 14);
+        assert_eq!(
+            traces[0].transaction_hash,
+            transaction_hash!("0x732fb32fb3d431eb4aa363fffbd6983e035401a11b2cffa96e19abb1bda9038")
+        );
+        assert_eq!(
+            traces[13].transaction_hash,
+            transaction_hash!("0x1f7d352e544d8cc59c50670f12bdbdf2ecdb932d20d023814597162d6be6b0a")
+        );
+        for trace in traces {
+            assert_matches::assert_matches!(
+                trace.trace_root,
+                TransactionTrace::Invoke(InvokeTxnTrace {
+                    execute_invocation: Some(_),
+                    ..
+                })
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn deploy_is_not_supported() {
+        let (_db_dir, context) = crate::test_setup::mainnet_context();
+        // Mainnet block 5 contains a deploy transaction.
+        let input = TraceBlockTransactionsInput {
+            block_id: BlockId::Number(pathfinder_common::BlockNumber::new_or_panic(5)),
+        };
+
+        let error = trace_block_transactions(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceBlockTransactionsError::DeployNotSupported);
+    }
+}
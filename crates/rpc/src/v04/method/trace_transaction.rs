@@ -0,0 +1,142 @@
+use crate::context::RpcContext;
+use crate::executor::ReExecutionError;
+use anyhow::Context;
+use pathfinder_common::TransactionHash;
+use pathfinder_executor::CallError;
+use serde::{Deserialize, Serialize};
+
+use super::simulate_transactions::dto::TransactionTrace;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TraceTransactionInput {
+    transaction_hash: TransactionHash,
+}
+
+#[derive(Debug, Serialize, Eq, PartialEq)]
+pub struct TraceTransactionOutput(pub TransactionTrace);
+
+crate::error::generate_rpc_error_subset!(
+    TraceTransactionError: TxnHashNotFoundV04,
+    BlockPruned,
+    DeployNotSupported
+);
+
+impl From<ReExecutionError> for TraceTransactionError {
+    fn from(value: ReExecutionError) -> Self {
+        match value {
+            ReExecutionError::BlockNotFound => {
+                Self::Internal(anyhow::anyhow!("Block of transaction missing"))
+            }
+            ReExecutionError::BlockPruned => Self::BlockPruned,
+            ReExecutionError::DeployNotSupported => Self::DeployNotSupported,
+            ReExecutionError::Internal(e) => Self::Internal(e),
+        }
+    }
+}
+
+impl From<CallError> for TraceTransactionError {
+    fn from(value: CallError) -> Self {
+        match value {
+            CallError::Internal(e) => Self::Internal(e),
+            other => Self::Internal(anyhow::anyhow!("Re-execution failed: {other:?}")),
+        }
+    }
+}
+
+pub async fn trace_transaction(
+    context: RpcContext,
+    input: TraceTransactionInput,
+) -> Result<TraceTransactionOutput, TraceTransactionError> {
+    let storage = context.execution_storage.clone();
+    let chain_id = context.chain_id;
+    let span = tracing::Span::current();
+
+    let trace = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+
+        let block_hash = {
+            let mut db = storage
+                .connection()
+                .context("Opening database connection")?;
+            let db_tx = db.transaction().context("Creating database transaction")?;
+
+            db_tx
+                .transaction_block_hash(input.transaction_hash)
+                .context("Reading transaction's block hash")?
+                .ok_or(TraceTransactionError::TxnHashNotFoundV04)?
+        };
+
+        // Only the transactions preceding the requested one are relevant for its trace.
+        let (execution_state, transactions) = crate::executor::re_execution_state(
+            &storage,
+            chain_id,
+            block_hash.into(),
+            Some(input.transaction_hash),
+        )?;
+
+        let (transaction_hash, trace) = pathfinder_executor::trace(execution_state, transactions)?
+            .pop()
+            .context("Re-execution produced no trace")?;
+        debug_assert_eq!(transaction_hash, input.transaction_hash);
+
+        Ok::<_, TraceTransactionError>(trace)
+    })
+    .await
+    .context("Tracing transaction")??;
+
+    Ok(TraceTransactionOutput(trace.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+
+    #[tokio::test]
+    async fn transaction_not_found() {
+        let context = RpcContext::for_tests();
+        let input = TraceTransactionInput {
+            transaction_hash: transaction_hash_bytes!(b"non-existent"),
+        };
+
+        let error = trace_transaction(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceTransactionError::TxnHashNotFoundV04);
+    }
+
+    #[tokio::test]
+    async fn mainnet_transaction() {
+        use super::super::simulate_transactions::dto::{InvokeTxnTrace, TransactionTrace};
+
+        let (_db_dir, context) = crate::test_setup::mainnet_context();
+        // The second transaction of mainnet block 5, which precedes its deploy transaction.
+        let input = TraceTransactionInput {
+            transaction_hash: transaction_hash!(
+                "0x1d29d5c52ee865a533b052397772bcdf05f70d584df8cefba3c05744804402a"
+            ),
+        };
+
+        let trace = trace_transaction(context, input).await.unwrap().0;
+        assert_matches::assert_matches!(
+            trace,
+            TransactionTrace::Invoke(InvokeTxnTrace {
+                execute_invocation: Some(_),
+                ..
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn deploy_is_not_supported() {
+        let (_db_dir, context) = crate::test_setup::mainnet_context();
+        // Follows the deploy transaction of mainnet block 5.
+        let input = TraceTransactionInput {
+            transaction_hash: transaction_hash!(
+                "0xc52079f33dcb44a58904fac3803fd908ac28d6632b67179ee06f2daccb4b5"
+            ),
+        };
+
+        let error = trace_transaction(context, input).await.unwrap_err();
+        assert_matches::assert_matches!(error, TraceTransactionError::DeployNotSupported);
+    }
+}
@@ -14,6 +14,27 @@ pub fn register_subscriptions(
         subscription::subscribe_new_heads::subscribe_new_heads,
         ws_broadcast_txs.new_head,
     )?;
+    let module = module.register_subscription_with_input(
+        "pathfinder_subscribe_events",
+        "pathfinder_subscription_event",
+        "pathfinder_unsubscribe_events",
+        subscription::subscribe_events::subscribe_events,
+        ws_broadcast_txs.events,
+    )?;
+    let module = module.register_subscription_with_input(
+        "pathfinder_subscribe_transactionStatus",
+        "pathfinder_subscription_transactionStatus",
+        "pathfinder_unsubscribe_transactionStatus",
+        subscription::subscribe_transaction_status::subscribe_transaction_status,
+        ws_broadcast_txs.transaction_status,
+    )?;
+    let module = module.register_subscription(
+        "pathfinder_subscribe_pendingTransactions",
+        "pathfinder_subscription_pendingTransactions",
+        "pathfinder_unsubscribe_pendingTransactions",
+        subscription::subscribe_pending_transactions::subscribe_pending_transactions,
+        ws_broadcast_txs.pending_transactions,
+    )?;
 
     Ok(module)
 }
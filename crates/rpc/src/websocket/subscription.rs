@@ -1 +1,4 @@
+pub(super) mod subscribe_events;
 pub(super) mod subscribe_new_heads;
+pub(super) mod subscribe_pending_transactions;
+pub(super) mod subscribe_transaction_status;
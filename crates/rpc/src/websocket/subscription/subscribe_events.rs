@@ -0,0 +1,157 @@
+use crate::context::RpcContext;
+use crate::error::RpcError;
+use crate::websocket::types::{EmittedEvents, EventFilter, SubscriptionBroadcaster};
+use futures::StreamExt;
+use jsonrpsee::core::error::SubscriptionClosed;
+use jsonrpsee::types::error::SubscriptionEmptyError;
+use jsonrpsee::SubscriptionSink;
+use tokio_stream::wrappers::BroadcastStream;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeEventsInput {
+    filter: EventFilter,
+}
+
+pub fn subscribe_events(
+    _context: RpcContext,
+    input: SubscribeEventsInput,
+    mut sink: SubscriptionSink,
+    ws_events_tx: &SubscriptionBroadcaster<EmittedEvents>,
+) -> Result<(), SubscriptionEmptyError> {
+    let filter = input.filter;
+
+    if filter.keys.len() > pathfinder_storage::EVENT_KEY_FILTER_LIMIT {
+        let error = RpcError::TooManyKeysInFilter {
+            limit: pathfinder_storage::EVENT_KEY_FILTER_LIMIT,
+            requested: filter.keys.len(),
+        };
+        let _ = sink.reject(jsonrpsee::core::Error::from(error));
+        return Ok(());
+    }
+
+    // Each broadcast message holds all events of a block, which we flatten into
+    // one subscription message per matching event.
+    let ws_events_tx = BroadcastStream::new(ws_events_tx.0.subscribe())
+        .map(move |events| {
+            let events = match events {
+                Ok(events) => events
+                    .iter()
+                    .filter(|event| filter.matches(event))
+                    .cloned()
+                    .map(Ok)
+                    .collect::<Vec<_>>(),
+                Err(error) => vec![Err(error)],
+            };
+            futures::stream::iter(events)
+        })
+        .flatten();
+
+    tokio::spawn(async move {
+        futures::pin_mut!(ws_events_tx);
+
+        match sink.pipe_from_try_stream(ws_events_tx).await {
+            SubscriptionClosed::Success => {
+                sink.close(SubscriptionClosed::Success);
+            }
+            SubscriptionClosed::RemotePeerAborted => {
+                tracing::trace!("WS: events subscription peer aborted");
+            }
+            SubscriptionClosed::Failed(error) => {
+                tracing::trace!("WS: events subscription failed {error:?}");
+                sink.close(error);
+            }
+        };
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::types::EmittedEvent;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockHash, BlockNumber};
+
+    fn event() -> EmittedEvent {
+        EmittedEvent {
+            data: vec![event_data!("0x1")],
+            keys: vec![event_key!("0xa"), event_key!("0xb")],
+            from_address: contract_address!("0x1234"),
+            block_hash: Some(block_hash!("0xabcd")),
+            block_number: Some(BlockNumber::new_or_panic(1)),
+            transaction_hash: transaction_hash!("0x5678"),
+        }
+    }
+
+    #[test]
+    fn parsing() {
+        let input = serde_json::json!({
+            "filter": {
+                "address": "0x1234",
+                "keys": [["0xa"], []],
+            }
+        });
+
+        let input = serde_json::from_value::<SubscribeEventsInput>(input).unwrap();
+        let expected = SubscribeEventsInput {
+            filter: EventFilter {
+                from_address: Some(contract_address!("0x1234")),
+                keys: vec![vec![event_key!("0xa")], vec![]],
+                include_pending: false,
+            },
+        };
+        assert_eq!(input, expected);
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        assert!(EventFilter::default().matches(&event()));
+    }
+
+    #[test]
+    fn from_address() {
+        let mut filter = EventFilter {
+            from_address: Some(contract_address!("0x1234")),
+            ..Default::default()
+        };
+        assert!(filter.matches(&event()));
+
+        filter.from_address = Some(contract_address!("0x1"));
+        assert!(!filter.matches(&event()));
+    }
+
+    #[test]
+    fn keys() {
+        let filter = EventFilter {
+            keys: vec![vec![], vec![event_key!("0xc"), event_key!("0xb")]],
+            ..Default::default()
+        };
+        assert!(filter.matches(&event()));
+
+        let filter = EventFilter {
+            keys: vec![vec![event_key!("0xb")]],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&event()));
+    }
+
+    #[test]
+    fn pending() {
+        let pending = EmittedEvent {
+            block_hash: None,
+            block_number: None,
+            ..event()
+        };
+
+        let mut filter = EventFilter::default();
+        assert!(!filter.matches(&pending));
+
+        filter.include_pending = true;
+        assert!(filter.matches(&pending));
+        assert!(filter.matches(&EmittedEvent {
+            block_hash: Some(BlockHash::ZERO),
+            ..event()
+        }));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::context::{RpcContext, RpcVersion};
+use crate::v04::{PendingTransactionReceipt, TransactionWithHash};
+use crate::websocket::types::{PendingTransactions, SubscriptionBroadcaster};
+use futures::{Stream, StreamExt};
+use jsonrpsee::core::error::SubscriptionClosed;
+use jsonrpsee::types::error::SubscriptionEmptyError;
+use jsonrpsee::SubscriptionSink;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+
+/// A pending transaction along with its receipt, in the JSON-RPC v0.4 format.
+#[derive(Clone, Debug, serde::Serialize, PartialEq, Eq)]
+pub struct PendingTransaction {
+    pub transaction: TransactionWithHash,
+    pub receipt: PendingTransactionReceipt,
+}
+
+/// Pushes the transactions which were added to the pending block since the previous message.
+pub fn subscribe_pending_transactions(
+    _context: RpcContext,
+    mut sink: SubscriptionSink,
+    ws_pending_transactions_tx: &SubscriptionBroadcaster<PendingTransactions>,
+) -> Result<(), SubscriptionEmptyError> {
+    let ws_pending_transactions_tx = stream(ws_pending_transactions_tx);
+
+    tokio::spawn(async move {
+        match sink.pipe_from_try_stream(ws_pending_transactions_tx).await {
+            SubscriptionClosed::Success => {
+                sink.close(SubscriptionClosed::Success);
+            }
+            SubscriptionClosed::RemotePeerAborted => {
+                tracing::trace!("WS: pendingTransactions subscription peer aborted");
+            }
+            SubscriptionClosed::Failed(error) => {
+                tracing::trace!("WS: pendingTransactions subscription failed {error:?}");
+                sink.close(error);
+            }
+        };
+    });
+    Ok(())
+}
+
+/// Converts each broadcast of new pending transactions into a message.
+fn stream(
+    ws_pending_transactions_tx: &SubscriptionBroadcaster<PendingTransactions>,
+) -> impl Stream<Item = Result<Vec<PendingTransaction>, BroadcastStreamRecvError>> {
+    BroadcastStream::new(ws_pending_transactions_tx.0.subscribe()).map(|transactions| {
+        transactions.map(|transactions| {
+            transactions
+                .iter()
+                .map(|(transaction, receipt)| PendingTransaction {
+                    receipt: PendingTransactionReceipt::from(
+                        receipt.clone(),
+                        transaction,
+                        RpcVersion::V04,
+                    ),
+                    transaction: pathfinder_common::transaction::Transaction::from(
+                        transaction.clone(),
+                    )
+                    .into(),
+                })
+                .collect::<Vec<_>>()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocket::types::WebsocketSenders;
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{TransactionHash, TransactionIndex};
+    use starknet_gateway_types::reply::transaction as gateway;
+    use std::sync::Arc;
+
+    fn transaction(index: u64) -> (gateway::Transaction, gateway::Receipt) {
+        let transaction_hash = TransactionHash(index.into());
+        let transaction = gateway::Transaction::Invoke(gateway::InvokeTransaction::V1(
+            gateway::InvokeTransactionV1 {
+                calldata: vec![],
+                sender_address: contract_address!("0x1"),
+                max_fee: fee!("0x2"),
+                signature: vec![],
+                nonce: transaction_nonce!("0x3"),
+                transaction_hash,
+            },
+        ));
+        let receipt = gateway::Receipt {
+            actual_fee: None,
+            events: vec![],
+            execution_resources: None,
+            l1_to_l2_consumed_message: None,
+            l2_to_l1_messages: vec![],
+            transaction_hash,
+            transaction_index: TransactionIndex::new_or_panic(index),
+            execution_status: Default::default(),
+            revert_error: None,
+        };
+        (transaction, receipt)
+    }
+
+    #[tokio::test]
+    async fn pushes_each_broadcast() {
+        let senders = WebsocketSenders::for_test();
+        let mut stream = stream(&senders.pending_transactions);
+
+        let first = vec![transaction(0), transaction(1)];
+        let second = vec![transaction(2)];
+        senders
+            .pending_transactions
+            .send_if_receiving(Arc::new(first.clone()));
+        senders
+            .pending_transactions
+            .send_if_receiving(Arc::new(second.clone()));
+
+        for expected in [first, second] {
+            let message = stream.next().await.unwrap().unwrap();
+
+            let hashes = message
+                .iter()
+                .map(|pending| pending.transaction.transaction_hash)
+                .collect::<Vec<_>>();
+            let expected_hashes = expected
+                .iter()
+                .map(|(transaction, _)| transaction.hash())
+                .collect::<Vec<_>>();
+            assert_eq!(hashes, expected_hashes);
+
+            let expected = expected
+                .into_iter()
+                .map(|(transaction, receipt)| PendingTransaction {
+                    receipt: PendingTransactionReceipt::from(
+                        receipt,
+                        &transaction,
+                        RpcVersion::V04,
+                    ),
+                    transaction: transaction.into(),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(message, expected);
+        }
+    }
+}
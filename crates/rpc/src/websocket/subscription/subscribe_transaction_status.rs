@@ -0,0 +1,365 @@
+use crate::context::RpcContext;
+use crate::error::RpcError;
+use crate::pathfinder::methods::TransactionStatus;
+use crate::websocket::types::{SubscriptionBroadcaster, TransactionStatusUpdate};
+use anyhow::Context;
+use futures::StreamExt;
+use jsonrpsee::core::error::SubscriptionClosed;
+use jsonrpsee::types::error::SubscriptionEmptyError;
+use jsonrpsee::SubscriptionSink;
+use pathfinder_common::{BlockNumber, TransactionHash};
+use starknet_gateway_types::reply::transaction::ExecutionStatus;
+use tokio_stream::wrappers::BroadcastStream;
+
+#[derive(serde::Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SubscribeTransactionStatusInput {
+    transaction_hash: TransactionHash,
+}
+
+/// Pushes the status of a transaction each time it changes, starting with its current status.
+///
+/// The subscription is closed once the transaction reaches a final status, i.e. `ACCEPTED_ON_L1`,
+/// `REJECTED`, `REVERTED` or `ABORTED`. Statuses never move backwards, so L2 reorgs which drop
+/// the transaction's block are not reported.
+pub fn subscribe_transaction_status(
+    context: RpcContext,
+    input: SubscribeTransactionStatusInput,
+    mut sink: SubscriptionSink,
+    ws_transaction_status_tx: &SubscriptionBroadcaster<TransactionStatusUpdate>,
+) -> Result<(), SubscriptionEmptyError> {
+    let transaction_hash = input.transaction_hash;
+    // Subscribe before fetching the current status so that no update can slip in between.
+    let updates = BroadcastStream::new(ws_transaction_status_tx.0.subscribe());
+
+    tokio::spawn(async move {
+        let (status, block_number) = match current_status(&context, transaction_hash).await {
+            Ok(current) => current,
+            Err(error) => {
+                let _ = sink.reject(jsonrpsee::core::Error::from(RpcError::Internal(error)));
+                return;
+            }
+        };
+
+        let tracker = StatusTracker {
+            transaction_hash,
+            status,
+            block_number,
+        };
+        let is_final = tracker.is_final();
+
+        let changes = futures::stream::unfold(
+            (tracker, updates, context),
+            |(mut tracker, mut updates, context)| async move {
+                while !tracker.is_final() {
+                    let changed = match updates.next().await? {
+                        Ok(update) => match tracker.apply(&update) {
+                            Some(status) => Ok(Some(status)),
+                            // Rejections are only known to the gateway, so check back with it
+                            // once per block while the transaction has not been seen yet.
+                            None if tracker.is_unseen()
+                                && matches!(
+                                    update,
+                                    TransactionStatusUpdate::AcceptedOnL2 { .. }
+                                ) =>
+                            {
+                                tracker.refresh(&context).await
+                            }
+                            None => Ok(None),
+                        }
+                        .transpose(),
+                        // We missed some updates so we can no longer rely on them.
+                        Err(_lagged) => tracker.refresh(&context).await.transpose(),
+                    };
+
+                    if let Some(changed) = changed {
+                        return Some((changed, (tracker, updates, context)));
+                    }
+                }
+
+                None
+            },
+        );
+
+        let initial = futures::stream::once(async move { anyhow::Ok(status) });
+        let stream = if is_final {
+            initial.boxed()
+        } else {
+            initial.chain(changes).boxed()
+        };
+
+        match sink.pipe_from_try_stream(stream).await {
+            SubscriptionClosed::Success => {
+                sink.close(SubscriptionClosed::Success);
+            }
+            SubscriptionClosed::RemotePeerAborted => {
+                tracing::trace!("WS: transactionStatus subscription peer aborted");
+            }
+            SubscriptionClosed::Failed(error) => {
+                tracing::trace!("WS: transactionStatus subscription failed {error:?}");
+                sink.close(error);
+            }
+        };
+    });
+    Ok(())
+}
+
+/// Follows a single transaction's status as [TransactionStatusUpdate]s arrive.
+#[derive(Debug, PartialEq)]
+struct StatusTracker {
+    transaction_hash: TransactionHash,
+    status: TransactionStatus,
+    /// The block containing the transaction, once it is known to be in storage.
+    block_number: Option<BlockNumber>,
+}
+
+impl StatusTracker {
+    /// Applies the update, returning the new status if it changed.
+    fn apply(&mut self, update: &TransactionStatusUpdate) -> Option<TransactionStatus> {
+        let status = match update {
+            TransactionStatusUpdate::Pending(transactions) => {
+                match self.execution_status(transactions)? {
+                    ExecutionStatus::Reverted => TransactionStatus::Reverted,
+                    ExecutionStatus::Succeeded => TransactionStatus::Pending,
+                }
+            }
+            TransactionStatusUpdate::AcceptedOnL2 {
+                block_number,
+                transactions,
+            } => {
+                let execution_status = self.execution_status(transactions)?;
+                self.block_number = Some(*block_number);
+
+                match execution_status {
+                    ExecutionStatus::Reverted => TransactionStatus::Reverted,
+                    ExecutionStatus::Succeeded => TransactionStatus::AcceptedOnL2,
+                }
+            }
+            TransactionStatusUpdate::AcceptedOnL1(l1_block) => match self.block_number {
+                Some(block_number) if block_number <= *l1_block => TransactionStatus::AcceptedOnL1,
+                _ => return None,
+            },
+        };
+
+        self.advance(status)
+    }
+
+    /// Re-fetches the current status from storage and the gateway, returning it if it changed.
+    async fn refresh(&mut self, context: &RpcContext) -> anyhow::Result<Option<TransactionStatus>> {
+        let (status, block_number) = current_status(context, self.transaction_hash).await?;
+        if self.block_number.is_none() {
+            self.block_number = block_number;
+        }
+
+        Ok(self.advance(status))
+    }
+
+    fn advance(&mut self, status: TransactionStatus) -> Option<TransactionStatus> {
+        if self.is_final() || progress(status) <= progress(self.status) {
+            return None;
+        }
+
+        self.status = status;
+        Some(status)
+    }
+
+    fn execution_status<'a>(
+        &self,
+        transactions: &'a [(TransactionHash, ExecutionStatus)],
+    ) -> Option<&'a ExecutionStatus> {
+        transactions
+            .iter()
+            .find_map(|(hash, status)| (hash == &self.transaction_hash).then_some(status))
+    }
+
+    fn is_unseen(&self) -> bool {
+        matches!(
+            self.status,
+            TransactionStatus::NotReceived | TransactionStatus::Received
+        )
+    }
+
+    fn is_final(&self) -> bool {
+        progress(self.status) == FINAL
+    }
+}
+
+const FINAL: u8 = 4;
+
+/// Orders statuses by how far along the transaction is.
+fn progress(status: TransactionStatus) -> u8 {
+    match status {
+        TransactionStatus::NotReceived => 0,
+        TransactionStatus::Received => 1,
+        TransactionStatus::Pending => 2,
+        TransactionStatus::AcceptedOnL2 => 3,
+        TransactionStatus::AcceptedOnL1
+        | TransactionStatus::Rejected
+        | TransactionStatus::Reverted
+        | TransactionStatus::Aborted => FINAL,
+    }
+}
+
+/// The transaction's current status, along with its block number if it is in storage.
+///
+/// Unlike `pathfinder_getTransactionStatus`, transactions in the pending block are reported as `PENDING`.
+async fn current_status(
+    context: &RpcContext,
+    transaction_hash: TransactionHash,
+) -> anyhow::Result<(TransactionStatus, Option<BlockNumber>)> {
+    let storage = context.storage.clone();
+    let span = tracing::Span::current();
+
+    let db_status = tokio::task::spawn_blocking(move || {
+        let _g = span.enter();
+
+        let mut db = storage
+            .connection()
+            .context("Opening database connection")?;
+        let db_tx = db.transaction().context("Creating database transaction")?;
+
+        let Some((_, receipt, block_hash)) = db_tx
+            .transaction_with_receipt(transaction_hash)
+            .context("Fetching receipt from database")?
+        else {
+            return anyhow::Ok(None);
+        };
+
+        let (block_number, _) = db_tx
+            .block_id(block_hash.into())
+            .context("Fetching block number")?
+            .context("Block of stored transaction is missing")?;
+
+        let status = if receipt.execution_status == ExecutionStatus::Reverted {
+            TransactionStatus::Reverted
+        } else if db_tx
+            .block_is_l1_accepted(block_hash.into())
+            .context("Quering block's status")?
+        {
+            TransactionStatus::AcceptedOnL1
+        } else {
+            TransactionStatus::AcceptedOnL2
+        };
+
+        Ok(Some((status, Some(block_number))))
+    })
+    .await
+    .context("Joining database task")??;
+
+    if let Some(db_status) = db_status {
+        return Ok(db_status);
+    }
+
+    if let Some(pending) = &context.pending_data {
+        if let Some(block) = pending.block().await {
+            let receipt = block
+                .transaction_receipts
+                .iter()
+                .find(|receipt| receipt.transaction_hash == transaction_hash);
+
+            match receipt.map(|receipt| &receipt.execution_status) {
+                Some(ExecutionStatus::Reverted) => return Ok((TransactionStatus::Reverted, None)),
+                Some(ExecutionStatus::Succeeded) => return Ok((TransactionStatus::Pending, None)),
+                None => {}
+            }
+        }
+    }
+
+    use starknet_gateway_client::GatewayApi;
+    let status = context
+        .sequencer
+        .transaction(transaction_hash)
+        .await
+        .context("Fetching transaction from gateway")?
+        .status;
+
+    Ok((status.into(), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+    use std::sync::Arc;
+
+    fn tracker(status: TransactionStatus) -> StatusTracker {
+        StatusTracker {
+            transaction_hash: transaction_hash!("0x1"),
+            status,
+            block_number: None,
+        }
+    }
+
+    fn transactions(status: ExecutionStatus) -> Arc<Vec<(TransactionHash, ExecutionStatus)>> {
+        Arc::new(vec![
+            (transaction_hash!("0x2"), ExecutionStatus::Succeeded),
+            (transaction_hash!("0x1"), status),
+        ])
+    }
+
+    #[test]
+    fn full_lifecycle() {
+        let mut tracker = tracker(TransactionStatus::Received);
+
+        let pending = TransactionStatusUpdate::Pending(transactions(ExecutionStatus::Succeeded));
+        assert_eq!(tracker.apply(&pending), Some(TransactionStatus::Pending));
+        // Repeated updates are not reported again.
+        assert_eq!(tracker.apply(&pending), None);
+
+        // An L1 update before the transaction is in a block has no effect.
+        let l1 = TransactionStatusUpdate::AcceptedOnL1(BlockNumber::new_or_panic(10));
+        assert_eq!(tracker.apply(&l1), None);
+
+        let l2 = TransactionStatusUpdate::AcceptedOnL2 {
+            block_number: BlockNumber::new_or_panic(10),
+            transactions: transactions(ExecutionStatus::Succeeded),
+        };
+        assert_eq!(tracker.apply(&l2), Some(TransactionStatus::AcceptedOnL2));
+
+        let l1_behind = TransactionStatusUpdate::AcceptedOnL1(BlockNumber::new_or_panic(9));
+        assert_eq!(tracker.apply(&l1_behind), None);
+
+        assert_eq!(tracker.apply(&l1), Some(TransactionStatus::AcceptedOnL1));
+        assert!(tracker.is_final());
+    }
+
+    #[test]
+    fn reverted_is_final() {
+        let mut tracker = tracker(TransactionStatus::Received);
+
+        let pending = TransactionStatusUpdate::Pending(transactions(ExecutionStatus::Reverted));
+        assert_eq!(tracker.apply(&pending), Some(TransactionStatus::Reverted));
+        assert!(tracker.is_final());
+
+        let l2 = TransactionStatusUpdate::AcceptedOnL2 {
+            block_number: BlockNumber::new_or_panic(10),
+            transactions: transactions(ExecutionStatus::Reverted),
+        };
+        assert_eq!(tracker.apply(&l2), None);
+    }
+
+    #[test]
+    fn other_transactions_are_ignored() {
+        let mut tracker = tracker(TransactionStatus::Received);
+
+        let l2 = TransactionStatusUpdate::AcceptedOnL2 {
+            block_number: BlockNumber::new_or_panic(10),
+            transactions: Arc::new(vec![(transaction_hash!("0x2"), ExecutionStatus::Succeeded)]),
+        };
+        assert_eq!(tracker.apply(&l2), None);
+        assert_eq!(tracker.block_number, None);
+    }
+
+    #[tokio::test]
+    async fn current_status_from_storage() {
+        let context = RpcContext::for_tests();
+        // This transaction is in block 0 which is L1 accepted.
+        let status = current_status(&context, transaction_hash_bytes!(b"txn 0"))
+            .await
+            .unwrap();
+        assert_eq!(
+            status,
+            (TransactionStatus::AcceptedOnL1, Some(BlockNumber::GENESIS))
+        );
+    }
+}
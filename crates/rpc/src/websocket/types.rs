@@ -1,12 +1,14 @@
 // Types used for web socket subscription events
 use pathfinder_common::{
-    BlockHash, BlockNumber, BlockTimestamp, GasPrice, SequencerAddress, StarknetVersion,
-    StateCommitment,
+    BlockHash, BlockNumber, BlockTimestamp, ContractAddress, EventData, EventKey, GasPrice,
+    SequencerAddress, StarknetVersion, StateCommitment, TransactionHash,
 };
 use pathfinder_serde::GasPriceAsHexStr;
 use serde::Deserialize;
 use serde_with::serde_as;
+use starknet_gateway_types::reply::transaction::{ExecutionStatus, Receipt, Transaction};
 use starknet_gateway_types::reply::{Block, Status};
+use std::sync::Arc;
 use tokio::sync::broadcast;
 
 #[derive(Debug, Clone)]
@@ -59,15 +61,147 @@ impl From<&Block> for BlockHeader {
     }
 }
 
+/// An event as pushed to `pathfinder_subscribe_events` subscribers.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct EmittedEvent {
+    pub data: Vec<EventData>,
+    pub keys: Vec<EventKey>,
+    pub from_address: ContractAddress,
+    /// [None] for pending events.
+    pub block_hash: Option<BlockHash>,
+    /// [None] for pending events.
+    pub block_number: Option<BlockNumber>,
+    pub transaction_hash: TransactionHash,
+}
+
+impl EmittedEvent {
+    /// Collects all events emitted by the block, in order.
+    pub fn from_block(block: &Block) -> Vec<Self> {
+        Self::collect(
+            &block.transaction_receipts,
+            Some(block.block_hash),
+            Some(block.block_number),
+        )
+    }
+
+    /// Collects all events emitted by the given pending receipts, in order.
+    pub fn from_pending_receipts(receipts: &[Receipt]) -> Vec<Self> {
+        Self::collect(receipts, None, None)
+    }
+
+    fn collect(
+        receipts: &[Receipt],
+        block_hash: Option<BlockHash>,
+        block_number: Option<BlockNumber>,
+    ) -> Vec<Self> {
+        receipts
+            .iter()
+            .flat_map(|receipt| {
+                receipt.events.iter().map(move |event| Self {
+                    data: event.data.clone(),
+                    keys: event.keys.clone(),
+                    from_address: event.from_address,
+                    block_hash,
+                    block_number,
+                    transaction_hash: receipt.transaction_hash,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The events of a single block, broadcast as one message so that large blocks
+/// don't overrun the channel capacity.
+pub type EmittedEvents = Arc<Vec<EmittedEvent>>;
+
+/// Filter parameters of `pathfinder_subscribe_events`.
+///
+/// Follows the same address and key matching rules as `starknet_getEvents`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct EventFilter {
+    #[serde(default, alias = "address")]
+    pub from_address: Option<ContractAddress>,
+    #[serde(default)]
+    pub keys: Vec<Vec<EventKey>>,
+    /// Also push events from the pending block.
+    #[serde(default)]
+    pub include_pending: bool,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &EmittedEvent) -> bool {
+        if !self.include_pending && event.block_hash.is_none() {
+            return false;
+        }
+
+        if let Some(address) = self.from_address {
+            if event.from_address != address {
+                return false;
+            }
+        }
+
+        // An empty set of keys at a position matches any key.
+        event
+            .keys
+            .iter()
+            .zip(self.keys.iter())
+            .all(|(key, filter)| filter.is_empty() || filter.contains(key))
+    }
+}
+
+/// Transaction status changes observed by sync, which drive `pathfinder_subscribe_transactionStatus`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TransactionStatusUpdate {
+    /// Transactions newly seen in the pending block.
+    Pending(Arc<Vec<(TransactionHash, ExecutionStatus)>>),
+    /// Transactions of a block which was committed to storage.
+    AcceptedOnL2 {
+        block_number: BlockNumber,
+        transactions: Arc<Vec<(TransactionHash, ExecutionStatus)>>,
+    },
+    /// All blocks up to and including this one are now accepted on L1.
+    AcceptedOnL1(BlockNumber),
+}
+
+impl TransactionStatusUpdate {
+    pub fn pending(receipts: &[Receipt]) -> Self {
+        Self::Pending(Arc::new(Self::transactions(receipts)))
+    }
+
+    pub fn accepted_on_l2(block: &Block) -> Self {
+        Self::AcceptedOnL2 {
+            block_number: block.block_number,
+            transactions: Arc::new(Self::transactions(&block.transaction_receipts)),
+        }
+    }
+
+    fn transactions(receipts: &[Receipt]) -> Vec<(TransactionHash, ExecutionStatus)> {
+        receipts
+            .iter()
+            .map(|receipt| (receipt.transaction_hash, receipt.execution_status.clone()))
+            .collect()
+    }
+}
+
+/// Transactions newly seen in the pending block along with their receipts.
+pub type PendingTransactions = Arc<Vec<(Transaction, Receipt)>>;
+
 #[derive(Debug, Clone)]
 pub struct WebsocketSenders {
     pub new_head: SubscriptionBroadcaster<BlockHeader>,
+    pub events: SubscriptionBroadcaster<EmittedEvents>,
+    pub transaction_status: SubscriptionBroadcaster<TransactionStatusUpdate>,
+    pub pending_transactions: SubscriptionBroadcaster<PendingTransactions>,
 }
 
 impl WebsocketSenders {
     pub fn with_capacity(capacity: usize) -> WebsocketSenders {
         WebsocketSenders {
             new_head: SubscriptionBroadcaster(broadcast::channel(capacity).0),
+            events: SubscriptionBroadcaster(broadcast::channel(capacity).0),
+            transaction_status: SubscriptionBroadcaster(broadcast::channel(capacity).0),
+            pending_transactions: SubscriptionBroadcaster(broadcast::channel(capacity).0),
         }
     }
 }
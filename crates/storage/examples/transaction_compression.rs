@@ -0,0 +1,157 @@
+use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+use pathfinder_storage::{BlockId, Storage};
+
+const COMPRESSION_LEVEL: i32 = 10;
+const DICTIONARY_SIZE: usize = 112_640;
+/// Every n-th block of the range is used to train the dictionary.
+const TRAINING_STEP: usize = 10;
+
+/// Compares the space used by the transactions and receipts of a range of blocks when compressed:
+///
+/// - per transaction and receipt, which is how they were stored up to database revision 42,
+/// - per block,
+/// - per block using a dictionary trained on a sample of the range, which is how they are stored now.
+///
+/// The time taken to decompress all blocks is reported as well, along with the latency of looking
+/// up the transactions of the range by hash in the database, which decompresses their block up to
+/// the transaction.
+///
+/// Usage:
+/// `cargo run --release -p pathfinder-storage --example transaction_compression ./mainnet.sqlite 100000 110000`
+fn main() -> anyhow::Result<()> {
+    let database_path = std::env::args().nth(1).context("Missing database path")?;
+    let first: u64 = std::env::args()
+        .nth(2)
+        .context("Missing first block")?
+        .parse()
+        .context("Parsing first block")?;
+    let last: u64 = std::env::args()
+        .nth(3)
+        .context("Missing last block")?
+        .parse()
+        .context("Parsing last block")?;
+
+    let storage =
+        Storage::open_read_only(database_path.into())?.create_pool(NonZeroU32::new(1).unwrap())?;
+    let mut db = storage.connection()?;
+    let tx = db.transaction()?;
+
+    let mut blocks = Vec::new();
+    for number in first..=last {
+        let number = BlockNumber::new(number).context("Invalid block number")?;
+        let Some(data) = tx.transaction_data_for_block(BlockId::Number(number))? else {
+            break;
+        };
+        if !data.is_empty() {
+            blocks.push(data);
+        }
+    }
+    anyhow::ensure!(!blocks.is_empty(), "No transactions in the block range");
+
+    let mut json = 0;
+    let mut per_transaction = Size::default();
+    let mut samples = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        for (transaction, receipt) in block {
+            for data in [
+                serde_json::to_vec(transaction)?,
+                serde_json::to_vec(receipt)?,
+            ] {
+                json += data.len();
+                per_transaction.add(&data, None)?;
+            }
+        }
+
+        if i % TRAINING_STEP == 0 {
+            samples.push(serde_json::to_vec(block)?);
+        }
+    }
+
+    let start = Instant::now();
+    let dictionary =
+        zstd::dict::from_samples(&samples, DICTIONARY_SIZE).context("Training dictionary")?;
+    let training_time = start.elapsed();
+
+    let mut per_block = Size::default();
+    let mut per_block_dictionary = Size::default();
+    for block in &blocks {
+        let data = serde_json::to_vec(block)?;
+        per_block.add(&data, None)?;
+        per_block_dictionary.add(&data, Some(&dictionary))?;
+    }
+
+    println!(
+        "{} blocks with {} transactions, {json} bytes of JSON",
+        blocks.len(),
+        blocks.iter().map(Vec::len).sum::<usize>()
+    );
+    println!(
+        "Dictionary of {} bytes trained on {} blocks in {training_time:?}",
+        dictionary.len(),
+        samples.len()
+    );
+
+    let mut lookups = Vec::new();
+    for block in &blocks {
+        for (transaction, _) in block {
+            let start = Instant::now();
+            tx.transaction_with_receipt(transaction.hash())?
+                .context("Transaction missing")?;
+            lookups.push(start.elapsed());
+        }
+    }
+    lookups.sort();
+    println!(
+        "Transaction lookup by hash: {:?} on average, {:?} at the 99th percentile, {:?} at most",
+        lookups.iter().sum::<Duration>() / lookups.len() as u32,
+        lookups[lookups.len() * 99 / 100],
+        lookups[lookups.len() - 1]
+    );
+
+    println!();
+    println!(
+        "{:<30} {:>14} {:>8} {:>14}",
+        "", "bytes", "ratio", "decompression"
+    );
+    for (name, size) in [
+        ("per transaction", per_transaction),
+        ("per block", per_block),
+        ("per block with dictionary", per_block_dictionary),
+    ] {
+        println!(
+            "{name:<30} {:>14} {:>7.1}x {:>14?}",
+            size.bytes,
+            json as f64 / size.bytes as f64,
+            size.decompression
+        );
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Size {
+    bytes: usize,
+    decompression: Duration,
+}
+
+impl Size {
+    fn add(&mut self, data: &[u8], dictionary: Option<&[u8]>) -> anyhow::Result<()> {
+        let dictionary = dictionary.unwrap_or_default();
+        let compressed = zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, dictionary)?
+            .compress(data)?;
+        self.bytes += compressed.len();
+
+        let start = Instant::now();
+        let mut decoder =
+            zstd::stream::read::Decoder::with_dictionary(compressed.as_slice(), dictionary)?;
+        std::io::copy(&mut decoder, &mut std::io::sink())?;
+        self.decompression += start.elapsed();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+//! Storage backends behind [Transaction](crate::Transaction).
+//!
+//! The block, transaction, event, class and state update accessors of a transaction are
+//! performed by a [StorageBackend], and the state trie nodes by a [TrieBackend].
+//!
+//! By default everything is stored in sqlite. The `memory-trie-backend` feature instead keeps
+//! the trie nodes in an in-process key-value store, which is intended for benchmarking
+//! trie-heavy sync without sqlite. Such a store does not persist across restarts, so it
+//! refuses to open a database which already contains blocks.
+//!
+//! Additional trie backends implement [TrieBackend], and provide a `TrieStore` shared by all
+//! connections and a `TrieTransaction` which is committed along with the sqlite transaction.
+//! Additional storage backends implement [StorageBackend] in the same way.
+
+// Only one of the backends is in use, depending on the enabled features.
+#[cfg_attr(not(feature = "memory-trie-backend"), allow(dead_code))]
+mod memory;
+#[cfg_attr(feature = "memory-trie-backend", allow(dead_code))]
+mod sqlite;
+
+use pathfinder_common::trie::TrieNode;
+use pathfinder_common::{
+    BlockHash, BlockHeader, BlockNumber, CasmHash, ClassCommitmentLeafHash, ClassHash,
+    ContractAddress, ContractNonce, SierraHash, StateUpdate, StorageAddress, StorageValue,
+    TransactionHash,
+};
+use stark_hash::Felt;
+use starknet_gateway_types::reply::transaction as gateway;
+
+use crate::{BlockFeeData, BlockId, EventFilter, KeyFilter, PageOfEvents, StorageChange};
+
+#[cfg(feature = "memory-trie-backend")]
+pub(crate) use memory::{TrieStore, TrieTransaction};
+#[cfg(not(feature = "memory-trie-backend"))]
+pub(crate) use sqlite::{TrieStore, TrieTransaction};
+
+/// Identifies one of the state tries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrieTable {
+    Class,
+    Contract,
+    Storage,
+}
+
+/// All non-trie accessors of a storage backend, performed as part of a database
+/// [Transaction](crate::Transaction).
+pub trait StorageBackend:
+    BlockBackend + TransactionBackend + EventBackend + ClassBackend + StateUpdateBackend
+{
+}
+
+impl<T> StorageBackend for T where
+    T: BlockBackend + TransactionBackend + EventBackend + ClassBackend + StateUpdateBackend
+{
+}
+
+pub trait BlockBackend {
+    fn insert_block_header(&self, header: &BlockHeader) -> anyhow::Result<()>;
+
+    fn block_header(&self, block: BlockId) -> anyhow::Result<Option<BlockHeader>>;
+
+    /// Removes the block header, body and state update of the block.
+    fn purge_block(&self, block: BlockNumber) -> anyhow::Result<()>;
+
+    fn block_id(&self, block: BlockId) -> anyhow::Result<Option<(BlockNumber, BlockHash)>>;
+
+    fn block_exists(&self, block: BlockId) -> anyhow::Result<bool>;
+
+    fn block_is_l1_accepted(&self, block: BlockId) -> anyhow::Result<bool>;
+
+    /// Returns the fee related data of the blocks in `from..=to`, in ascending order.
+    fn block_fee_history(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> anyhow::Result<Vec<BlockFeeData>>;
+}
+
+pub trait TransactionBackend {
+    /// Inserts the transactions of the block along with their receipts and events.
+    fn insert_transaction_data(
+        &self,
+        block_hash: BlockHash,
+        block_number: BlockNumber,
+        transaction_data: &[(gateway::Transaction, gateway::Receipt)],
+    ) -> anyhow::Result<()>;
+
+    fn transaction_block_hash(&self, hash: TransactionHash) -> anyhow::Result<Option<BlockHash>>;
+
+    fn transaction(&self, hash: TransactionHash) -> anyhow::Result<Option<gateway::Transaction>>;
+
+    fn transaction_with_receipt(
+        &self,
+        hash: TransactionHash,
+    ) -> anyhow::Result<Option<(gateway::Transaction, gateway::Receipt, BlockHash)>>;
+
+    fn transaction_at_block(
+        &self,
+        block: BlockId,
+        index: usize,
+    ) -> anyhow::Result<Option<gateway::Transaction>>;
+
+    fn transaction_data_for_block(
+        &self,
+        block: BlockId,
+    ) -> anyhow::Result<Option<Vec<(gateway::Transaction, gateway::Receipt)>>>;
+
+    fn transaction_count(&self, block: BlockId) -> anyhow::Result<usize>;
+}
+
+pub trait EventBackend {
+    fn events<K: KeyFilter>(&self, filter: &EventFilter<K>) -> anyhow::Result<PageOfEvents>;
+
+    fn event_count(
+        &self,
+        from_block: Option<BlockNumber>,
+        to_block: Option<BlockNumber>,
+        contract_address: Option<ContractAddress>,
+        keys: &dyn KeyFilter,
+    ) -> anyhow::Result<usize>;
+}
+
+/// Class definitions are stored and returned uncompressed.
+pub trait ClassBackend {
+    fn insert_sierra_class(
+        &self,
+        sierra_hash: &SierraHash,
+        sierra_definition: &[u8],
+        casm_hash: &CasmHash,
+        casm_definition: &[u8],
+        compiler_version: &str,
+    ) -> anyhow::Result<()>;
+
+    fn insert_cairo_class(&self, cairo_hash: ClassHash, definition: &[u8]) -> anyhow::Result<()>;
+
+    fn insert_class_commitment_leaf(
+        &self,
+        leaf: &ClassCommitmentLeafHash,
+        casm_hash: &CasmHash,
+    ) -> anyhow::Result<()>;
+
+    fn class_definitions_exist(&self, classes: &[ClassHash]) -> anyhow::Result<Vec<bool>>;
+
+    fn class_definition(&self, class_hash: ClassHash) -> anyhow::Result<Option<Vec<u8>>>;
+
+    fn class_definition_at(
+        &self,
+        block_id: BlockId,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<Vec<u8>>>;
+
+    fn casm_definition(&self, class_hash: ClassHash) -> anyhow::Result<Option<Vec<u8>>>;
+
+    fn casm_definition_at(
+        &self,
+        block_id: BlockId,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<Vec<u8>>>;
+
+    fn casm_hash(&self, class_hash: ClassHash) -> anyhow::Result<Option<CasmHash>>;
+
+    fn casm_hash_at(
+        &self,
+        block_id: BlockId,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<CasmHash>>;
+}
+
+pub trait StateUpdateBackend {
+    fn insert_state_update(
+        &self,
+        block_number: BlockNumber,
+        state_update: &StateUpdate,
+    ) -> anyhow::Result<()>;
+
+    fn state_update(&self, block: BlockId) -> anyhow::Result<Option<StateUpdate>>;
+
+    fn storage_value(
+        &self,
+        block: BlockId,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+    ) -> anyhow::Result<Option<StorageValue>>;
+
+    /// Returns the values written to the storage slot by the blocks in `from..=to`, in
+    /// ascending block order. At most `limit` values are returned.
+    fn storage_history(
+        &self,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+        from: BlockNumber,
+        to: BlockNumber,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(BlockNumber, StorageValue)>>;
+
+    /// Returns the storage values written to the contract by the blocks in `from..=to`,
+    /// ordered by block and then storage address. At most `limit` changes are returned.
+    fn storage_changes(
+        &self,
+        contract_address: ContractAddress,
+        from: BlockNumber,
+        to: BlockNumber,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<StorageChange>>;
+
+    fn contract_class_hash(
+        &self,
+        block_id: BlockId,
+        contract_address: ContractAddress,
+    ) -> anyhow::Result<Option<ClassHash>>;
+
+    fn contract_nonce(
+        &self,
+        contract_address: ContractAddress,
+        block_id: BlockId,
+    ) -> anyhow::Result<Option<ContractNonce>>;
+
+    fn contract_exists(
+        &self,
+        contract_address: ContractAddress,
+        block_id: BlockId,
+    ) -> anyhow::Result<bool>;
+}
+
+/// The trie node operations of a storage backend, performed as part of a database
+/// [Transaction](crate::Transaction).
+///
+/// Each node is stored along with its reference count, which is maintained by the trie
+/// insertion and removal logic of [Transaction](crate::Transaction).
+pub trait TrieBackend {
+    /// Returns the node and its reference count. Nodes inserted before reference counting
+    /// was introduced have no reference count.
+    fn node(&self, trie: TrieTable, hash: &Felt)
+        -> anyhow::Result<Option<(TrieNode, Option<u64>)>>;
+
+    /// Inserts the node with a reference count of one, unless it already exists. Returns
+    /// true if the node was inserted.
+    fn insert_node(&self, trie: TrieTable, hash: &Felt, node: &TrieNode) -> anyhow::Result<bool>;
+
+    /// Increments the reference count of the node.
+    fn reference_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()>;
+
+    /// Decrements the reference count of the node.
+    fn release_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()>;
+
+    fn delete_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()>;
+
+    /// Returns the number of nodes in the trie, and how many of those have a reference count
+    /// of zero.
+    fn node_counts(&self, trie: TrieTable) -> anyhow::Result<(u64, u64)>;
+}
@@ -0,0 +1,228 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use anyhow::Context;
+use pathfinder_common::trie::TrieNode;
+use stark_hash::Felt;
+
+use super::{TrieBackend, TrieTable};
+
+type Key = (TrieTable, Felt);
+
+/// Keeps the trie nodes, and their reference counts, in memory. The store is shared by all
+/// connections created from the same [StorageManager](crate::StorageManager).
+#[derive(Clone, Default)]
+pub(crate) struct TrieStore(Arc<RwLock<HashMap<Key, (TrieNode, u64)>>>);
+
+impl TrieStore {
+    /// The trie nodes of existing blocks were not stored in this process, so only a database
+    /// without any blocks can be used.
+    pub(crate) fn check_database(connection: &rusqlite::Connection) -> anyhow::Result<()> {
+        let has_blocks: bool = connection
+            .query_row("SELECT EXISTS(SELECT 1 FROM block_headers)", [], |row| {
+                row.get(0)
+            })
+            .context("Checking for existing blocks")?;
+        anyhow::ensure!(
+            !has_blocks,
+            "The database already contains blocks, but the memory trie backend does not persist trie nodes. Use a new database, or build pathfinder without the memory-trie-backend feature."
+        );
+        Ok(())
+    }
+
+    pub(crate) fn begin(&self) -> TrieTransaction {
+        TrieTransaction {
+            store: self.clone(),
+            changes: Default::default(),
+        }
+    }
+}
+
+/// Buffers the node changes of a transaction, which are only applied to the [TrieStore] once
+/// the transaction is committed. Dropping it discards the changes.
+#[derive(Default)]
+pub(crate) struct TrieTransaction {
+    store: TrieStore,
+    /// The changed nodes, where `None` marks a deleted node.
+    changes: RefCell<HashMap<Key, Option<(TrieNode, u64)>>>,
+}
+
+impl TrieTransaction {
+    pub(crate) fn backend<'a>(&'a self, _tx: &'a rusqlite::Transaction<'_>) -> MemoryTries<'a> {
+        MemoryTries(self)
+    }
+
+    pub(crate) fn commit(self) {
+        let mut nodes = self.store.0.write().unwrap();
+        for (key, node) in self.changes.into_inner() {
+            match node {
+                Some(node) => nodes.insert(key, node),
+                None => nodes.remove(&key),
+            };
+        }
+    }
+
+    fn get(&self, key: &Key) -> Option<(TrieNode, u64)> {
+        match self.changes.borrow().get(key) {
+            Some(change) => change.clone(),
+            None => self.store.0.read().unwrap().get(key).cloned(),
+        }
+    }
+
+    fn set_ref_count(&self, key: Key, update: impl FnOnce(u64) -> u64) {
+        if let Some((node, ref_count)) = self.get(&key) {
+            self.changes
+                .borrow_mut()
+                .insert(key, Some((node, update(ref_count))));
+        }
+    }
+}
+
+pub(crate) struct MemoryTries<'a>(&'a TrieTransaction);
+
+impl TrieBackend for MemoryTries<'_> {
+    fn node(
+        &self,
+        trie: TrieTable,
+        hash: &Felt,
+    ) -> anyhow::Result<Option<(TrieNode, Option<u64>)>> {
+        Ok(self
+            .0
+            .get(&(trie, *hash))
+            .map(|(node, ref_count)| (node, Some(ref_count))))
+    }
+
+    fn insert_node(&self, trie: TrieTable, hash: &Felt, node: &TrieNode) -> anyhow::Result<bool> {
+        let key = (trie, *hash);
+        if self.0.get(&key).is_some() {
+            return Ok(false);
+        }
+
+        self.0
+            .changes
+            .borrow_mut()
+            .insert(key, Some((node.clone(), 1)));
+        Ok(true)
+    }
+
+    fn reference_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()> {
+        self.0.set_ref_count((trie, *hash), |count| count + 1);
+        Ok(())
+    }
+
+    fn release_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()> {
+        self.0
+            .set_ref_count((trie, *hash), |count| count.saturating_sub(1));
+        Ok(())
+    }
+
+    fn delete_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()> {
+        self.0.changes.borrow_mut().insert((trie, *hash), None);
+        Ok(())
+    }
+
+    fn node_counts(&self, trie: TrieTable) -> anyhow::Result<(u64, u64)> {
+        let changes = self.0.changes.borrow();
+        let store = self.0.store.0.read().unwrap();
+
+        let unchanged = store
+            .iter()
+            .filter(|(key, _)| !changes.contains_key(key))
+            .map(|(key, (_, ref_count))| (key, ref_count));
+        let changed = changes
+            .iter()
+            .filter_map(|(key, node)| node.as_ref().map(|(_, ref_count)| (key, ref_count)));
+
+        Ok(unchanged
+            .chain(changed)
+            .filter(|(key, _)| key.0 == trie)
+            .fold((0, 0), |(nodes, orphaned), (_, ref_count)| {
+                (nodes + 1, orphaned + u64::from(*ref_count == 0))
+            }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::felt_bytes;
+
+    use super::*;
+
+    fn node() -> TrieNode {
+        TrieNode::Binary {
+            left: felt_bytes!(b"left"),
+            right: felt_bytes!(b"right"),
+        }
+    }
+
+    #[test]
+    fn changes_are_applied_on_commit() {
+        let store = TrieStore::default();
+        let hash = felt_bytes!(b"node");
+
+        let tx = store.begin();
+        let tries = MemoryTries(&tx);
+        assert!(tries.insert_node(TrieTable::Class, &hash, &node()).unwrap());
+        assert!(!tries.insert_node(TrieTable::Class, &hash, &node()).unwrap());
+        tries.reference_node(TrieTable::Class, &hash).unwrap();
+
+        // Other transactions only see committed nodes, and the tries are kept apart.
+        let other = store.begin();
+        assert_eq!(
+            MemoryTries(&other).node(TrieTable::Class, &hash).unwrap(),
+            None
+        );
+        tx.commit();
+        assert_eq!(
+            MemoryTries(&other).node(TrieTable::Class, &hash).unwrap(),
+            Some((node(), Some(2)))
+        );
+        assert_eq!(
+            MemoryTries(&other).node(TrieTable::Storage, &hash).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn database_with_blocks_is_refused() {
+        let connection = rusqlite::Connection::open_in_memory().unwrap();
+        connection
+            .execute("CREATE TABLE block_headers (number INTEGER)", [])
+            .unwrap();
+        TrieStore::check_database(&connection).unwrap();
+
+        connection
+            .execute("INSERT INTO block_headers VALUES (0)", [])
+            .unwrap();
+        TrieStore::check_database(&connection).unwrap_err();
+    }
+
+    #[test]
+    fn dropped_transaction_is_discarded() {
+        let store = TrieStore::default();
+        let hash = felt_bytes!(b"node");
+
+        let tx = store.begin();
+        MemoryTries(&tx)
+            .insert_node(TrieTable::Storage, &hash, &node())
+            .unwrap();
+        tx.commit();
+
+        let tx = store.begin();
+        MemoryTries(&tx)
+            .delete_node(TrieTable::Storage, &hash)
+            .unwrap();
+        assert_eq!(
+            MemoryTries(&tx).node(TrieTable::Storage, &hash).unwrap(),
+            None
+        );
+        drop(tx);
+
+        let tx = store.begin();
+        assert_eq!(
+            MemoryTries(&tx).node(TrieTable::Storage, &hash).unwrap(),
+            Some((node(), Some(1)))
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Context;
+use pathfinder_common::trie::TrieNode;
+use stark_hash::Felt;
+
+use super::{TrieBackend, TrieTable};
+use crate::prelude::*;
+
+/// Stores the trie nodes in the `tree_*` tables of the sqlite database.
+#[derive(Clone, Default)]
+pub(crate) struct TrieStore;
+
+impl TrieStore {
+    /// Any database can be used, since the trie nodes are stored in it.
+    pub(crate) fn check_database(_connection: &rusqlite::Connection) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    pub(crate) fn begin(&self) -> TrieTransaction {
+        TrieTransaction
+    }
+}
+
+/// Trie node changes are part of the sqlite transaction, so there is nothing to track here.
+#[derive(Default)]
+pub(crate) struct TrieTransaction;
+
+impl TrieTransaction {
+    pub(crate) fn backend<'a>(&'a self, tx: &'a rusqlite::Transaction<'_>) -> SqliteTries<'a> {
+        SqliteTries(tx)
+    }
+
+    pub(crate) fn commit(self) {}
+}
+
+pub(crate) struct SqliteTries<'a>(&'a rusqlite::Connection);
+
+struct Queries {
+    select: &'static str,
+    insert: &'static str,
+    reference: &'static str,
+    release: &'static str,
+    delete: &'static str,
+    count: &'static str,
+}
+
+macro_rules! queries {
+    ($table: literal) => {
+        Queries {
+            select: concat!("SELECT data, ref_count FROM ", $table, " WHERE hash = ?"),
+            insert: concat!(
+                "INSERT OR IGNORE INTO ",
+                $table,
+                " (hash, data, ref_count) VALUES (?, ?, 1)"
+            ),
+            reference: concat!(
+                "UPDATE ",
+                $table,
+                " SET ref_count = ref_count + 1 WHERE hash = ?"
+            ),
+            release: concat!(
+                "UPDATE ",
+                $table,
+                " SET ref_count = ref_count - 1 WHERE hash = ?"
+            ),
+            delete: concat!("DELETE FROM ", $table, " WHERE hash = ?"),
+            count: concat!(
+                "SELECT COUNT(*), COALESCE(SUM(ref_count = 0), 0) FROM ",
+                $table
+            ),
+        }
+    };
+}
+
+const CLASS_QUERIES: Queries = queries!("tree_class");
+const CONTRACT_QUERIES: Queries = queries!("tree_contracts");
+const STORAGE_QUERIES: Queries = queries!("tree_global");
+
+fn queries(trie: TrieTable) -> &'static Queries {
+    match trie {
+        TrieTable::Class => &CLASS_QUERIES,
+        TrieTable::Contract => &CONTRACT_QUERIES,
+        TrieTable::Storage => &STORAGE_QUERIES,
+    }
+}
+
+impl TrieBackend for SqliteTries<'_> {
+    fn node(
+        &self,
+        trie: TrieTable,
+        hash: &Felt,
+    ) -> anyhow::Result<Option<(TrieNode, Option<u64>)>> {
+        // We rely on sqlite caching the statement here. Storing the statement would be nice,
+        // however that leads to &mut requirements or interior mutable work-arounds.
+        let mut stmt = self
+            .0
+            .prepare_cached(queries(trie).select)
+            .context("Creating select statement")?;
+
+        stmt.query_row(params![&hash.as_be_bytes().as_slice()], |row| {
+            let node = row.get_trie_node(0)?;
+            let ref_count = row.get_optional_i64(1)?;
+            Ok((node, ref_count.map(|count| count as u64)))
+        })
+        .optional()
+        .context("Querying node")
+    }
+
+    fn insert_node(&self, trie: TrieTable, hash: &Felt, node: &TrieNode) -> anyhow::Result<bool> {
+        let inserted = self
+            .0
+            .prepare_cached(queries(trie).insert)
+            .context("Creating insert statement")?
+            .execute(params![&hash.as_be_bytes().as_slice(), node])
+            .context("Inserting node")?;
+
+        Ok(inserted > 0)
+    }
+
+    fn reference_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()> {
+        self.0
+            .prepare_cached(queries(trie).reference)
+            .context("Creating reference statement")?
+            .execute(params![&hash.as_be_bytes().as_slice()])
+            .context("Referencing node")?;
+
+        Ok(())
+    }
+
+    fn release_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()> {
+        self.0
+            .prepare_cached(queries(trie).release)
+            .context("Creating release statement")?
+            .execute(params![&hash.as_be_bytes().as_slice()])
+            .context("Releasing node")?;
+
+        Ok(())
+    }
+
+    fn delete_node(&self, trie: TrieTable, hash: &Felt) -> anyhow::Result<()> {
+        self.0
+            .prepare_cached(queries(trie).delete)
+            .context("Creating delete statement")?
+            .execute(params![&hash.as_be_bytes().as_slice()])
+            .context("Deleting node")?;
+
+        Ok(())
+    }
+
+    fn node_counts(&self, trie: TrieTable) -> anyhow::Result<(u64, u64)> {
+        self.0
+            .query_row(queries(trie).count, [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .context("Counting nodes")
+    }
+}
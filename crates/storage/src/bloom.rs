@@ -0,0 +1,146 @@
+//! Per-block bloom filters over the addresses and keys of emitted events, which allow
+//! event queries to skip blocks which cannot contain matching events.
+
+use pathfinder_common::event::Event;
+use pathfinder_common::{ContractAddress, EventKey};
+use sha3::{Digest, Keccak256};
+use stark_hash::Felt;
+
+/// Yields a false positive rate of roughly 0.06% with [HASH_COUNT] hashes.
+const BITS_PER_ITEM: usize = 16;
+/// The number of bits set per item, each taken from a different part of the item's hash.
+const HASH_COUNT: usize = 8;
+
+/// A bloom filter over the `from_address` and the keys, by position, of a block's events.
+///
+/// The filter is sized according to the number of items it contains, and a filter without any
+/// items contains nothing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BloomFilter(Vec<u8>);
+
+impl BloomFilter {
+    /// Creates a filter containing the addresses and keys of `events`.
+    pub fn for_events<'a, I>(events: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Event>,
+        I::IntoIter: Clone,
+    {
+        let events = events.into_iter();
+        let items: usize = events.clone().map(|event| 1 + event.keys.len()).sum();
+
+        let mut filter = Self(vec![0u8; (items * BITS_PER_ITEM + 7) / 8]);
+        for event in events {
+            filter.insert(Item::Address(&event.from_address));
+            for (index, key) in event.keys.iter().enumerate() {
+                filter.insert(Item::Key(index, key));
+            }
+        }
+
+        filter
+    }
+
+    /// Restores a filter from the bytes returned by [BloomFilter::as_bytes].
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns `false` if no event was emitted by `address`.
+    pub fn may_contain_address(&self, address: &ContractAddress) -> bool {
+        self.contains(Item::Address(address))
+    }
+
+    /// Returns `false` if no event has `key` at position `index`.
+    pub fn may_contain_key(&self, index: usize, key: &EventKey) -> bool {
+        self.contains(Item::Key(index, key))
+    }
+
+    fn insert(&mut self, item: Item<'_>) {
+        let bits = self.0.len() * 8;
+        for bit in item.bits(bits) {
+            self.0[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    fn contains(&self, item: Item<'_>) -> bool {
+        let bits = self.0.len() * 8;
+        if bits == 0 {
+            return false;
+        }
+        item.bits(bits)
+            .all(|bit| self.0[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+}
+
+enum Item<'a> {
+    Address(&'a ContractAddress),
+    Key(usize, &'a EventKey),
+}
+
+impl Item<'_> {
+    /// The indices of the [HASH_COUNT] bits representing this item in a filter of `bits` bits.
+    fn bits(&self, bits: usize) -> impl Iterator<Item = usize> {
+        let (tag, index, value): (u8, usize, &Felt) = match self {
+            Item::Address(address) => (0, 0, &address.0),
+            Item::Key(index, key) => (1, *index, &key.0),
+        };
+
+        let mut hasher = Keccak256::new();
+        hasher.update([tag]);
+        hasher.update((index as u64).to_be_bytes());
+        hasher.update(value.as_be_bytes());
+        let hash = hasher.finalize();
+
+        (0..HASH_COUNT).map(move |i| {
+            let word = u32::from_be_bytes(hash[i * 4..i * 4 + 4].try_into().unwrap());
+            word as usize % bits
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pathfinder_common::macro_prelude::*;
+
+    #[test]
+    fn contains_inserted_items() {
+        let events = vec![
+            Event {
+                data: vec![],
+                from_address: contract_address!("0x1"),
+                keys: vec![event_key!("0xa"), event_key!("0xb")],
+            },
+            Event {
+                data: vec![],
+                from_address: contract_address!("0x2"),
+                keys: vec![],
+            },
+        ];
+
+        let filter = BloomFilter::for_events(&events);
+        let filter = BloomFilter::from_bytes(filter.as_bytes().to_vec());
+
+        assert!(filter.may_contain_address(&contract_address!("0x1")));
+        assert!(filter.may_contain_address(&contract_address!("0x2")));
+        assert!(filter.may_contain_key(0, &event_key!("0xa")));
+        assert!(filter.may_contain_key(1, &event_key!("0xb")));
+
+        assert!(!filter.may_contain_address(&contract_address!("0x3")));
+        // Keys are positional.
+        assert!(!filter.may_contain_key(1, &event_key!("0xa")));
+        assert!(!filter.may_contain_key(0, &event_key!("0xb")));
+    }
+
+    #[test]
+    fn empty_filter_contains_nothing() {
+        let filter = BloomFilter::for_events(&[]);
+
+        assert!(filter.as_bytes().is_empty());
+        assert!(!filter.may_contain_address(&contract_address!("0x1")));
+        assert!(!filter.may_contain_key(0, &event_key!("0x1")));
+    }
+}
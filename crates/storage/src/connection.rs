@@ -1,12 +1,18 @@
 use std::collections::HashMap;
+use std::num::NonZeroU64;
 
 mod block;
 mod class;
 mod ethereum;
 mod event;
+mod event_index;
+mod l1_message;
+mod options;
 mod reference;
+mod sqlite;
 mod state;
 mod state_update;
+mod stats;
 mod transaction;
 mod trie;
 
@@ -16,9 +22,21 @@ pub use rusqlite::TransactionBehavior;
 pub use event::KEY_FILTER_LIMIT as EVENT_KEY_FILTER_LIMIT;
 pub use event::*;
 
+pub use event_index::EventIndexPolicy;
+
+pub use l1_message::L1ToL2MessageRecord;
+
+pub use block::BlockFeeData;
+
+pub use state_update::StorageChange;
+
+pub use stats::{BlockRangeStats, DatabaseStats, TableStats, TrieStats};
+
 pub use transaction::TransactionStatus;
 
-pub use trie::{ClassTrieReader, ContractTrieReader, StorageTrieReader};
+pub use trie::{
+    ClassTrieReader, ContractTrieReader, StorageTrieReader, TriePruneMode, TrieRootChange,
+};
 
 use pathfinder_common::trie::TrieNode;
 use pathfinder_common::{
@@ -26,24 +44,26 @@ use pathfinder_common::{
     ClassHash, ContractAddress, ContractNonce, ContractRoot, ContractStateHash, SierraHash,
     StateUpdate, StorageAddress, StorageCommitment, StorageValue, TransactionHash,
 };
-use pathfinder_ethereum::EthereumStateUpdate;
+use pathfinder_ethereum::{EthereumStateUpdate, L1ToL2MessageLog};
+use primitive_types::H256;
 use stark_hash::Felt;
 use starknet_gateway_types::reply::transaction as gateway;
 
+use crate::backend::{StorageBackend, TrieBackend, TrieStore, TrieTable, TrieTransaction};
 use crate::BlockId;
 
 type PooledConnection = r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>;
 
-pub struct Connection(PooledConnection);
+pub struct Connection(PooledConnection, TrieStore);
 
 impl Connection {
-    pub(crate) fn from_inner(inner: PooledConnection) -> Self {
-        Self(inner)
+    pub(crate) fn from_inner(inner: PooledConnection, tries: TrieStore) -> Self {
+        Self(inner, tries)
     }
 
     pub fn transaction(&mut self) -> anyhow::Result<Transaction<'_>> {
         let tx = self.0.transaction()?;
-        Ok(Transaction(tx))
+        Ok(Transaction(tx, self.1.begin()))
     }
 
     pub fn transaction_with_behavior(
@@ -51,21 +71,16 @@ impl Connection {
         behavior: TransactionBehavior,
     ) -> anyhow::Result<Transaction<'_>> {
         let tx = self.0.transaction_with_behavior(behavior)?;
-        Ok(Transaction(tx))
+        Ok(Transaction(tx, self.1.begin()))
     }
 }
 
-pub struct Transaction<'inner>(rusqlite::Transaction<'inner>);
+pub struct Transaction<'inner>(rusqlite::Transaction<'inner>, TrieTransaction);
 
 impl<'inner> Transaction<'inner> {
     // The implementations here are intentionally kept as simple wrappers. This lets the real implementations
     // be kept in separate files with more reasonable LOC counts and easier test oversight.
 
-    #[cfg(test)]
-    pub(crate) fn from_inner(tx: rusqlite::Transaction<'inner>) -> Self {
-        Self(tx)
-    }
-
     // TODO: get rid of this in favor of storing contract roots in a separate table similar to
     //       nonces and storage updates. This would remove the last reliance on navigating the
     //       global trie -- since this is required to retrieve the state hash, and thereby the
@@ -88,30 +103,39 @@ impl<'inner> Transaction<'inner> {
     }
 
     pub fn insert_block_header(&self, header: &BlockHeader) -> anyhow::Result<()> {
-        block::insert_block_header(self, header)
+        self.backend().insert_block_header(header)
     }
 
     pub fn block_header(&self, block: BlockId) -> anyhow::Result<Option<BlockHeader>> {
-        block::block_header(self, block)
+        self.backend().block_header(block)
     }
 
     /// Removes all data related to this block.
     ///
     /// This includes block header, block body and state update information.
     pub fn purge_block(&self, block: BlockNumber) -> anyhow::Result<()> {
-        block::purge_block(self, block)
+        self.backend().purge_block(block)
     }
 
     pub fn block_id(&self, block: BlockId) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
-        block::block_id(self, block)
+        self.backend().block_id(block)
     }
 
     pub fn block_exists(&self, block: BlockId) -> anyhow::Result<bool> {
-        block::block_exists(self, block)
+        self.backend().block_exists(block)
     }
 
     pub fn block_is_l1_accepted(&self, block: BlockId) -> anyhow::Result<bool> {
-        block::block_is_l1_accepted(self, block)
+        self.backend().block_is_l1_accepted(block)
+    }
+
+    /// Returns the fee related data of the blocks in the inclusive range `from..=to`, in ascending order.
+    pub fn block_fee_history(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> anyhow::Result<Vec<BlockFeeData>> {
+        self.backend().block_fee_history(from, to)
     }
 
     pub fn update_l1_l2_pointer(&self, block: Option<BlockNumber>) -> anyhow::Result<()> {
@@ -137,6 +161,44 @@ impl<'inner> Transaction<'inner> {
         ethereum::latest_l1_state(self)
     }
 
+    /// Returns the first and last Ethereum block whose state update logs are stored in the L1
+    /// state, if any.
+    pub fn l1_state_scanned(&self) -> anyhow::Result<Option<(u64, u64)>> {
+        ethereum::l1_state_scanned(self)
+    }
+
+    pub fn set_l1_state_scanned(&self, from: u64, until: u64) -> anyhow::Result<()> {
+        ethereum::set_l1_state_scanned(self, from, until)
+    }
+
+    /// Returns the latest block whose hash matches the L1 state, if any.
+    pub fn latest_l1_l2_match(&self) -> anyhow::Result<Option<BlockNumber>> {
+        ethereum::latest_l1_l2_match(self)
+    }
+
+    /// Returns the last Ethereum block whose L1 to L2 message logs are stored, if any.
+    pub fn l1_messages_synced_until(&self) -> anyhow::Result<Option<u64>> {
+        l1_message::l1_messages_synced_until(self)
+    }
+
+    /// Stores the L1 to L2 message logs of the core contract, up to and including Ethereum block
+    /// `synced_until`.
+    pub fn insert_l1_to_l2_message_logs(
+        &self,
+        logs: &[L1ToL2MessageLog],
+        synced_until: u64,
+    ) -> anyhow::Result<()> {
+        l1_message::insert_l1_to_l2_message_logs(self, logs, synced_until)
+    }
+
+    /// Returns the L1 to L2 messages sent by the L1 transaction.
+    pub fn l1_to_l2_messages(
+        &self,
+        l1_transaction_hash: H256,
+    ) -> anyhow::Result<Vec<L1ToL2MessageRecord>> {
+        l1_message::l1_to_l2_messages(self, l1_transaction_hash)
+    }
+
     /// Inserts the transaction, receipt and event data.
     pub fn insert_transaction_data(
         &self,
@@ -144,28 +206,29 @@ impl<'inner> Transaction<'inner> {
         block_number: BlockNumber,
         transaction_data: &[(gateway::Transaction, gateway::Receipt)],
     ) -> anyhow::Result<()> {
-        transaction::insert_transactions(self, block_hash, block_number, transaction_data)
+        self.backend()
+            .insert_transaction_data(block_hash, block_number, transaction_data)
     }
 
     pub fn transaction_block_hash(
         &self,
         hash: TransactionHash,
     ) -> anyhow::Result<Option<BlockHash>> {
-        transaction::transaction_block_hash(self, hash)
+        self.backend().transaction_block_hash(hash)
     }
 
     pub fn transaction(
         &self,
         hash: TransactionHash,
     ) -> anyhow::Result<Option<gateway::Transaction>> {
-        transaction::transaction(self, hash)
+        self.backend().transaction(hash)
     }
 
     pub fn transaction_with_receipt(
         &self,
         hash: TransactionHash,
     ) -> anyhow::Result<Option<(gateway::Transaction, gateway::Receipt, BlockHash)>> {
-        transaction::transaction_with_receipt(self, hash)
+        self.backend().transaction_with_receipt(hash)
     }
 
     pub fn transaction_at_block(
@@ -173,22 +236,22 @@ impl<'inner> Transaction<'inner> {
         block: BlockId,
         index: usize,
     ) -> anyhow::Result<Option<gateway::Transaction>> {
-        transaction::transaction_at_block(self, block, index)
+        self.backend().transaction_at_block(block, index)
     }
 
     pub fn transaction_data_for_block(
         &self,
         block: BlockId,
     ) -> anyhow::Result<Option<Vec<(gateway::Transaction, gateway::Receipt)>>> {
-        transaction::transaction_data_for_block(self, block)
+        self.backend().transaction_data_for_block(block)
     }
 
     pub fn transaction_count(&self, block: BlockId) -> anyhow::Result<usize> {
-        transaction::transaction_count(self, block)
+        self.backend().transaction_count(block)
     }
 
     pub fn events(&self, filter: &EventFilter<impl KeyFilter>) -> anyhow::Result<PageOfEvents> {
-        event::get_events(self, filter)
+        self.backend().events(filter)
     }
 
     pub fn event_count(
@@ -198,7 +261,30 @@ impl<'inner> Transaction<'inner> {
         contract_address: Option<ContractAddress>,
         keys: &dyn KeyFilter,
     ) -> anyhow::Result<usize> {
-        event::event_count(self, from_block, to_block, contract_address, keys)
+        self.backend()
+            .event_count(from_block, to_block, contract_address, keys)
+    }
+
+    pub fn event_index_policy(&self) -> anyhow::Result<EventIndexPolicy> {
+        event_index::event_index_policy(self)
+    }
+
+    /// Changes which events are indexed. If this differs from the current policy, all existing
+    /// blocks have to be re-indexed using [Transaction::reindex_events].
+    pub fn set_event_index_policy(&self, policy: &EventIndexPolicy) -> anyhow::Result<()> {
+        event_index::set_event_index_policy(self, policy)
+    }
+
+    /// Returns the last block which still has to be re-indexed after the event index policy
+    /// changed, if any.
+    pub fn event_reindex_until(&self) -> anyhow::Result<Option<BlockNumber>> {
+        event_index::event_reindex_until(self)
+    }
+
+    /// Re-indexes the events of up to `max_blocks` blocks, in descending order. Returns the last
+    /// block which still has to be re-indexed, or `None` once all blocks are done.
+    pub fn reindex_events(&self, max_blocks: u64) -> anyhow::Result<Option<BlockNumber>> {
+        event_index::reindex_events(self, max_blocks)
     }
 
     pub fn insert_sierra_class(
@@ -209,8 +295,7 @@ impl<'inner> Transaction<'inner> {
         casm_definition: &[u8],
         compiler_version: &str,
     ) -> anyhow::Result<()> {
-        class::insert_sierra_class(
-            self,
+        self.backend().insert_sierra_class(
             sierra_hash,
             sierra_definition,
             casm_hash,
@@ -225,7 +310,7 @@ impl<'inner> Transaction<'inner> {
         cairo_hash: ClassHash,
         definition: &[u8],
     ) -> anyhow::Result<()> {
-        class::insert_cairo_class(self, cairo_hash, definition)
+        self.backend().insert_cairo_class(cairo_hash, definition)
     }
 
     pub fn insert_class_commitment_leaf(
@@ -233,19 +318,19 @@ impl<'inner> Transaction<'inner> {
         leaf: &ClassCommitmentLeafHash,
         casm_hash: &CasmHash,
     ) -> anyhow::Result<()> {
-        class::insert_class_commitment_leaf(self, leaf, casm_hash)
+        self.backend().insert_class_commitment_leaf(leaf, casm_hash)
     }
 
     /// Returns whether the Sierra or Cairo class definition exists in the database.
     ///
     /// Note that this does not indicate that the class is actually declared -- only that we stored it.
     pub fn class_definitions_exist(&self, classes: &[ClassHash]) -> anyhow::Result<Vec<bool>> {
-        class::classes_exist(self, classes)
+        self.backend().class_definitions_exist(classes)
     }
 
     /// Returns the uncompressed class definition.
     pub fn class_definition(&self, class_hash: ClassHash) -> anyhow::Result<Option<Vec<u8>>> {
-        class::class_definition(self, class_hash)
+        self.backend().class_definition(class_hash)
     }
 
     /// Returns the uncompressed class definition if it has been declared at `block_id`.
@@ -254,12 +339,12 @@ impl<'inner> Transaction<'inner> {
         block_id: BlockId,
         class_hash: ClassHash,
     ) -> anyhow::Result<Option<Vec<u8>>> {
-        class::class_definition_at(self, block_id, class_hash)
+        self.backend().class_definition_at(block_id, class_hash)
     }
 
     /// Returns the uncompressed compiled class definition.
     pub fn casm_definition(&self, class_hash: ClassHash) -> anyhow::Result<Option<Vec<u8>>> {
-        class::casm_definition(self, class_hash)
+        self.backend().casm_definition(class_hash)
     }
 
     /// Returns the uncompressed compiled class definition if it has been declared at `block_id`.
@@ -268,7 +353,7 @@ impl<'inner> Transaction<'inner> {
         block_id: BlockId,
         class_hash: ClassHash,
     ) -> anyhow::Result<Option<Vec<u8>>> {
-        class::casm_definition_at(self, block_id, class_hash)
+        self.backend().casm_definition_at(block_id, class_hash)
     }
 
     pub fn contract_class_hash(
@@ -276,12 +361,13 @@ impl<'inner> Transaction<'inner> {
         block_id: BlockId,
         contract_address: ContractAddress,
     ) -> anyhow::Result<Option<ClassHash>> {
-        state_update::contract_class_hash(self, block_id, contract_address)
+        self.backend()
+            .contract_class_hash(block_id, contract_address)
     }
 
     /// Returns the compiled class hash for a class.
     pub fn casm_hash(&self, class_hash: ClassHash) -> anyhow::Result<Option<CasmHash>> {
-        class::casm_hash(self, class_hash)
+        self.backend().casm_hash(class_hash)
     }
 
     /// Returns the compiled class hash for a class if it has been declared at `block_id`.
@@ -290,7 +376,7 @@ impl<'inner> Transaction<'inner> {
         block_id: BlockId,
         class_hash: ClassHash,
     ) -> anyhow::Result<Option<CasmHash>> {
-        class::casm_hash_at(self, block_id, class_hash)
+        self.backend().casm_hash_at(block_id, class_hash)
     }
 
     /// Stores the class trie information using reference counting.
@@ -299,7 +385,7 @@ impl<'inner> Transaction<'inner> {
         root: ClassCommitment,
         nodes: &HashMap<Felt, TrieNode>,
     ) -> anyhow::Result<usize> {
-        trie::insert_class_trie(&self.0, root.0, nodes)
+        trie::insert_trie(self.trie_backend(), TrieTable::Class, root.0, nodes)
     }
 
     /// Stores a single contract's storage trie information using reference counting.
@@ -308,7 +394,7 @@ impl<'inner> Transaction<'inner> {
         root: ContractRoot,
         nodes: &HashMap<Felt, TrieNode>,
     ) -> anyhow::Result<usize> {
-        trie::insert_contract_trie(&self.0, root.0, nodes)
+        trie::insert_trie(self.trie_backend(), TrieTable::Contract, root.0, nodes)
     }
 
     /// Stores the global starknet storage trie information using reference counting.
@@ -317,7 +403,7 @@ impl<'inner> Transaction<'inner> {
         root: StorageCommitment,
         nodes: &HashMap<Felt, TrieNode>,
     ) -> anyhow::Result<usize> {
-        trie::insert_storage_trie(&self.0, root.0, nodes)
+        trie::insert_trie(self.trie_backend(), TrieTable::Storage, root.0, nodes)
     }
 
     pub fn class_trie_reader(&self) -> ClassTrieReader<'_> {
@@ -332,16 +418,48 @@ impl<'inner> Transaction<'inner> {
         ContractTrieReader::new(self)
     }
 
+    /// Returns how much history of the state tries is kept by this database.
+    pub fn trie_prune_mode(&self) -> anyhow::Result<TriePruneMode> {
+        trie::trie_prune_mode(self)
+    }
+
+    /// Configures how much history of the state tries is kept. Pruning can only be enabled
+    /// on a new database, and cannot be disabled again once enabled.
+    pub fn set_trie_prune_mode(&self, mode: TriePruneMode) -> anyhow::Result<()> {
+        trie::set_trie_prune_mode(self, mode)
+    }
+
+    /// Records a trie root change at `block`, which is required to prune the old root later on.
+    pub fn insert_trie_root_change(
+        &self,
+        block: BlockNumber,
+        change: TrieRootChange,
+    ) -> anyhow::Result<()> {
+        trie::insert_trie_root_change(self, block, change)
+    }
+
+    /// Removes the trie nodes which are no longer reachable from the tries kept for `head`.
+    pub fn prune_tries(&self, head: BlockNumber) -> anyhow::Result<()> {
+        trie::prune_tries(self, head)
+    }
+
+    /// Returns true if the state tries of this block have been pruned, in which case
+    /// its state can no longer be proven or executed against.
+    pub fn block_pruned(&self, block: BlockNumber) -> anyhow::Result<bool> {
+        trie::block_pruned(self, block)
+    }
+
     pub fn insert_state_update(
         &self,
         block_number: BlockNumber,
         state_update: &StateUpdate,
     ) -> anyhow::Result<()> {
-        state_update::insert_state_update(self, block_number, state_update)
+        self.backend()
+            .insert_state_update(block_number, state_update)
     }
 
     pub fn state_update(&self, block: BlockId) -> anyhow::Result<Option<StateUpdate>> {
-        state_update::state_update(self, block)
+        self.backend().state_update(block)
     }
 
     pub fn storage_value(
@@ -350,7 +468,35 @@ impl<'inner> Transaction<'inner> {
         contract_address: ContractAddress,
         key: StorageAddress,
     ) -> anyhow::Result<Option<StorageValue>> {
-        state_update::storage_value(self, block, contract_address, key)
+        self.backend().storage_value(block, contract_address, key)
+    }
+
+    /// Returns the values written to storage slot `key` of a contract by the blocks in the
+    /// inclusive range `from..=to`, in ascending block order. At most `limit` values are returned.
+    pub fn storage_history(
+        &self,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+        from: BlockNumber,
+        to: BlockNumber,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(BlockNumber, StorageValue)>> {
+        self.backend()
+            .storage_history(contract_address, key, from, to, limit)
+    }
+
+    /// Returns the storage values written to a contract by the blocks in the inclusive range
+    /// `from..=to`, ordered by block and then storage address. At most `limit` changes are
+    /// returned, if given.
+    pub fn storage_changes(
+        &self,
+        contract_address: ContractAddress,
+        from: BlockNumber,
+        to: BlockNumber,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<StorageChange>> {
+        self.backend()
+            .storage_changes(contract_address, from, to, limit)
     }
 
     pub fn contract_nonce(
@@ -358,7 +504,7 @@ impl<'inner> Transaction<'inner> {
         contract_address: ContractAddress,
         block_id: BlockId,
     ) -> anyhow::Result<Option<ContractNonce>> {
-        state_update::contract_nonce(self, contract_address, block_id)
+        self.backend().contract_nonce(contract_address, block_id)
     }
 
     pub fn contract_exists(
@@ -366,14 +512,34 @@ impl<'inner> Transaction<'inner> {
         contract_address: ContractAddress,
         block_id: BlockId,
     ) -> anyhow::Result<bool> {
-        state_update::contract_exists(self, contract_address, block_id)
+        self.backend().contract_exists(contract_address, block_id)
     }
 
     pub(self) fn inner(&self) -> &rusqlite::Transaction<'_> {
         &self.0
     }
 
+    /// The backend storing all but the trie nodes, see [backend](crate::backend).
+    pub(self) fn backend(&self) -> impl StorageBackend + '_ {
+        sqlite::SqliteBackend(self)
+    }
+
+    /// The backend storing the trie nodes, see [backend](crate::backend).
+    pub(self) fn trie_backend(&self) -> impl TrieBackend + '_ {
+        self.1.backend(&self.0)
+    }
+
+    /// Returns the size of each table, the number of nodes of each trie, and the data added by
+    /// every `block_range_size` blocks. This reads the entire database, and takes a while for
+    /// large databases.
+    pub fn database_stats(&self, block_range_size: NonZeroU64) -> anyhow::Result<DatabaseStats> {
+        stats::database_stats(self, block_range_size)
+    }
+
     pub fn commit(self) -> anyhow::Result<()> {
-        Ok(self.0.commit()?)
+        self.0.commit()?;
+        // The trie nodes are only committed once the data referencing them is.
+        self.1.commit();
+        Ok(())
     }
 }
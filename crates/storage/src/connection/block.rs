@@ -1,5 +1,7 @@
 use anyhow::Context;
-use pathfinder_common::{BlockHash, BlockHeader, BlockNumber, StarknetVersion};
+use pathfinder_common::{
+    BlockHash, BlockHeader, BlockNumber, BlockTimestamp, GasPrice, StarknetVersion,
+};
 
 use crate::{prelude::*, BlockId};
 
@@ -110,6 +112,8 @@ pub(super) fn purge_block(tx: &Transaction<'_>, block: BlockNumber) -> anyhow::R
         )
         .context("Deleting block from block_headers table")?;
 
+    super::trie::revert_trie_root_changes(tx, block).context("Reverting trie root changes")?;
+
     Ok(())
 }
 
@@ -262,6 +266,45 @@ pub(super) fn block_is_l1_accepted(tx: &Transaction<'_>, block: BlockId) -> anyh
     Ok(block_number <= l1_l2)
 }
 
+/// The fee related data of a single block, as returned by [Transaction::block_fee_history].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockFeeData {
+    pub number: BlockNumber,
+    pub timestamp: BlockTimestamp,
+    pub gas_price: GasPrice,
+    pub transaction_count: usize,
+    pub event_count: usize,
+}
+
+pub(super) fn block_fee_history(
+    tx: &Transaction<'_>,
+    from: BlockNumber,
+    to: BlockNumber,
+) -> anyhow::Result<Vec<BlockFeeData>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            "SELECT number, timestamp, gas_price, transaction_count, event_count FROM block_headers
+            WHERE number BETWEEN ? AND ? ORDER BY number ASC",
+        )
+        .context("Preparing statement")?;
+
+    let rows = stmt
+        .query_map(params![&from, &to], |row| {
+            Ok(BlockFeeData {
+                number: row.get_block_number(0)?,
+                timestamp: row.get_timestamp(1)?,
+                gas_price: row.get_gas_price(2)?,
+                transaction_count: row.get(3)?,
+                event_count: row.get(4)?,
+            })
+        })
+        .context("Querying block fee history")?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Iterating over rows")
+}
+
 #[cfg(test)]
 mod tests {
     use pathfinder_common::macro_prelude::*;
@@ -409,6 +452,28 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn fee_history() {
+        let (mut connection, headers) = setup();
+        let tx = connection.transaction().unwrap();
+
+        let result = tx
+            .block_fee_history(headers[1].number, headers[2].number + 10)
+            .unwrap();
+        let expected = headers[1..]
+            .iter()
+            .map(|header| BlockFeeData {
+                number: header.number,
+                timestamp: header.timestamp,
+                gas_price: header.gas_price,
+                transaction_count: header.transaction_count,
+                event_count: header.event_count,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn purge_block() {
         let (mut connection, headers) = setup();
@@ -1,8 +1,14 @@
+use anyhow::Context;
 use pathfinder_common::BlockNumber;
 use pathfinder_ethereum::EthereumStateUpdate;
 
 use crate::prelude::*;
 
+/// The `storage_options` entries holding the first and last Ethereum block whose state update
+/// logs are stored in `l1_state`.
+const SCANNED_FROM_OPTION: &str = "l1_state_scanned_from";
+const SCANNED_UNTIL_OPTION: &str = "l1_state_scanned_until";
+
 pub(super) fn upsert_l1_state(
     tx: &Transaction<'_>,
     update: &EthereumStateUpdate,
@@ -75,13 +81,49 @@ pub(super) fn latest_l1_state(tx: &Transaction<'_>) -> anyhow::Result<Option<Eth
         .map_err(|e| e.into())
 }
 
+/// Returns the range of Ethereum blocks whose state update logs are stored, as set by
+/// [set_l1_state_scanned].
+pub(super) fn l1_state_scanned(tx: &Transaction<'_>) -> anyhow::Result<Option<(u64, u64)>> {
+    let from = super::options::option(tx, SCANNED_FROM_OPTION)?;
+    let until = super::options::option(tx, SCANNED_UNTIL_OPTION)?;
+
+    Ok(from
+        .zip(until)
+        .map(|(from, until)| (from as u64, until as u64)))
+}
+
+pub(super) fn set_l1_state_scanned(
+    tx: &Transaction<'_>,
+    from: u64,
+    until: u64,
+) -> anyhow::Result<()> {
+    super::options::set_option(tx, SCANNED_FROM_OPTION, Some(from.try_into_sql_int()?))?;
+    super::options::set_option(tx, SCANNED_UNTIL_OPTION, Some(until.try_into_sql_int()?))
+}
+
+/// Returns the latest block whose hash matches the hash attested to on L1.
+pub(super) fn latest_l1_l2_match(tx: &Transaction<'_>) -> anyhow::Result<Option<BlockNumber>> {
+    tx.inner()
+        .query_row(
+            r"SELECT starknet_block_number FROM l1_state
+            JOIN canonical_blocks ON canonical_blocks.number = l1_state.starknet_block_number
+            WHERE canonical_blocks.hash = l1_state.starknet_block_hash
+            ORDER BY starknet_block_number DESC
+            LIMIT 1",
+            [],
+            |row| row.get_block_number(0),
+        )
+        .optional()
+        .context("Querying latest L1/L2 match")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use crate::Storage;
     use pathfinder_common::macro_prelude::*;
-    use pathfinder_common::{BlockHash, StateCommitment};
+    use pathfinder_common::{BlockHash, BlockHeader, StateCommitment};
     use pathfinder_ethereum::EthereumStateUpdate;
     use stark_hash::Felt;
 
@@ -146,6 +188,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn latest_l1_l2_match() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let updates = create_updates();
+        for update in &updates {
+            let header = BlockHeader::builder()
+                .with_number(update.block_number)
+                .finalize_with_hash(update.block_hash);
+            tx.insert_block_header(&header).unwrap();
+        }
+
+        assert_eq!(super::latest_l1_l2_match(&tx).unwrap(), None);
+
+        upsert_l1_state(&tx, &updates[0]).unwrap();
+        upsert_l1_state(&tx, &updates[1]).unwrap();
+        let mismatch = EthereumStateUpdate {
+            block_hash: block_hash!("0x1234"),
+            ..updates[2].clone()
+        };
+        upsert_l1_state(&tx, &mismatch).unwrap();
+
+        assert_eq!(
+            super::latest_l1_l2_match(&tx).unwrap(),
+            Some(updates[1].block_number)
+        );
+    }
+
     #[test]
     fn upsert_overwrites() {
         let storage = Storage::in_memory().unwrap();
@@ -1,5 +1,6 @@
 use crate::params::ToSql;
 use crate::prelude::*;
+use crate::{BloomFilter, EventIndexPolicy};
 
 use anyhow::Context;
 use pathfinder_common::event::Event;
@@ -19,7 +20,16 @@ pub struct EventFilter<K: KeyFilter> {
     pub contract_address: Option<ContractAddress>,
     pub keys: K,
     pub page_size: usize,
-    pub offset: usize,
+    /// The position of the first event to return, as given by [PageOfEvents::continuation].
+    pub start: Option<EventCursor>,
+}
+
+/// The position of an event, ordered by block number, transaction index and event index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EventCursor {
+    pub block_number: BlockNumber,
+    pub transaction_index: usize,
+    pub event_index: usize,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -38,17 +48,22 @@ pub enum EventFilterError {
     PageSizeTooBig(usize),
     #[error("Event query too broad. Reduce the block range or add more keys.")]
     TooManyMatches,
+    #[error("The requested events are not indexed by this node")]
+    NotIndexed,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PageOfEvents {
     pub events: Vec<EmittedEvent>,
-    pub is_last_page: bool,
+    /// The position of the first event of the next page, or [None] if this is the last page.
+    pub continuation: Option<EventCursor>,
 }
 
 pub trait KeyFilter {
     fn count(&self, tx: &Transaction<'_>) -> anyhow::Result<Option<usize>>;
     fn apply(&self, strategy: QueryStrategy) -> Option<KeyFilterResult<'_>>;
+    /// Returns `false` if a block with this [BloomFilter] cannot contain matching events.
+    fn may_match(&self, bloom: &BloomFilter) -> bool;
 }
 
 #[derive(Debug, PartialEq)]
@@ -63,8 +78,9 @@ pub(super) fn insert_events(
     block_number: BlockNumber,
     transaction_hash: TransactionHash,
     events: &[Event],
+    policy: &EventIndexPolicy,
 ) -> anyhow::Result<()> {
-    let mut stmt = tx.inner().prepare(
+    let mut stmt = tx.inner().prepare_cached(
         r"INSERT INTO starknet_events ( block_number,  idx,  transaction_hash,  from_address,  keys,  data)
                                VALUES (:block_number, :idx, :transaction_hash, :from_address, :keys, :data)"
     )?;
@@ -72,7 +88,13 @@ pub(super) fn insert_events(
     let mut keys = String::new();
     let mut buffer = Vec::new();
 
+    // The index of an event within its transaction is kept, even if preceding events are not
+    // indexed.
     for (idx, event) in events.iter().enumerate() {
+        if !policy.indexes(&event.from_address) {
+            continue;
+        }
+
         keys.clear();
         event_keys_to_base64_strings(&event.keys, &mut keys);
 
@@ -92,6 +114,21 @@ pub(super) fn insert_events(
     Ok(())
 }
 
+/// Stores the [BloomFilter] over all events of a block, which lets [get_events] skip the block.
+pub(super) fn insert_events_filter(
+    tx: &Transaction<'_>,
+    block_number: BlockNumber,
+    bloom: &BloomFilter,
+) -> anyhow::Result<()> {
+    tx.inner()
+        .execute(
+            "INSERT OR REPLACE INTO starknet_events_filters (block_number, bloom) VALUES (?, ?)",
+            params![&block_number, &bloom.as_bytes()],
+        )
+        .context("Inserting events filter")?;
+    Ok(())
+}
+
 pub fn event_count(
     tx: &Transaction<'_>,
     from_block: Option<BlockNumber>,
@@ -113,6 +150,7 @@ pub fn event_count(
         to_block.as_ref(),
         contract_address.as_ref(),
         keys,
+        None,
         strategy,
     );
 
@@ -140,19 +178,66 @@ pub(super) fn get_events<K: KeyFilter>(
         anyhow::bail!("Invalid page size");
     }
 
-    let strategy = select_query_strategy(
+    // Events before the start cursor's block can never be part of the result, so we narrow
+    // the block range which lets the query seek directly to the cursor using the block index.
+    let from_block = match (filter.from_block, filter.start) {
+        (Some(from_block), Some(start)) if from_block > start.block_number => Some(from_block),
+        (_, Some(start)) => Some(start.block_number),
+        (from_block, None) => from_block,
+    };
+
+    super::event_index::ensure_events_indexed(tx, from_block, filter.contract_address.as_ref())?;
+
+    let strategy = match select_query_strategy(
         tx,
-        filter.from_block.as_ref(),
+        from_block.as_ref(),
         filter.to_block.as_ref(),
         filter.contract_address.as_ref(),
         &filter.keys,
-    )?;
+    ) {
+        // Scanning the block range is cheap if most blocks can be skipped, so key filters too
+        // broad for the FTS index are still fine.
+        Err(e)
+            if matches!(
+                e.downcast_ref::<EventFilterError>(),
+                Some(EventFilterError::TooManyMatches)
+            ) =>
+        {
+            return get_events_by_block_filters(tx, filter, from_block);
+        }
+        Ok(QueryStrategy::BlockRangeFirst)
+            if filter.keys.apply(QueryStrategy::BlockRangeFirst).is_some() =>
+        {
+            return get_events_by_block_filters(tx, filter, from_block);
+        }
+        strategy => strategy?,
+    };
 
-    let base_query = r#"SELECT
+    let (query, params) = event_query(
+        EVENTS_BASE_QUERY,
+        from_block.as_ref(),
+        filter.to_block.as_ref(),
+        filter.contract_address.as_ref(),
+        &filter.keys,
+        filter.start.as_ref(),
+        strategy,
+    );
+
+    let mut events = Vec::new();
+    let continuation = fetch_events(tx, query, params, filter.page_size, &mut events)?;
+
+    Ok(PageOfEvents {
+        events,
+        continuation,
+    })
+}
+
+const EVENTS_BASE_QUERY: &str = r#"SELECT
               block_number,
               block_headers.hash as block_hash,
               transaction_hash,
               starknet_transactions.idx as transaction_idx,
+              starknet_events.idx as event_idx,
               from_address,
               data,
               starknet_events.keys as keys
@@ -160,28 +245,94 @@ pub(super) fn get_events<K: KeyFilter>(
            INNER JOIN starknet_transactions ON (starknet_transactions.hash = starknet_events.transaction_hash)
            INNER JOIN block_headers ON (block_headers.number = starknet_events.block_number)"#;
 
-    let (mut base_query, mut params) = event_query(
-        base_query,
-        filter.from_block.as_ref(),
-        filter.to_block.as_ref(),
-        filter.contract_address.as_ref(),
-        &filter.keys,
-        strategy,
-    );
+/// Scans the block range one block at a time, skipping blocks whose [BloomFilter] rules out
+/// any matching events. Blocks without a filter are always queried.
+fn get_events_by_block_filters<K: KeyFilter>(
+    tx: &Transaction<'_>,
+    filter: &EventFilter<K>,
+    from_block: Option<BlockNumber>,
+) -> anyhow::Result<PageOfEvents> {
+    let mut statement = tx
+        .inner()
+        .prepare(
+            r"SELECT canonical_blocks.number, starknet_events_filters.bloom
+            FROM canonical_blocks
+            LEFT JOIN starknet_events_filters ON (starknet_events_filters.block_number = canonical_blocks.number)
+            WHERE canonical_blocks.number BETWEEN :from_block AND :to_block
+            ORDER BY canonical_blocks.number",
+        )
+        .context("Preparing block filter query")?;
+    let mut rows = statement
+        .query(named_params![
+            ":from_block": &from_block.unwrap_or(BlockNumber::GENESIS),
+            ":to_block": &filter.to_block.unwrap_or(BlockNumber::MAX),
+        ])
+        .context("Querying block filters")?;
+
+    let mut events = Vec::new();
+    while let Some(row) = rows.next().context("Fetching next block filter")? {
+        let block_number = row.get_block_number(0)?;
+        if let Some(bloom) = row.get_ref_unwrap(1).as_blob_or_null()? {
+            let bloom = BloomFilter::from_bytes(bloom.to_vec());
+            let address_matches = filter
+                .contract_address
+                .map_or(true, |address| bloom.may_contain_address(&address));
+            if !address_matches || !filter.keys.may_match(&bloom) {
+                continue;
+            }
+        }
+
+        let start = filter
+            .start
+            .filter(|start| start.block_number == block_number);
+        let (query, params) = event_query(
+            EVENTS_BASE_QUERY,
+            Some(&block_number),
+            Some(&block_number),
+            filter.contract_address.as_ref(),
+            &filter.keys,
+            start.as_ref(),
+            QueryStrategy::BlockRangeFirst,
+        );
+
+        // Once the page is full this only checks whether there is a next page.
+        let limit = filter.page_size - events.len();
+        let continuation = fetch_events(tx, query, params, limit, &mut events)?;
+        if continuation.is_some() {
+            return Ok(PageOfEvents {
+                events,
+                continuation,
+            });
+        }
+    }
 
+    Ok(PageOfEvents {
+        events,
+        continuation: None,
+    })
+}
+
+/// Appends up to `limit` events matching the query to `events`, and returns the position of the
+/// next matching event if there are more.
+fn fetch_events(
+    tx: &Transaction<'_>,
+    mut query: std::borrow::Cow<'_, str>,
+    mut params: Vec<(&'static str, rusqlite::types::ToSqlOutput<'_>)>,
+    limit: usize,
+    events: &mut Vec<EmittedEvent>,
+) -> anyhow::Result<Option<EventCursor>> {
     // We have to be able to decide if there are more events. We request one extra event
-    // above the requested page size, so that we can decide.
-    let limit = filter.page_size + 1;
-    params.push((":limit", limit.try_into_sql()?));
-    params.push((":offset", filter.offset.try_into_sql()?));
+    // above the limit, so that we can decide.
+    let sql_limit = limit + 1;
+    params.push((":limit", sql_limit.try_into_sql()?));
 
-    base_query.to_mut().push_str(
-        " ORDER BY block_number, transaction_idx, starknet_events.idx LIMIT :limit OFFSET :offset",
-    );
+    query
+        .to_mut()
+        .push_str(" ORDER BY block_number, transaction_idx, starknet_events.idx LIMIT :limit");
 
     let mut statement = tx
         .inner()
-        .prepare(&base_query)
+        .prepare_cached(&query)
         .context("Preparing SQL query")?;
     let params = params
         .iter()
@@ -191,59 +342,58 @@ pub(super) fn get_events<K: KeyFilter>(
         .query(params.as_slice())
         .context("Executing SQL query")?;
 
-    let mut is_last_page = true;
-    let mut emitted_events = Vec::new();
+    let mut fetched = 0;
     while let Some(row) = rows.next().context("Fetching next event")? {
-        if emitted_events.len() == filter.page_size {
-            // We already have a full page, and are just fetching the extra event
-            // This means that there are more pages.
-            is_last_page = false;
-        } else {
-            let block_number = row.get_block_number("block_number")?;
-            let block_hash = row.get_block_hash("block_hash")?;
-            let transaction_hash = row.get_transaction_hash("transaction_hash")?;
-            let from_address = row.get_contract_address("from_address")?;
-
-            let data = row.get_ref_unwrap("data").as_blob().unwrap();
-            let data: Vec<_> = data
-                .chunks_exact(32)
-                .map(|data| {
-                    let data = Felt::from_be_slice(data).unwrap();
-                    EventData(data)
-                })
-                .collect();
+        if fetched == limit {
+            // We already have all requested events, and are just fetching the extra event
+            // This means that there are more events, starting at this event.
+            return Ok(Some(EventCursor {
+                block_number: row.get_block_number("block_number")?,
+                transaction_index: row.get("transaction_idx")?,
+                event_index: row.get("event_idx")?,
+            }));
+        }
+        fetched += 1;
+
+        let block_number = row.get_block_number("block_number")?;
+        let block_hash = row.get_block_hash("block_hash")?;
+        let transaction_hash = row.get_transaction_hash("transaction_hash")?;
+        let from_address = row.get_contract_address("from_address")?;
+
+        let data = row.get_ref_unwrap("data").as_blob().unwrap();
+        let data: Vec<_> = data
+            .chunks_exact(32)
+            .map(|data| {
+                let data = Felt::from_be_slice(data).unwrap();
+                EventData(data)
+            })
+            .collect();
 
-            let keys = row.get_ref_unwrap("keys").as_str().unwrap();
+        let keys = row.get_ref_unwrap("keys").as_str().unwrap();
 
-            // no need to allocate a vec for this in loop
-            let mut temp = [0u8; 32];
+        // no need to allocate a vec for this in loop
+        let mut temp = [0u8; 32];
 
-            let keys: Vec<_> = keys
-                .split(' ')
-                .map(|key| {
-                    let used =
-                        base64::decode_config_slice(key, base64::STANDARD, &mut temp).unwrap();
-                    let key = Felt::from_be_slice(&temp[..used]).unwrap();
-                    EventKey(key)
-                })
-                .collect();
-
-            let event = EmittedEvent {
-                data,
-                from_address,
-                keys,
-                block_hash,
-                block_number,
-                transaction_hash,
-            };
-            emitted_events.push(event);
-        }
+        let keys: Vec<_> = keys
+            .split(' ')
+            .map(|key| {
+                let used = base64::decode_config_slice(key, base64::STANDARD, &mut temp).unwrap();
+                let key = Felt::from_be_slice(&temp[..used]).unwrap();
+                EventKey(key)
+            })
+            .collect();
+
+        events.push(EmittedEvent {
+            data,
+            from_address,
+            keys,
+            block_hash,
+            block_number,
+            transaction_hash,
+        });
     }
 
-    Ok(PageOfEvents {
-        events: emitted_events,
-        is_last_page,
-    })
+    Ok(None)
 }
 
 fn event_keys_to_base64_strings(keys: &[EventKey], out: &mut String) {
@@ -288,6 +438,7 @@ fn encode_event_key_and_index_to_base32(index: u8, key: &EventKey, output: &mut
 /// [["key1_value1", "key1_value2"], [], ["key3_value1"]] means:
 /// ((key1 == "key1_value1" OR key1 == "key1_value2") AND (key3 == "key3_value1")).
 pub struct V03KeyFilter {
+    keys: Vec<Vec<EventKey>>,
     key_fts_expression: Option<String>,
 }
 
@@ -322,7 +473,10 @@ impl V03KeyFilter {
             Some(key_fts_expression)
         };
 
-        Self { key_fts_expression }
+        Self {
+            keys,
+            key_fts_expression,
+        }
     }
 }
 
@@ -358,6 +512,12 @@ impl KeyFilter for V03KeyFilter {
             }
         }
     }
+
+    fn may_match(&self, bloom: &BloomFilter) -> bool {
+        self.keys.iter().enumerate().all(|(index, values)| {
+            values.is_empty() || values.iter().any(|key| bloom.may_contain_key(index, key))
+        })
+    }
 }
 
 fn event_query<'query, 'arg>(
@@ -366,6 +526,7 @@ fn event_query<'query, 'arg>(
     to_block: Option<&'arg BlockNumber>,
     contract_address: Option<&'arg ContractAddress>,
     keys: &'arg (dyn KeyFilter + 'arg),
+    start: Option<&'arg EventCursor>,
     strategy: QueryStrategy,
 ) -> (
     std::borrow::Cow<'query, str>,
@@ -400,6 +561,18 @@ fn event_query<'query, 'arg>(
         params.push((":contract_address", contract_address.to_sql()));
     }
 
+    // resume from the cursor, this requires the query to join the transactions table
+    if let Some(start) = start {
+        where_statement_parts.push("(block_number, starknet_transactions.idx, starknet_events.idx) >= (:start_block, :start_transaction_idx, :start_event_idx)");
+        params.push((":start_block", start.block_number.to_sql()));
+        // Indices originate from the database and therefore always fit.
+        params.push((
+            ":start_transaction_idx",
+            (start.transaction_index as i64).into(),
+        ));
+        params.push((":start_event_idx", (start.event_index as i64).into()));
+    }
+
     // Filter on keys: this is using an FTS5 full-text index (virtual table) on the keys.
     // The idea is that we convert keys to a space-separated list of Base64/Base32 encoded string
     // representation and then use the full-text index to find events matching the events.
@@ -637,7 +810,7 @@ mod tests {
             // we're using a key which is present in _all_ events as the 2nd key
             keys: V03KeyFilter::new(vec![vec![], vec![event_key!("0xdeadbeef")]]),
             page_size: test_utils::NUM_EVENTS,
-            offset: 0,
+            start: None,
         };
 
         let events = get_events(&tx, &filter).unwrap();
@@ -645,7 +818,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: vec![expected_event.clone()],
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -757,7 +930,7 @@ mod tests {
                 contract_address: None,
                 keys: V03KeyFilter::new(vec![]),
                 page_size: 1024,
-                offset: 0,
+                start: None,
             },
         )
         .unwrap()
@@ -788,7 +961,7 @@ mod tests {
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
             page_size: test_utils::NUM_EVENTS,
-            offset: 0,
+            start: None,
         };
 
         let expected_events = &emitted_events[test_utils::EVENTS_PER_BLOCK * BLOCK_NUMBER
@@ -798,7 +971,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: expected_events.to_vec(),
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -817,7 +990,7 @@ mod tests {
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
             page_size: test_utils::NUM_EVENTS,
-            offset: 0,
+            start: None,
         };
 
         let expected_events =
@@ -827,7 +1000,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: expected_events.to_vec(),
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -846,7 +1019,7 @@ mod tests {
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
             page_size: test_utils::NUM_EVENTS,
-            offset: 0,
+            start: None,
         };
 
         let expected_events = &emitted_events[test_utils::EVENTS_PER_BLOCK * FROM_BLOCK_NUMBER..];
@@ -855,7 +1028,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: expected_events.to_vec(),
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -875,7 +1048,7 @@ mod tests {
             contract_address: Some(expected_event.from_address),
             keys: V03KeyFilter::new(vec![]),
             page_size: test_utils::NUM_EVENTS,
-            offset: 0,
+            start: None,
         };
 
         let events = get_events(&tx, &filter).unwrap();
@@ -883,7 +1056,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: vec![expected_event.clone()],
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -905,7 +1078,7 @@ mod tests {
                 vec![expected_event.keys[1]],
             ]),
             page_size: test_utils::NUM_EVENTS,
-            offset: 0,
+            start: None,
         };
 
         let events = get_events(&tx, &filter).unwrap();
@@ -913,7 +1086,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: vec![expected_event.clone()],
-                is_last_page: true,
+                continuation: None,
             }
         );
 
@@ -930,7 +1103,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: vec![],
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -948,7 +1121,7 @@ mod tests {
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
             page_size: test_utils::NUM_EVENTS,
-            offset: 0,
+            start: None,
         };
 
         let events = get_events(&tx, &filter).unwrap();
@@ -956,7 +1129,7 @@ mod tests {
             events,
             PageOfEvents {
                 events: emitted_events,
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -974,50 +1147,69 @@ mod tests {
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
             page_size: 10,
-            offset: 0,
+            start: None,
         };
-        let events = get_events(&tx, &filter).unwrap();
-        assert_eq!(
-            events,
-            PageOfEvents {
-                events: emitted_events[..10].to_vec(),
-                is_last_page: false,
-            }
-        );
+        let page = get_events(&tx, &filter).unwrap();
+        assert_eq!(page.events, emitted_events[..10].to_vec());
+        assert!(page.continuation.is_some());
 
         let filter = EventFilter {
-            from_block: None,
-            to_block: None,
-            contract_address: None,
-            keys: V03KeyFilter::new(vec![]),
-            page_size: 10,
-            offset: 10,
+            start: page.continuation,
+            ..filter
         };
-        let events = get_events(&tx, &filter).unwrap();
+        let page = get_events(&tx, &filter).unwrap();
+        assert_eq!(page.events, emitted_events[10..20].to_vec());
+        assert!(page.continuation.is_some());
+
+        let filter = EventFilter {
+            start: page.continuation,
+            ..filter
+        };
+        let page = get_events(&tx, &filter).unwrap();
+        assert_eq!(page.events, emitted_events[20..30].to_vec());
+        assert!(page.continuation.is_some());
+
+        let filter = EventFilter {
+            start: page.continuation,
+            ..filter
+        };
+        let page = get_events(&tx, &filter).unwrap();
         assert_eq!(
-            events,
+            page,
             PageOfEvents {
-                events: emitted_events[10..20].to_vec(),
-                is_last_page: false,
+                events: emitted_events[30..40].to_vec(),
+                continuation: None,
             }
         );
+    }
 
+    #[test]
+    fn get_events_with_cursor_and_block_range() {
+        let (storage, test_data) = test_utils::setup_test_storage();
+        let emitted_events = test_data.events;
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        // The cursor points into block 1, but the filter only starts at block 2.
         let filter = EventFilter {
-            from_block: None,
+            from_block: Some(BlockNumber::new_or_panic(2)),
             to_block: None,
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
-            page_size: 10,
-            offset: 30,
+            page_size: test_utils::EVENTS_PER_BLOCK,
+            start: Some(EventCursor {
+                block_number: BlockNumber::new_or_panic(1),
+                transaction_index: 1,
+                event_index: 0,
+            }),
         };
-        let events = get_events(&tx, &filter).unwrap();
-        assert_eq!(
-            events,
-            PageOfEvents {
-                events: emitted_events[30..40].to_vec(),
-                is_last_page: true,
-            }
-        );
+        let page = get_events(&tx, &filter).unwrap();
+        let expected = emitted_events
+            .iter()
+            .filter(|event| event.block_number == BlockNumber::new_or_panic(2))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(page.events, expected);
     }
 
     #[test]
@@ -1034,14 +1226,18 @@ mod tests {
             keys: V03KeyFilter::new(vec![]),
             page_size: PAGE_SIZE,
             // _after_ the last one
-            offset: test_utils::NUM_BLOCKS * test_utils::EVENTS_PER_BLOCK,
+            start: Some(EventCursor {
+                block_number: BlockNumber::new_or_panic(test_utils::NUM_BLOCKS as u64),
+                transaction_index: 0,
+                event_index: 0,
+            }),
         };
         let events = get_events(&tx, &filter).unwrap();
         assert_eq!(
             events,
             PageOfEvents {
                 events: vec![],
-                is_last_page: true,
+                continuation: None,
             }
         );
     }
@@ -1058,7 +1254,7 @@ mod tests {
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
             page_size: 0,
-            offset: 0,
+            start: None,
         };
         let result = get_events(&tx, &filter);
         assert!(result.is_err());
@@ -1070,7 +1266,7 @@ mod tests {
             contract_address: None,
             keys: V03KeyFilter::new(vec![]),
             page_size: PAGE_SIZE_LIMIT + 1,
-            offset: 0,
+            start: None,
         };
         let result = get_events(&tx, &filter);
         assert!(result.is_err());
@@ -1097,51 +1293,128 @@ mod tests {
             from_block: None,
             to_block: None,
             contract_address: None,
-            keys: keys_for_expected_events.clone(),
+            keys: keys_for_expected_events,
             page_size: 2,
-            offset: 0,
+            start: None,
         };
-        let events = get_events(&tx, &filter).unwrap();
+        let page = get_events(&tx, &filter).unwrap();
+        assert_eq!(page.events, expected_events[..2].to_vec());
+        assert!(page.continuation.is_some());
+
+        let filter = EventFilter {
+            start: page.continuation,
+            ..filter
+        };
+        let page = get_events(&tx, &filter).unwrap();
+        assert_eq!(page.events, expected_events[2..4].to_vec());
+        assert!(page.continuation.is_some());
+
+        let filter = EventFilter {
+            start: page.continuation,
+            ..filter
+        };
+        let page = get_events(&tx, &filter).unwrap();
         assert_eq!(
-            events,
+            page,
             PageOfEvents {
-                events: expected_events[..2].to_vec(),
-                is_last_page: false,
+                events: expected_events[4..].to_vec(),
+                continuation: None,
             }
         );
+    }
 
-        let filter = EventFilter {
+    #[test]
+    fn get_events_by_block_filters_with_paging() {
+        let (storage, test_data) = test_utils::setup_test_storage();
+        let emitted_events = test_data.events;
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        // These events span two blocks.
+        let expected_events = &emitted_events[27..32];
+        let mut filter = EventFilter {
             from_block: None,
             to_block: None,
             contract_address: None,
-            keys: keys_for_expected_events.clone(),
+            keys: V03KeyFilter::new(vec![
+                expected_events.iter().map(|e| e.keys[0]).collect(),
+                expected_events.iter().map(|e| e.keys[1]).collect(),
+            ]),
             page_size: 2,
-            offset: 2,
+            start: None,
         };
-        let events = get_events(&tx, &filter).unwrap();
-        assert_eq!(
-            events,
-            PageOfEvents {
-                events: expected_events[2..4].to_vec(),
-                is_last_page: false,
+
+        let mut events = Vec::new();
+        loop {
+            let from_block = filter.start.map(|start| start.block_number);
+            let page = get_events_by_block_filters(&tx, &filter, from_block).unwrap();
+            assert_eq!(
+                page.events.len(),
+                usize::min(2, expected_events.len() - events.len())
+            );
+            events.extend(page.events);
+
+            match page.continuation {
+                Some(continuation) => filter.start = Some(continuation),
+                None => break,
             }
-        );
+        }
+
+        assert_eq!(events, expected_events);
+    }
+
+    #[test]
+    fn get_events_by_block_filters_scans_blocks_without_filter() {
+        let (storage, test_data) = test_utils::setup_test_storage();
+        let emitted_events = test_data.events;
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
 
+        let expected_event = &emitted_events[27];
         let filter = EventFilter {
             from_block: None,
             to_block: None,
             contract_address: None,
-            keys: keys_for_expected_events,
-            page_size: 2,
-            offset: 4,
+            keys: V03KeyFilter::new(vec![vec![expected_event.keys[0]]]),
+            page_size: test_utils::NUM_EVENTS,
+            start: None,
         };
-        let events = get_events(&tx, &filter).unwrap();
+        let expected = PageOfEvents {
+            events: vec![expected_event.clone()],
+            continuation: None,
+        };
+
+        let filters: usize = tx
+            .inner()
+            .query_row("SELECT COUNT(1) FROM starknet_events_filters", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(filters, test_utils::NUM_BLOCKS);
         assert_eq!(
-            events,
-            PageOfEvents {
-                events: expected_events[4..].to_vec(),
-                is_last_page: true,
-            }
+            get_events_by_block_filters(&tx, &filter, None).unwrap(),
+            expected
+        );
+
+        // Databases may contain blocks without a filter, which must not be skipped.
+        tx.inner()
+            .execute("DELETE FROM starknet_events_filters", [])
+            .unwrap();
+        assert_eq!(
+            get_events_by_block_filters(&tx, &filter, None).unwrap(),
+            expected
+        );
+
+        // A key which was never emitted matches nothing.
+        let filter = EventFilter {
+            keys: V03KeyFilter::new(vec![vec![event_key!("0xdeadbeefdeadbeef")]]),
+            ..filter
+        };
+        assert_eq!(
+            get_events_by_block_filters(&tx, &filter, None)
+                .unwrap()
+                .events,
+            vec![]
         );
     }
 
@@ -0,0 +1,320 @@
+use std::collections::BTreeSet;
+
+use anyhow::Context;
+use pathfinder_common::{BlockNumber, ContractAddress};
+
+use super::options::{option, set_option};
+use crate::prelude::*;
+use crate::{BlockId, EventFilterError};
+
+/// The `storage_options` entry holding the [EventIndexPolicy]. It is only present if the policy
+/// is not [EventIndexPolicy::All].
+const POLICY_OPTION: &str = "event_index_policy";
+/// The `storage_options` entry holding the last block whose events still have to be re-indexed
+/// after the [EventIndexPolicy] changed. Blocks are re-indexed from this block down to genesis.
+const REINDEX_OPTION: &str = "event_reindex_until";
+
+/// Determines which events are indexed in the events table, and can therefore be queried.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventIndexPolicy {
+    All,
+    None,
+    /// Only events emitted by these contracts are indexed.
+    Contracts(BTreeSet<ContractAddress>),
+}
+
+impl EventIndexPolicy {
+    pub fn indexes(&self, address: &ContractAddress) -> bool {
+        match self {
+            EventIndexPolicy::All => true,
+            EventIndexPolicy::None => false,
+            EventIndexPolicy::Contracts(contracts) => contracts.contains(address),
+        }
+    }
+
+    fn to_option(&self) -> Option<i64> {
+        match self {
+            EventIndexPolicy::All => None,
+            EventIndexPolicy::None => Some(1),
+            EventIndexPolicy::Contracts(_) => Some(2),
+        }
+    }
+}
+
+pub(super) fn event_index_policy(tx: &Transaction<'_>) -> anyhow::Result<EventIndexPolicy> {
+    match option(tx, POLICY_OPTION)? {
+        None => Ok(EventIndexPolicy::All),
+        Some(1) => Ok(EventIndexPolicy::None),
+        Some(2) => {
+            let contracts = tx
+                .inner()
+                .prepare_cached("SELECT contract_address FROM event_index_contracts")
+                .context("Preparing contracts query")?
+                .query_map([], |row| row.get_contract_address(0))
+                .context("Querying contracts")?
+                .collect::<Result<_, _>>()
+                .context("Iterating over contracts")?;
+            Ok(EventIndexPolicy::Contracts(contracts))
+        }
+        Some(other) => anyhow::bail!("Unknown event index policy {other}"),
+    }
+}
+
+/// Changes the [EventIndexPolicy] of the database, and schedules all existing blocks to be
+/// re-indexed by [reindex_events] if it differs from the current policy.
+///
+/// Contracts whose events are fully indexed under both the current and the new policy remain
+/// queryable while re-indexing.
+pub(super) fn set_event_index_policy(
+    tx: &Transaction<'_>,
+    policy: &EventIndexPolicy,
+) -> anyhow::Result<()> {
+    let current = event_index_policy(tx)?;
+    if &current == policy {
+        return Ok(());
+    }
+
+    let reindex_until = option(tx, REINDEX_OPTION)?;
+    let complete = |address: &ContractAddress| -> anyhow::Result<bool> {
+        match (&current, reindex_until) {
+            (EventIndexPolicy::None, _) => Ok(false),
+            (EventIndexPolicy::All, None) => Ok(true),
+            _ => is_complete(tx, address),
+        }
+    };
+
+    let contracts = match policy {
+        EventIndexPolicy::Contracts(contracts) => contracts
+            .iter()
+            .map(|address| Ok((*address, complete(address)?)))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        // Only contracts which remain complete need to be tracked, which can only be those of
+        // the previous allowlist.
+        EventIndexPolicy::All => tx
+            .inner()
+            .prepare("SELECT contract_address FROM event_index_contracts WHERE complete = 1")
+            .context("Preparing complete contracts query")?
+            .query_map([], |row| row.get_contract_address(0))
+            .context("Querying complete contracts")?
+            .map(|address| Ok((address?, true)))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        EventIndexPolicy::None => Vec::new(),
+    };
+
+    tx.inner()
+        .execute("DELETE FROM event_index_contracts", [])
+        .context("Deleting contracts")?;
+    let mut insert = tx
+        .inner()
+        .prepare("INSERT INTO event_index_contracts (contract_address, complete) VALUES (?, ?)")
+        .context("Preparing contract insert")?;
+    for (address, complete) in contracts {
+        insert
+            .execute(params![&address, &i64::from(complete)])
+            .context("Inserting contract")?;
+    }
+
+    set_option(tx, POLICY_OPTION, policy.to_option()).context("Setting policy")?;
+
+    // The previous policy may have indexed events which are no longer wanted, so all blocks are
+    // re-indexed even if no events are missing.
+    let latest = tx
+        .block_id(BlockId::Latest)
+        .context("Querying latest block")?;
+    match latest {
+        Some((latest, _)) => set_option(tx, REINDEX_OPTION, Some(latest.get() as i64))
+            .context("Scheduling re-index")?,
+        None => finish_reindex(tx)?,
+    }
+
+    Ok(())
+}
+
+/// Returns the last block which still has to be re-indexed, if any.
+pub(super) fn event_reindex_until(tx: &Transaction<'_>) -> anyhow::Result<Option<BlockNumber>> {
+    Ok(option(tx, REINDEX_OPTION)?.map(|block| BlockNumber::new_or_panic(block as u64)))
+}
+
+/// Re-indexes the events of up to `max_blocks` blocks which were scheduled by
+/// [set_event_index_policy], starting at the latest block. Returns the last block which still
+/// has to be re-indexed, or `None` once all blocks are done.
+pub(super) fn reindex_events(
+    tx: &Transaction<'_>,
+    max_blocks: u64,
+) -> anyhow::Result<Option<BlockNumber>> {
+    let Some(until) = event_reindex_until(tx)? else {
+        return Ok(None);
+    };
+
+    let policy = event_index_policy(tx)?;
+    let mut delete = tx
+        .inner()
+        .prepare("DELETE FROM starknet_events WHERE block_number = ?")
+        .context("Preparing event delete")?;
+
+    let first = until.get().saturating_sub(max_blocks.saturating_sub(1));
+    for block in (first..=until.get()).rev().map(BlockNumber::new_or_panic) {
+        delete.execute(params![&block]).context("Deleting events")?;
+
+        // Blocks purged by a reorg since scheduling have no transactions left.
+        let transactions = tx
+            .transaction_data_for_block(block.into())
+            .context("Fetching transactions")?
+            .unwrap_or_default();
+        for (_, receipt) in transactions {
+            super::event::insert_events(
+                tx,
+                block,
+                receipt.transaction_hash,
+                &receipt.events,
+                &policy,
+            )
+            .context("Inserting events")?;
+        }
+    }
+
+    match first.checked_sub(1) {
+        Some(next) => {
+            set_option(tx, REINDEX_OPTION, Some(next as i64)).context("Updating progress")?;
+            Ok(Some(BlockNumber::new_or_panic(next)))
+        }
+        None => {
+            finish_reindex(tx)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Fails with [EventFilterError::NotIndexed] unless all events matching the filter from
+/// `from_block` onwards are indexed.
+pub(super) fn ensure_events_indexed(
+    tx: &Transaction<'_>,
+    from_block: Option<BlockNumber>,
+    contract_address: Option<&ContractAddress>,
+) -> anyhow::Result<()> {
+    let policy = event_index_policy(tx)?;
+
+    let allowed = match (&policy, contract_address) {
+        (EventIndexPolicy::None, _) | (EventIndexPolicy::Contracts(_), None) => false,
+        (policy, Some(address)) => policy.indexes(address),
+        (EventIndexPolicy::All, None) => true,
+    };
+    if !allowed {
+        return Err(EventFilterError::NotIndexed.into());
+    }
+
+    let reindexed = match event_reindex_until(tx)? {
+        None => true,
+        Some(until) => from_block.map_or(false, |from| from > until),
+    };
+    let complete = match contract_address {
+        Some(address) if !reindexed => is_complete(tx, address)?,
+        _ => reindexed,
+    };
+    if !complete {
+        return Err(EventFilterError::NotIndexed.into());
+    }
+
+    Ok(())
+}
+
+/// Marks all blocks as re-indexed, which makes all contracts of the policy complete.
+fn finish_reindex(tx: &Transaction<'_>) -> anyhow::Result<()> {
+    set_option(tx, REINDEX_OPTION, None).context("Clearing re-index progress")?;
+
+    let query = match event_index_policy(tx)? {
+        EventIndexPolicy::Contracts(_) => "UPDATE event_index_contracts SET complete = 1",
+        _ => "DELETE FROM event_index_contracts",
+    };
+    tx.inner()
+        .execute(query, [])
+        .context("Updating contracts")?;
+
+    Ok(())
+}
+
+/// Returns true if the contract's events are indexed for all blocks, including those which
+/// have yet to be re-indexed.
+fn is_complete(tx: &Transaction<'_>, address: &ContractAddress) -> anyhow::Result<bool> {
+    tx.inner()
+        .prepare_cached("SELECT complete FROM event_index_contracts WHERE contract_address = ?")
+        .context("Preparing contract query")?
+        .query_row(params![address], |row| row.get::<_, bool>(0))
+        .optional()
+        .map(|complete| complete.unwrap_or(false))
+        .context("Querying contract")
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+
+    use super::*;
+    use crate::test_utils;
+
+    fn contracts(addresses: &[ContractAddress]) -> EventIndexPolicy {
+        EventIndexPolicy::Contracts(addresses.iter().copied().collect())
+    }
+
+    #[test]
+    fn policy_changes_reindex() {
+        let (storage, test_data) = test_utils::setup_test_storage();
+        let emitted = test_data.events;
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let latest = tx.block_id(BlockId::Latest).unwrap().unwrap().0;
+        let indexed = |tx: &Transaction<'_>| -> usize {
+            tx.inner()
+                .query_row("SELECT COUNT(*) FROM starknet_events", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(indexed(&tx), emitted.len());
+
+        let a = emitted[0].from_address;
+        let b = emitted[1].from_address;
+        let unknown = contract_address!("0x1234");
+
+        // Events are still fully indexed for `a`, but not for `b` until re-indexed.
+        set_event_index_policy(&tx, &contracts(&[a, unknown])).unwrap();
+        assert_eq!(event_reindex_until(&tx).unwrap(), Some(latest));
+        ensure_events_indexed(&tx, None, Some(&a)).unwrap();
+        ensure_events_indexed(&tx, None, Some(&unknown)).unwrap();
+        let error = ensure_events_indexed(&tx, None, None).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<EventFilterError>(),
+            Some(&EventFilterError::NotIndexed)
+        );
+        ensure_events_indexed(&tx, None, Some(&b)).unwrap_err();
+
+        set_event_index_policy(&tx, &contracts(&[a, b])).unwrap();
+        ensure_events_indexed(&tx, None, Some(&b)).unwrap_err();
+
+        // Blocks after the re-index progress are fully indexed.
+        assert_eq!(
+            reindex_events(&tx, 1).unwrap(),
+            Some(BlockNumber::new_or_panic(latest.get() - 1))
+        );
+        ensure_events_indexed(&tx, Some(latest), Some(&b)).unwrap();
+        ensure_events_indexed(&tx, None, Some(&b)).unwrap_err();
+
+        assert_eq!(reindex_events(&tx, u64::MAX).unwrap(), None);
+        ensure_events_indexed(&tx, None, Some(&b)).unwrap();
+        let expected = emitted
+            .iter()
+            .filter(|event| event.from_address == a || event.from_address == b)
+            .count();
+        assert_eq!(indexed(&tx), expected);
+
+        set_event_index_policy(&tx, &EventIndexPolicy::None).unwrap();
+        ensure_events_indexed(&tx, Some(latest), Some(&a)).unwrap_err();
+        reindex_events(&tx, u64::MAX).unwrap();
+        assert_eq!(indexed(&tx), 0);
+
+        set_event_index_policy(&tx, &EventIndexPolicy::All).unwrap();
+        ensure_events_indexed(&tx, None, Some(&a)).unwrap_err();
+        reindex_events(&tx, u64::MAX).unwrap();
+        ensure_events_indexed(&tx, None, None).unwrap();
+        assert_eq!(indexed(&tx), emitted.len());
+    }
+}
@@ -0,0 +1,332 @@
+//! L1 to L2 messages sent through the Starknet core contract, and the L1 handler transactions
+//! which consumed them on L2.
+
+use anyhow::Context;
+use pathfinder_common::{
+    BlockNumber, EntryPoint, EthereumAddress, L1ToL2MessageNonce, L1ToL2MessagePayloadElem,
+    TransactionHash,
+};
+use pathfinder_ethereum::{L1ToL2Message, L1ToL2MessageEvent, L1ToL2MessageLog};
+use primitive_types::{H160, H256};
+use stark_hash::Felt;
+use starknet_gateway_types::reply::transaction as gateway;
+
+use crate::prelude::*;
+
+/// The `storage_options` entry holding the last Ethereum block whose message logs are stored.
+const SYNCED_UNTIL_OPTION: &str = "l1_messages_synced_until";
+
+/// An L1 to L2 message, along with what happened to it on L1 and L2.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L1ToL2MessageRecord {
+    pub message: L1ToL2Message,
+    pub message_hash: H256,
+    pub l1_block_number: u64,
+    pub l1_transaction_hash: H256,
+    /// The L1 transaction which started the cancellation of the message.
+    pub cancellation_started_by: Option<H256>,
+    /// The L1 transaction which completed the cancellation of the message.
+    pub canceled_by: Option<H256>,
+    /// The L1 handler transaction which consumed the message, and its block.
+    pub consumed_by: Option<(TransactionHash, BlockNumber)>,
+}
+
+/// Returns the last Ethereum block whose message logs were stored by
+/// [insert_l1_to_l2_message_logs].
+pub(super) fn l1_messages_synced_until(tx: &Transaction<'_>) -> anyhow::Result<Option<u64>> {
+    let until = super::options::option(tx, SYNCED_UNTIL_OPTION)?;
+    Ok(until.map(|until| until as u64))
+}
+
+/// Stores the message logs emitted by the core contract up to and including Ethereum block
+/// `synced_until`.
+///
+/// Cancellation logs of messages sent before the first stored block are ignored.
+pub(super) fn insert_l1_to_l2_message_logs(
+    tx: &Transaction<'_>,
+    logs: &[L1ToL2MessageLog],
+    synced_until: u64,
+) -> anyhow::Result<()> {
+    let mut insert = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR IGNORE INTO l1_to_l2_messages (
+                message_hash,
+                l1_transaction_hash,
+                l1_block_number,
+                from_address,
+                to_address,
+                selector,
+                payload,
+                nonce
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .context("Preparing insert statement")?;
+    let mut cancellation_started = tx
+        .inner()
+        .prepare_cached(
+            "UPDATE l1_to_l2_messages SET cancellation_started_by = ? WHERE message_hash = ?",
+        )
+        .context("Preparing cancellation statement")?;
+    let mut canceled = tx
+        .inner()
+        .prepare_cached("UPDATE l1_to_l2_messages SET canceled_by = ? WHERE message_hash = ?")
+        .context("Preparing cancellation statement")?;
+
+    for log in logs {
+        let hash = log.message.hash();
+        match log.event {
+            L1ToL2MessageEvent::Sent => {
+                let payload = log
+                    .message
+                    .payload
+                    .iter()
+                    .flat_map(|elem| elem.0.to_be_bytes())
+                    .collect::<Vec<_>>();
+                insert
+                    .execute(params![
+                        &hash.as_bytes(),
+                        &log.l1_transaction_hash.as_bytes(),
+                        &log.l1_block_number.try_into_sql_int()?,
+                        &log.message.from_address.0.as_bytes(),
+                        &log.message.to_address,
+                        &log.message.selector,
+                        &payload,
+                        &log.message.nonce,
+                    ])
+                    .context("Inserting message")?;
+            }
+            L1ToL2MessageEvent::CancellationStarted => {
+                cancellation_started
+                    .execute(params![
+                        &log.l1_transaction_hash.as_bytes(),
+                        &hash.as_bytes()
+                    ])
+                    .context("Updating message")?;
+            }
+            L1ToL2MessageEvent::Canceled => {
+                canceled
+                    .execute(params![
+                        &log.l1_transaction_hash.as_bytes(),
+                        &hash.as_bytes()
+                    ])
+                    .context("Updating message")?;
+            }
+        }
+    }
+
+    super::options::set_option(
+        tx,
+        SYNCED_UNTIL_OPTION,
+        Some(synced_until.try_into_sql_int()?),
+    )
+}
+
+/// Returns the messages sent by the L1 transaction, in the order they were sent.
+pub(super) fn l1_to_l2_messages(
+    tx: &Transaction<'_>,
+    l1_transaction_hash: H256,
+) -> anyhow::Result<Vec<L1ToL2MessageRecord>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT
+                message_hash,
+                l1_block_number,
+                from_address,
+                to_address,
+                selector,
+                payload,
+                nonce,
+                cancellation_started_by,
+                canceled_by,
+                transaction_hash,
+                block_number
+            FROM l1_to_l2_messages
+            LEFT JOIN l1_handler_transactions USING (message_hash)
+            WHERE l1_transaction_hash = ?
+            ORDER BY l1_to_l2_messages.rowid",
+        )
+        .context("Preparing statement")?;
+
+    let mut rows = stmt
+        .query(params![&l1_transaction_hash.as_bytes()])
+        .context("Querying messages")?;
+
+    let mut messages = Vec::new();
+    while let Some(row) = rows.next().context("Fetching next message")? {
+        let payload = row
+            .get_blob(5)?
+            .chunks(32)
+            .map(|elem| Felt::from_be_slice(elem).map(L1ToL2MessagePayloadElem))
+            .collect::<Result<_, _>>()
+            .context("Parsing payload")?;
+        let consumed_by = match row.get_optional_felt(9)? {
+            Some(hash) => Some((TransactionHash(hash), row.get_block_number(10)?)),
+            None => None,
+        };
+
+        messages.push(L1ToL2MessageRecord {
+            message: L1ToL2Message {
+                from_address: EthereumAddress(H160::from_slice(row.get_blob(2)?)),
+                to_address: row.get_contract_address(3)?,
+                selector: EntryPoint(row.get_felt(4)?),
+                payload,
+                nonce: L1ToL2MessageNonce(row.get_felt(6)?),
+            },
+            message_hash: H256::from_slice(row.get_blob(0)?),
+            l1_block_number: row.get_i64(1)? as u64,
+            l1_transaction_hash,
+            cancellation_started_by: row.get_optional_blob(7)?.map(H256::from_slice),
+            canceled_by: row.get_optional_blob(8)?.map(H256::from_slice),
+            consumed_by,
+        });
+    }
+
+    Ok(messages)
+}
+
+/// Links the messages consumed by the block's L1 handler transactions to these transactions.
+pub(super) fn insert_l1_handler_transactions(
+    tx: &Transaction<'_>,
+    block_number: BlockNumber,
+    transactions: &[(gateway::Transaction, gateway::Receipt)],
+) -> anyhow::Result<()> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"INSERT OR REPLACE INTO l1_handler_transactions (message_hash, transaction_hash, block_number)
+            VALUES (?, ?, ?)",
+        )
+        .context("Preparing statement")?;
+
+    for (transaction, _) in transactions {
+        let gateway::Transaction::L1Handler(transaction) = transaction else {
+            continue;
+        };
+        let Some(message) = l1_handler_message(transaction) else {
+            continue;
+        };
+
+        stmt.execute(params![
+            &message.hash().as_bytes(),
+            &transaction.transaction_hash,
+            &block_number
+        ])
+        .context("Inserting L1 handler transaction")?;
+    }
+
+    Ok(())
+}
+
+/// Returns the message consumed by the L1 handler transaction, whose first calldata element is
+/// the sender of the message. Returns `None` if the calldata is empty.
+fn l1_handler_message(transaction: &gateway::L1HandlerTransaction) -> Option<L1ToL2Message> {
+    let (from_address, payload) = transaction.calldata.split_first()?;
+
+    Some(L1ToL2Message {
+        from_address: EthereumAddress(H160::from(H256(from_address.0.to_be_bytes()))),
+        to_address: transaction.contract_address,
+        selector: transaction.entry_point_selector,
+        payload: payload
+            .iter()
+            .map(|elem| L1ToL2MessagePayloadElem(elem.0))
+            .collect(),
+        nonce: L1ToL2MessageNonce(transaction.nonce.0),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::{BlockHeader, TransactionIndex, TransactionVersion};
+
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn message_status() {
+        let storage = Storage::in_memory().unwrap();
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+
+        let l1_transaction_hash = H256::from_low_u64_be(1);
+        let message = L1ToL2Message {
+            from_address: EthereumAddress(H160::from_low_u64_be(2)),
+            to_address: contract_address!("0x3"),
+            selector: EntryPoint(felt!("0x4")),
+            payload: vec![L1ToL2MessagePayloadElem(felt!("0x5"))],
+            nonce: L1ToL2MessageNonce(felt!("0x6")),
+        };
+        let log = |event, l1_transaction_hash| L1ToL2MessageLog {
+            message: message.clone(),
+            event,
+            l1_block_number: 10,
+            l1_transaction_hash,
+        };
+
+        tx.insert_l1_to_l2_message_logs(&[log(L1ToL2MessageEvent::Sent, l1_transaction_hash)], 10)
+            .unwrap();
+        assert_eq!(tx.l1_messages_synced_until().unwrap(), Some(10));
+
+        let expected = L1ToL2MessageRecord {
+            message: message.clone(),
+            message_hash: message.hash(),
+            l1_block_number: 10,
+            l1_transaction_hash,
+            cancellation_started_by: None,
+            canceled_by: None,
+            consumed_by: None,
+        };
+        assert_eq!(
+            tx.l1_to_l2_messages(l1_transaction_hash).unwrap(),
+            vec![expected.clone()]
+        );
+
+        let cancellation = H256::from_low_u64_be(7);
+        tx.insert_l1_to_l2_message_logs(
+            &[log(L1ToL2MessageEvent::CancellationStarted, cancellation)],
+            11,
+        )
+        .unwrap();
+
+        // Consume the message on L2.
+        let header = BlockHeader::builder().finalize_with_hash(block_hash_bytes!(b"block"));
+        tx.insert_block_header(&header).unwrap();
+        let l1_handler = gateway::L1HandlerTransaction {
+            contract_address: message.to_address,
+            entry_point_selector: message.selector,
+            nonce: transaction_nonce!("0x6"),
+            calldata: vec![call_param!("0x2"), call_param!("0x5")],
+            transaction_hash: transaction_hash_bytes!(b"l1 handler"),
+            version: TransactionVersion::ZERO,
+        };
+        let receipt = gateway::Receipt {
+            actual_fee: None,
+            events: vec![],
+            execution_resources: None,
+            l1_to_l2_consumed_message: None,
+            l2_to_l1_messages: vec![],
+            transaction_hash: l1_handler.transaction_hash,
+            transaction_index: TransactionIndex::new_or_panic(0),
+            execution_status: Default::default(),
+            revert_error: Default::default(),
+        };
+        tx.insert_transaction_data(
+            header.hash,
+            header.number,
+            &[(gateway::Transaction::L1Handler(l1_handler.clone()), receipt)],
+        )
+        .unwrap();
+
+        let expected = L1ToL2MessageRecord {
+            cancellation_started_by: Some(cancellation),
+            consumed_by: Some((l1_handler.transaction_hash, header.number)),
+            ..expected
+        };
+        assert_eq!(
+            tx.l1_to_l2_messages(l1_transaction_hash).unwrap(),
+            vec![expected]
+        );
+    }
+}
@@ -0,0 +1,34 @@
+//! Settings and progress markers kept in the `storage_options` table.
+
+use anyhow::Context;
+
+use crate::prelude::*;
+
+pub(super) fn option(tx: &Transaction<'_>, option: &str) -> anyhow::Result<Option<i64>> {
+    tx.inner()
+        .prepare_cached("SELECT value FROM storage_options WHERE option = ?")
+        .context("Preparing option query")?
+        .query_row([option], |row| row.get(0))
+        .optional()
+        .with_context(|| format!("Querying {option}"))
+}
+
+/// Removes the option if `value` is `None`.
+pub(super) fn set_option(
+    tx: &Transaction<'_>,
+    option: &str,
+    value: Option<i64>,
+) -> anyhow::Result<()> {
+    match value {
+        Some(value) => tx.inner().execute(
+            "INSERT OR REPLACE INTO storage_options (option, value) VALUES (?, ?)",
+            params![&option, &value],
+        ),
+        None => tx
+            .inner()
+            .execute("DELETE FROM storage_options WHERE option = ?", [option]),
+    }
+    .with_context(|| format!("Setting {option}"))?;
+
+    Ok(())
+}
@@ -0,0 +1,253 @@
+//! The sqlite implementation of [StorageBackend](crate::backend::StorageBackend).
+use pathfinder_common::{
+    BlockHash, BlockHeader, BlockNumber, CasmHash, ClassCommitmentLeafHash, ClassHash,
+    ContractAddress, ContractNonce, SierraHash, StateUpdate, StorageAddress, StorageValue,
+    TransactionHash,
+};
+use starknet_gateway_types::reply::transaction as gateway;
+
+use super::{block, class, event, state_update, transaction};
+use crate::backend::{
+    BlockBackend, ClassBackend, EventBackend, StateUpdateBackend, TransactionBackend,
+};
+use crate::{
+    BlockFeeData, BlockId, EventFilter, KeyFilter, PageOfEvents, StorageChange, Transaction,
+};
+
+/// Performs the accessors on the tables of the transaction's sqlite database.
+pub(crate) struct SqliteBackend<'a>(pub(super) &'a Transaction<'a>);
+
+impl BlockBackend for SqliteBackend<'_> {
+    fn insert_block_header(&self, header: &BlockHeader) -> anyhow::Result<()> {
+        block::insert_block_header(self.0, header)
+    }
+
+    fn block_header(&self, block: BlockId) -> anyhow::Result<Option<BlockHeader>> {
+        block::block_header(self.0, block)
+    }
+
+    fn purge_block(&self, block: BlockNumber) -> anyhow::Result<()> {
+        block::purge_block(self.0, block)
+    }
+
+    fn block_id(&self, block: BlockId) -> anyhow::Result<Option<(BlockNumber, BlockHash)>> {
+        block::block_id(self.0, block)
+    }
+
+    fn block_exists(&self, block: BlockId) -> anyhow::Result<bool> {
+        block::block_exists(self.0, block)
+    }
+
+    fn block_is_l1_accepted(&self, block: BlockId) -> anyhow::Result<bool> {
+        block::block_is_l1_accepted(self.0, block)
+    }
+
+    fn block_fee_history(
+        &self,
+        from: BlockNumber,
+        to: BlockNumber,
+    ) -> anyhow::Result<Vec<BlockFeeData>> {
+        block::block_fee_history(self.0, from, to)
+    }
+}
+
+impl TransactionBackend for SqliteBackend<'_> {
+    fn insert_transaction_data(
+        &self,
+        block_hash: BlockHash,
+        block_number: BlockNumber,
+        transaction_data: &[(gateway::Transaction, gateway::Receipt)],
+    ) -> anyhow::Result<()> {
+        transaction::insert_transactions(self.0, block_hash, block_number, transaction_data)
+    }
+
+    fn transaction_block_hash(&self, hash: TransactionHash) -> anyhow::Result<Option<BlockHash>> {
+        transaction::transaction_block_hash(self.0, hash)
+    }
+
+    fn transaction(&self, hash: TransactionHash) -> anyhow::Result<Option<gateway::Transaction>> {
+        transaction::transaction(self.0, hash)
+    }
+
+    fn transaction_with_receipt(
+        &self,
+        hash: TransactionHash,
+    ) -> anyhow::Result<Option<(gateway::Transaction, gateway::Receipt, BlockHash)>> {
+        transaction::transaction_with_receipt(self.0, hash)
+    }
+
+    fn transaction_at_block(
+        &self,
+        block: BlockId,
+        index: usize,
+    ) -> anyhow::Result<Option<gateway::Transaction>> {
+        transaction::transaction_at_block(self.0, block, index)
+    }
+
+    fn transaction_data_for_block(
+        &self,
+        block: BlockId,
+    ) -> anyhow::Result<Option<Vec<(gateway::Transaction, gateway::Receipt)>>> {
+        transaction::transaction_data_for_block(self.0, block)
+    }
+
+    fn transaction_count(&self, block: BlockId) -> anyhow::Result<usize> {
+        transaction::transaction_count(self.0, block)
+    }
+}
+
+impl EventBackend for SqliteBackend<'_> {
+    fn events<K: KeyFilter>(&self, filter: &EventFilter<K>) -> anyhow::Result<PageOfEvents> {
+        event::get_events(self.0, filter)
+    }
+
+    fn event_count(
+        &self,
+        from_block: Option<BlockNumber>,
+        to_block: Option<BlockNumber>,
+        contract_address: Option<ContractAddress>,
+        keys: &dyn KeyFilter,
+    ) -> anyhow::Result<usize> {
+        event::event_count(self.0, from_block, to_block, contract_address, keys)
+    }
+}
+
+impl ClassBackend for SqliteBackend<'_> {
+    fn insert_sierra_class(
+        &self,
+        sierra_hash: &SierraHash,
+        sierra_definition: &[u8],
+        casm_hash: &CasmHash,
+        casm_definition: &[u8],
+        compiler_version: &str,
+    ) -> anyhow::Result<()> {
+        class::insert_sierra_class(
+            self.0,
+            sierra_hash,
+            sierra_definition,
+            casm_hash,
+            casm_definition,
+            compiler_version,
+        )
+    }
+
+    fn insert_cairo_class(&self, cairo_hash: ClassHash, definition: &[u8]) -> anyhow::Result<()> {
+        class::insert_cairo_class(self.0, cairo_hash, definition)
+    }
+
+    fn insert_class_commitment_leaf(
+        &self,
+        leaf: &ClassCommitmentLeafHash,
+        casm_hash: &CasmHash,
+    ) -> anyhow::Result<()> {
+        class::insert_class_commitment_leaf(self.0, leaf, casm_hash)
+    }
+
+    fn class_definitions_exist(&self, classes: &[ClassHash]) -> anyhow::Result<Vec<bool>> {
+        class::classes_exist(self.0, classes)
+    }
+
+    fn class_definition(&self, class_hash: ClassHash) -> anyhow::Result<Option<Vec<u8>>> {
+        class::class_definition(self.0, class_hash)
+    }
+
+    fn class_definition_at(
+        &self,
+        block_id: BlockId,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        class::class_definition_at(self.0, block_id, class_hash)
+    }
+
+    fn casm_definition(&self, class_hash: ClassHash) -> anyhow::Result<Option<Vec<u8>>> {
+        class::casm_definition(self.0, class_hash)
+    }
+
+    fn casm_definition_at(
+        &self,
+        block_id: BlockId,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        class::casm_definition_at(self.0, block_id, class_hash)
+    }
+
+    fn casm_hash(&self, class_hash: ClassHash) -> anyhow::Result<Option<CasmHash>> {
+        class::casm_hash(self.0, class_hash)
+    }
+
+    fn casm_hash_at(
+        &self,
+        block_id: BlockId,
+        class_hash: ClassHash,
+    ) -> anyhow::Result<Option<CasmHash>> {
+        class::casm_hash_at(self.0, block_id, class_hash)
+    }
+}
+
+impl StateUpdateBackend for SqliteBackend<'_> {
+    fn insert_state_update(
+        &self,
+        block_number: BlockNumber,
+        state_update: &StateUpdate,
+    ) -> anyhow::Result<()> {
+        state_update::insert_state_update(self.0, block_number, state_update)
+    }
+
+    fn state_update(&self, block: BlockId) -> anyhow::Result<Option<StateUpdate>> {
+        state_update::state_update(self.0, block)
+    }
+
+    fn storage_value(
+        &self,
+        block: BlockId,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+    ) -> anyhow::Result<Option<StorageValue>> {
+        state_update::storage_value(self.0, block, contract_address, key)
+    }
+
+    fn storage_history(
+        &self,
+        contract_address: ContractAddress,
+        key: StorageAddress,
+        from: BlockNumber,
+        to: BlockNumber,
+        limit: usize,
+    ) -> anyhow::Result<Vec<(BlockNumber, StorageValue)>> {
+        state_update::storage_history(self.0, contract_address, key, from, to, limit)
+    }
+
+    fn storage_changes(
+        &self,
+        contract_address: ContractAddress,
+        from: BlockNumber,
+        to: BlockNumber,
+        limit: Option<usize>,
+    ) -> anyhow::Result<Vec<StorageChange>> {
+        state_update::storage_changes(self.0, contract_address, from, to, limit)
+    }
+
+    fn contract_class_hash(
+        &self,
+        block_id: BlockId,
+        contract_address: ContractAddress,
+    ) -> anyhow::Result<Option<ClassHash>> {
+        state_update::contract_class_hash(self.0, block_id, contract_address)
+    }
+
+    fn contract_nonce(
+        &self,
+        contract_address: ContractAddress,
+        block_id: BlockId,
+    ) -> anyhow::Result<Option<ContractNonce>> {
+        state_update::contract_nonce(self.0, contract_address, block_id)
+    }
+
+    fn contract_exists(
+        &self,
+        contract_address: ContractAddress,
+        block_id: BlockId,
+    ) -> anyhow::Result<bool> {
+        state_update::contract_exists(self.0, contract_address, block_id)
+    }
+}
@@ -308,6 +308,84 @@ pub(super) fn storage_value(
     .map_err(|e| e.into())
 }
 
+/// A storage value written by a block, as returned by [Transaction::storage_changes].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageChange {
+    pub block_number: BlockNumber,
+    pub key: StorageAddress,
+    pub value: StorageValue,
+}
+
+pub(super) fn storage_history(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+    key: StorageAddress,
+    from: BlockNumber,
+    to: BlockNumber,
+    limit: usize,
+) -> anyhow::Result<Vec<(BlockNumber, StorageValue)>> {
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT block_number, storage_value FROM storage_updates
+                WHERE contract_address = ? AND storage_address = ? AND block_number BETWEEN ? AND ?
+                ORDER BY block_number ASC LIMIT ?",
+        )
+        .context("Preparing statement")?;
+
+    let rows = stmt
+        .query_map(
+            params![
+                &contract_address,
+                &key,
+                &from,
+                &to,
+                &limit.try_into_sql_int()?
+            ],
+            |row| Ok((row.get_block_number(0)?, row.get_storage_value(1)?)),
+        )
+        .context("Querying storage history")?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Iterating over rows")
+}
+
+pub(super) fn storage_changes(
+    tx: &Transaction<'_>,
+    contract_address: ContractAddress,
+    from: BlockNumber,
+    to: BlockNumber,
+    limit: Option<usize>,
+) -> anyhow::Result<Vec<StorageChange>> {
+    // A negative limit means no limit.
+    let limit = match limit {
+        Some(limit) => limit.try_into_sql_int()?,
+        None => -1,
+    };
+
+    let mut stmt = tx
+        .inner()
+        .prepare_cached(
+            r"SELECT block_number, storage_address, storage_value FROM storage_updates
+                WHERE contract_address = ? AND block_number BETWEEN ? AND ?
+                ORDER BY block_number ASC, storage_address ASC LIMIT ?",
+        )
+        .context("Preparing statement")?;
+
+    let rows = stmt
+        .query_map(params![&contract_address, &from, &to, &limit], |row| {
+            Ok(StorageChange {
+                block_number: row.get_block_number(0)?,
+                key: row.get_storage_address(1)?,
+                value: row.get_storage_value(2)?,
+            })
+        })
+        .context("Querying storage changes")?;
+
+    rows.collect::<Result<Vec<_>, _>>()
+        .context("Iterating over rows")
+}
+
 pub(super) fn contract_exists(
     tx: &Transaction<'_>,
     contract_address: ContractAddress,
@@ -736,4 +814,93 @@ mod tests {
             assert_eq!(by_number, None);
         }
     }
+
+    #[test]
+    fn storage_history_and_changes() {
+        let mut db = crate::Storage::in_memory().unwrap().connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let contract = contract_address!("0x12345");
+        let other_contract = contract_address!("0x6789");
+        let key_a = storage_address!("0xa");
+        let key_b = storage_address!("0xb");
+
+        let header_0 = BlockHeader::builder().finalize_with_hash(block_hash!("0xabc"));
+        let header_1 = header_0
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xabcdef"));
+        let header_2 = header_1
+            .child_builder()
+            .finalize_with_hash(block_hash!("0xa111123"));
+
+        let diff_0 = StateUpdate::default()
+            .with_storage_update(contract, key_a, storage_value!("0x1"))
+            .with_storage_update(other_contract, key_a, storage_value!("0x100"));
+        let diff_1 = StateUpdate::default()
+            .with_storage_update(contract, key_b, storage_value!("0x2"))
+            .with_storage_update(contract, key_a, storage_value!("0x3"));
+        let diff_2 =
+            StateUpdate::default().with_storage_update(contract, key_b, storage_value!("0x4"));
+
+        for (header, diff) in [
+            (&header_0, &diff_0),
+            (&header_1, &diff_1),
+            (&header_2, &diff_2),
+        ] {
+            tx.insert_block_header(header).unwrap();
+            tx.insert_state_update(header.number, diff).unwrap();
+        }
+
+        let history = tx
+            .storage_history(contract, key_a, header_0.number, header_2.number, 10)
+            .unwrap();
+        assert_eq!(
+            history,
+            vec![
+                (header_0.number, storage_value!("0x1")),
+                (header_1.number, storage_value!("0x3")),
+            ]
+        );
+
+        let history = tx
+            .storage_history(contract, key_b, header_2.number, header_2.number, 10)
+            .unwrap();
+        assert_eq!(history, vec![(header_2.number, storage_value!("0x4"))]);
+
+        let history = tx
+            .storage_history(contract, key_a, header_0.number, header_2.number, 1)
+            .unwrap();
+        assert_eq!(history, vec![(header_0.number, storage_value!("0x1"))]);
+
+        let changes = tx
+            .storage_changes(contract, header_1.number, header_2.number, None)
+            .unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                StorageChange {
+                    block_number: header_1.number,
+                    key: key_a,
+                    value: storage_value!("0x3"),
+                },
+                StorageChange {
+                    block_number: header_1.number,
+                    key: key_b,
+                    value: storage_value!("0x2"),
+                },
+                StorageChange {
+                    block_number: header_2.number,
+                    key: key_b,
+                    value: storage_value!("0x4"),
+                },
+            ]
+        );
+
+        let changes = tx
+            .storage_changes(contract, header_0.number, header_2.number, Some(2))
+            .unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].block_number, header_0.number);
+        assert_eq!(changes[1].block_number, header_1.number);
+    }
 }
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroU64;
+
+use anyhow::Context;
+use pathfinder_common::BlockNumber;
+
+use crate::backend::{TrieBackend, TrieTable};
+use crate::prelude::*;
+
+/// Statistics on the space used by the database, see [Transaction::database_stats].
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DatabaseStats {
+    /// The tables, from largest to smallest.
+    pub tables: Vec<TableStats>,
+    pub tries: Vec<TrieStats>,
+    /// The data added by each range of blocks, in ascending block order.
+    pub block_ranges: Vec<BlockRangeStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TableStats {
+    pub name: String,
+    pub rows: u64,
+    /// The size of the table's pages, including their unused space.
+    pub bytes: u64,
+    /// The size of the pages of all the table's indexes.
+    pub index_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TrieStats {
+    pub trie: TrieTable,
+    pub nodes: u64,
+    /// Nodes with a reference count of zero. These are not part of any stored trie and only
+    /// take up space.
+    pub orphaned_nodes: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct BlockRangeStats {
+    pub first_block: BlockNumber,
+    pub last_block: BlockNumber,
+    pub transactions: u64,
+    /// The compressed size of the transactions and receipts.
+    pub transaction_bytes: u64,
+    pub events: u64,
+    pub storage_updates: u64,
+    pub nonce_updates: u64,
+    pub contract_updates: u64,
+    /// The number of classes declared.
+    pub classes: u64,
+    /// The compressed size of the declared class definitions.
+    pub class_bytes: u64,
+}
+
+pub(super) fn database_stats(
+    tx: &Transaction<'_>,
+    block_range_size: NonZeroU64,
+) -> anyhow::Result<DatabaseStats> {
+    let tables = table_stats(tx).context("Querying table sizes")?;
+
+    let tries = [TrieTable::Class, TrieTable::Contract, TrieTable::Storage]
+        .into_iter()
+        .map(|trie| {
+            let (nodes, orphaned_nodes) = tx.trie_backend().node_counts(trie)?;
+            Ok(TrieStats {
+                trie,
+                nodes,
+                orphaned_nodes,
+            })
+        })
+        .collect::<anyhow::Result<_>>()
+        .context("Counting trie nodes")?;
+
+    let block_ranges =
+        block_range_stats(tx, block_range_size).context("Querying block range sizes")?;
+
+    Ok(DatabaseStats {
+        tables,
+        tries,
+        block_ranges,
+    })
+}
+
+fn table_stats(tx: &Transaction<'_>) -> anyhow::Result<Vec<TableStats>> {
+    // Pages are attributed to the table which owns them, or to the table of the owning index.
+    // Virtual tables have no pages of their own, and are therefore left out in favour of
+    // their shadow tables.
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            r"SELECT sqlite_schema.tbl_name,
+                SUM(CASE WHEN sqlite_schema.type = 'table' THEN dbstat.pgsize ELSE 0 END),
+                SUM(CASE WHEN sqlite_schema.type = 'index' THEN dbstat.pgsize ELSE 0 END)
+            FROM dbstat
+            JOIN sqlite_schema ON dbstat.name = sqlite_schema.name
+            GROUP BY sqlite_schema.tbl_name",
+        )
+        .context("Preparing page size query")?;
+
+    let mut tables = stmt
+        .query_map([], |row| {
+            Ok(TableStats {
+                name: row.get(0)?,
+                rows: 0,
+                bytes: row.get(1)?,
+                index_bytes: row.get(2)?,
+            })
+        })
+        .context("Querying page sizes")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Iterating over page sizes")?;
+
+    for table in &mut tables {
+        table.rows = tx
+            .inner()
+            .query_row(
+                &format!(r#"SELECT COUNT(*) FROM "{}""#, table.name),
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("Counting rows of {}", table.name))?;
+    }
+
+    tables.sort_by_key(|table| std::cmp::Reverse(table.bytes + table.index_bytes));
+
+    Ok(tables)
+}
+
+fn block_range_stats(
+    tx: &Transaction<'_>,
+    block_range_size: NonZeroU64,
+) -> anyhow::Result<Vec<BlockRangeStats>> {
+    let size = block_range_size.get().try_into_sql_int()?;
+    let mut ranges = BTreeMap::new();
+
+    let mut stmt = tx
+        .inner()
+        .prepare(
+            r"SELECT canonical_blocks.number / ?, MIN(canonical_blocks.number),
+                MAX(canonical_blocks.number), SUM(transaction_count), SUM(event_count)
+            FROM canonical_blocks
+            JOIN block_headers ON block_headers.hash = canonical_blocks.hash
+            GROUP BY 1",
+        )
+        .context("Preparing block query")?;
+    let mut rows = stmt.query(params![&size]).context("Querying blocks")?;
+    while let Some(row) = rows.next().context("Iterating over blocks")? {
+        let range: u64 = row.get(0)?;
+        ranges.insert(
+            range,
+            BlockRangeStats {
+                first_block: row.get_block_number(1)?,
+                last_block: row.get_block_number(2)?,
+                transactions: row.get(3)?,
+                events: row.get(4)?,
+                ..Default::default()
+            },
+        );
+    }
+
+    add_to_ranges(
+        tx,
+        &mut ranges,
+        size,
+        "SELECT block_number / ?, COUNT(*), SUM(LENGTH(data)) FROM block_transactions GROUP BY 1",
+        |stats, _, bytes| stats.transaction_bytes = bytes,
+    )
+    .context("Querying transaction sizes")?;
+    add_to_ranges(
+        tx,
+        &mut ranges,
+        size,
+        "SELECT block_number / ?, COUNT(*), 0 FROM storage_updates GROUP BY 1",
+        |stats, count, _| stats.storage_updates = count,
+    )
+    .context("Counting storage updates")?;
+    add_to_ranges(
+        tx,
+        &mut ranges,
+        size,
+        "SELECT block_number / ?, COUNT(*), 0 FROM nonce_updates GROUP BY 1",
+        |stats, count, _| stats.nonce_updates = count,
+    )
+    .context("Counting nonce updates")?;
+    add_to_ranges(
+        tx,
+        &mut ranges,
+        size,
+        "SELECT block_number / ?, COUNT(*), 0 FROM contract_updates GROUP BY 1",
+        |stats, count, _| stats.contract_updates = count,
+    )
+    .context("Counting contract updates")?;
+    add_to_ranges(
+        tx,
+        &mut ranges,
+        size,
+        r"SELECT block_number / ?, COUNT(*), COALESCE(SUM(LENGTH(definition)), 0)
+            FROM class_definitions WHERE block_number IS NOT NULL GROUP BY 1",
+        |stats, count, bytes| {
+            stats.classes = count;
+            stats.class_bytes = bytes;
+        },
+    )
+    .context("Querying class sizes")?;
+
+    Ok(ranges.into_values().collect())
+}
+
+/// Adds the values of a query grouped by block range to the matching ranges. The query
+/// returns the range, followed by two values passed to `update`.
+fn add_to_ranges(
+    tx: &Transaction<'_>,
+    ranges: &mut BTreeMap<u64, BlockRangeStats>,
+    size: i64,
+    query: &str,
+    update: fn(&mut BlockRangeStats, u64, u64),
+) -> anyhow::Result<()> {
+    let mut stmt = tx.inner().prepare(query).context("Preparing query")?;
+    let mut rows = stmt.query(params![&size]).context("Querying")?;
+    while let Some(row) = rows.next().context("Iterating over rows")? {
+        let range: u64 = row.get(0)?;
+        if let Some(stats) = ranges.get_mut(&range) {
+            update(stats, row.get(1)?, row.get(2)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::macro_prelude::*;
+    use pathfinder_common::trie::TrieNode;
+    use pathfinder_common::BlockHeader;
+
+    use super::*;
+    use crate::Storage;
+
+    #[test]
+    fn stats() {
+        let storage = Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        let genesis = BlockHeader::builder()
+            .with_number(BlockNumber::GENESIS)
+            .with_transaction_count(3)
+            .finalize_with_hash(block_hash!("0x0"));
+        let block_1 = genesis
+            .child_builder()
+            .with_event_count(2)
+            .finalize_with_hash(block_hash!("0x1"));
+        let block_2 = block_1
+            .child_builder()
+            .with_transaction_count(1)
+            .finalize_with_hash(block_hash!("0x2"));
+        for header in [&genesis, &block_1, &block_2] {
+            tx.insert_block_header(header).unwrap();
+        }
+
+        tx.insert_class_trie(
+            class_commitment!("0x1"),
+            &[(
+                felt!("0x1"),
+                TrieNode::Binary {
+                    left: felt!("0x2"),
+                    right: felt!("0x3"),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        )
+        .unwrap();
+
+        let stats = tx.database_stats(NonZeroU64::new(2).unwrap()).unwrap();
+
+        let headers = stats
+            .tables
+            .iter()
+            .find(|table| table.name == "block_headers")
+            .unwrap();
+        assert_eq!(headers.rows, 3);
+        assert!(headers.bytes > 0);
+        assert!(headers.index_bytes > 0);
+
+        assert_eq!(
+            stats.tries[0],
+            TrieStats {
+                trie: TrieTable::Class,
+                nodes: 1,
+                orphaned_nodes: 0,
+            }
+        );
+
+        assert_eq!(
+            stats.block_ranges,
+            vec![
+                BlockRangeStats {
+                    first_block: genesis.number,
+                    last_block: block_1.number,
+                    transactions: 3,
+                    events: 2,
+                    ..Default::default()
+                },
+                BlockRangeStats {
+                    first_block: block_2.number,
+                    last_block: block_2.number,
+                    transactions: 1,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+}
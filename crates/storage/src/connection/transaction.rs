@@ -4,13 +4,23 @@ use anyhow::Context;
 use pathfinder_common::{BlockHash, BlockNumber, TransactionHash};
 use starknet_gateway_types::reply::transaction as gateway;
 
-use crate::{prelude::*, BlockId};
+use crate::{prelude::*, BlockId, BloomFilter};
 
 pub enum TransactionStatus {
     L1Accepted,
     L2Accepted,
 }
 
+/// The zstd compression level of the transaction data.
+const COMPRESSION_LEVEL: i32 = 10;
+/// The maximum size of a trained dictionary, which is zstd's default.
+const DICTIONARY_SIZE: usize = 112_640;
+/// Until a dictionary exists, training is attempted whenever the block number is a multiple of
+/// this. Training uses the data of the preceding blocks, up to this number of blocks.
+const DICTIONARY_TRAINING_INTERVAL: u64 = 1000;
+/// Fewer samples than this are not worth training a dictionary on.
+const MIN_TRAINING_SAMPLES: usize = 100;
+
 pub(super) fn insert_transactions(
     tx: &Transaction<'_>,
     block_hash: BlockHash,
@@ -21,98 +31,298 @@ pub(super) fn insert_transactions(
         return Ok(());
     }
 
-    let mut compressor = zstd::bulk::Compressor::new(10).context("Create zstd compressor")?;
-    for (i, (transaction, receipt)) in transaction_data.iter().enumerate() {
-        // Serialize and compress transaction data.
-        let tx_data = serde_json::to_vec(&transaction).context("Serializing transaction")?;
-        let tx_data = compressor
-            .compress(&tx_data)
-            .context("Compressing transaction")?;
+    let policy = super::event_index::event_index_policy(tx).context("Querying event policy")?;
 
-        let serialized_receipt = serde_json::to_vec(&receipt).context("Serializing receipt")?;
-        let serialized_receipt = compressor
-            .compress(&serialized_receipt)
-            .context("Compressing receipt")?;
+    // All transactions and receipts of the block are compressed together, as they share a lot of data.
+    // The JSON array is assembled here to record where each transaction is located in it.
+    let mut data = vec![b'['];
 
+    for (i, (transaction, receipt)) in transaction_data.iter().enumerate() {
         let execution_status = match receipt.execution_status {
             gateway::ExecutionStatus::Succeeded => 0,
             gateway::ExecutionStatus::Reverted => 1,
         };
 
-        tx.inner().execute(r"INSERT OR REPLACE INTO starknet_transactions (hash,  idx,  block_hash,  tx,  receipt,  execution_status) 
-                                                                  VALUES (:hash, :idx, :block_hash, :tx, :receipt, :execution_status)",
+        if i > 0 {
+            data.push(b',');
+        }
+        let offset = data.len();
+        serde_json::to_writer(&mut data, &(transaction, receipt))
+            .context("Serializing transaction")?;
+        let length = data.len() - offset;
+
+        tx.inner().execute(r"INSERT OR REPLACE INTO starknet_transactions (hash,  idx,  block_hash,  execution_status,  data_offset,  data_length)
+                                                                  VALUES (:hash, :idx, :block_hash, :execution_status, :data_offset, :data_length)",
             named_params![
             ":hash": &transaction.hash(),
             ":idx": &i.try_into_sql_int()?,
             ":block_hash": &block_hash,
-            ":tx": &tx_data,
-            ":receipt": &serialized_receipt,
             ":execution_status": &execution_status,
-        ]).context("Inserting transaction data")?;
+            ":data_offset": &offset.try_into_sql_int()?,
+            ":data_length": &length.try_into_sql_int()?,
+        ]).context("Inserting transaction")?;
 
         // insert events from receipt
-        super::event::insert_events(tx, block_number, receipt.transaction_hash, &receipt.events)
-            .context("Inserting events")?;
+        super::event::insert_events(
+            tx,
+            block_number,
+            receipt.transaction_hash,
+            &receipt.events,
+            &policy,
+        )
+        .context("Inserting events")?;
+    }
+
+    super::l1_message::insert_l1_handler_transactions(tx, block_number, transaction_data)
+        .context("Inserting L1 handler transactions")?;
+
+    let mut dictionary = latest_dictionary(tx).context("Querying dictionary")?;
+    if dictionary.is_none()
+        && block_number != BlockNumber::GENESIS
+        && block_number.get() % DICTIONARY_TRAINING_INTERVAL == 0
+    {
+        dictionary = train_dictionary(tx).context("Training dictionary")?;
     }
 
+    data.push(b']');
+    let (dictionary_id, data) = match dictionary {
+        Some((id, dictionary)) => {
+            let data = zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, &dictionary)
+                .context("Creating zstd compressor")?
+                .compress(&data)
+                .context("Compressing transactions")?;
+            (Some(id), data)
+        }
+        None => {
+            let data = zstd::bulk::compress(&data, COMPRESSION_LEVEL)
+                .context("Compressing transactions")?;
+            (None, data)
+        }
+    };
+
+    tx.inner()
+        .execute(
+            "INSERT OR REPLACE INTO block_transactions (block_number, dictionary_id, data) VALUES (?, ?, ?)",
+            params![&block_number, &dictionary_id, &data],
+        )
+        .context("Inserting block transactions")?;
+
+    let bloom = BloomFilter::for_events(
+        transaction_data
+            .iter()
+            .flat_map(|(_, receipt)| receipt.events.iter()),
+    );
+    super::event::insert_events_filter(tx, block_number, &bloom)
+        .context("Inserting events filter")?;
+
     Ok(())
 }
 
-pub(super) fn transaction(
-    tx: &Transaction<'_>,
-    transaction: TransactionHash,
-) -> anyhow::Result<Option<gateway::Transaction>> {
-    let mut stmt = tx
+/// Returns the id and contents of the most recently trained dictionary.
+fn latest_dictionary(tx: &Transaction<'_>) -> anyhow::Result<Option<(i64, Vec<u8>)>> {
+    tx.inner()
+        .prepare_cached(
+            "SELECT id, dictionary FROM transaction_dictionaries ORDER BY id DESC LIMIT 1",
+        )
+        .context("Preparing dictionary query")?
+        .query_row([], |row| Ok((row.get_i64(0)?, row.get_blob(1)?.to_vec())))
+        .optional()
+        .context("Querying dictionary")
+}
+
+/// Trains a dictionary on the data of the most recent blocks, which were compressed without one.
+/// Returns `None` if there are too few blocks with transactions, or training fails.
+fn train_dictionary(tx: &Transaction<'_>) -> anyhow::Result<Option<(i64, Vec<u8>)>> {
+    let samples = tx
         .inner()
-        .prepare("SELECT tx FROM starknet_transactions WHERE hash = ?")
-        .context("Preparing statement")?;
+        .prepare(
+            "SELECT data FROM block_transactions WHERE dictionary_id IS NULL ORDER BY block_number DESC LIMIT ?",
+        )
+        .context("Preparing sample query")?
+        .query_map(params![&DICTIONARY_TRAINING_INTERVAL.try_into_sql_int()?], |row| {
+            Ok(row.get_blob(0)?.to_vec())
+        })
+        .context("Querying samples")?
+        .map(|data| {
+            let data = data.context("Fetching sample")?;
+            zstd::decode_all(data.as_slice()).context("Decompressing sample")
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if samples.len() < MIN_TRAINING_SAMPLES {
+        return Ok(None);
+    }
+
+    let dictionary = match zstd::dict::from_samples(&samples, DICTIONARY_SIZE) {
+        Ok(dictionary) => dictionary,
+        Err(error) => {
+            tracing::warn!(%error, "Training transaction dictionary failed");
+            return Ok(None);
+        }
+    };
+
+    tx.inner()
+        .execute(
+            "INSERT INTO transaction_dictionaries (dictionary) VALUES (?)",
+            params![&dictionary],
+        )
+        .context("Inserting dictionary")?;
+    tracing::debug!(size=%dictionary.len(), "Trained transaction dictionary");
+
+    Ok(Some((tx.inner().last_insert_rowid(), dictionary)))
+}
+
+/// Returns the compressed transaction data of the block and the dictionary it was compressed
+/// with, or `None` if the block has no transactions.
+fn compressed_block_transactions(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    tx.inner()
+        .prepare_cached(
+            r"SELECT data, dictionary FROM block_transactions
+            LEFT JOIN transaction_dictionaries ON transaction_dictionaries.id = block_transactions.dictionary_id
+            WHERE block_number = ?",
+        )
+        .context("Preparing statement")?
+        .query_row(params![&block], |row| {
+            let data = row.get_blob(0)?.to_vec();
+            // An empty dictionary is the same as none.
+            let dictionary = row.get_optional_blob(1)?.unwrap_or_default().to_vec();
+            Ok((data, dictionary))
+        })
+        .optional()
+        .context("Querying block transactions")
+}
+
+/// Returns the transactions and receipts of the block, or `None` if the block has no transactions.
+fn block_transactions(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<Option<Vec<(gateway::Transaction, gateway::Receipt)>>> {
+    let Some((data, dictionary)) = compressed_block_transactions(tx, block)? else {
+        return Ok(None);
+    };
+
+    let mut decoder = zstd::stream::read::Decoder::with_dictionary(data.as_slice(), &dictionary)
+        .context("Creating zstd decoder")?;
+    let mut transactions = Vec::new();
+    std::io::Read::read_to_end(&mut decoder, &mut transactions)
+        .context("Decompressing transactions")?;
+    let transactions =
+        serde_json::from_slice(&transactions).context("Deserializing transactions")?;
+
+    Ok(Some(transactions))
+}
+
+/// Where a transaction is stored.
+struct TransactionLocation {
+    block_number: BlockNumber,
+    block_hash: BlockHash,
+    index: usize,
+    /// The offset and length of the transaction within the decompressed data of its block.
+    data: (usize, usize),
+}
 
-    let mut rows = stmt
-        .query(params![&transaction])
-        .context("Executing query")?;
+/// Returns the transaction and receipt at the location, only decompressing the block's data up
+/// to it and only deserializing the transaction itself.
+fn block_transaction(
+    tx: &Transaction<'_>,
+    location: &TransactionLocation,
+) -> anyhow::Result<Option<(gateway::Transaction, gateway::Receipt)>> {
+    let (offset, length) = location.data;
 
-    let row = match rows.next()? {
-        Some(row) => row,
-        None => return Ok(None),
+    let Some((data, dictionary)) = compressed_block_transactions(tx, location.block_number)? else {
+        return Ok(None);
     };
 
-    let transaction = row.get_ref_unwrap(0).as_blob()?;
-    let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
+    let mut decoder = zstd::stream::read::Decoder::with_dictionary(data.as_slice(), &dictionary)
+        .context("Creating zstd decoder")?;
+    std::io::copy(
+        &mut std::io::Read::take(&mut decoder, offset as u64),
+        &mut std::io::sink(),
+    )
+    .context("Decompressing preceding transactions")?;
+    let mut transaction = vec![0; length];
+    std::io::Read::read_exact(&mut decoder, &mut transaction)
+        .context("Decompressing transaction")?;
     let transaction = serde_json::from_slice(&transaction).context("Deserializing transaction")?;
 
     Ok(Some(transaction))
 }
 
-pub(super) fn transaction_with_receipt(
+fn transaction_location(
     tx: &Transaction<'_>,
-    txn_hash: TransactionHash,
-) -> anyhow::Result<Option<(gateway::Transaction, gateway::Receipt, BlockHash)>> {
-    let mut stmt = tx
-        .inner()
-        .prepare("SELECT tx, receipt, block_hash FROM starknet_transactions WHERE hash = ?1")
-        .context("Preparing statement")?;
+    transaction: TransactionHash,
+) -> anyhow::Result<Option<TransactionLocation>> {
+    tx.inner()
+        .prepare_cached(
+            r"SELECT canonical_blocks.number, block_hash, idx, data_offset, data_length FROM starknet_transactions
+            JOIN canonical_blocks ON canonical_blocks.hash = starknet_transactions.block_hash
+            WHERE starknet_transactions.hash = ?",
+        )
+        .context("Preparing statement")?
+        .query_row(params![&transaction], |row| {
+            Ok(TransactionLocation {
+                block_number: row.get_block_number(0)?,
+                block_hash: row.get_block_hash(1)?,
+                index: row.get_i64(2)? as usize,
+                data: data_location(row, 3)?,
+            })
+        })
+        .optional()
+        .context("Querying transaction location")
+}
 
-    let mut rows = stmt.query(params![&txn_hash]).context("Executing query")?;
+fn transaction_location_at_block(
+    tx: &Transaction<'_>,
+    block_number: BlockNumber,
+    block_hash: BlockHash,
+    index: usize,
+) -> anyhow::Result<Option<TransactionLocation>> {
+    tx.inner()
+        .prepare_cached(
+            "SELECT data_offset, data_length FROM starknet_transactions WHERE block_hash = ? AND idx = ?",
+        )
+        .context("Preparing statement")?
+        .query_row(params![&block_hash, &index.try_into_sql_int()?], |row| {
+            Ok(TransactionLocation {
+                block_number,
+                block_hash,
+                index,
+                data: data_location(row, 0)?,
+            })
+        })
+        .optional()
+        .context("Querying transaction location")
+}
 
-    let row = match rows.next()? {
-        Some(row) => row,
-        None => return Ok(None),
-    };
+/// Reads the offset and length of the transaction data from the columns starting at `index`.
+fn data_location(row: &rusqlite::Row<'_>, index: usize) -> rusqlite::Result<(usize, usize)> {
+    let offset = row.get_i64(index)?;
+    let length = row.get_i64(index + 1)?;
+    Ok((offset as usize, length as usize))
+}
 
-    let transaction = row.get_ref_unwrap("tx").as_blob()?;
-    let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-    let transaction = serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+pub(super) fn transaction(
+    tx: &Transaction<'_>,
+    transaction: TransactionHash,
+) -> anyhow::Result<Option<gateway::Transaction>> {
+    Ok(transaction_with_receipt(tx, transaction)?.map(|(transaction, _, _)| transaction))
+}
 
-    let receipt = match row.get_ref_unwrap("receipt").as_blob_or_null()? {
-        Some(data) => data,
-        None => return Ok(None),
+pub(super) fn transaction_with_receipt(
+    tx: &Transaction<'_>,
+    txn_hash: TransactionHash,
+) -> anyhow::Result<Option<(gateway::Transaction, gateway::Receipt, BlockHash)>> {
+    let Some(location) = transaction_location(tx, txn_hash)? else {
+        return Ok(None);
     };
-    let receipt = zstd::decode_all(receipt).context("Decompressing receipt")?;
-    let receipt = serde_json::from_slice(&receipt).context("Deserializing receipt")?;
 
-    let block_hash = row.get_block_hash("block_hash")?;
+    let transaction = block_transaction(tx, &location)?
+        .map(|(transaction, receipt)| (transaction, receipt, location.block_hash));
 
-    Ok(Some((transaction, receipt, block_hash)))
+    Ok(transaction)
 }
 
 pub(super) fn transaction_at_block(
@@ -120,34 +330,16 @@ pub(super) fn transaction_at_block(
     block: BlockId,
     index: usize,
 ) -> anyhow::Result<Option<gateway::Transaction>> {
-    // Identify block hash
-    let Some((_, block_hash)) = tx.block_id(block)? else {
+    let Some((block_number, block_hash)) = tx.block_id(block)? else {
         return Ok(None);
     };
-
-    let mut stmt = tx
-        .inner()
-        .prepare("SELECT tx FROM starknet_transactions WHERE block_hash = ? AND idx = ?")
-        .context("Preparing statement")?;
-
-    let mut rows = stmt
-        .query(params![&block_hash, &index.try_into_sql_int()?])
-        .context("Executing query")?;
-
-    let row = match rows.next()? {
-        Some(row) => row,
-        None => return Ok(None),
-    };
-
-    let transaction = match row.get_ref_unwrap(0).as_blob_or_null()? {
-        Some(data) => data,
-        None => return Ok(None),
+    let Some(location) = transaction_location_at_block(tx, block_number, block_hash, index)? else {
+        return Ok(None);
     };
 
-    let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-    let transaction = serde_json::from_slice(&transaction).context("Deserializing transaction")?;
+    let transaction = block_transaction(tx, &location)?.map(|(transaction, _)| transaction);
 
-    Ok(Some(transaction))
+    Ok(transaction)
 }
 
 pub(super) fn transaction_count(tx: &Transaction<'_>, block: BlockId) -> anyhow::Result<usize> {
@@ -186,41 +378,11 @@ pub(super) fn transaction_data_for_block(
     tx: &Transaction<'_>,
     block: BlockId,
 ) -> anyhow::Result<Option<Vec<(gateway::Transaction, gateway::Receipt)>>> {
-    let Some((_, block_hash)) = tx.block_id(block)? else {
+    let Some((block_number, _)) = tx.block_id(block)? else {
         return Ok(None);
     };
 
-    let mut stmt = tx
-        .inner()
-        .prepare(
-            "SELECT tx, receipt FROM starknet_transactions WHERE block_hash = ? ORDER BY idx ASC",
-        )
-        .context("Preparing statement")?;
-
-    let mut rows = stmt
-        .query(params![&block_hash])
-        .context("Executing query")?;
-
-    let mut data = Vec::new();
-    while let Some(row) = rows.next()? {
-        let receipt = row
-            .get_ref_unwrap("receipt")
-            .as_blob_or_null()?
-            .context("Receipt data missing")?;
-        let receipt = zstd::decode_all(receipt).context("Decompressing transaction receipt")?;
-        let receipt =
-            serde_json::from_slice(&receipt).context("Deserializing transaction receipt")?;
-
-        let transaction = row
-            .get_ref_unwrap("tx")
-            .as_blob_or_null()?
-            .context("Transaction data missing")?;
-        let transaction = zstd::decode_all(transaction).context("Decompressing transaction")?;
-        let transaction =
-            serde_json::from_slice(&transaction).context("Deserializing transaction")?;
-
-        data.push((transaction, receipt));
-    }
+    let data = block_transactions(tx, block_number)?.unwrap_or_default();
 
     Ok(Some(data))
 }
@@ -508,4 +670,47 @@ mod tests {
             super::transaction_block_hash(&tx, transaction_hash_bytes!(b"invalid hash")).unwrap();
         assert_eq!(invalid, None);
     }
+
+    #[test]
+    fn compressed_with_dictionary() {
+        let (mut db, header, body) = setup();
+        let tx = db.transaction().unwrap();
+
+        // zstd accepts any content as a raw dictionary.
+        let dictionary = serde_json::to_vec(&body[..2]).unwrap();
+        tx.inner()
+            .execute(
+                "INSERT INTO transaction_dictionaries (dictionary) VALUES (?)",
+                params![&dictionary],
+            )
+            .unwrap();
+
+        let child = header
+            .child_builder()
+            .finalize_with_hash(block_hash_bytes!(b"child hash"));
+        tx.insert_block_header(&child).unwrap();
+        tx.insert_transaction_data(child.hash, child.number, &body[..3])
+            .unwrap();
+
+        let dictionary_id: Option<i64> = tx
+            .inner()
+            .query_row(
+                "SELECT dictionary_id FROM block_transactions WHERE block_number = ?",
+                params![&child.number],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(dictionary_id, Some(1));
+
+        let result = super::transaction_data_for_block(&tx, child.number.into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(result, &body[..3]);
+
+        // Blocks compressed before the dictionary existed are unaffected.
+        let result = super::transaction_data_for_block(&tx, header.number.into())
+            .unwrap()
+            .unwrap();
+        assert_eq!(result, body);
+    }
 }
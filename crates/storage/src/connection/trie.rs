@@ -2,71 +2,173 @@ use std::collections::HashMap;
 
 use anyhow::Context;
 use pathfinder_common::trie::TrieNode;
+use pathfinder_common::{BlockNumber, ClassCommitment, ContractRoot, StorageCommitment};
 use stark_hash::Felt;
 
+use crate::backend::{TrieBackend, TrieTable};
 use crate::prelude::*;
 
-insert_trie!(insert_class_trie, tree_class, ClassTrieReader);
-insert_trie!(insert_contract_trie, tree_contracts, ContractTrieReader);
-insert_trie!(insert_storage_trie, tree_global, StorageTrieReader);
-
-/// Creates the sql for inserting a node or incrementing its reference count if it already exists, returning
-/// the final reference count.
-macro_rules! insert_trie {
-    ($fn_name: ident, $table: ident, $reader_struct: ident) => {
-        /// Stores the node data for this trie in the `$table` table and returns the number of
-        /// new nodes that were added i.e. the nodes not already present in the database.
-        ///
-        /// Inserts nodes starting from the root.
-        ///
-        /// **NOTE**: since [TrieNode] does not identify leaf nodes explicitly, this function assumes that
-        /// any node definition not present in the hash map is in fact a leaf node. This means we recurse through
-        /// child nodes until the child node already exists in the database, or the child definition is not present
-        /// in the hash map (indicating it is a leaf node, and should not be inserted).
-        pub(super) fn $fn_name(
-            tx: &rusqlite::Transaction<'_>,
-            root: Felt,
-            nodes: &HashMap<Felt, TrieNode>,
-        ) -> anyhow::Result<usize> {
-            let mut to_insert = Vec::new();
-            to_insert.push(root);
-
-            let mut stmt = tx
-                .prepare_cached(concat!(
-                    "INSERT OR IGNORE INTO ",
-                    stringify!($table),
-                    "(hash, data) VALUES(?, ?) ",
-                ))
-                .context("Creating insert statement")?;
-
-            let mut count = 0;
-
-            // cargo fmt misbehaves on the let some else expression.
-            #[cfg_attr(rustfmt, rustfmt_skip)]
-            while let Some(hash) = to_insert.pop() {
-                let Some(node) = nodes.get(&hash) else {
-                    continue;
-                };
-                let inserted = stmt
-                    .execute(params![&hash.as_be_bytes().as_slice(), node])
-                    .context("Inserting node")?;
-
-                if inserted == 1 {
-                    count += 1;
-
-                    match node {
-                        TrieNode::Binary { left, right } => {
-                            to_insert.push(*left);
-                            to_insert.push(*right);
-                        }
-                        TrieNode::Edge { child, .. } => to_insert.push(*child),
-                    }
-                }
+trie_reader!(ClassTrieReader, TrieTable::Class);
+trie_reader!(ContractTrieReader, TrieTable::Contract);
+trie_reader!(StorageTrieReader, TrieTable::Storage);
+
+/// The height of all of Starknet's tries. Nodes at this depth are leaves, which are not
+/// stored in the trie tables.
+const TRIE_HEIGHT: usize = 251;
+
+/// The `storage_options` entry holding the number of blocks of trie history to keep. It is
+/// only present if the database is in [TriePruneMode::Prune].
+const TRIE_HISTORY_OPTION: &str = "trie_history";
+
+/// Determines how much history of the state tries is kept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TriePruneMode {
+    /// The tries of all blocks are kept.
+    Archive,
+    /// Only the tries of the latest block and the `history` blocks preceding it are kept.
+    Prune { history: u64 },
+}
+
+/// The root of a trie being replaced by a new root at some block.
+///
+/// The old root is removed once the block falls out of the history kept in
+/// [TriePruneMode::Prune], or the new root is removed if the block is purged by a reorg.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TrieRootChange {
+    Storage {
+        old: StorageCommitment,
+        new: StorageCommitment,
+    },
+    Contract {
+        old: ContractRoot,
+        new: ContractRoot,
+    },
+    Class {
+        old: ClassCommitment,
+        new: ClassCommitment,
+    },
+}
+
+/// Identifies the trie table in `trie_root_changes`.
+const STORAGE_TRIE: i64 = 0;
+const CONTRACT_TRIE: i64 = 1;
+const CLASS_TRIE: i64 = 2;
+
+impl TrieRootChange {
+    fn into_parts(self) -> (i64, Felt, Felt) {
+        match self {
+            TrieRootChange::Storage { old, new } => (STORAGE_TRIE, old.0, new.0),
+            TrieRootChange::Contract { old, new } => (CONTRACT_TRIE, old.0, new.0),
+            TrieRootChange::Class { old, new } => (CLASS_TRIE, old.0, new.0),
+        }
+    }
+}
+
+/// Stores the nodes of the trie with the given root and returns the number of new nodes that
+/// were added i.e. the nodes not already present in the backend.
+///
+/// Inserts nodes starting from the root. The reference count of a node is the number of
+/// parent nodes referencing it, plus the number of times it was inserted as a root. Each
+/// call therefore adds a reference to the root, which is released by [remove_trie].
+///
+/// **NOTE**: since [TrieNode] does not identify leaf nodes explicitly, this function tracks
+/// the height of each node to identify leaves, which are not inserted. Any other node
+/// definition not present in the hash map is assumed to exist in the backend already, in
+/// which case only its reference count is incremented.
+pub(super) fn insert_trie(
+    backend: impl TrieBackend,
+    trie: TrieTable,
+    root: Felt,
+    nodes: &HashMap<Felt, TrieNode>,
+) -> anyhow::Result<usize> {
+    let mut to_insert = Vec::new();
+    to_insert.push((root, TRIE_HEIGHT));
+
+    let mut count = 0;
+
+    while let Some((hash, height)) = to_insert.pop() {
+        if height == 0 {
+            continue;
+        }
+
+        let inserted = match nodes.get(&hash) {
+            Some(node) => backend.insert_node(trie, &hash, node)?,
+            None => false,
+        };
+
+        if !inserted {
+            backend.reference_node(trie, &hash)?;
+            continue;
+        }
+
+        count += 1;
+
+        match &nodes[&hash] {
+            TrieNode::Binary { left, right } => {
+                to_insert.push((*left, height - 1));
+                to_insert.push((*right, height - 1));
+            }
+            TrieNode::Edge { child, path } => {
+                to_insert.push((*child, height.saturating_sub(path.len())))
             }
+        }
+    }
+
+    Ok(count)
+}
 
-            Ok(count)
+/// Releases one reference to the trie with the given root, as added by [insert_trie]. Nodes
+/// which are no longer referenced are deleted, which in turn releases their references to
+/// their children.
+///
+/// Returns the number of deleted nodes. Nodes without a reference count, which were
+/// inserted before reference counting was introduced, are never deleted.
+pub(super) fn remove_trie(
+    backend: impl TrieBackend,
+    trie: TrieTable,
+    root: Felt,
+) -> anyhow::Result<usize> {
+    let mut to_remove = Vec::new();
+    to_remove.push((root, TRIE_HEIGHT));
+
+    let mut count = 0;
+
+    while let Some((hash, height)) = to_remove.pop() {
+        if height == 0 {
+            continue;
         }
 
+        let (node, ref_count) = match backend.node(trie, &hash)? {
+            Some((node, Some(ref_count))) => (node, ref_count),
+            // Either the empty trie, or an untracked node.
+            Some((_, None)) | None => continue,
+        };
+
+        if ref_count > 1 {
+            backend.release_node(trie, &hash)?;
+            continue;
+        }
+
+        backend.delete_node(trie, &hash)?;
+        count += 1;
+
+        match node {
+            TrieNode::Binary { left, right } => {
+                to_remove.push((left, height - 1));
+                to_remove.push((right, height - 1));
+            }
+            TrieNode::Edge { child, path } => {
+                to_remove.push((child, height.saturating_sub(path.len())))
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+/// Creates a reader for the nodes of a trie, for use as the storage of a merkle tree.
+macro_rules! trie_reader {
+    ($reader_struct: ident, $trie: expr) => {
         pub struct $reader_struct<'tx>(&'tx Transaction<'tx>);
 
         impl<'tx> $reader_struct<'tx> {
@@ -75,30 +177,174 @@ macro_rules! insert_trie {
             }
 
             pub fn get(&self, node: &stark_hash::Felt) -> anyhow::Result<Option<TrieNode>> {
-                // We rely on sqlite caching the statement here. Storing the statement would be nice,
-                // however that leads to &mut requirements or interior mutable work-arounds.
-                let mut stmt = self
-                    .0
-                    .inner()
-                    .prepare_cached(concat!(
-                        "SELECT data FROM ",
-                        stringify!($table),
-                        " WHERE hash = ?",
-                    ))
-                    .context("Creating get statement")?;
-
-                let node: Option<TrieNode> = stmt
-                    .query_row(params![&node.as_be_bytes().as_slice()], |row| {
-                        row.get_trie_node(0)
-                    })
-                    .optional()?;
-
-                Ok(node)
+                let node = self.0.trie_backend().node($trie, node)?;
+                Ok(node.map(|(node, _)| node))
             }
         }
     };
 }
-use insert_trie;
+use trie_reader;
+
+pub(super) fn trie_prune_mode(tx: &Transaction<'_>) -> anyhow::Result<TriePruneMode> {
+    let history = super::options::option(tx, TRIE_HISTORY_OPTION)?;
+
+    Ok(match history {
+        Some(history) => TriePruneMode::Prune {
+            history: history as u64,
+        },
+        None => TriePruneMode::Archive,
+    })
+}
+
+pub(super) fn set_trie_prune_mode(tx: &Transaction<'_>, mode: TriePruneMode) -> anyhow::Result<()> {
+    match (trie_prune_mode(tx)?, mode) {
+        (TriePruneMode::Archive, TriePruneMode::Archive) => return Ok(()),
+        (TriePruneMode::Prune { .. }, TriePruneMode::Archive) => {
+            anyhow::bail!("The state tries of this database have been pruned, it cannot be used in archive mode")
+        }
+        (TriePruneMode::Archive, TriePruneMode::Prune { .. }) => {
+            // Tries inserted before now lack the root changes required to prune them.
+            let is_empty = tx
+                .block_id(crate::BlockId::Latest)
+                .context("Querying latest block")?
+                .is_none();
+            anyhow::ensure!(
+                is_empty,
+                "State trie pruning can only be enabled on a new database"
+            );
+        }
+        (TriePruneMode::Prune { .. }, TriePruneMode::Prune { .. }) => {}
+    }
+
+    if let TriePruneMode::Prune { history } = mode {
+        super::options::set_option(tx, TRIE_HISTORY_OPTION, Some(history.try_into_sql_int()?))?;
+    }
+
+    Ok(())
+}
+
+/// Records the change of a trie's root at `block` so that the old root can be pruned later on.
+/// Does nothing unless the database is in [TriePruneMode::Prune].
+pub(super) fn insert_trie_root_change(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+    change: TrieRootChange,
+) -> anyhow::Result<()> {
+    if trie_prune_mode(tx)? == TriePruneMode::Archive {
+        return Ok(());
+    }
+
+    let (trie, old, new) = change.into_parts();
+    tx.inner()
+        .prepare_cached(
+            "INSERT INTO trie_root_changes (block_number, trie, old_root, new_root) VALUES (?, ?, ?, ?)",
+        )
+        .context("Preparing root change insert statement")?
+        .execute(params![
+            &block,
+            &trie,
+            &old.as_be_bytes().as_slice(),
+            &new.as_be_bytes().as_slice(),
+        ])
+        .context("Inserting root change")?;
+
+    Ok(())
+}
+
+/// Removes the tries which are no longer required by any block in the history kept for `head`,
+/// i.e. the roots which were replaced at or before block `head - history`.
+pub(super) fn prune_tries(tx: &Transaction<'_>, head: BlockNumber) -> anyhow::Result<()> {
+    let TriePruneMode::Prune { history } = trie_prune_mode(tx)? else {
+        return Ok(());
+    };
+    let Some(last) = head
+        .get()
+        .checked_sub(history)
+        .map(BlockNumber::new_or_panic)
+    else {
+        return Ok(());
+    };
+
+    let removed = remove_trie_roots(
+        tx,
+        "SELECT trie, old_root FROM trie_root_changes WHERE block_number <= ?",
+        "DELETE FROM trie_root_changes WHERE block_number <= ?",
+        last,
+    )?;
+    tracing::trace!(%head, removed_nodes=%removed, "Pruned state tries");
+
+    Ok(())
+}
+
+/// Undoes the root changes of a block which is being purged, by removing the tries which
+/// were inserted for it. The roots it replaced are in use again, and are no longer pruned.
+pub(super) fn revert_trie_root_changes(
+    tx: &Transaction<'_>,
+    block: BlockNumber,
+) -> anyhow::Result<()> {
+    remove_trie_roots(
+        tx,
+        "SELECT trie, new_root FROM trie_root_changes WHERE block_number = ?",
+        "DELETE FROM trie_root_changes WHERE block_number = ?",
+        block,
+    )?;
+
+    Ok(())
+}
+
+/// Returns true if the state tries of the block have been pruned.
+pub(super) fn block_pruned(tx: &Transaction<'_>, block: BlockNumber) -> anyhow::Result<bool> {
+    let TriePruneMode::Prune { history } = trie_prune_mode(tx)? else {
+        return Ok(false);
+    };
+    let Some((latest, _)) = tx
+        .block_id(crate::BlockId::Latest)
+        .context("Querying latest block")?
+    else {
+        return Ok(false);
+    };
+
+    Ok(block.get().saturating_add(history) < latest.get())
+}
+
+/// Removes the roots selected from `trie_root_changes` by `select`, and then deletes the
+/// changes using `delete`. Both queries take `block` as their only parameter.
+fn remove_trie_roots(
+    tx: &Transaction<'_>,
+    select: &str,
+    delete: &str,
+    block: BlockNumber,
+) -> anyhow::Result<usize> {
+    let roots = tx
+        .inner()
+        .prepare_cached(select)
+        .context("Preparing root change query")?
+        .query_map(params![&block], |row| {
+            let trie = row.get_i64(0)?;
+            let root = row.get_felt(1)?;
+            Ok((trie, root))
+        })
+        .context("Querying root changes")?
+        .collect::<Result<Vec<_>, _>>()
+        .context("Iterating over root changes")?;
+
+    let mut removed = 0;
+    for (trie, root) in roots {
+        let trie = match trie {
+            STORAGE_TRIE => TrieTable::Storage,
+            CONTRACT_TRIE => TrieTable::Contract,
+            CLASS_TRIE => TrieTable::Class,
+            other => anyhow::bail!("Unknown trie {other} in root changes"),
+        };
+        removed += remove_trie(tx.trie_backend(), trie, root).context("Removing trie")?;
+    }
+
+    tx.inner()
+        .execute(delete, params![&block])
+        .context("Deleting root changes")?;
+
+    Ok(removed)
+}
 
 #[cfg(test)]
 mod tests {
@@ -126,15 +372,9 @@ mod tests {
         //                  /         \
         //            leaf 1           leaf 2
 
-        insert_trie!(insert_test, tree_test, TestTrieReader);
-
-        let mut db = rusqlite::Connection::open_in_memory().unwrap();
+        let storage = crate::Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
         let tx = db.transaction().unwrap();
-        tx.execute(
-            "CREATE TABLE tree_test(hash BLOB PRIMARY KEY, data BLOB, ref_count INTEGER)",
-            [],
-        )
-        .unwrap();
 
         let root = felt_bytes!(b"root node");
         let l_child = felt_bytes!(b"left child");
@@ -173,22 +413,19 @@ mod tests {
         nodes.insert(edge, edge_node.clone());
         nodes.insert(duplicate, duplicate_node.clone());
 
-        let count = insert_test(&tx, root, &nodes).unwrap();
-        let db_count: usize = tx
-            .query_row("SELECT COUNT(1) FROM tree_test", [], |row| row.get(0))
-            .unwrap();
+        let count = tx.insert_class_trie(ClassCommitment(root), &nodes).unwrap();
 
         // We expect inserts for the root, left and right children, edge and duplicate node.
         assert_eq!(count, 5);
-        assert_eq!(count, db_count);
 
         // Inserting the same trie again should do nothing
-        let count = insert_test(&tx, root, &nodes).unwrap();
+        let count = tx.insert_class_trie(ClassCommitment(root), &nodes).unwrap();
         assert_eq!(count, 0);
 
-        let tx = Transaction::from_inner(tx);
+        let root_hash = root;
+        let hashes = [root, l_child, r_child, edge, duplicate];
 
-        let reader = TestTrieReader::new(&tx);
+        let reader = tx.class_trie_reader();
 
         let root = reader.get(&root).unwrap().unwrap();
         assert_eq!(root, root_node);
@@ -205,5 +442,100 @@ mod tests {
         assert!(leaf_1.is_none());
         let leaf_2 = reader.get(&leaf_2).unwrap();
         assert!(leaf_2.is_none());
+
+        // The trie was inserted twice, so the first removal should only release the root.
+        let count = remove_trie(tx.trie_backend(), TrieTable::Class, root_hash).unwrap();
+        assert_eq!(count, 0);
+
+        // The duplicate node is referenced twice and must only be deleted along with its last parent.
+        let count = remove_trie(tx.trie_backend(), TrieTable::Class, root_hash).unwrap();
+        assert_eq!(count, 5);
+        for hash in hashes {
+            assert!(reader.get(&hash).unwrap().is_none());
+        }
+    }
+
+    /// A storage trie with a single leaf, consisting of only the root edge node.
+    fn single_leaf_trie(key: Felt, value: Felt) -> (Felt, HashMap<Felt, TrieNode>) {
+        let node = TrieNode::Edge {
+            child: value,
+            path: key.view_bits().to_bitvec(),
+        };
+        let root = node.hash::<pathfinder_common::hash::PedersenHash>();
+        (root, HashMap::from([(root, node)]))
+    }
+
+    #[test]
+    fn prune_mode() {
+        let storage = crate::Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+
+        assert_eq!(tx.trie_prune_mode().unwrap(), TriePruneMode::Archive);
+        tx.set_trie_prune_mode(TriePruneMode::Prune { history: 10 })
+            .unwrap();
+        assert_eq!(
+            tx.trie_prune_mode().unwrap(),
+            TriePruneMode::Prune { history: 10 }
+        );
+        tx.set_trie_prune_mode(TriePruneMode::Prune { history: 5 })
+            .unwrap();
+        assert_eq!(
+            tx.trie_prune_mode().unwrap(),
+            TriePruneMode::Prune { history: 5 }
+        );
+        tx.set_trie_prune_mode(TriePruneMode::Archive).unwrap_err();
+    }
+
+    #[test]
+    fn prune_tries() {
+        let storage = crate::Storage::in_memory().unwrap();
+        let mut db = storage.connection().unwrap();
+        let tx = db.transaction().unwrap();
+        tx.set_trie_prune_mode(TriePruneMode::Prune { history: 1 })
+            .unwrap();
+
+        let (root_0, nodes_0) = single_leaf_trie(felt_bytes!(b"key"), felt_bytes!(b"value 0"));
+        let (root_1, nodes_1) = single_leaf_trie(felt_bytes!(b"key"), felt_bytes!(b"value 1"));
+        let (root_2, nodes_2) = single_leaf_trie(felt_bytes!(b"key"), felt_bytes!(b"value 2"));
+
+        tx.insert_storage_trie(root_0, &nodes_0).unwrap();
+        tx.insert_storage_trie(root_1, &nodes_1).unwrap();
+        tx.insert_trie_root_change(
+            BlockNumber::new_or_panic(1),
+            TrieRootChange::Storage {
+                old: StorageCommitment(root_0),
+                new: StorageCommitment(root_1),
+            },
+        )
+        .unwrap();
+        tx.insert_storage_trie(root_2, &nodes_2).unwrap();
+        tx.insert_trie_root_change(
+            BlockNumber::new_or_panic(2),
+            TrieRootChange::Storage {
+                old: StorageCommitment(root_1),
+                new: StorageCommitment(root_2),
+            },
+        )
+        .unwrap();
+
+        let reader = tx.storage_trie_reader();
+
+        // Block 0 is still within the history of block 1.
+        tx.prune_tries(BlockNumber::new_or_panic(1)).unwrap();
+        assert!(reader.get(&root_0).unwrap().is_some());
+
+        tx.prune_tries(BlockNumber::new_or_panic(2)).unwrap();
+        assert!(reader.get(&root_0).unwrap().is_none());
+        assert!(reader.get(&root_1).unwrap().is_some());
+
+        // Reverting block 2 removes its trie, leaving block 1's trie in place.
+        revert_trie_root_changes(&tx, BlockNumber::new_or_panic(2)).unwrap();
+        assert!(reader.get(&root_1).unwrap().is_some());
+        assert!(reader.get(&root_2).unwrap().is_none());
+
+        // The reverted change must no longer prune block 1's trie.
+        tx.prune_tries(BlockNumber::new_or_panic(3)).unwrap();
+        assert!(reader.get(&root_1).unwrap().is_some());
     }
 }
@@ -1,24 +1,31 @@
 //! Local storage.
 //!
-//! Currently this consists of a Sqlite backend implementation.
+//! Currently this consists of a Sqlite backend implementation, accessed through the traits
+//! of the [backend] module. The nodes of the state tries can be stored in a different
+//! backend instead.
 
 // This is intended for internal use only -- do not make public.
 mod prelude;
 
+pub mod backend;
+mod bloom;
 mod connection;
 pub mod fake;
 mod params;
 mod schema;
+pub mod snapshot;
 pub mod test_utils;
 
 use std::num::NonZeroU32;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+pub use bloom::BloomFilter;
 pub use connection::*;
 
 use pathfinder_common::{BlockHash, BlockNumber};
 use rusqlite::functions::FunctionFlags;
+use rusqlite::OpenFlags;
 
 use anyhow::Context;
 use r2d2::Pool;
@@ -88,20 +95,36 @@ struct Inner {
     /// Uses [`Arc`] to allow _shallow_ [Storage] cloning
     database_path: Arc<PathBuf>,
     pool: Pool<SqliteConnectionManager>,
+    tries: backend::TrieStore,
 }
 
-pub struct StorageManager(PathBuf);
+pub struct StorageManager {
+    database_path: PathBuf,
+    tries: backend::TrieStore,
+    flags: OpenFlags,
+}
 
 impl StorageManager {
+    fn new(database_path: PathBuf, flags: OpenFlags) -> Self {
+        Self {
+            database_path,
+            tries: backend::TrieStore::default(),
+            flags,
+        }
+    }
+
     pub fn create_pool(&self, capacity: NonZeroU32) -> anyhow::Result<Storage> {
-        let pool_manager = SqliteConnectionManager::file(&self.0).with_init(setup_connection);
+        let pool_manager = SqliteConnectionManager::file(&self.database_path)
+            .with_flags(self.flags)
+            .with_init(setup_connection);
         let pool = Pool::builder()
             .max_size(capacity.get())
             .build(pool_manager)?;
 
         Ok(Storage(Inner {
-            database_path: Arc::new(self.0.clone()),
+            database_path: Arc::new(self.database_path.clone()),
             pool,
+            tries: self.tries.clone(),
         }))
     }
 }
@@ -122,18 +145,48 @@ impl Storage {
         setup_connection(&mut connection).context("Setting up database connection")?;
         setup_journal_mode(&mut connection, journal_mode).context("Setting journal mode")?;
         migrate_database(&mut connection).context("Migrate database")?;
+        backend::TrieStore::check_database(&connection)?;
         connection
             .close()
             .map_err(|(_connection, error)| error)
             .context("Closing DB after setting journal mode")?;
 
-        Ok(StorageManager(database_path))
+        Ok(StorageManager::new(database_path, OpenFlags::default()))
+    }
+
+    /// Opens an existing database without modifying it, and returns a [storage manager](StorageManager)
+    /// whose connections can only read from the database.
+    ///
+    /// No migrations are performed, so this fails unless the database has already been migrated to
+    /// the latest schema, e.g. by another pathfinder process syncing to the same database. Note that
+    /// the trie nodes of [backends](backend) other than sqlite are not shared between processes,
+    /// which is why such backends refuse to open a database which already contains blocks.
+    pub fn open_read_only(database_path: PathBuf) -> anyhow::Result<StorageManager> {
+        let flags = OpenFlags::default()
+            .difference(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE)
+            .union(OpenFlags::SQLITE_OPEN_READ_ONLY);
+
+        let connection = rusqlite::Connection::open_with_flags(&database_path, flags)
+            .context("Opening database")?;
+        let version = schema_version(&connection).context("Reading schema version")?;
+        let latest_version = schema::BASE_SCHEMA_REVISION + schema::migrations().len();
+        anyhow::ensure!(
+            version == latest_version,
+            "Database version {version} does not match the expected version {latest_version}. Migrate the database by running pathfinder without read-only mode first."
+        );
+        backend::TrieStore::check_database(&connection)?;
+        connection
+            .close()
+            .map_err(|(_connection, error)| error)
+            .context("Closing database")?;
+
+        Ok(StorageManager::new(database_path, flags))
     }
 
     /// Returns a new Sqlite [Connection] to the database.
     pub fn connection(&self) -> anyhow::Result<Connection> {
         let conn = self.0.pool.get()?;
-        Ok(Connection::from_inner(conn))
+        Ok(Connection::from_inner(conn, self.0.tries.clone()))
     }
 
     /// Convenience function for tests to create an in-memory database.
@@ -167,6 +220,26 @@ impl Storage {
     pub fn path(&self) -> &Path {
         &self.0.database_path
     }
+
+    /// Writes a consistent copy of the database to `destination` using SQLite's
+    /// [online backup API](https://www.sqlite.org/backup.html).
+    ///
+    /// This is safe to use while other connections are writing to the database. The
+    /// copy is made in a single step, and therefore reflects the state of the database
+    /// at the start of the backup.
+    pub fn backup(&self, destination: &Path) -> anyhow::Result<()> {
+        let source = self.0.pool.get().context("Getting database connection")?;
+        let mut destination = rusqlite::Connection::open(destination)
+            .context("Opening backup destination database")?;
+
+        rusqlite::backup::Backup::new(&source, &mut destination)
+            .context("Starting database backup")?
+            // A negative number of pages copies the entire database in one step.
+            .run_to_completion(-1, std::time::Duration::ZERO, None)
+            .context("Copying database")?;
+
+        Ok(())
+    }
 }
 
 fn setup_journal_mode(
@@ -452,4 +525,26 @@ mod tests {
 
         assert_eq!(version, expected, "RPC database fixture needs migrating");
     }
+
+    #[test]
+    fn read_only() {
+        let db_dir = tempfile::TempDir::new().unwrap();
+        let db_path = db_dir.path().join("test.sqlite");
+
+        // Unmigrated databases are rejected.
+        rusqlite::Connection::open(&db_path).unwrap();
+        Storage::open_read_only(db_path.clone()).unwrap_err();
+
+        Storage::migrate(db_path.clone(), JournalMode::WAL).unwrap();
+        let storage = Storage::open_read_only(db_path)
+            .unwrap()
+            .create_pool(NonZeroU32::new(1).unwrap())
+            .unwrap();
+
+        let mut connection = storage.connection().unwrap();
+        let tx = connection.transaction().unwrap();
+        assert_eq!(tx.block_id(BlockId::Latest).unwrap(), None);
+        tx.insert_block_header(&pathfinder_common::BlockHeader::default())
+            .unwrap_err();
+    }
 }
@@ -9,6 +9,12 @@ mod revision_0036;
 mod revision_0037;
 mod revision_0038;
 mod revision_0039;
+mod revision_0040;
+mod revision_0041;
+mod revision_0042;
+mod revision_0043;
+mod revision_0044;
+mod revision_0045;
 
 pub(crate) use base::base_schema;
 
@@ -27,6 +33,12 @@ pub fn migrations() -> &'static [MigrationFn] {
         revision_0037::migrate,
         revision_0038::migrate,
         revision_0039::migrate,
+        revision_0040::migrate,
+        revision_0041::migrate,
+        revision_0042::migrate,
+        revision_0043::migrate,
+        revision_0044::migrate,
+        revision_0045::migrate,
     ]
 }
 
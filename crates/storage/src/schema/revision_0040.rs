@@ -0,0 +1,21 @@
+use anyhow::Context;
+
+/// This migration adds the tables required for pruning state tries: the trie root changes
+/// per block, and a generic table of storage options in which the pruning mode is stored.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"
+CREATE TABLE trie_root_changes (
+    block_number INTEGER NOT NULL,
+    trie INTEGER NOT NULL,
+    old_root BLOB NOT NULL,
+    new_root BLOB NOT NULL
+);
+CREATE INDEX trie_root_changes_block_number ON trie_root_changes(block_number);
+CREATE TABLE storage_options (
+    option TEXT PRIMARY KEY NOT NULL,
+    value INTEGER NOT NULL
+);",
+    )
+    .context("Creating trie_root_changes and storage_options tables")
+}
@@ -0,0 +1,99 @@
+use anyhow::Context;
+use pathfinder_common::event::Event;
+use pathfinder_common::{BlockNumber, EventKey};
+use stark_hash::Felt;
+
+use crate::bloom::BloomFilter;
+use crate::params::{params, RowExt};
+
+/// This migration adds a bloom filter over the event addresses and keys of each block,
+/// and creates the filters of all blocks which contain events.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        r"CREATE TABLE starknet_events_filters (
+    block_number INTEGER PRIMARY KEY NOT NULL REFERENCES canonical_blocks(number) ON DELETE CASCADE,
+    bloom BLOB NOT NULL
+)",
+        [],
+    )
+    .context("Creating starknet_events_filters table")?;
+
+    let latest: Option<u64> = tx
+        .query_row("SELECT MAX(block_number) FROM starknet_events", [], |row| {
+            row.get(0)
+        })
+        .context("Querying latest block with events")?;
+    let Some(latest) = latest else {
+        return Ok(());
+    };
+
+    tracing::info!(
+        "Creating event bloom filters, this may take a while. Progress will be logged regularly."
+    );
+
+    let mut query = tx
+        .prepare(
+            "SELECT block_number, from_address, keys FROM starknet_events ORDER BY block_number",
+        )
+        .context("Preparing event query")?;
+    let mut insert = tx
+        .prepare("INSERT INTO starknet_events_filters (block_number, bloom) VALUES (?, ?)")
+        .context("Preparing filter insert statement")?;
+
+    let mut rows = query.query([]).context("Querying events")?;
+
+    let mut timer = std::time::Instant::now();
+    let mut current: Option<BlockNumber> = None;
+    let mut events = Vec::new();
+    let mut key_buffer = [0u8; 32];
+
+    while let Some(row) = rows.next().context("Fetching next event")? {
+        let block_number = row.get_block_number(0)?;
+        if current != Some(block_number) {
+            if let Some(current) = current {
+                let filter = BloomFilter::for_events(&events);
+                insert
+                    .execute(params![&current, &filter.as_bytes()])
+                    .context("Inserting event filter")?;
+                events.clear();
+            }
+            current = Some(block_number);
+
+            if timer.elapsed() > std::time::Duration::from_secs(10) {
+                let progress = block_number.get() * 100 / latest.max(1);
+                tracing::info!(%block_number, "Creating event bloom filters, {progress}% done");
+                timer = std::time::Instant::now();
+            }
+        }
+
+        let from_address = row.get_contract_address(1)?;
+        let keys = row
+            .get_ref_unwrap(2)
+            .as_str_or_null()?
+            .unwrap_or_default()
+            .split(' ')
+            .filter(|key| !key.is_empty())
+            .map(|key| {
+                let used = base64::decode_config_slice(key, base64::STANDARD, &mut key_buffer)
+                    .context("Decoding event key")?;
+                let key = Felt::from_be_slice(&key_buffer[..used]).context("Parsing event key")?;
+                Ok(EventKey(key))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        events.push(Event {
+            data: Vec::new(),
+            from_address,
+            keys,
+        });
+    }
+
+    if let Some(current) = current {
+        let filter = BloomFilter::for_events(&events);
+        insert
+            .execute(params![&current, &filter.as_bytes()])
+            .context("Inserting event filter")?;
+    }
+
+    Ok(())
+}
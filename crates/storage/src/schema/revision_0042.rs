@@ -0,0 +1,15 @@
+use anyhow::Context;
+
+/// This migration adds an index on the storage updates of a contract by block, which is used
+/// to query all storage changes of a contract over a range of blocks.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tracing::info!("Creating storage_updates index, this may take a while");
+
+    tx.execute(
+        "CREATE INDEX storage_updates_contract_address_block_number ON storage_updates(contract_address, block_number)",
+        [],
+    )
+    .context("Creating storage_updates_contract_address_block_number index")?;
+
+    Ok(())
+}
@@ -0,0 +1,241 @@
+use anyhow::Context;
+use pathfinder_common::BlockHash;
+
+use crate::params::{params, RowExt, TryIntoSqlInt};
+
+/// Matches the compression level of the per-transaction data being replaced.
+const COMPRESSION_LEVEL: i32 = 10;
+/// The maximum size of the trained dictionary, which is zstd's default.
+const DICTIONARY_SIZE: usize = 112_640;
+/// The number of blocks, spread over the chain, used to train the dictionary.
+const TRAINING_BLOCKS: u64 = 10_000;
+/// Fewer samples than this are not worth training a dictionary on.
+const MIN_TRAINING_SAMPLES: usize = 100;
+/// Limits the memory used for training.
+const MAX_TRAINING_BYTES: usize = 256 * 1024 * 1024;
+
+/// This migration replaces the separately compressed transaction and receipt of each row in
+/// `starknet_transactions` with a single blob per block, holding all of the block's transactions
+/// and receipts. Blobs are compressed using a zstd dictionary trained on a sample of the existing
+/// blocks, which removes most of the redundancy between calldata and signatures.
+///
+/// `starknet_transactions` is kept to locate transactions by hash, and records where each
+/// transaction lies within the decompressed data of its block, so that looking up a single
+/// transaction only deserializes that transaction instead of the entire block.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"CREATE TABLE transaction_dictionaries (
+    id INTEGER PRIMARY KEY NOT NULL,
+    dictionary BLOB NOT NULL
+);
+CREATE TABLE block_transactions (
+    block_number INTEGER PRIMARY KEY NOT NULL REFERENCES canonical_blocks(number) ON DELETE CASCADE,
+    dictionary_id INTEGER REFERENCES transaction_dictionaries(id),
+    data BLOB NOT NULL
+);",
+    )
+    .context("Creating tables")?;
+
+    tx.execute_batch(
+        r"ALTER TABLE starknet_transactions ADD COLUMN data_offset INTEGER;
+ALTER TABLE starknet_transactions ADD COLUMN data_length INTEGER;",
+    )
+    .context("Adding transaction location columns")?;
+
+    let latest: Option<u64> = tx
+        .query_row("SELECT MAX(number) FROM canonical_blocks", [], |row| {
+            row.get(0)
+        })
+        .context("Querying latest block")?;
+
+    if let Some(latest) = latest {
+        compress_blocks(tx, latest)?;
+    }
+
+    tx.execute_batch(
+        r"ALTER TABLE starknet_transactions DROP COLUMN tx;
+ALTER TABLE starknet_transactions DROP COLUMN receipt;",
+    )
+    .context("Dropping transaction data columns")?;
+
+    Ok(())
+}
+
+fn compress_blocks(tx: &rusqlite::Transaction<'_>, latest: u64) -> anyhow::Result<()> {
+    tracing::info!(
+        "Compressing transactions per block, this may take a while. Progress will be logged regularly."
+    );
+
+    let mut blocks = tx
+        .prepare("SELECT number, hash FROM canonical_blocks ORDER BY number")
+        .context("Preparing block query")?;
+    let mut transactions = tx
+        .prepare(
+            "SELECT idx, tx, receipt FROM starknet_transactions WHERE block_hash = ? ORDER BY idx",
+        )
+        .context("Preparing transaction query")?;
+    let mut insert = tx
+        .prepare(
+            "INSERT INTO block_transactions (block_number, dictionary_id, data) VALUES (?, ?, ?)",
+        )
+        .context("Preparing insert statement")?;
+    let mut locate = tx
+        .prepare(
+            r"UPDATE starknet_transactions SET data_offset = ?, data_length = ?
+            WHERE block_hash = ? AND idx = ?",
+        )
+        .context("Preparing location update statement")?;
+
+    let dictionary = train_dictionary(&mut blocks, &mut transactions, latest)?;
+    let (dictionary_id, mut compressor) = match &dictionary {
+        Some(dictionary) => {
+            tx.execute(
+                "INSERT INTO transaction_dictionaries (dictionary) VALUES (?)",
+                params![dictionary],
+            )
+            .context("Inserting dictionary")?;
+            let compressor = zstd::bulk::Compressor::with_dictionary(COMPRESSION_LEVEL, dictionary)
+                .context("Creating zstd compressor")?;
+            (Some(tx.last_insert_rowid()), compressor)
+        }
+        None => (
+            None,
+            zstd::bulk::Compressor::new(COMPRESSION_LEVEL).context("Creating zstd compressor")?,
+        ),
+    };
+
+    let mut rows = blocks.query([]).context("Querying blocks")?;
+    let mut timer = std::time::Instant::now();
+    let mut old_size = 0;
+    let mut new_size = 0;
+
+    while let Some(row) = rows.next().context("Fetching next block")? {
+        let block_number = row.get_block_number(0)?;
+        let block_hash = row.get_block_hash(1)?;
+
+        let Some(block) = block_data(&mut transactions, block_hash)? else {
+            continue;
+        };
+        let data = compressor
+            .compress(&block.data)
+            .context("Compressing transactions")?;
+        old_size += block.size;
+        new_size += data.len();
+
+        insert
+            .execute(params![&block_number, &dictionary_id, &data])
+            .context("Inserting block transactions")?;
+
+        for (idx, offset, length) in block.locations {
+            locate
+                .execute(params![
+                    &offset.try_into_sql_int()?,
+                    &length.try_into_sql_int()?,
+                    &block_hash,
+                    &idx
+                ])
+                .context("Updating transaction location")?;
+        }
+
+        if timer.elapsed() > std::time::Duration::from_secs(10) {
+            let progress = block_number.get() * 100 / latest.max(1);
+            tracing::info!(%block_number, "Compressing transactions, {progress}% done");
+            timer = std::time::Instant::now();
+        }
+    }
+
+    tracing::info!(%old_size, %new_size, "Compressed transactions");
+
+    Ok(())
+}
+
+/// Trains the dictionary on the data of blocks spread evenly over the chain. Returns `None` if
+/// there are too few blocks with transactions, or training fails.
+fn train_dictionary(
+    blocks: &mut rusqlite::Statement<'_>,
+    transactions: &mut rusqlite::Statement<'_>,
+    latest: u64,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    let step = ((latest + 1) / TRAINING_BLOCKS).max(1) as usize;
+
+    let mut samples = Vec::new();
+    let mut total_size = 0;
+
+    let mut rows = blocks.query([]).context("Querying blocks")?;
+    let mut index = 0;
+    while let Some(row) = rows.next().context("Fetching next block")? {
+        index += 1;
+        if (index - 1) % step != 0 {
+            continue;
+        }
+
+        let block_hash = row.get_block_hash(1)?;
+        if let Some(block) = block_data(transactions, block_hash)? {
+            total_size += block.data.len();
+            samples.push(block.data);
+        }
+
+        if total_size > MAX_TRAINING_BYTES {
+            break;
+        }
+    }
+
+    if samples.len() < MIN_TRAINING_SAMPLES {
+        return Ok(None);
+    }
+
+    match zstd::dict::from_samples(&samples, DICTIONARY_SIZE) {
+        Ok(dictionary) => Ok(Some(dictionary)),
+        Err(error) => {
+            tracing::warn!(%error, "Training transaction dictionary failed, compressing without it");
+            Ok(None)
+        }
+    }
+}
+
+struct BlockData {
+    /// The JSON array of `[transaction, receipt]` pairs of the block.
+    data: Vec<u8>,
+    /// The index, offset and length of each pair within `data`.
+    locations: Vec<(i64, usize, usize)>,
+    /// The size of the compressed data the pairs were read from.
+    size: usize,
+}
+
+/// Returns `None` if the block has no transactions.
+fn block_data(
+    transactions: &mut rusqlite::Statement<'_>,
+    block_hash: BlockHash,
+) -> anyhow::Result<Option<BlockData>> {
+    let mut rows = transactions
+        .query(params![&block_hash])
+        .context("Querying transactions")?;
+
+    let mut data = vec![b'['];
+    let mut locations = Vec::new();
+    let mut size = 0;
+    while let Some(row) = rows.next().context("Fetching next transaction")? {
+        let idx = row.get_i64(0)?;
+        let transaction = row.get_blob(1)?;
+        let receipt = row.get_optional_blob(2)?.context("Receipt data missing")?;
+        size += transaction.len() + receipt.len();
+
+        if data.len() > 1 {
+            data.push(b',');
+        }
+        let offset = data.len();
+        data.push(b'[');
+        data.extend(zstd::decode_all(transaction).context("Decompressing transaction")?);
+        data.push(b',');
+        data.extend(zstd::decode_all(receipt).context("Decompressing receipt")?);
+        data.push(b']');
+        locations.push((idx, offset, data.len() - offset));
+    }
+    data.push(b']');
+
+    Ok((size > 0).then_some(BlockData {
+        data,
+        locations,
+        size,
+    }))
+}
@@ -0,0 +1,18 @@
+use anyhow::Context;
+
+/// This migration adds the table holding the contracts whose events are indexed, when the event
+/// index policy is limited to an allowlist of contracts. `complete` marks contracts whose events
+/// are indexed for all blocks, including those still awaiting re-indexing after the policy
+/// changed.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute(
+        r"CREATE TABLE event_index_contracts (
+    contract_address BLOB PRIMARY KEY NOT NULL,
+    complete INTEGER NOT NULL
+)",
+        [],
+    )
+    .context("Creating event_index_contracts table")?;
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use anyhow::Context;
+use pathfinder_common::{EthereumAddress, L1ToL2MessageNonce, L1ToL2MessagePayloadElem};
+use pathfinder_ethereum::L1ToL2Message;
+use primitive_types::{H160, H256};
+use starknet_gateway_types::reply::transaction as gateway;
+
+use crate::params::{params, RowExt};
+
+/// This migration adds the tables tracking L1 to L2 messages:
+///
+/// - `l1_to_l2_messages` holds the messages sent to the core contract on L1, and the L1
+///   transactions which cancelled them. It is filled by the L1 message sync, starting from the
+///   Ethereum block at which it is first run.
+/// - `l1_handler_transactions` links the hash of each message to the L1 handler transaction
+///   which consumed it on L2. This is filled for the existing blocks here.
+pub(crate) fn migrate(tx: &rusqlite::Transaction<'_>) -> anyhow::Result<()> {
+    tx.execute_batch(
+        r"CREATE TABLE l1_to_l2_messages (
+    message_hash BLOB PRIMARY KEY NOT NULL,
+    l1_transaction_hash BLOB NOT NULL,
+    l1_block_number INTEGER NOT NULL,
+    from_address BLOB NOT NULL,
+    to_address BLOB NOT NULL,
+    selector BLOB NOT NULL,
+    payload BLOB NOT NULL,
+    nonce BLOB NOT NULL,
+    cancellation_started_by BLOB,
+    canceled_by BLOB
+);
+CREATE INDEX l1_to_l2_messages_l1_transaction_hash ON l1_to_l2_messages(l1_transaction_hash);
+CREATE TABLE l1_handler_transactions (
+    message_hash BLOB PRIMARY KEY NOT NULL,
+    transaction_hash BLOB NOT NULL,
+    block_number INTEGER NOT NULL REFERENCES canonical_blocks(number) ON DELETE CASCADE
+);",
+    )
+    .context("Creating tables")?;
+
+    let latest: Option<u64> = tx
+        .query_row("SELECT MAX(number) FROM canonical_blocks", [], |row| {
+            row.get(0)
+        })
+        .context("Querying latest block")?;
+
+    if let Some(latest) = latest {
+        insert_l1_handler_transactions(tx, latest)?;
+    }
+
+    Ok(())
+}
+
+fn insert_l1_handler_transactions(
+    tx: &rusqlite::Transaction<'_>,
+    latest: u64,
+) -> anyhow::Result<()> {
+    tracing::info!(
+        "Indexing L1 handler transactions, this may take a while. Progress will be logged regularly."
+    );
+
+    let mut blocks = tx
+        .prepare(
+            r"SELECT block_number, data, dictionary FROM block_transactions
+            LEFT JOIN transaction_dictionaries ON transaction_dictionaries.id = block_transactions.dictionary_id
+            ORDER BY block_number",
+        )
+        .context("Preparing block query")?;
+    let mut insert = tx
+        .prepare(
+            r"INSERT OR REPLACE INTO l1_handler_transactions (message_hash, transaction_hash, block_number)
+            VALUES (?, ?, ?)",
+        )
+        .context("Preparing insert statement")?;
+
+    let mut rows = blocks.query([]).context("Querying blocks")?;
+    let mut timer = std::time::Instant::now();
+
+    while let Some(row) = rows.next().context("Fetching next block")? {
+        let block_number = row.get_block_number(0)?;
+        let data = row.get_blob(1)?;
+        let dictionary = row.get_optional_blob(2)?.unwrap_or_default();
+
+        let mut decoder = zstd::stream::read::Decoder::with_dictionary(data, dictionary)
+            .context("Creating zstd decoder")?;
+        let mut transactions = Vec::new();
+        std::io::Read::read_to_end(&mut decoder, &mut transactions)
+            .context("Decompressing transactions")?;
+        let transactions: Vec<(gateway::Transaction, gateway::Receipt)> =
+            serde_json::from_slice(&transactions).context("Deserializing transactions")?;
+
+        for (transaction, _) in transactions {
+            let gateway::Transaction::L1Handler(transaction) = transaction else {
+                continue;
+            };
+            let Some((from_address, payload)) = transaction.calldata.split_first() else {
+                continue;
+            };
+
+            let message = L1ToL2Message {
+                from_address: EthereumAddress(H160::from(H256(from_address.0.to_be_bytes()))),
+                to_address: transaction.contract_address,
+                selector: transaction.entry_point_selector,
+                payload: payload
+                    .iter()
+                    .map(|elem| L1ToL2MessagePayloadElem(elem.0))
+                    .collect(),
+                nonce: L1ToL2MessageNonce(transaction.nonce.0),
+            };
+
+            insert
+                .execute(params![
+                    &message.hash().as_bytes(),
+                    &transaction.transaction_hash,
+                    &block_number
+                ])
+                .context("Inserting L1 handler transaction")?;
+        }
+
+        if timer.elapsed() > std::time::Duration::from_secs(10) {
+            let progress = block_number.get() * 100 / latest.max(1);
+            tracing::info!(%block_number, "Indexing L1 handler transactions, {progress}% done");
+            timer = std::time::Instant::now();
+        }
+    }
+
+    Ok(())
+}
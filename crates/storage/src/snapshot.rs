@@ -0,0 +1,366 @@
+//! Snapshot archives of the database, used to provision new nodes without syncing from genesis.
+//!
+//! An archive consists of
+//! - the [MAGIC] bytes,
+//! - the length of the [SnapshotMetadata] as a big-endian `u32`,
+//! - the JSON encoded [SnapshotMetadata],
+//! - the zstd compressed database file.
+//!
+//! The metadata includes a SHA3-256 checksum of the uncompressed database, which is verified
+//! when the archive is [unpacked](unpack).
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use pathfinder_common::{BlockHash, BlockNumber, ChainId};
+use sha3::{Digest, Sha3_256};
+
+use crate::prelude::*;
+use crate::Storage;
+
+/// Identifies a snapshot archive and its format version.
+const MAGIC: &[u8; 8] = b"PFSNAPv1";
+
+/// Favours speed over size, since the database is large and mostly incompressible hashes.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Describes the database contained in a snapshot archive.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotMetadata {
+    pub chain_id: ChainId,
+    pub head_number: BlockNumber,
+    pub head_hash: BlockHash,
+    pub schema_version: usize,
+    /// Hex encoded SHA3-256 hash of the uncompressed database.
+    pub checksum: String,
+    /// Size of the uncompressed database in bytes.
+    pub size: u64,
+}
+
+/// Writes a consistent snapshot of the database to a new `archive`, using [Storage::backup].
+///
+/// This is safe to use while the node is running.
+pub fn export(
+    storage: &Storage,
+    chain_id: ChainId,
+    archive: &Path,
+) -> anyhow::Result<SnapshotMetadata> {
+    anyhow::ensure!(
+        !archive.exists(),
+        "Snapshot archive {} already exists",
+        archive.display()
+    );
+
+    let backup = TempFile::next_to(archive, "backup");
+    storage.backup(&backup.0).context("Backing up database")?;
+
+    let (schema_version, head) = describe_database(&backup.0)?;
+    let (head_number, head_hash) = head.context("Database contains no blocks")?;
+    let size = std::fs::metadata(&backup.0)
+        .context("Reading database backup size")?
+        .len();
+
+    // The checksum is only known once the database has been compressed. It is hex encoded with
+    // a fixed length, so a placeholder keeps the length of the metadata, which is then
+    // rewritten in place.
+    let mut metadata = SnapshotMetadata {
+        chain_id,
+        head_number,
+        head_hash,
+        schema_version,
+        checksum: hex::encode([0u8; 32]),
+        size,
+    };
+
+    let partial = TempFile::next_to(archive, "partial");
+    let mut writer = BufWriter::new(File::create(&partial.0).context("Creating archive")?);
+    write_metadata(&mut writer, &metadata).context("Writing archive metadata")?;
+
+    let encoder = zstd::Encoder::new(writer, COMPRESSION_LEVEL).context("Creating compressor")?;
+    let mut encoder = ChecksumWriter::new(encoder);
+    std::io::copy(
+        &mut File::open(&backup.0).context("Opening database backup")?,
+        &mut encoder,
+    )
+    .context("Compressing database")?;
+
+    let (encoder, checksum, compressed) = encoder.finish();
+    anyhow::ensure!(
+        compressed == size,
+        "Compressed {compressed} bytes of the {size} byte database backup"
+    );
+    let mut file = encoder
+        .finish()
+        .context("Finishing compression")?
+        .into_inner()
+        .context("Flushing archive")?;
+
+    metadata.checksum = checksum;
+    file.seek(SeekFrom::Start(0))
+        .context("Seeking to archive metadata")?;
+    write_metadata(&mut file, &metadata).context("Writing archive metadata")?;
+    file.sync_all().context("Syncing archive")?;
+
+    partial.persist(archive)?;
+
+    Ok(metadata)
+}
+
+/// Reads the [SnapshotMetadata] of an archive, without verifying its contents.
+pub fn read_metadata(archive: &Path) -> anyhow::Result<SnapshotMetadata> {
+    let mut reader = BufReader::new(File::open(archive).context("Opening archive")?);
+    read_metadata_from(&mut reader)
+}
+
+/// Decompresses the database contained in `archive` to the new file `destination`.
+///
+/// The database is only written to `destination` if it matches the archive's checksum,
+/// and its schema version and latest block match the archive's metadata.
+pub fn unpack(archive: &Path, destination: &Path) -> anyhow::Result<SnapshotMetadata> {
+    anyhow::ensure!(
+        !destination.exists(),
+        "Database {} already exists",
+        destination.display()
+    );
+
+    let mut reader = BufReader::new(File::open(archive).context("Opening archive")?);
+    let metadata = read_metadata_from(&mut reader)?;
+
+    let latest_version = crate::schema::BASE_SCHEMA_REVISION + crate::schema::migrations().len();
+    anyhow::ensure!(
+        metadata.schema_version <= latest_version,
+        "Snapshot schema version {} is newer than this application expected {latest_version}",
+        metadata.schema_version
+    );
+
+    let partial = TempFile::next_to(destination, "partial");
+    let writer = BufWriter::new(File::create(&partial.0).context("Creating database")?);
+    let mut writer = ChecksumWriter::new(writer);
+    let mut decoder = zstd::Decoder::with_buffer(reader).context("Creating decompressor")?;
+    std::io::copy(&mut decoder, &mut writer).context("Decompressing database")?;
+
+    let (writer, checksum, size) = writer.finish();
+    writer
+        .into_inner()
+        .context("Flushing database")?
+        .sync_all()
+        .context("Syncing database")?;
+
+    anyhow::ensure!(
+        size == metadata.size,
+        "Database size {size} does not match the expected size {}",
+        metadata.size
+    );
+    anyhow::ensure!(
+        checksum == metadata.checksum,
+        "Database checksum {checksum} does not match the expected checksum {}",
+        metadata.checksum
+    );
+
+    let (schema_version, head) = describe_database(&partial.0)?;
+    anyhow::ensure!(
+        schema_version == metadata.schema_version,
+        "Database schema version {schema_version} does not match the expected version {}",
+        metadata.schema_version
+    );
+    anyhow::ensure!(
+        head == Some((metadata.head_number, metadata.head_hash)),
+        "Database head {head:?} does not match the expected head {} {}",
+        metadata.head_number,
+        metadata.head_hash
+    );
+
+    partial.persist(destination)?;
+
+    Ok(metadata)
+}
+
+fn write_metadata(writer: &mut impl Write, metadata: &SnapshotMetadata) -> anyhow::Result<()> {
+    let metadata = serde_json::to_vec(metadata).context("Serializing metadata")?;
+    let length = u32::try_from(metadata.len()).context("Metadata length")?;
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&length.to_be_bytes())?;
+    writer.write_all(&metadata)?;
+
+    Ok(())
+}
+
+fn read_metadata_from(reader: &mut impl Read) -> anyhow::Result<SnapshotMetadata> {
+    let mut magic = [0u8; MAGIC.len()];
+    reader
+        .read_exact(&mut magic)
+        .context("Reading archive header")?;
+    anyhow::ensure!(&magic == MAGIC, "Not a snapshot archive");
+
+    let mut length = [0u8; 4];
+    reader
+        .read_exact(&mut length)
+        .context("Reading metadata length")?;
+
+    let mut metadata = vec![0u8; u32::from_be_bytes(length) as usize];
+    reader
+        .read_exact(&mut metadata)
+        .context("Reading metadata")?;
+
+    serde_json::from_slice(&metadata).context("Parsing metadata")
+}
+
+/// Returns the schema version and the latest block of the database at `path`.
+fn describe_database(path: &Path) -> anyhow::Result<(usize, Option<(BlockNumber, BlockHash)>)> {
+    let connection = rusqlite::Connection::open(path).context("Opening database")?;
+
+    let schema_version = crate::schema_version(&connection).context("Reading schema version")?;
+
+    let head = connection
+        .query_row(
+            "SELECT number, hash FROM canonical_blocks ORDER BY number DESC LIMIT 1",
+            [],
+            |row| {
+                let number = row.get_block_number(0)?;
+                let hash = row.get_block_hash(1)?;
+                Ok((number, hash))
+            },
+        )
+        .optional()
+        .context("Querying latest block")?;
+
+    Ok((schema_version, head))
+}
+
+/// Forwards all writes to the inner writer, while hashing and counting the written bytes.
+struct ChecksumWriter<W> {
+    inner: W,
+    hasher: Sha3_256,
+    size: u64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            hasher: Sha3_256::new(),
+            size: 0,
+        }
+    }
+
+    /// Returns the inner writer, the hex encoded checksum and the number of bytes written.
+    fn finish(self) -> (W, String, u64) {
+        let checksum = hex::encode(self.hasher.finalize());
+        (self.inner, checksum, self.size)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A file which is removed when dropped, unless it is persisted.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// A file in the same directory as `path`, so that it can be renamed to `path`.
+    fn next_to(path: &Path, suffix: &str) -> Self {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{suffix}"));
+        Self(path.with_file_name(name))
+    }
+
+    fn persist(self, path: &Path) -> anyhow::Result<()> {
+        std::fs::rename(&self.0, path)
+            .with_context(|| format!("Moving {} to {}", self.0.display(), path.display()))?;
+        std::mem::forget(self);
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pathfinder_common::felt_bytes;
+
+    use super::*;
+
+    #[test]
+    fn export_and_unpack() {
+        let (storage, test_data) = crate::test_utils::setup_test_storage();
+        let head = test_data.headers.last().unwrap();
+        let chain_id = ChainId(felt_bytes!(b"SN_TEST"));
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("snapshot.pfsnap");
+        let database = dir.path().join("restored.sqlite");
+
+        let metadata = export(&storage, chain_id, &archive).unwrap();
+        assert_eq!(metadata.chain_id, chain_id);
+        assert_eq!(metadata.head_number, head.number);
+        assert_eq!(metadata.head_hash, head.hash);
+        assert_eq!(read_metadata(&archive).unwrap(), metadata);
+
+        // Exporting must not overwrite an existing archive.
+        export(&storage, chain_id, &archive).unwrap_err();
+
+        let unpacked = unpack(&archive, &database).unwrap();
+        assert_eq!(unpacked, metadata);
+        let (schema_version, restored_head) = describe_database(&database).unwrap();
+        assert_eq!(schema_version, metadata.schema_version);
+        assert_eq!(restored_head, Some((head.number, head.hash)));
+
+        // Only the requested files should remain.
+        let files = std::fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(files, 2);
+    }
+
+    #[test]
+    fn unpack_rejects_checksum_mismatch() {
+        let (storage, _) = crate::test_utils::setup_test_storage();
+
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("snapshot.pfsnap");
+        let tampered = dir.path().join("tampered.pfsnap");
+        let database = dir.path().join("restored.sqlite");
+
+        let mut metadata = export(&storage, ChainId(felt_bytes!(b"SN_TEST")), &archive).unwrap();
+
+        // Replace the metadata, keeping the compressed database as is.
+        let original = std::fs::read(&archive).unwrap();
+        let data_start = MAGIC.len()
+            + 4
+            + u32::from_be_bytes(original[MAGIC.len()..MAGIC.len() + 4].try_into().unwrap())
+                as usize;
+        metadata.checksum = hex::encode([0u8; 32]);
+        let mut contents = Vec::new();
+        write_metadata(&mut contents, &metadata).unwrap();
+        contents.extend_from_slice(&original[data_start..]);
+        std::fs::write(&tampered, contents).unwrap();
+
+        let error = unpack(&tampered, &database).unwrap_err();
+        assert!(error.to_string().contains("checksum"));
+        assert!(!database.exists());
+    }
+
+    #[test]
+    fn read_metadata_rejects_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("not-a-snapshot");
+        std::fs::write(&file, b"definitely not a snapshot archive").unwrap();
+
+        read_metadata(&file).unwrap_err();
+    }
+}
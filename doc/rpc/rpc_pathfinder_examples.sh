@@ -25,4 +25,24 @@ rpc_call '{
         ]
     },
     "id": 0
-}'
\ No newline at end of file
+}'
+rpc_call '{
+    "jsonrpc": "2.0",
+    "method": "pathfinder_getProofs",
+    "params": {
+        "block_id": "latest",
+        "contracts": [
+            {
+                "contract_address": "0x23371b227eaecd8e8920cd429d2cd0f3fee6abaacca08d3ab82a7cdd",
+                "keys": [
+                    "0x1",
+                    "0xfffffffff"
+                ]
+            }
+        ],
+        "class_hashes": [
+            "0x1"
+        ]
+    },
+    "id": 0
+}'